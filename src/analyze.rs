@@ -0,0 +1,173 @@
+use crate::manifest::Manifest;
+use crate::render::IterationBuffer;
+use crate::{Fractal, fixed_path, frame_info, iterfile, output};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(clap::Args, Debug)]
+pub struct AnalyzeArgs {
+    /// Frames of a run rendered with --save-iterations.
+    #[arg(long, default_value = "out/frames")]
+    run_dir: String,
+    /// Directory the report goes to, as `analysis.csv`.
+    #[arg(long, default_value = "out")]
+    out_dir: String,
+    /// Report file instead of `analysis.csv` in --out-dir: JSON if it ends in
+    /// `.json`, CSV otherwise.
+    #[arg(long)]
+    output: Option<String>,
+}
+
+/// Measurements of the set within one frame's view, in plane units.
+#[derive(Debug, Clone, Copy)]
+pub struct Stats {
+    pub interior_fraction: f64,
+    pub area: f64,
+    pub boundary_length: f64,
+    /// Box-counting dimension of the boundary pixels, if there are enough of them.
+    pub dimension: Option<f64>,
+}
+
+/// Measures the interior pixels of `iterations`, each `pixel` plane units wide.
+///
+/// The boundary length counts pixel edges between interior and exterior,
+/// scaled by π/4, the mean excess of such a staircase over a curve of any
+/// direction.
+pub fn stats(iterations: &IterationBuffer, pixel: f64) -> Stats {
+    let (w, h) = (iterations.width as usize, iterations.height as usize);
+    let inside: Vec<bool> = iterations.values.iter().map(|v| *v < 0.0).collect();
+    let interior = inside.iter().filter(|i| **i).count();
+    let mut edges = 0usize;
+    let mut boundary = vec![false; w * h];
+    for y in 0..h {
+        for x in 0..w {
+            let idx = y * w + x;
+            for (nx, ny) in [(x + 1, y), (x, y + 1)] {
+                if nx < w && ny < h && inside[ny * w + nx] != inside[idx] {
+                    edges += 1;
+                    boundary[idx] = true;
+                    boundary[ny * w + nx] = true;
+                }
+            }
+        }
+    }
+    let pixels = (w * h).max(1) as f64;
+    Stats {
+        interior_fraction: interior as f64 / pixels,
+        area: interior as f64 * pixel * pixel,
+        boundary_length: edges as f64 * pixel * std::f64::consts::FRAC_PI_4,
+        dimension: box_dimension(&boundary, w, h),
+    }
+}
+
+/// Slope of log(boxes hit) over log(1 / box size) for boxes of 1, 2, 4, …
+/// pixels up to a quarter of the shorter side, by least squares.
+fn box_dimension(marked: &[bool], w: usize, h: usize) -> Option<f64> {
+    let mut points = Vec::new();
+    let mut size = 1;
+    while size <= w.min(h) / 4 {
+        let (bw, bh) = (w.div_ceil(size), h.div_ceil(size));
+        let mut hit = vec![false; bw * bh];
+        for (idx, _) in marked.iter().enumerate().filter(|(_, m)| **m) {
+            let (x, y) = (idx % w, idx / w);
+            hit[(y / size) * bw + x / size] = true;
+        }
+        let count = hit.iter().filter(|h| **h).count();
+        if count > 0 {
+            points.push(((1.0 / size as f64).ln(), (count as f64).ln()));
+        }
+        size *= 2;
+    }
+    if points.len() < 2 {
+        return None;
+    }
+    let n = points.len() as f64;
+    let (sx, sy) = points
+        .iter()
+        .fold((0.0, 0.0), |(sx, sy), (x, y)| (sx + x, sy + y));
+    let (mx, my) = (sx / n, sy / n);
+    let (sxy, sxx) = points.iter().fold((0.0, 0.0), |(sxy, sxx), (x, y)| {
+        (sxy + (x - mx) * (y - my), sxx + (x - mx) * (x - mx))
+    });
+    Some(sxy / sxx)
+}
+
+/// Plane units per pixel of a `width`×`height` frame at `zoom`.
+pub fn pixel_size(width: u32, height: u32, zoom: f64) -> f64 {
+    zoom / (width.min(height) as f64 / 2.0)
+}
+
+pub fn run(args: AnalyzeArgs) -> Result<(), String> {
+    let dir = output::resolve_dir(Path::new(&args.run_dir))?;
+    let manifest = Manifest::read(&dir)?;
+    let render_args = manifest.render_args()?;
+    if matches!(render_args.fractal, Fractal::Flame | Fractal::Buddhabrot) {
+        return Err(
+            "analyze needs escape-time iterations, flames and the Buddhabrot store densities"
+                .to_string(),
+        );
+    }
+    let src = dir.join(iterfile::ITERATIONS_DIR);
+    let mut files: Vec<_> = fs::read_dir(&src)
+        .map_err(|e| format!("read {src:?}: {e} (was the run rendered with --save-iterations?)"))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "iter"))
+        .collect();
+    files.sort();
+    if files.is_empty() {
+        return Err(format!("no .iter files in {src:?}"));
+    }
+
+    let path = fixed_path(render_args.fractal);
+    let mut rows = Vec::new();
+    for file in &files {
+        let index: u32 = file
+            .file_stem()
+            .and_then(|stem| stem.to_str()?.strip_prefix("frame_")?.parse().ok())
+            .ok_or_else(|| format!("{file:?}: can't tell which frame this is"))?;
+        let iterations = iterfile::load(file)?;
+        let info = frame_info(&render_args, &path, index);
+        let pixel = pixel_size(iterations.width, iterations.height, info.zoom);
+        rows.push((index, info.zoom, stats(&iterations, pixel)));
+    }
+
+    let report = &match &args.output {
+        Some(file) => PathBuf::from(file),
+        None => output::resolve_dir(Path::new(&args.out_dir))?.join("analysis.csv"),
+    };
+    if let Some(parent) = report.parent() {
+        fs::create_dir_all(parent).map_err(|e| output::describe_error("create", parent, &e))?;
+    }
+    let dimension = |stats: &Stats| stats.dimension.map(|d| format!("{d:.4}"));
+    let text = if report.extension().is_some_and(|ext| ext == "json") {
+        let items: Vec<String> = rows
+            .iter()
+            .map(|(index, zoom, s)| {
+                format!(
+                    "  {{\"frame\": {index}, \"zoom\": {zoom:e}, \"interior_fraction\": {:.6}, \"area\": {:e}, \"boundary_length\": {:e}, \"box_dimension\": {}}}",
+                    s.interior_fraction,
+                    s.area,
+                    s.boundary_length,
+                    dimension(s).unwrap_or_else(|| "null".to_string())
+                )
+            })
+            .collect();
+        format!("[\n{}\n]\n", items.join(",\n"))
+    } else {
+        let mut csv =
+            String::from("frame,zoom,interior_fraction,area,boundary_length,box_dimension\n");
+        for (index, zoom, s) in &rows {
+            csv.push_str(&format!(
+                "{index},{zoom:e},{:.6},{:e},{:e},{}\n",
+                s.interior_fraction,
+                s.area,
+                s.boundary_length,
+                dimension(s).unwrap_or_default()
+            ));
+        }
+        csv
+    };
+    fs::write(report, text).map_err(|e| output::describe_error("save", report, &e))?;
+    println!("analyzed {} frames -> {}", rows.len(), report.display());
+    Ok(())
+}
@@ -0,0 +1,66 @@
+use crate::render::{INTERIOR, IterationBuffer};
+use std::fmt;
+use std::str::FromStr;
+
+/// Color given to pixels whose escape-time value is NaN, so they stand out.
+pub const NAN_COLOR: [u8; 3] = [255, 0, 255];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Policy {
+    Off,
+    /// Report anomalous frames and record them in the manifest.
+    Warn,
+    /// Re-iterate anomalous frames with a doubled iteration limit first.
+    Retry,
+}
+
+impl FromStr for Policy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Policy, String> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "off" => Ok(Policy::Off),
+            "warn" => Ok(Policy::Warn),
+            "retry" => Ok(Policy::Retry),
+            _ => Err(format!(
+                "unknown anomaly policy {s:?}, expected off, warn or retry"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Anomaly {
+    /// Every pixel is inside the set, so the frame is solid black.
+    AllInterior,
+    /// Every pixel has the same escape value, so the frame is one flat color.
+    Uniform,
+    /// This many pixels produced NaN and are drawn in [`NAN_COLOR`].
+    Nan(usize),
+}
+
+impl fmt::Display for Anomaly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Anomaly::AllInterior => write!(f, "all-black"),
+            Anomaly::Uniform => write!(f, "one-color"),
+            Anomaly::Nan(count) => write!(f, "nan:{count}"),
+        }
+    }
+}
+
+pub fn scan(iterations: &IterationBuffer) -> Option<Anomaly> {
+    let nan = iterations.values.iter().filter(|v| v.is_nan()).count();
+    if nan > 0 {
+        return Some(Anomaly::Nan(nan));
+    }
+    let first = *iterations.values.first()?;
+    if iterations.values.iter().any(|&v| v != first) {
+        return None;
+    }
+    if first == INTERIOR {
+        Some(Anomaly::AllInterior)
+    } else {
+        Some(Anomaly::Uniform)
+    }
+}
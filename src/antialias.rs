@@ -0,0 +1,169 @@
+use crate::palette::Palette;
+use crate::render::{self, INTERIOR, IterationBuffer};
+use crate::{FrameInfo, RenderArgs, perturbation, rotation_sweep, scheduler};
+use image::{ImageBuffer, Rgb};
+use rayon::prelude::*;
+
+/// Most moments within one frame a boundary pixel of a turning view is
+/// sampled at.
+const MAX_SWEEP_STEPS: u32 = 8;
+
+/// Subdirectory and manifest variant of the sample-count maps.
+pub const LABEL: &str = "aa-samples";
+
+/// Moments a boundary pixel at (`x`, `y`) is sampled at while the view
+/// turns `sweep` degrees in one frame, see [`smooth_boundary`].
+fn sweep_steps(x: f64, y: f64, width: u32, height: u32, sweep: f64, n: u32) -> u32 {
+    // Pixels the footprint moves along the arc: radius times angle.
+    let radius = (x - width as f64 / 2.0).hypot(y - height as f64 / 2.0);
+    let arc = radius * sweep.to_radians().abs();
+    ((arc * n as f64).ceil() as u32).clamp(1, MAX_SWEEP_STEPS)
+}
+
+/// Pixels with an interior neighbour while being exterior themselves, or the
+/// other way round: the ones straddling the set boundary.
+fn boundary_pixels(iterations: &IterationBuffer) -> Vec<usize> {
+    let (w, h) = (iterations.width as i64, iterations.height as i64);
+    let inside = |x: i64, y: i64| iterations.values[(y * w + x) as usize] == INTERIOR;
+    (0..w * h)
+        .into_par_iter()
+        .filter(|&idx| {
+            let (x, y) = (idx % w, idx / w);
+            let here = inside(x, y);
+            (-1..=1).any(|dy: i64| {
+                (-1..=1).any(|dx: i64| {
+                    let (nx, ny) = (x + dx, y + dy);
+                    nx >= 0 && ny >= 0 && nx < w && ny < h && inside(nx, ny) != here
+                })
+            })
+        })
+        .map(|idx| idx as usize)
+        .collect()
+}
+
+/// Replaces the boundary pixels of `img` by the average color of an N×N grid
+/// of samples inside each pixel (N = --boundary-aa), colored like the rest of
+/// the frame. Everywhere else the frame keeps its single sample per pixel.
+///
+/// While a rotation track turns the view, a pixel's footprint in the plane
+/// also sweeps along an arc over the frame, longer the farther it is from the
+/// center. The grid is then repeated at evenly spaced angles across that
+/// sweep, as many as keep the samples along the arc about as dense as across
+/// the pixel, so edges blur along the motion instead of stepping.
+///
+/// Frames the perturbation backend renders, such as deep ones past f64, are
+/// sampled by perturbation around the same reference orbit, since their
+/// samples all round to the same f64 points.
+pub fn smooth_boundary(
+    img: &mut ImageBuffer<Rgb<u8>, Vec<u8>>,
+    iterations: &IterationBuffer,
+    args: &RenderArgs,
+    info: &FrameInfo,
+    palette: &Palette,
+    phase: f64,
+) {
+    let n = args.boundary_aa;
+    let (width, height) = (iterations.width, iterations.height);
+    let sweep = rotation_sweep(args, info);
+    let w = width as usize;
+    let max_iter = iterations.max_iter;
+    let offset = |s: u32, count: u32| (s as f64 + 0.5) / count as f64 - 0.5;
+    let reference = (scheduler::backend_for(args, info).name() == "perturbation")
+        .then(|| scheduler::reference(args, info, width, height, max_iter));
+    let colors: Vec<(usize, [u8; 3])> = boundary_pixels(iterations)
+        .into_par_iter()
+        .map(|idx| {
+            let (x, y) = ((idx % w) as f64, (idx / w) as f64);
+            let steps = sweep_steps(x, y, width, height, sweep, n);
+            let mut sum = [0.0; 3];
+            for step in 0..steps {
+                let rotation = info.rotation + sweep * offset(step, steps);
+                let point = render::transformed_mapping(
+                    width,
+                    height,
+                    info.center,
+                    info.zoom,
+                    rotation,
+                    info.transform,
+                    info.warp,
+                );
+                let delta = perturbation::frame_offsets(
+                    width,
+                    height,
+                    info.zoom,
+                    rotation,
+                    info.transform,
+                    info.warp,
+                );
+                for sy in 0..n {
+                    for sx in 0..n {
+                        let (px, py) = (x + offset(sx, n), y + offset(sy, n));
+                        let value = match &reference {
+                            Some(reference) => {
+                                reference.escape(delta(px, py), max_iter, args.derivative_bailout)
+                            }
+                            None => render::mandelbrot_smooth(
+                                point(px, py),
+                                max_iter,
+                                args.derivative_bailout,
+                            ),
+                        };
+                        let color = iterations.color_of(value, palette, phase);
+                        for (acc, channel) in sum.iter_mut().zip(color) {
+                            *acc += channel as f64;
+                        }
+                    }
+                }
+            }
+            let samples = (n * n * steps) as f64;
+            (idx, sum.map(|acc| (acc / samples).round() as u8))
+        })
+        .collect();
+    for (idx, color) in colors {
+        img.as_mut()[idx * 3..idx * 3 + 3].copy_from_slice(&color);
+    }
+}
+
+/// How many samples each pixel had in total under [`smooth_boundary`].
+#[derive(Debug, Clone, Copy)]
+pub struct SampleReport {
+    pub boundary: usize,
+    pub samples: u64,
+    pub pixels: usize,
+}
+
+/// Maps how many samples [`smooth_boundary`] takes of each pixel, for
+/// tuning --boundary-aa: black for the single sample of most pixels, up the
+/// fire palette on a log scale to white at the most a pixel of this frame
+/// can get, N×N or N×N times the most sweep steps while the view turns.
+pub fn sample_map(
+    iterations: &IterationBuffer,
+    args: &RenderArgs,
+    info: &FrameInfo,
+) -> (ImageBuffer<Rgb<u8>, Vec<u8>>, SampleReport) {
+    let n = args.boundary_aa;
+    let (width, height) = (iterations.width, iterations.height);
+    let sweep = rotation_sweep(args, info);
+    let mut counts = vec![1; iterations.values.len()];
+    let boundary = boundary_pixels(iterations);
+    for &idx in &boundary {
+        let (x, y) = ((idx % width as usize) as f64, (idx / width as usize) as f64);
+        counts[idx] = n * n * sweep_steps(x, y, width, height, sweep, n);
+    }
+    let most = n * n * if sweep == 0.0 { 1 } else { MAX_SWEEP_STEPS };
+    let palette: Palette = "fire".parse().expect("built-in palette");
+    let mut img: ImageBuffer<Rgb<u8>, Vec<u8>> = ImageBuffer::new(width, height);
+    img.as_mut()
+        .par_chunks_mut(3)
+        .zip(&counts)
+        .for_each(|(pixel, &count)| {
+            let t = (count as f64).ln() / (most as f64).ln().max(f64::MIN_POSITIVE);
+            pixel.copy_from_slice(&palette.color(t));
+        });
+    let report = SampleReport {
+        boundary: boundary.len(),
+        samples: counts.iter().map(|&c| c as u64).sum(),
+        pixels: counts.len(),
+    };
+    (img, report)
+}
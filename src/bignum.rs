@@ -0,0 +1,162 @@
+/// A signed fixed-point number of any precision: `limbs` read as one two's
+/// complement integer, little-endian, over `2^(64 * (limbs - 1))`. The top
+/// limb is the integer part, far more than the values near the Mandelbrot
+/// set need.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fixed {
+    limbs: Vec<u64>,
+}
+
+impl Fixed {
+    pub fn zero(limbs: usize) -> Fixed {
+        Fixed {
+            limbs: vec![0; limbs.max(2)],
+        }
+    }
+
+    /// `value` exactly, down to the precision of `limbs`.
+    pub fn from_f64(value: f64, limbs: usize) -> Fixed {
+        let mut out = Fixed::zero(limbs);
+        if value == 0.0 || !value.is_finite() {
+            return out;
+        }
+        let bits = value.abs().to_bits();
+        let exponent = ((bits >> 52) & 0x7ff) as i64;
+        let (mantissa, exponent) = match exponent {
+            0 => (bits & ((1 << 52) - 1), -1074),
+            _ => ((bits & ((1 << 52) - 1)) | (1 << 52), exponent - 1075),
+        };
+        // value = mantissa * 2^exponent; the integer part starts at this bit.
+        let shift = exponent + 64 * (out.limbs.len() as i64 - 1);
+        if shift <= -64 {
+            return out;
+        }
+        if shift < 0 {
+            out.limbs[0] = mantissa >> -shift;
+        } else {
+            let (limb, bit) = ((shift / 64) as usize, (shift % 64) as u32);
+            let wide = (mantissa as u128) << bit;
+            for (k, part) in [wide as u64, (wide >> 64) as u64].into_iter().enumerate() {
+                if let Some(slot) = out.limbs.get_mut(limb + k) {
+                    *slot = part;
+                }
+            }
+        }
+        if value < 0.0 { out.neg() } else { out }
+    }
+
+    /// A plain decimal such as `-0.74364388703715870475219` with as many
+    /// digits as `limbs` can hold.
+    pub fn parse(s: &str, limbs: usize) -> Result<Fixed, String> {
+        let text = s.trim();
+        let (negative, digits) = match text.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, text.strip_prefix('+').unwrap_or(text)),
+        };
+        let (int, frac) = digits.split_once('.').unwrap_or((digits, ""));
+        let valid = |part: &str| part.chars().all(|ch| ch.is_ascii_digit());
+        if (int.is_empty() && frac.is_empty()) || !valid(int) || !valid(frac) {
+            return Err(format!("invalid decimal {s:?}"));
+        }
+        let int: u64 = match int {
+            "" => 0,
+            _ => int
+                .parse()
+                .ok()
+                .filter(|v| *v < 1 << 32)
+                .ok_or_else(|| format!("decimal {s:?} is too large"))?,
+        };
+        let mut out = Fixed::zero(limbs);
+        let top = out.limbs.len() - 1;
+        // Horner from the last digit: frac = (digit + frac) / 10.
+        for digit in frac.bytes().rev() {
+            out.limbs[top] = (digit - b'0') as u64;
+            let mut rest = 0u128;
+            for limb in out.limbs.iter_mut().rev() {
+                let current = (rest << 64) | *limb as u128;
+                *limb = (current / 10) as u64;
+                rest = current % 10;
+            }
+        }
+        out.limbs[top] = int;
+        Ok(if negative { out.neg() } else { out })
+    }
+
+    fn is_negative(&self) -> bool {
+        (self.limbs[self.limbs.len() - 1] as i64) < 0
+    }
+
+    pub fn neg(&self) -> Fixed {
+        let mut out = self.clone();
+        let mut carry = true;
+        for limb in &mut out.limbs {
+            let (value, overflow) = (!*limb).overflowing_add(carry as u64);
+            *limb = value;
+            carry = overflow;
+        }
+        out
+    }
+
+    pub fn add(&self, other: &Fixed) -> Fixed {
+        let mut out = self.clone();
+        let mut carry = false;
+        for (limb, b) in out.limbs.iter_mut().zip(&other.limbs) {
+            let (value, first) = limb.overflowing_add(*b);
+            let (value, second) = value.overflowing_add(carry as u64);
+            *limb = value;
+            carry = first || second;
+        }
+        out
+    }
+
+    pub fn sub(&self, other: &Fixed) -> Fixed {
+        self.add(&other.neg())
+    }
+
+    pub fn mul(&self, other: &Fixed) -> Fixed {
+        let negative = self.is_negative() != other.is_negative();
+        let a = if self.is_negative() {
+            self.neg()
+        } else {
+            self.clone()
+        };
+        let b = if other.is_negative() {
+            other.neg()
+        } else {
+            other.clone()
+        };
+        let n = a.limbs.len();
+        let mut product = vec![0u64; 2 * n];
+        for (i, x) in a.limbs.iter().enumerate() {
+            let mut carry = 0u128;
+            for (j, y) in b.limbs.iter().enumerate() {
+                let current = *x as u128 * *y as u128 + product[i + j] as u128 + carry;
+                product[i + j] = current as u64;
+                carry = current >> 64;
+            }
+            product[i + n] = carry as u64;
+        }
+        let out = Fixed {
+            limbs: product[n - 1..2 * n - 1].to_vec(),
+        };
+        if negative { out.neg() } else { out }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        let (negative, magnitude) = match self.is_negative() {
+            true => (true, self.neg()),
+            false => (false, self.clone()),
+        };
+        let top = magnitude.limbs.len() - 1;
+        // Three limbs carry far more than the 53 bits an f64 keeps.
+        let value: f64 = magnitude
+            .limbs
+            .iter()
+            .enumerate()
+            .rev()
+            .take(3)
+            .map(|(i, limb)| *limb as f64 * 2f64.powi(64 * (i as i32 - top as i32)))
+            .sum();
+        if negative { -value } else { value }
+    }
+}
@@ -0,0 +1,221 @@
+use crate::FrameInfo;
+use crate::anomaly::NAN_COLOR;
+use crate::flame::Rng;
+use crate::health;
+use crate::render::{Complex, IDENTITY, INTERIOR, IterationBuffer, in_main_bulbs};
+use image::{ImageBuffer, Rgb};
+use rayon::prelude::*;
+use std::str::FromStr;
+
+/// Independent sampling runs; fixed so the result does not depend on the thread count.
+const CHUNKS: u64 = 256;
+/// Half the side of the square starting points `c` are drawn from; every
+/// escaping orbit starts inside it.
+const SAMPLE_RADIUS: f64 = 2.0;
+/// Share of the visited pixels below full brightness; the few hottest
+/// pixels around the orbits' fixed points would otherwise darken the rest.
+const PEAK_QUANTILE: f64 = 0.999;
+/// Levels per channel of a packed Nebulabrot pixel.
+const CHANNEL_LEVELS: f64 = 65535.0;
+
+/// `--nebulabrot`: iteration limits of the red, green and blue channels,
+/// e.g. `5000,500,50`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bands(pub [u32; 3]);
+
+impl FromStr for Bands {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Bands, String> {
+        let limits = s
+            .split(',')
+            .map(|v| {
+                v.trim()
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|n| *n >= 1)
+                    .ok_or_else(|| format!("invalid iteration limit {v:?} in {s:?}"))
+            })
+            .collect::<Result<Vec<u32>, String>>()?;
+        match limits[..] {
+            [r, g, b] => Ok(Bands([r, g, b])),
+            _ => Err(format!(
+                "expected three limits for red, green and blue, got {s:?}"
+            )),
+        }
+    }
+}
+
+/// The Buddhabrot: how often the escaping Mandelbrot orbits pass through
+/// each pixel, rather than how long each pixel's own orbit takes to escape.
+#[derive(Debug, Clone, Copy)]
+pub struct Buddhabrot {
+    /// Starting points drawn per output pixel.
+    pub samples_per_pixel: u32,
+    /// Accumulate three channels instead of one, see [`Bands`].
+    pub bands: Option<Bands>,
+}
+
+impl Buddhabrot {
+    /// Samples starting points uniformly over the square around the set and
+    /// plots every orbit that escapes within the limit. Densities are scaled
+    /// to the frame's [`PEAK_QUANTILE`] and square-rooted: one value per pixel
+    /// colored with the palette, or with bands three packed channels that only
+    /// [`colorize_bands`] understands. Pixels no orbit passed are [`INTERIOR`].
+    /// `view` places the frame in the plane, as its pixel mapping does.
+    pub fn render(
+        &self,
+        width: u32,
+        height: u32,
+        view: &FrameInfo,
+        max_iter: u32,
+    ) -> IterationBuffer {
+        let (w, h) = (width as usize, height as usize);
+        let (center, transform) = (view.center, view.transform);
+        let scale = view.zoom / (w.min(h) as f64 / 2.0);
+        let (sin, cos) = view.rotation.to_radians().sin_cos();
+        let [a, b, c, d] = transform;
+        let det = a * d - b * c;
+        // The inverse of `render::transformed_mapping`.
+        let pixel = |z: Complex| {
+            let (dx, dy) = (z.re - center.re, z.im - center.im);
+            let (mut dx, mut dy) = (dx * cos + dy * sin, dy * cos - dx * sin);
+            if transform != IDENTITY {
+                (dx, dy) = ((d * dx - b * dy) / det, (a * dy - c * dx) / det);
+            }
+            let x = dx / scale + w as f64 / 2.0 + 0.5;
+            let y = dy / scale + h as f64 / 2.0 + 0.5;
+            (x >= 0.0 && y >= 0.0 && x < w as f64 && y < h as f64)
+                .then(|| y as usize * w + x as usize)
+        };
+        let limits = match self.bands {
+            Some(Bands(limits)) => limits.to_vec(),
+            None => vec![max_iter],
+        };
+        let limit = limits.iter().copied().max().unwrap_or(max_iter);
+        let samples = w as u64 * h as u64 * self.samples_per_pixel.max(1) as u64;
+        let per_chunk = samples.div_ceil(CHUNKS);
+        let channels = limits.len();
+
+        let counts = (0..CHUNKS)
+            .into_par_iter()
+            .fold(
+                || vec![0u32; w * h * channels],
+                |mut counts, chunk| {
+                    let mut rng = Rng::new(chunk);
+                    for _ in 0..per_chunk {
+                        let c = Complex {
+                            re: (rng.next_f64() * 2.0 - 1.0) * SAMPLE_RADIUS,
+                            im: (rng.next_f64() * 2.0 - 1.0) * SAMPLE_RADIUS,
+                        };
+                        if in_main_bulbs(c) {
+                            continue;
+                        }
+                        let Some(escape) = escape_time(c, limit) else {
+                            continue;
+                        };
+                        // The first point is `c` itself and would only add the uniform
+                        // sampling as a haze over the whole frame.
+                        let mut z = c;
+                        for _ in 1..escape {
+                            z = z.mul(z).add(c);
+                            let Some(idx) = pixel(z) else {
+                                continue;
+                            };
+                            for (k, band) in limits.iter().enumerate() {
+                                if escape <= *band {
+                                    let slot = idx * channels + k;
+                                    counts[slot] = counts[slot].saturating_add(1);
+                                }
+                            }
+                        }
+                    }
+                    health::progressed();
+                    counts
+                },
+            )
+            .reduce(
+                || vec![0u32; w * h * channels],
+                |mut a, b| {
+                    for (x, y) in a.iter_mut().zip(b) {
+                        *x = x.saturating_add(y);
+                    }
+                    a
+                },
+            );
+
+        let peaks: Vec<f64> = (0..channels)
+            .map(|k| {
+                let mut hits: Vec<u32> = counts
+                    .iter()
+                    .skip(k)
+                    .step_by(channels)
+                    .copied()
+                    .filter(|n| *n > 0)
+                    .collect();
+                hits.sort_unstable();
+                let at = ((hits.len() as f64 * PEAK_QUANTILE) as usize).min(hits.len().max(1) - 1);
+                hits.get(at).copied().unwrap_or(1).max(1) as f64
+            })
+            .collect();
+        let level = |n: u32, k: usize| (n as f64 / peaks[k]).min(1.0).sqrt();
+        let values = counts
+            .par_chunks(channels)
+            .map(|pixel| {
+                if pixel.iter().all(|n| *n == 0) {
+                    INTERIOR
+                } else if channels == 1 {
+                    level(pixel[0], 0) * max_iter as f64
+                } else {
+                    pixel.iter().enumerate().fold(0.0, |packed, (k, n)| {
+                        packed * (CHANNEL_LEVELS + 1.0) + (level(*n, k) * CHANNEL_LEVELS).round()
+                    })
+                }
+            })
+            .collect();
+
+        IterationBuffer {
+            width,
+            height,
+            max_iter,
+            values,
+        }
+    }
+}
+
+/// Iterations until the orbit of `c` leaves radius 2, if it does within `limit`.
+fn escape_time(c: Complex, limit: u32) -> Option<u32> {
+    let mut z = Complex::ZERO;
+    for iter in 0..limit {
+        z = z.mul(z).add(c);
+        if z.norm_sqr() > 4.0 {
+            return Some(iter + 1);
+        }
+    }
+    None
+}
+
+/// Red, green and blue from the packed channels of a `--nebulabrot` frame.
+pub fn colorize_bands(iterations: &IterationBuffer) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+    let mut img: ImageBuffer<Rgb<u8>, Vec<u8>> =
+        ImageBuffer::new(iterations.width, iterations.height);
+    img.as_mut()
+        .par_chunks_mut(3)
+        .zip(iterations.values.par_iter())
+        .for_each(|(pixel, &value)| {
+            let color = if value.is_nan() {
+                NAN_COLOR
+            } else if value < 0.0 {
+                [0, 0, 0]
+            } else {
+                let base = CHANNEL_LEVELS + 1.0;
+                let channel = |k: i32| {
+                    let level = (value / base.powi(2 - k)).floor() % base;
+                    (level / CHANNEL_LEVELS * 255.0).round() as u8
+                };
+                [channel(0), channel(1), channel(2)]
+            };
+            pixel.copy_from_slice(&color);
+        });
+    img
+}
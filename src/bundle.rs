@@ -0,0 +1,173 @@
+use crate::manifest::{FrameEntry, MANIFEST_FILE, Manifest};
+use crate::{RenderArgs, config, fixed_path, frame_info};
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const BLOCK: usize = 512;
+
+/// A ustar archive written entry by entry, gzip-compressed for `.tar.gz`/`.tgz`.
+struct Tar {
+    out: Output,
+    mtime: u64,
+}
+
+enum Output {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+}
+
+impl Output {
+    fn writer(&mut self) -> &mut dyn Write {
+        match self {
+            Output::Plain(w) => w,
+            Output::Gzip(w) => w,
+        }
+    }
+}
+
+impl Tar {
+    fn create(path: &Path) -> Result<Tar, String> {
+        let name = path.to_string_lossy().to_ascii_lowercase();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("create {parent:?}: {e}"))?;
+        }
+        let file = BufWriter::new(File::create(path).map_err(|e| format!("create {path:?}: {e}"))?);
+        let out = if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Output::Gzip(GzEncoder::new(file, Compression::default()))
+        } else {
+            Output::Plain(file)
+        };
+        let mtime = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        Ok(Tar { out, mtime })
+    }
+
+    fn add(&mut self, name: &str, data: &[u8]) -> Result<(), String> {
+        let mut header = [0u8; BLOCK];
+        let (prefix, base) = split_name(name)?;
+        header[..base.len()].copy_from_slice(base.as_bytes());
+        octal(&mut header[100..108], 0o644);
+        octal(&mut header[108..116], 0);
+        octal(&mut header[116..124], 0);
+        octal(&mut header[124..136], data.len() as u64);
+        octal(&mut header[136..148], self.mtime);
+        header[156] = b'0';
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[263..265].copy_from_slice(b"00");
+        header[345..345 + prefix.len()].copy_from_slice(prefix.as_bytes());
+        header[148..156].fill(b' ');
+        let checksum: u32 = header.iter().map(|&b| b as u32).sum();
+        octal(&mut header[148..155], checksum as u64);
+
+        let padding = (BLOCK - data.len() % BLOCK) % BLOCK;
+        let out = self.out.writer();
+        out.write_all(&header)
+            .and_then(|()| out.write_all(data))
+            .and_then(|()| out.write_all(&[0; BLOCK][..padding]))
+            .map_err(|e| format!("write bundle entry {name}: {e}"))
+    }
+
+    fn finish(mut self) -> Result<(), String> {
+        self.out
+            .writer()
+            .write_all(&[0; 2 * BLOCK])
+            .and_then(|()| match self.out {
+                Output::Plain(mut w) => w.flush(),
+                Output::Gzip(w) => w.finish()?.flush(),
+            })
+            .map_err(|e| format!("write bundle: {e}"))
+    }
+}
+
+/// Accepts `.tar.gz`, `.tgz` and `.tar` bundle paths.
+pub fn parse_path(s: &str) -> Result<String, String> {
+    let name = s.to_ascii_lowercase();
+    if [".tar.gz", ".tgz", ".tar"]
+        .iter()
+        .any(|ext| name.ends_with(ext))
+    {
+        Ok(s.to_string())
+    } else {
+        Err(format!(
+            "unsupported bundle {s:?}, expected a .tar.gz or .tar file (zstd is not available in this build)"
+        ))
+    }
+}
+
+/// Splits a path into the ustar `prefix` (155 bytes) and `name` (100 bytes) fields.
+fn split_name(name: &str) -> Result<(&str, &str), String> {
+    if name.len() <= 100 {
+        return Ok(("", name));
+    }
+    name.char_indices()
+        .filter(|&(i, c)| c == '/' && i <= 155 && name.len() - i - 1 <= 100)
+        .map(|(i, _)| (&name[..i], &name[i + 1..]))
+        .next()
+        .ok_or_else(|| format!("path too long for a tar entry: {name}"))
+}
+
+/// Zero-padded octal with a trailing NUL, filling the field.
+fn octal(field: &mut [u8], value: u64) {
+    let last = field.len() - 1;
+    let digits = format!("{value:0last$o}");
+    field[..last].copy_from_slice(digits.as_bytes());
+    field[last] = 0;
+}
+
+/// Where a frame file goes under `frames/`: where the manifest has it, or
+/// for a file the manifest lists elsewhere, such as in --out-dir after it
+/// moved to --fallback-dir, where the run would have put it.
+fn bundled_name(entry: &FrameEntry) -> String {
+    let file = Path::new(&entry.file);
+    if !file.is_absolute() {
+        return entry.file.replace('\\', "/");
+    }
+    let name = file.file_name().unwrap_or_default().to_string_lossy();
+    match &entry.variant {
+        Some(label) => format!("{label}/{name}"),
+        None => name.into_owned(),
+    }
+}
+
+/// Packs what another machine needs to reproduce or continue this run: the
+/// resolved settings as `config.toml` (palettes and layers included), the
+/// zoom path as `path.txt` and the manifest under `frames/`, plus the frame
+/// files themselves with `--bundle-frames`.
+pub fn export(args: &RenderArgs, manifest: &Manifest, bundle: &Path) -> Result<(), String> {
+    let mut tar = Tar::create(bundle)?;
+    tar.add(
+        "config.toml",
+        config::to_toml(&manifest.settings).as_bytes(),
+    )?;
+
+    let path = fixed_path(args.fractal);
+    let mut path_text = String::from("# frame zoom center_re center_im\n");
+    let rendered = manifest.frames.iter().filter(|f| f.variant.is_none());
+    for frame in rendered.map(|f| f.index) {
+        let info = frame_info(args, &path, frame);
+        path_text.push_str(&format!(
+            "{frame} {:e} {:.17} {:.17}\n",
+            info.zoom, info.center.re, info.center.im
+        ));
+    }
+    tar.add("path.txt", path_text.as_bytes())?;
+
+    let manifest_path = manifest.dir().join(MANIFEST_FILE);
+    let manifest_bytes =
+        fs::read(&manifest_path).map_err(|e| format!("read {manifest_path:?}: {e}"))?;
+    tar.add(&format!("frames/{MANIFEST_FILE}"), &manifest_bytes)?;
+    if args.bundle_frames {
+        for entry in &manifest.frames {
+            let file = manifest.dir().join(&entry.file);
+            let bytes = fs::read(&file).map_err(|e| format!("read {file:?}: {e}"))?;
+            tar.add(&format!("frames/{}", bundled_name(entry)), &bytes)?;
+        }
+    }
+    tar.finish()
+}
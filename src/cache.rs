@@ -0,0 +1,200 @@
+use crate::render::IterationBuffer;
+use crate::{FrameInfo, output, sha256};
+use flate2::Compression;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+const MAGIC: &[u8; 4] = b"MBCF";
+const FORMAT: &str = "mandelbrot-cache-1";
+
+/// Settings that never change a frame's iterations.
+const OUTPUT_ONLY: [&str; 61] = [
+    "boundary_aa",
+    "deflicker",
+    "deflicker_tolerance",
+    "coloring",
+    "chunk",
+    "chunk_size",
+    "palette",
+    "palette_phase",
+    "palettes",
+    "compare",
+    "view",
+    "view_columns",
+    "layer",
+    "texture",
+    "palette_jitter",
+    "palette_jitter_every",
+    "palette_seed",
+    "overlay",
+    "strings",
+    "grid",
+    "legend",
+    "legend_file",
+    "canvas",
+    "preset",
+    "stats_hud",
+    "save_iterations",
+    "format",
+    "quality",
+    "bit_depth",
+    "exr_distance",
+    "error_map",
+    "aa_sample_map",
+    "anomaly",
+    "anomaly_retries",
+    "frame_budget",
+    "frame_order",
+    "start_frame",
+    "end_frame",
+    "frame_jobs",
+    "threads",
+    "nice",
+    "out_dir",
+    "fallback_dir",
+    "log_file",
+    "preview_video",
+    "preview_scale",
+    "preview_only",
+    "safe_area",
+    "non_interactive",
+    "status_file",
+    "bundle",
+    "bundle_frames",
+    "stream",
+    "stream_lead",
+    "realtime",
+    "encode",
+    "encode_segment",
+    "no_resume",
+    "resume",
+    "cache_dir",
+    "reference_dir",
+];
+
+/// Settings that only decide where each frame looks, which the key already
+/// records exactly. With --cascade they also decide which frames share a
+/// keyframe, so they stay in the key.
+const TIMELINE: [&str; 6] = [
+    "frames",
+    "fps",
+    "zoom_start",
+    "zoom_end",
+    "zoom_rate",
+    "endless",
+];
+
+/// Iteration data of finished frames, stored under a hash of everything that
+/// went into them, so any later run that needs exactly the same frame reuses
+/// it: after an interruption, with more frames appended to an endless run,
+/// or with only the coloring changed.
+pub struct FrameCache {
+    dir: PathBuf,
+    settings: String,
+}
+
+impl FrameCache {
+    pub fn open(
+        dir: &Path,
+        settings: &[(String, String)],
+        cascade: bool,
+    ) -> Result<FrameCache, String> {
+        fs::create_dir_all(dir).map_err(|e| output::describe_error("create", dir, &e))?;
+        let mut relevant: Vec<&(String, String)> = settings
+            .iter()
+            .filter(|(key, _)| !OUTPUT_ONLY.contains(&key.as_str()))
+            .filter(|(key, _)| cascade || !TIMELINE.contains(&key.as_str()))
+            .collect();
+        // Stable, so repeated keys keep their order.
+        relevant.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut text = format!("{FORMAT}\n");
+        for (key, value) in relevant {
+            text.push_str(&format!("{key}={value}\n"));
+        }
+        Ok(FrameCache {
+            dir: dir.to_path_buf(),
+            settings: text,
+        })
+    }
+
+    fn path(&self, info: &FrameInfo) -> PathBuf {
+        let key = format!(
+            "{}frame={} total={} t={:x} center={:x},{:x} zoom={:x} fps={}\n",
+            self.settings,
+            info.index,
+            info.total,
+            info.t.to_bits(),
+            info.center.re.to_bits(),
+            info.center.im.to_bits(),
+            info.zoom.to_bits(),
+            info.fps
+        );
+        let hash = sha256::hex_digest(key.as_bytes());
+        self.dir.join(&hash[..2]).join(format!("{hash}.bin"))
+    }
+
+    /// The stored iterations of this frame, if any. Unreadable entries are
+    /// treated as missing and get rendered again.
+    pub fn load(&self, info: &FrameInfo) -> Option<IterationBuffer> {
+        let bytes = fs::read(self.path(info)).ok()?;
+        decode(&bytes)
+    }
+
+    pub fn store(&self, info: &FrameInfo, iterations: &IterationBuffer) -> Result<(), String> {
+        let path = self.path(info);
+        let parent = path.parent().expect("cache entries live in a subdirectory");
+        fs::create_dir_all(parent).map_err(|e| output::describe_error("create", parent, &e))?;
+        let tmp = output::tmp_path(&path);
+        fs::write(&tmp, encode(iterations))
+            .and_then(|()| fs::rename(&tmp, &path))
+            .map_err(|e| {
+                let _ = fs::remove_file(&tmp);
+                output::describe_error("save", &path, &e)
+            })
+    }
+}
+
+/// Header and deflated little-endian f64 values, exact unlike the f32
+/// `.iter` files, so a cached frame colors to the same pixels.
+fn encode(iterations: &IterationBuffer) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&iterations.width.to_le_bytes());
+    bytes.extend_from_slice(&iterations.height.to_le_bytes());
+    bytes.extend_from_slice(&iterations.max_iter.to_le_bytes());
+    let mut encoder = DeflateEncoder::new(bytes, Compression::fast());
+    for value in &iterations.values {
+        encoder
+            .write_all(&value.to_le_bytes())
+            .expect("writing to a Vec cannot fail");
+    }
+    encoder.finish().expect("writing to a Vec cannot fail")
+}
+
+fn decode(bytes: &[u8]) -> Option<IterationBuffer> {
+    if bytes.len() < 16 || &bytes[..4] != MAGIC {
+        return None;
+    }
+    let word = |i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+    let (width, height, max_iter) = (word(4), word(8), word(12));
+    let mut raw = Vec::new();
+    DeflateDecoder::new(&bytes[16..])
+        .read_to_end(&mut raw)
+        .ok()?;
+    if raw.len() != width as usize * height as usize * 8 {
+        return None;
+    }
+    let values = raw
+        .chunks_exact(8)
+        .map(|chunk| f64::from_le_bytes(chunk.try_into().unwrap()))
+        .collect();
+    Some(IterationBuffer {
+        width,
+        height,
+        max_iter,
+        values,
+    })
+}
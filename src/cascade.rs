@@ -0,0 +1,165 @@
+use crate::render::{self, Complex, INTERIOR, IterationBuffer};
+use crate::{FrameInfo, RenderArgs, fixed_path, frame_info};
+use std::sync::{Arc, Mutex};
+
+/// Keyframes are never rendered larger than this many times the frame size
+/// per side; frames deeper than that are upscaled slightly.
+const MAX_OVERSAMPLE: f64 = 4.0;
+/// Keyframes kept around so layers with their own --max-iter and re-renders
+/// of neighbouring frames don't recompute them.
+const CACHED_KEYS: usize = 4;
+
+/// Everything a keyframe's content depends on.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct KeyId {
+    first: u32,
+    width: u32,
+    height: u32,
+    max_iter: u32,
+    derivative_bailout: bool,
+    center: (u64, u64),
+    zoom: u64,
+}
+
+struct Key {
+    id: KeyId,
+    center: Complex,
+    /// Plane units per keyframe pixel.
+    scale: f64,
+    iterations: IterationBuffer,
+}
+
+static KEYS: Mutex<Vec<Arc<Key>>> = Mutex::new(Vec::new());
+
+/// Iterations of a Mandelbrot frame under `--cascade N`.
+///
+/// Every N frames share one keyframe that covers all of their views at the
+/// pixel density of the deepest one. Each frame resamples it instead of
+/// iterating, so only one keyframe is iterated per N frames; pixels the
+/// keyframe doesn't reach are iterated directly. Groups where the keyframe
+/// would cost more than the frames themselves are iterated normally.
+pub fn iterate(
+    args: &RenderArgs,
+    info: &FrameInfo,
+    width: u32,
+    height: u32,
+    max_iter: u32,
+) -> IterationBuffer {
+    let Some(key) = keyframe(args, info.index, width, height, max_iter) else {
+        return render::iterate_frame(
+            width,
+            height,
+            info.center,
+            info.zoom,
+            max_iter,
+            args.derivative_bailout,
+        );
+    };
+    let point = render::pixel_mapping(width, height, info.center, info.zoom);
+    let (kw, kh) = (key.iterations.width, key.iterations.height);
+    IterationBuffer::from_fn(width, height, max_iter, |x, y| {
+        let c = point(x, y);
+        let u = (c.re - key.center.re) / key.scale + kw as f64 / 2.0;
+        let v = (c.im - key.center.im) / key.scale + kh as f64 / 2.0;
+        if u < 0.0 || v < 0.0 || u > (kw - 1) as f64 || v > (kh - 1) as f64 {
+            return render::mandelbrot_smooth(c, max_iter, args.derivative_bailout);
+        }
+        sample(&key.iterations, u, v)
+    })
+}
+
+/// Bilinear between exterior values; nearest when an interior or NaN
+/// pixel is involved, since blending with those has no meaning.
+fn sample(key: &IterationBuffer, u: f64, v: f64) -> f64 {
+    let (x0, y0) = (u.floor() as u32, v.floor() as u32);
+    let (x1, y1) = ((x0 + 1).min(key.width - 1), (y0 + 1).min(key.height - 1));
+    let (fx, fy) = (u - x0 as f64, v - y0 as f64);
+    let at = |x: u32, y: u32| key.values[(y * key.width + x) as usize];
+    let corners = [at(x0, y0), at(x1, y0), at(x0, y1), at(x1, y1)];
+    if corners.iter().any(|&c| c == INTERIOR || !c.is_finite()) {
+        let x = if fx < 0.5 { x0 } else { x1 };
+        let y = if fy < 0.5 { y0 } else { y1 };
+        return at(x, y);
+    }
+    let top = corners[0] + (corners[1] - corners[0]) * fx;
+    let bottom = corners[2] + (corners[3] - corners[2]) * fx;
+    top + (bottom - top) * fy
+}
+
+fn keyframe(
+    args: &RenderArgs,
+    frame: u32,
+    width: u32,
+    height: u32,
+    max_iter: u32,
+) -> Option<Arc<Key>> {
+    let path = fixed_path(args.fractal);
+    let n = args.cascade.max(1);
+    let first = frame / n * n;
+    let last = first.saturating_add(n - 1).min(args.total_frames() - 1);
+
+    // Bounding box of every view in the group, and its finest pixel scale.
+    let half_min = width.min(height) as f64 / 2.0;
+    let (ax, ay) = (
+        width as f64 / 2.0 / half_min,
+        height as f64 / 2.0 / half_min,
+    );
+    let (mut lo, mut hi) = ((f64::MAX, f64::MAX), (f64::MIN, f64::MIN));
+    let mut narrowest = f64::MAX;
+    for f in first..=last {
+        let view = frame_info(args, &path, f);
+        lo.0 = lo.0.min(view.center.re - view.zoom * ax);
+        lo.1 = lo.1.min(view.center.im - view.zoom * ay);
+        hi.0 = hi.0.max(view.center.re + view.zoom * ax);
+        hi.1 = hi.1.max(view.center.im + view.zoom * ay);
+        narrowest = narrowest.min(view.zoom);
+    }
+    let center = Complex {
+        re: (lo.0 + hi.0) / 2.0,
+        im: (lo.1 + hi.1) / 2.0,
+    };
+    let zoom = ((hi.0 - lo.0) / 2.0 / ax).max((hi.1 - lo.1) / 2.0 / ay);
+    let id = KeyId {
+        first,
+        width,
+        height,
+        max_iter,
+        derivative_bailout: args.derivative_bailout,
+        center: (center.re.to_bits(), center.im.to_bits()),
+        zoom: zoom.to_bits(),
+    };
+
+    let oversample = (zoom / narrowest).clamp(1.0, MAX_OVERSAMPLE);
+    let kw = (width as f64 * oversample).ceil() as u32;
+    let kh = (height as f64 * oversample).ceil() as u32;
+    if kw as u64 * kh as u64 >= (last - first + 1) as u64 * width as u64 * height as u64 {
+        // The group zooms or pans so fast that the keyframe would cost more
+        // than iterating its frames one by one.
+        return None;
+    }
+
+    let cached = |keys: &[Arc<Key>]| keys.iter().find(|k| k.id == id).cloned();
+    if let Some(key) = cached(&KEYS.lock().unwrap_or_else(|e| e.into_inner())) {
+        return Some(key);
+    }
+    // Iterated without the lock: under --frame-jobs a worker waiting in this
+    // frame's parallel loop may pick up another frame that needs a keyframe
+    // too, and would wait for a lock its own thread holds. Frames that miss
+    // the keyframe at once both iterate it; the first one stored is kept.
+    let iterations = render::iterate_frame(kw, kh, center, zoom, max_iter, args.derivative_bailout);
+    let key = Arc::new(Key {
+        id,
+        center,
+        scale: zoom / (kw.min(kh) as f64 / 2.0),
+        iterations,
+    });
+    let mut keys = KEYS.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(key) = cached(&keys) {
+        return Some(key);
+    }
+    if keys.len() == CACHED_KEYS {
+        keys.remove(0);
+    }
+    keys.push(key.clone());
+    Some(key)
+}
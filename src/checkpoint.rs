@@ -0,0 +1,72 @@
+use crate::{manifest, output};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub const CHECKPOINT_FILE: &str = "checkpoint.txt";
+
+/// Where an interrupted render stopped, one `key=value` per line in its
+/// output directory, for `--resume` to continue from.
+pub struct Checkpoint {
+    /// The first frame, in the run's --frame-order, it didn't finish.
+    pub next_frame: u32,
+    /// [`manifest::settings_hash`] of the run's settings.
+    pub settings: String,
+}
+
+impl Checkpoint {
+    pub fn new(next_frame: u32, settings: &[(String, String)]) -> Checkpoint {
+        Checkpoint {
+            next_frame,
+            settings: manifest::settings_hash(settings),
+        }
+    }
+
+    /// Replaces the checkpoint of `dir` atomically, so an interrupted write
+    /// never leaves half of one behind.
+    pub fn write(&self, dir: &Path) -> Result<PathBuf, String> {
+        let path = dir.join(CHECKPOINT_FILE);
+        let text = format!(
+            "next_frame={}\nsettings={}\n",
+            self.next_frame, self.settings
+        );
+        let tmp = output::tmp_path(&path);
+        fs::write(&tmp, text)
+            .and_then(|()| fs::rename(&tmp, &path))
+            .map_err(|e| output::describe_error("write", &path, &e))?;
+        Ok(path)
+    }
+
+    /// The checkpoint of `dir`, `None` if there is none.
+    pub fn read(dir: &Path) -> Result<Option<Checkpoint>, String> {
+        let path = dir.join(CHECKPOINT_FILE);
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(output::describe_error("read", &path, &e)),
+        };
+        let get = |key: &str| {
+            text.lines()
+                .find_map(|line| line.strip_prefix(key)?.strip_prefix('='))
+                .ok_or_else(|| format!("{}: no {key}", path.display()))
+        };
+        let next_frame = get("next_frame")?;
+        Ok(Some(Checkpoint {
+            next_frame: next_frame
+                .parse()
+                .map_err(|_| format!("{}: bad next_frame {next_frame:?}", path.display()))?,
+            settings: get("settings")?.to_string(),
+        }))
+    }
+
+    /// Removes the checkpoint of `dir` once the run it stopped is complete.
+    pub fn remove(dir: &Path) -> Result<(), String> {
+        let path = dir.join(CHECKPOINT_FILE);
+        match fs::remove_file(&path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => {
+                Err(output::describe_error("remove", &path, &e))
+            }
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,457 @@
+use crate::anomaly::NAN_COLOR;
+use crate::palette::Palette;
+use crate::render::{Complex, IterationBuffer};
+use crate::{FrameInfo, RenderArgs};
+use image::{ImageBuffer, Rgb};
+use rayon::prelude::*;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Per-pixel orbit data a coloring needs beyond the escape time, computed in
+/// a second pass over the exterior pixels only when asked for.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Needs {
+    pub distance: bool,
+    pub trap: bool,
+    pub stripe: bool,
+}
+
+impl Needs {
+    fn any(self) -> bool {
+        self.distance || self.trap || self.stripe
+    }
+
+    fn or(self, other: Needs) -> Needs {
+        Needs {
+            distance: self.distance || other.distance,
+            trap: self.trap || other.trap,
+            stripe: self.stripe || other.stripe,
+        }
+    }
+}
+
+/// Everything known about a frame's pixels when it is colored. The orbit
+/// vectors are empty unless some coloring of the frame needs them.
+pub struct FrameData<'a> {
+    pub iterations: &'a IterationBuffer,
+    /// Plane units per pixel.
+    pub pixel_scale: f64,
+    pub distance: Vec<f64>,
+    pub trap: Vec<f64>,
+    pub stripe: Vec<f64>,
+}
+
+/// One way of turning a frame's data into a value between 0 and 1 for
+/// every pixel; interior and NaN pixels may get any value, they are not used.
+pub trait Colorizer: Send + Sync {
+    fn needs(&self) -> Needs {
+        Needs::default()
+    }
+
+    fn shade(&self, frame: &FrameData) -> Vec<f64>;
+}
+
+type Constructor = fn() -> Arc<dyn Colorizer>;
+
+/// Colorizers selectable with `--coloring`, by name.
+const REGISTRY: [(&str, Constructor); 7] = [
+    ("smooth", || Arc::new(Smooth)),
+    ("histogram", || Arc::new(Histogram)),
+    ("de", || Arc::new(DistanceEstimate)),
+    ("trap", || Arc::new(OrbitTrap)),
+    ("stripes", || Arc::new(Stripes)),
+    ("glow", || Arc::new(Glow)),
+    ("edges", || Arc::new(Edges)),
+];
+
+/// `--coloring`: the first colorizer picks the palette position, every
+/// further one (joined with `*`) scales the brightness, e.g. `smooth*de`.
+#[derive(Clone)]
+pub struct Coloring {
+    spec: String,
+    colorizers: Vec<Arc<dyn Colorizer>>,
+}
+
+impl fmt::Debug for Coloring {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Coloring({:?})", self.spec)
+    }
+}
+
+impl FromStr for Coloring {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Coloring, String> {
+        let colorizers = s
+            .split('*')
+            .map(|name| {
+                let name = name.trim().to_ascii_lowercase();
+                REGISTRY
+                    .iter()
+                    .find(|(n, _)| *n == name)
+                    .map(|(_, make)| make())
+                    .ok_or_else(|| {
+                        let names: Vec<&str> = REGISTRY.iter().map(|(n, _)| *n).collect();
+                        format!(
+                            "unknown coloring {name:?}, expected one of {} joined with *",
+                            names.join(", ")
+                        )
+                    })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(Coloring {
+            spec: s.trim().to_ascii_lowercase(),
+            colorizers,
+        })
+    }
+}
+
+impl Coloring {
+    /// Plain smooth escape-time coloring, the fast path that needs no extra data.
+    pub fn is_smooth(&self) -> bool {
+        self.spec == "smooth"
+    }
+
+    fn needs(&self) -> Needs {
+        self.colorizers
+            .iter()
+            .fold(Needs::default(), |acc, c| acc.or(c.needs()))
+    }
+
+    /// Rejects colorings that need orbit data the chosen fractal can't provide.
+    pub fn check(&self, args: &RenderArgs) -> Result<(), String> {
+        if self.needs().any() && !args.quadratic_mandelbrot() {
+            return Err(format!(
+                "--coloring {} needs orbit data, which only --fractal mandelbrot with --power 2 provides",
+                self.spec
+            ));
+        }
+        if !self.is_smooth() && args.boundary_aa > 1 {
+            return Err("--boundary-aa only supports --coloring smooth".to_string());
+        }
+        Ok(())
+    }
+
+    pub fn colorize(
+        &self,
+        iterations: &IterationBuffer,
+        info: &FrameInfo,
+        palette: &Palette,
+        phase: f64,
+    ) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+        if self.is_smooth() {
+            return iterations.colorize_with_phase(palette, phase);
+        }
+        let frame = frame_data(iterations, info, self.needs());
+        let mut shades = self.colorizers.iter().map(|c| c.shade(&frame));
+        let position = shades.next().expect("at least one colorizer");
+        let brightness = shades.fold(vec![1.0; position.len()], |mut acc, shade| {
+            acc.iter_mut().zip(shade).for_each(|(a, s)| *a *= s);
+            acc
+        });
+        let mut img: ImageBuffer<Rgb<u8>, Vec<u8>> =
+            ImageBuffer::new(iterations.width, iterations.height);
+        img.as_mut()
+            .par_chunks_mut(3)
+            .enumerate()
+            .for_each(|(idx, pixel)| {
+                let value = iterations.values[idx];
+                let color = if value.is_nan() {
+                    NAN_COLOR
+                } else if value < 0.0 {
+                    [0, 0, 0]
+                } else {
+                    let t = position[idx].clamp(0.0, 1.0);
+                    let t = if phase == 0.0 {
+                        t
+                    } else {
+                        (t + phase).rem_euclid(1.0)
+                    };
+                    let b = brightness[idx].clamp(0.0, 1.0);
+                    palette.color(t).map(|c| (c as f64 * b).round() as u8)
+                };
+                pixel.copy_from_slice(&color);
+            });
+        img
+    }
+}
+
+/// Escape radius of the orbit pass; much larger than the kernel's 2 so the
+/// distance estimate is accurate.
+const ORBIT_ESCAPE_SQR: f64 = 1e6;
+/// Stripes per full turn of the orbit's argument.
+const STRIPE_DENSITY: f64 = 4.0;
+/// Pixels the escape-time gradient is spread over by `glow`.
+const GLOW_RADIUS: usize = 2;
+/// Brightness `glow` leaves where the escape time is flat.
+const GLOW_FLOOR: f64 = 0.25;
+/// Brightness of the middle of an `edges` line.
+const EDGE_SHADE: f64 = 0.4;
+
+fn frame_data<'a>(
+    iterations: &'a IterationBuffer,
+    info: &FrameInfo,
+    needs: Needs,
+) -> FrameData<'a> {
+    let (w, h) = (iterations.width, iterations.height);
+    let pixel_scale = info.zoom / (w.min(h) as f64 / 2.0);
+    let mut frame = FrameData {
+        iterations,
+        pixel_scale,
+        distance: Vec::new(),
+        trap: Vec::new(),
+        stripe: Vec::new(),
+    };
+    if !needs.any() {
+        return frame;
+    }
+    let point = info.pixel_mapping(w, h);
+    let orbits: Vec<(f64, f64, f64)> = (0..iterations.values.len())
+        .into_par_iter()
+        .map(|idx| {
+            if iterations.values[idx] < 0.0 || iterations.values[idx].is_nan() {
+                return (0.0, 0.0, 0.0);
+            }
+            let c = point(idx as u32 % w, idx as u32 / w);
+            orbit(c, iterations.max_iter)
+        })
+        .collect();
+    if needs.distance {
+        frame.distance = orbits.iter().map(|o| o.0).collect();
+    }
+    if needs.trap {
+        frame.trap = orbits.iter().map(|o| o.1).collect();
+    }
+    if needs.stripe {
+        frame.stripe = orbits.iter().map(|o| o.2).collect();
+    }
+    frame
+}
+
+/// Distance to the set of every pixel in pixels, 0 for interior and NaN pixels.
+pub fn pixel_distances(iterations: &IterationBuffer, info: &FrameInfo) -> Vec<f64> {
+    let needs = Needs {
+        distance: true,
+        ..Needs::default()
+    };
+    let frame = frame_data(iterations, info, needs);
+    frame
+        .distance
+        .iter()
+        .map(|d| d / frame.pixel_scale)
+        .collect()
+}
+
+/// Distance estimate, closest approach to the origin and stripe average of
+/// the orbit of `c`.
+fn orbit(c: Complex, max_iter: u32) -> (f64, f64, f64) {
+    let one = Complex { re: 1.0, im: 0.0 };
+    let (mut z, mut dz) = (Complex::ZERO, Complex::ZERO);
+    let (mut trap, mut stripe_sum, mut stripes) = (f64::MAX, 0.0, 0u32);
+    let mut iter = 0;
+    while iter < max_iter && z.norm_sqr() <= ORBIT_ESCAPE_SQR {
+        dz = z.add(z).mul(dz).add(one);
+        z = z.mul(z).add(c);
+        iter += 1;
+        trap = trap.min(z.norm_sqr().sqrt());
+        if iter > 1 && z.norm_sqr() <= ORBIT_ESCAPE_SQR {
+            stripe_sum += 0.5 * (STRIPE_DENSITY * z.im.atan2(z.re)).sin() + 0.5;
+            stripes += 1;
+        }
+    }
+    let r = z.norm_sqr().sqrt();
+    let distance = if r * r > ORBIT_ESCAPE_SQR {
+        r * r.ln() / dz.norm_sqr().sqrt()
+    } else {
+        0.0
+    };
+    let stripe = if stripes > 0 {
+        stripe_sum / stripes as f64
+    } else {
+        0.0
+    };
+    (distance, trap, stripe)
+}
+
+/// Escape time over `max_iter`, as frames have always been colored.
+struct Smooth;
+
+impl Colorizer for Smooth {
+    fn shade(&self, frame: &FrameData) -> Vec<f64> {
+        let max_iter = frame.iterations.max_iter as f64;
+        frame
+            .iterations
+            .values
+            .iter()
+            .map(|v| v / max_iter)
+            .collect()
+    }
+}
+
+/// Rank of the escape time among the frame's exterior pixels, so every
+/// palette color covers the same share of the picture at any depth.
+struct Histogram;
+
+impl Colorizer for Histogram {
+    fn shade(&self, frame: &FrameData) -> Vec<f64> {
+        let values = &frame.iterations.values;
+        let mut exterior: Vec<f64> = values.iter().copied().filter(|v| *v >= 0.0).collect();
+        exterior.sort_by(f64::total_cmp);
+        let count = exterior.len().max(1) as f64;
+        values
+            .par_iter()
+            .map(|v| exterior.partition_point(|e| e < v) as f64 / count)
+            .collect()
+    }
+}
+
+/// Distance to the set in pixels, dark right at the boundary.
+struct DistanceEstimate;
+
+impl Colorizer for DistanceEstimate {
+    fn needs(&self) -> Needs {
+        Needs {
+            distance: true,
+            ..Needs::default()
+        }
+    }
+
+    fn shade(&self, frame: &FrameData) -> Vec<f64> {
+        frame
+            .distance
+            .iter()
+            .map(|d| (d / frame.pixel_scale).clamp(0.0, 1.0).sqrt())
+            .collect()
+    }
+}
+
+/// Closest approach of the orbit to the origin.
+struct OrbitTrap;
+
+impl Colorizer for OrbitTrap {
+    fn needs(&self) -> Needs {
+        Needs {
+            trap: true,
+            ..Needs::default()
+        }
+    }
+
+    fn shade(&self, frame: &FrameData) -> Vec<f64> {
+        frame
+            .trap
+            .iter()
+            .map(|d| (d / 2.0).clamp(0.0, 1.0).sqrt())
+            .collect()
+    }
+}
+
+/// Average of `sin(density · arg z)` along the orbit.
+struct Stripes;
+
+impl Colorizer for Stripes {
+    fn needs(&self) -> Needs {
+        Needs {
+            stripe: true,
+            ..Needs::default()
+        }
+    }
+
+    fn shade(&self, frame: &FrameData) -> Vec<f64> {
+        frame.stripe.clone()
+    }
+}
+
+/// Escape-time change per pixel, from central differences with the exterior
+/// neighbours; 0 for pixels without any.
+fn gradient(iterations: &IterationBuffer) -> Vec<f64> {
+    let (w, h) = (iterations.width as usize, iterations.height as usize);
+    let values = &iterations.values;
+    // NaN fails the comparison too.
+    let exterior = |idx: usize| values[idx] >= 0.0;
+    (0..values.len())
+        .into_par_iter()
+        .map(|idx| {
+            if !exterior(idx) {
+                return 0.0;
+            }
+            let slope = |prev: Option<usize>, next: Option<usize>| match (
+                prev.filter(|&i| exterior(i)),
+                next.filter(|&i| exterior(i)),
+            ) {
+                (Some(p), Some(n)) => (values[n] - values[p]) / 2.0,
+                (Some(p), None) => values[idx] - values[p],
+                (None, Some(n)) => values[n] - values[idx],
+                (None, None) => 0.0,
+            };
+            let (x, y) = (idx % w, idx / w);
+            let dx = slope((x > 0).then(|| idx - 1), (x + 1 < w).then(|| idx + 1));
+            let dy = slope((y > 0).then(|| idx - w), (y + 1 < h).then(|| idx + w));
+            dx.hypot(dy)
+        })
+        .collect()
+}
+
+/// Mean of `values` over the square of `radius` pixels around each pixel.
+fn box_blur(values: &[f64], w: usize, h: usize, radius: usize) -> Vec<f64> {
+    let pass = |values: &[f64], horizontal: bool| -> Vec<f64> {
+        (0..values.len())
+            .into_par_iter()
+            .map(|idx| {
+                let (x, y) = (idx % w, idx / w);
+                let (pos, len) = if horizontal { (x, w) } else { (y, h) };
+                let (lo, hi) = (pos.saturating_sub(radius), (pos + radius).min(len - 1));
+                let sum: f64 = (lo..=hi)
+                    .map(|p| {
+                        if horizontal {
+                            values[y * w + p]
+                        } else {
+                            values[p * w + x]
+                        }
+                    })
+                    .sum();
+                sum / (hi - lo + 1) as f64
+            })
+            .collect()
+    };
+    pass(&pass(values, true), false)
+}
+
+/// Light spreading out from where the escape time changes fastest, worked
+/// out on the iterations before they become colors: the filaments glow and
+/// the flat bands in between fall dark.
+struct Glow;
+
+impl Colorizer for Glow {
+    fn shade(&self, frame: &FrameData) -> Vec<f64> {
+        let iterations = frame.iterations;
+        let (w, h) = (iterations.width as usize, iterations.height as usize);
+        box_blur(&gradient(iterations), w, h, GLOW_RADIUS)
+            .into_iter()
+            .map(|g| GLOW_FLOOR + (1.0 - GLOW_FLOOR) * (1.0 - (-g).exp()))
+            .collect()
+    }
+}
+
+/// Soft lines about a pixel wide where the whole number of iterations
+/// steps up, outlining the dwell bands.
+struct Edges;
+
+impl Colorizer for Edges {
+    fn shade(&self, frame: &FrameData) -> Vec<f64> {
+        let values = &frame.iterations.values;
+        gradient(frame.iterations)
+            .into_iter()
+            .zip(values)
+            .map(|(g, v)| {
+                if g <= 0.0 {
+                    return 1.0;
+                }
+                let fract = v.rem_euclid(1.0);
+                // Pixels from the nearest step.
+                let distance = fract.min(1.0 - fract) / g;
+                EDGE_SHADE + (1.0 - EDGE_SHADE) * distance.clamp(0.0, 1.0)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,87 @@
+use crate::composite::BlendMode;
+use crate::draw::{self, Paint};
+use crate::{RenderArgs, font, settings};
+use clap::Args;
+use image::{GenericImage, ImageBuffer, Rgb};
+
+/// Subdirectory and manifest variant of the side-by-side frames.
+pub const LABEL: &str = "compare";
+
+const TEXT: Paint = Paint {
+    color: [255, 255, 255, 255],
+    blend: BlendMode::Normal,
+};
+const BACKDROP: Paint = Paint {
+    color: [0, 0, 0, 160],
+    blend: BlendMode::Normal,
+};
+
+/// Parses one `--compare` override, `key=value` with the key spelled like a
+/// config file key or a flag (`max_iter` or `max-iter`).
+pub fn parse_override(s: &str) -> Result<(String, String), String> {
+    let (key, value) = s
+        .split_once('=')
+        .ok_or_else(|| format!("invalid comparison {s:?}, expected key=value"))?;
+    let key = key.trim().replace('-', "_");
+    let cmd = RenderArgs::augment_args(clap::Command::new("compare"));
+    if !cmd.get_arguments().any(|a| a.get_id() == key.as_str()) {
+        return Err(format!("unknown setting {key:?} in comparison {s:?}"));
+    }
+    Ok((key, value.trim().to_string()))
+}
+
+/// The right-hand side: the run's own settings with the overridden keys
+/// replaced, so everything not mentioned stays identical to the left side.
+pub fn other_args(
+    settings: &[(String, String)],
+    overrides: &[(String, String)],
+) -> Result<RenderArgs, String> {
+    let mut merged: Vec<(String, String)> = settings
+        .iter()
+        .filter(|(key, _)| key != "compare" && !overrides.iter().any(|(k, _)| k == key))
+        .cloned()
+        .collect();
+    merged.extend(overrides.iter().cloned());
+    settings::to_render_args(&merged).map_err(|e| format!("--compare: {e}"))
+}
+
+/// Puts the two renderings of a frame next to each other, each captioned
+/// with what sets it apart.
+pub fn side_by_side(
+    left: &ImageBuffer<Rgb<u8>, Vec<u8>>,
+    right: &ImageBuffer<Rgb<u8>, Vec<u8>>,
+    overrides: &[(String, String)],
+) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+    let height = left.height().max(right.height());
+    let mut img = ImageBuffer::new(left.width() + right.width(), height);
+    img.copy_from(left, 0, 0).expect("left half fits");
+    img.copy_from(right, left.width(), 0)
+        .expect("right half fits");
+    let changes: Vec<String> = overrides.iter().map(|(k, v)| format!("{k}={v}")).collect();
+    let scale = (height / 360).max(1);
+    caption(&mut img, 0, 0, scale, "A");
+    caption(
+        &mut img,
+        left.width() as i64,
+        0,
+        scale,
+        &format!("B  {}", changes.join(" ")),
+    );
+    img
+}
+
+/// Writes `text` on a dark box in the top-left corner of the area at `x`, `y`.
+pub fn caption(img: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, x: i64, y: i64, scale: u32, text: &str) {
+    let pad = 4 * scale as i64;
+    let w = font::text_width(text, scale) as i64;
+    let h = 7 * scale as i64;
+    draw::fill_rect(
+        img,
+        x + pad,
+        y + pad,
+        (w + 2 * pad) as u32,
+        (h + 2 * pad) as u32,
+        BACKDROP,
+    );
+    font::draw_text(img, x + 2 * pad, y + 2 * pad, scale, TEXT, text);
+}
@@ -0,0 +1,580 @@
+use clap::{Arg, ArgAction, Command};
+use std::fmt::Write;
+
+#[derive(clap::Args, Debug)]
+pub struct CompletionsArgs {
+    /// Shell to print a completion script for, or `man` for a manual page in roff.
+    #[arg(value_enum)]
+    target: Target,
+}
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+enum Target {
+    Bash,
+    Zsh,
+    Fish,
+    Powershell,
+    Man,
+}
+
+/// Prints a completion script or the manual page of `cli`. Both are read off
+/// the argument definitions themselves, so they list exactly the flags,
+/// subcommands and choices of this build.
+pub fn run(args: CompletionsArgs, mut cli: Command) -> Result<(), String> {
+    cli.build();
+    let spec = Spec::new(&cli);
+    let text = match args.target {
+        Target::Bash => bash(&spec),
+        Target::Zsh => zsh(&spec),
+        Target::Fish => fish(&spec),
+        Target::Powershell => powershell(&spec),
+        Target::Man => man(&spec),
+    };
+    print!("{text}");
+    Ok(())
+}
+
+/// A command and what may follow it.
+struct Spec {
+    name: String,
+    about: String,
+    options: Vec<Opt>,
+    subcommands: Vec<Spec>,
+}
+
+impl Spec {
+    fn new(cmd: &Command) -> Spec {
+        Spec {
+            name: cmd.get_name().to_string(),
+            about: cmd.get_about().map(|a| a.to_string()).unwrap_or_default(),
+            options: cmd
+                .get_arguments()
+                .filter(|a| !a.is_hide_set())
+                .map(Opt::new)
+                .collect(),
+            subcommands: cmd
+                .get_subcommands()
+                .filter(|c| !c.is_hide_set())
+                .map(Spec::new)
+                .collect(),
+        }
+    }
+
+    /// Everything that may be typed as the next word: flags, the choices of
+    /// positional arguments and subcommands.
+    fn words(&self) -> Vec<String> {
+        let mut words: Vec<String> = self.options.iter().flat_map(Opt::flags).collect();
+        for opt in self.options.iter().filter(|o| o.positional()) {
+            words.extend(opt.choices.iter().cloned());
+        }
+        words.extend(self.subcommands.iter().map(|s| s.name.clone()));
+        words
+    }
+}
+
+/// An argument of a command.
+struct Opt {
+    long: Option<String>,
+    short: Option<char>,
+    /// Name of the value, for arguments that take one.
+    value: Option<String>,
+    /// The values clap accepts, where it knows them.
+    choices: Vec<String>,
+    help: String,
+    long_help: String,
+    defaults: Vec<String>,
+    env: Option<String>,
+    repeatable: bool,
+}
+
+impl Opt {
+    fn new(arg: &Arg) -> Opt {
+        let takes_value = arg.get_action().takes_values();
+        let value = takes_value.then(|| {
+            arg.get_value_names()
+                .and_then(|names| names.first())
+                .map(|name| name.to_string())
+                .unwrap_or_else(|| arg.get_id().as_str().to_uppercase())
+        });
+        let choices = if takes_value {
+            arg.get_possible_values()
+                .iter()
+                .filter(|v| !v.is_hide_set())
+                .map(|v| v.get_name().to_string())
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let help = arg.get_help().map(|h| h.to_string()).unwrap_or_default();
+        Opt {
+            long: arg.get_long().map(str::to_string),
+            short: arg.get_short(),
+            value,
+            choices,
+            long_help: arg
+                .get_long_help()
+                .map(|h| h.to_string())
+                .unwrap_or_else(|| help.clone()),
+            help,
+            defaults: arg
+                .get_default_values()
+                .iter()
+                .map(|v| v.to_string_lossy().into_owned())
+                .collect(),
+            env: arg.get_env().map(|e| e.to_string_lossy().into_owned()),
+            repeatable: matches!(arg.get_action(), ArgAction::Append | ArgAction::Count),
+        }
+    }
+
+    fn positional(&self) -> bool {
+        self.long.is_none() && self.short.is_none()
+    }
+
+    /// `--long` and `-s`, as typed.
+    fn flags(&self) -> Vec<String> {
+        let long = self.long.iter().map(|l| format!("--{l}"));
+        let short = self.short.iter().map(|s| format!("-{s}"));
+        long.chain(short).collect()
+    }
+
+    /// The help on one line.
+    fn summary(&self) -> String {
+        one_line(&self.help)
+    }
+}
+
+fn one_line(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// The name as a shell function name.
+fn ident(name: &str) -> String {
+    name.replace('-', "_")
+}
+
+fn bash(spec: &Spec) -> String {
+    let func = format!("_{}", ident(&spec.name));
+    let names: Vec<&str> = spec.subcommands.iter().map(|s| s.name.as_str()).collect();
+    let mut out = String::new();
+    writeln!(out, "{func}() {{").unwrap();
+    out.push_str(
+        "    local cur=\"${COMP_WORDS[COMP_CWORD]}\" prev=\"${COMP_WORDS[COMP_CWORD-1]}\"\n",
+    );
+    out.push_str("    local cmd=\"\" word\n");
+    if !names.is_empty() {
+        out.push_str("    for word in \"${COMP_WORDS[@]:1:COMP_CWORD-1}\"; do\n");
+        out.push_str("        case \"$word\" in\n");
+        writeln!(
+            out,
+            "            {}) cmd=\"$word\"; break ;;",
+            names.join("|")
+        )
+        .unwrap();
+        out.push_str("        esac\n");
+        out.push_str("    done\n");
+    }
+    out.push_str("    case \"$cmd\" in\n");
+    for (pattern, command) in [("\"\"", spec)]
+        .into_iter()
+        .chain(spec.subcommands.iter().map(|s| (s.name.as_str(), s)))
+    {
+        writeln!(out, "        {pattern})").unwrap();
+        // Options whose value is one of a few words, then all other valued
+        // options, whose values are completed as file names.
+        let mut files = Vec::new();
+        let mut cases = String::new();
+        for opt in command
+            .options
+            .iter()
+            .filter(|o| o.value.is_some() && !o.positional())
+        {
+            if opt.choices.is_empty() {
+                files.extend(opt.flags());
+            } else {
+                writeln!(
+                    cases,
+                    "                {}) COMPREPLY=($(compgen -W \"{}\" -- \"$cur\")); return ;;",
+                    opt.flags().join("|"),
+                    opt.choices.join(" ")
+                )
+                .unwrap();
+            }
+        }
+        if !files.is_empty() {
+            writeln!(
+                cases,
+                "                {}) COMPREPLY=($(compgen -f -- \"$cur\")); return ;;",
+                files.join("|")
+            )
+            .unwrap();
+        }
+        if !cases.is_empty() {
+            out.push_str("            case \"$prev\" in\n");
+            out.push_str(&cases);
+            out.push_str("            esac\n");
+        }
+        writeln!(
+            out,
+            "            COMPREPLY=($(compgen -W \"{}\" -- \"$cur\")) ;;",
+            command.words().join(" ")
+        )
+        .unwrap();
+    }
+    out.push_str("    esac\n");
+    out.push_str("}\n");
+    writeln!(out, "complete -F {func} {}", spec.name).unwrap();
+    out
+}
+
+/// Text inside a single-quoted zsh `_arguments` spec.
+fn zsh_quote(text: &str) -> String {
+    one_line(text)
+        .replace('\'', "'\\''")
+        .replace('[', "\\[")
+        .replace(']', "\\]")
+        .replace(':', "\\:")
+}
+
+/// The `_arguments` specs of a command's arguments.
+fn zsh_specs(command: &Spec) -> Vec<String> {
+    let mut specs = Vec::new();
+    for opt in &command.options {
+        let action = if opt.choices.is_empty() {
+            "_default".to_string()
+        } else {
+            format!("({})", opt.choices.join(" "))
+        };
+        if opt.positional() {
+            let value = opt.value.as_deref().unwrap_or("ARG");
+            specs.push(format!("':{value}:{action}'"));
+            continue;
+        }
+        let repeat = if opt.repeatable { "*" } else { "" };
+        for flag in opt.flags() {
+            let help = zsh_quote(&opt.help);
+            match &opt.value {
+                Some(value) => {
+                    let eq = if flag.starts_with("--") { "=" } else { "+" };
+                    specs.push(format!("'{repeat}{flag}{eq}[{help}]:{value}:{action}'"))
+                }
+                None => specs.push(format!("'{repeat}{flag}[{help}]'")),
+            }
+        }
+    }
+    specs
+}
+
+fn zsh(spec: &Spec) -> String {
+    let func = format!("_{}", ident(&spec.name));
+    let mut out = String::new();
+    writeln!(out, "#compdef {}\n", spec.name).unwrap();
+    writeln!(out, "{func}() {{").unwrap();
+    out.push_str("    local curcontext=\"$curcontext\" state line\n");
+    out.push_str("    _arguments -C \\\n");
+    for s in zsh_specs(spec) {
+        writeln!(out, "        {s} \\").unwrap();
+    }
+    if spec.subcommands.is_empty() {
+        out.push_str("        && return 0\n");
+    } else {
+        writeln!(out, "        ':: :{func}_commands' \\").unwrap();
+        out.push_str("        '*::: :->command'\n");
+        out.push_str("    case $state in\n");
+        out.push_str("        command)\n");
+        out.push_str("            words=($line[1] \"${words[@]}\")\n");
+        out.push_str("            (( CURRENT += 1 ))\n");
+        out.push_str("            case $line[1] in\n");
+        for sub in &spec.subcommands {
+            writeln!(out, "                ({})", sub.name).unwrap();
+            out.push_str("                    _arguments \\\n");
+            for s in zsh_specs(sub) {
+                writeln!(out, "                        {s} \\").unwrap();
+            }
+            out.push_str("                        && return 0 ;;\n");
+        }
+        out.push_str("            esac ;;\n");
+        out.push_str("    esac\n");
+    }
+    out.push_str("}\n\n");
+    if !spec.subcommands.is_empty() {
+        writeln!(out, "{func}_commands() {{").unwrap();
+        out.push_str("    local commands=(\n");
+        for sub in &spec.subcommands {
+            let about = one_line(&sub.about)
+                .replace('\'', "'\\''")
+                .replace(':', "\\:");
+            writeln!(out, "        '{}:{about}'", sub.name).unwrap();
+        }
+        out.push_str("    )\n");
+        out.push_str("    _describe -t commands 'command' commands\n");
+        out.push_str("}\n\n");
+    }
+    writeln!(out, "{func} \"$@\"").unwrap();
+    out
+}
+
+/// Text inside single quotes in fish.
+fn fish_quote(text: &str) -> String {
+    one_line(text).replace('\\', "\\\\").replace('\'', "\\'")
+}
+
+fn fish(spec: &Spec) -> String {
+    let mut out = String::new();
+    let root = "__fish_use_subcommand";
+    let mut line = |condition: &str, opt: &Opt| {
+        let mut cmd = format!("complete -c {} -n \"{condition}\"", spec.name);
+        if let Some(long) = &opt.long {
+            write!(cmd, " -l {long}").unwrap();
+        }
+        if let Some(short) = opt.short {
+            write!(cmd, " -s {short}").unwrap();
+        }
+        if opt.value.is_some() && !opt.positional() {
+            cmd.push_str(" -r");
+        }
+        if !opt.choices.is_empty() {
+            write!(cmd, " -f -a \"{}\"", opt.choices.join(" ")).unwrap();
+        } else if opt.positional() {
+            return;
+        }
+        if !opt.help.is_empty() {
+            write!(cmd, " -d '{}'", fish_quote(&opt.summary())).unwrap();
+        }
+        writeln!(out, "{cmd}").unwrap();
+    };
+    for opt in &spec.options {
+        line(root, opt);
+    }
+    for sub in &spec.subcommands {
+        let condition = format!("__fish_seen_subcommand_from {}", sub.name);
+        for opt in &sub.options {
+            line(&condition, opt);
+        }
+    }
+    for sub in &spec.subcommands {
+        writeln!(
+            out,
+            "complete -c {} -n \"{root}\" -f -a \"{}\" -d '{}'",
+            spec.name,
+            sub.name,
+            fish_quote(&sub.about)
+        )
+        .unwrap();
+    }
+    out
+}
+
+/// Text inside single quotes in PowerShell.
+fn ps_quote(text: &str) -> String {
+    one_line(text).replace('\'', "''")
+}
+
+fn powershell(spec: &Spec) -> String {
+    let mut out = String::new();
+    writeln!(
+        out,
+        "Register-ArgumentCompleter -Native -CommandName '{}' -ScriptBlock {{",
+        spec.name
+    )
+    .unwrap();
+    out.push_str("    param($wordToComplete, $commandAst, $cursorPosition)\n");
+    out.push_str(
+        "    $elements = @($commandAst.CommandElements | Select-Object -Skip 1 | ForEach-Object { $_.ToString() })\n",
+    );
+    out.push_str(
+        "    if ($wordToComplete) { $elements = @($elements | Select-Object -SkipLast 1) }\n",
+    );
+    let names: Vec<String> = spec
+        .subcommands
+        .iter()
+        .map(|s| format!("'{}'", s.name))
+        .collect();
+    writeln!(out, "    $commands = @({})", names.join(", ")).unwrap();
+    out.push_str("    $command = ''\n");
+    out.push_str(
+        "    foreach ($element in $elements) { if ($commands -contains $element) { $command = $element; break } }\n",
+    );
+    out.push_str("    $previous = if ($elements.Count) { $elements[-1] } else { '' }\n");
+    out.push_str("    $choices = @{\n");
+    for (prefix, command) in [("", spec)]
+        .into_iter()
+        .chain(spec.subcommands.iter().map(|s| (s.name.as_str(), s)))
+    {
+        for opt in command
+            .options
+            .iter()
+            .filter(|o| !o.choices.is_empty() && !o.positional())
+        {
+            let choices: Vec<String> = opt
+                .choices
+                .iter()
+                .map(|c| format!("'{}'", ps_quote(c)))
+                .collect();
+            for flag in opt.flags() {
+                writeln!(out, "        '{prefix};{flag}' = @({})", choices.join(", ")).unwrap();
+            }
+        }
+    }
+    out.push_str("    }\n");
+    out.push_str("    $values = $choices[\"$command;$previous\"]\n");
+    out.push_str("    if ($values) {\n");
+    out.push_str(
+        "        $values | Where-Object { $_ -like \"$wordToComplete*\" } | ForEach-Object {\n",
+    );
+    out.push_str("            [System.Management.Automation.CompletionResult]::new($_, $_, 'ParameterValue', $_)\n");
+    out.push_str("        }\n");
+    out.push_str("        return\n");
+    out.push_str("    }\n");
+    out.push_str("    $words = switch ($command) {\n");
+    for (name, command) in [("", spec)]
+        .into_iter()
+        .chain(spec.subcommands.iter().map(|s| (s.name.as_str(), s)))
+    {
+        writeln!(out, "        '{name}' {{ @(").unwrap();
+        for opt in &command.options {
+            let help = if opt.help.is_empty() {
+                opt.flags().join(" ")
+            } else {
+                opt.summary()
+            };
+            if opt.positional() {
+                for choice in &opt.choices {
+                    writeln!(
+                        out,
+                        "            ,@('{}', 'ParameterValue', '{}')",
+                        ps_quote(choice),
+                        ps_quote(&help)
+                    )
+                    .unwrap();
+                }
+            } else {
+                for flag in opt.flags() {
+                    writeln!(
+                        out,
+                        "            ,@('{flag}', 'ParameterName', '{}')",
+                        ps_quote(&help)
+                    )
+                    .unwrap();
+                }
+            }
+        }
+        if name.is_empty() {
+            for sub in &spec.subcommands {
+                let about = if sub.about.is_empty() {
+                    &sub.name
+                } else {
+                    &sub.about
+                };
+                writeln!(
+                    out,
+                    "            ,@('{}', 'ParameterValue', '{}')",
+                    sub.name,
+                    ps_quote(about)
+                )
+                .unwrap();
+            }
+        }
+        out.push_str("        ) }\n");
+    }
+    out.push_str("    }\n");
+    out.push_str(
+        "    $words | Where-Object { $_[0] -like \"$wordToComplete*\" } | ForEach-Object {\n",
+    );
+    out.push_str("        [System.Management.Automation.CompletionResult]::new($_[0], $_[0], $_[1], $_[2])\n");
+    out.push_str("    }\n");
+    out.push_str("}\n");
+    out
+}
+
+/// Text for a roff body line: backslashes and dashes escaped, and lines that
+/// would read as requests guarded.
+fn roff(text: &str) -> String {
+    text.lines()
+        .map(|line| {
+            let line = line.trim().replace('\\', "\\e").replace('-', "\\-");
+            if line.starts_with('.') || line.starts_with('\'') {
+                format!("\\&{line}")
+            } else {
+                line
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The options of one command as tagged paragraphs.
+fn man_options(out: &mut String, command: &Spec) {
+    for opt in &command.options {
+        out.push_str(".TP\n");
+        let value = opt.value.as_deref().map(|v| format!("\\fI{}\\fR", roff(v)));
+        let tag = if opt.positional() {
+            value.unwrap_or_default()
+        } else {
+            let flags: Vec<String> = opt
+                .flags()
+                .iter()
+                .map(|f| format!("\\fB{}\\fR", roff(f)))
+                .collect();
+            match value {
+                Some(value) => format!("{} {value}", flags.join(", ")),
+                None => flags.join(", "),
+            }
+        };
+        writeln!(out, "{tag}").unwrap();
+        for (i, paragraph) in opt.long_help.split("\n\n").enumerate() {
+            if i > 0 {
+                out.push_str(".IP\n");
+            }
+            writeln!(out, "{}", roff(paragraph)).unwrap();
+        }
+        let mut notes = Vec::new();
+        if !opt.choices.is_empty() {
+            notes.push(format!("possible values: {}", opt.choices.join(", ")));
+        }
+        if !opt.defaults.is_empty() && opt.value.is_some() {
+            notes.push(format!("default: {}", opt.defaults.join(", ")));
+        }
+        if let Some(env) = &opt.env {
+            notes.push(format!("env: {env}"));
+        }
+        if !notes.is_empty() {
+            writeln!(out, ".br\n[{}]", roff(&notes.join("; "))).unwrap();
+        }
+    }
+}
+
+fn man(spec: &Spec) -> String {
+    let mut out = String::new();
+    writeln!(
+        out,
+        ".TH {} 1 \"\" \"{} {}\"",
+        roff(&spec.name.to_uppercase()),
+        roff(&spec.name),
+        env!("CARGO_PKG_VERSION")
+    )
+    .unwrap();
+    out.push_str(".SH NAME\n");
+    writeln!(out, "{} \\- {}", roff(&spec.name), roff(&spec.about)).unwrap();
+    out.push_str(".SH SYNOPSIS\n");
+    writeln!(out, "\\fB{}\\fR [\\fIOPTIONS\\fR]", roff(&spec.name)).unwrap();
+    if !spec.subcommands.is_empty() {
+        out.push_str(".br\n");
+        writeln!(
+            out,
+            "\\fB{}\\fR \\fICOMMAND\\fR [\\fIOPTIONS\\fR]",
+            roff(&spec.name)
+        )
+        .unwrap();
+    }
+    out.push_str(".SH OPTIONS\n");
+    man_options(&mut out, spec);
+    if !spec.subcommands.is_empty() {
+        out.push_str(".SH COMMANDS\n");
+        for sub in &spec.subcommands {
+            writeln!(out, ".SS {}", roff(&sub.name)).unwrap();
+            writeln!(out, "{}", roff(&sub.about)).unwrap();
+            man_options(&mut out, sub);
+        }
+    }
+    out
+}
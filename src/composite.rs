@@ -0,0 +1,121 @@
+use crate::coloring::Coloring;
+use crate::palette::Palette;
+use image::{ImageBuffer, Rgb};
+use rayon::prelude::*;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlendMode {
+    Normal,
+    Multiply,
+    Screen,
+    Overlay,
+    Add,
+}
+
+impl FromStr for BlendMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<BlendMode, String> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "normal" => Ok(BlendMode::Normal),
+            "multiply" => Ok(BlendMode::Multiply),
+            "screen" => Ok(BlendMode::Screen),
+            "overlay" => Ok(BlendMode::Overlay),
+            "add" => Ok(BlendMode::Add),
+            _ => Err(format!(
+                "unknown blend mode {s:?}, expected normal, multiply, screen, overlay or add"
+            )),
+        }
+    }
+}
+
+impl BlendMode {
+    /// Blends one channel of `top` onto `base`, both in 0..=1, before opacity is applied.
+    pub fn apply(self, base: f64, top: f64) -> f64 {
+        match self {
+            BlendMode::Normal => top,
+            BlendMode::Multiply => base * top,
+            BlendMode::Screen => 1.0 - (1.0 - base) * (1.0 - top),
+            BlendMode::Overlay => {
+                if base < 0.5 {
+                    2.0 * base * top
+                } else {
+                    1.0 - 2.0 * (1.0 - base) * (1.0 - top)
+                }
+            }
+            BlendMode::Add => (base + top).min(1.0),
+        }
+    }
+}
+
+/// An extra coloring of the frame composited over the base image.
+#[derive(Debug, Clone)]
+pub struct Layer {
+    pub palette: Palette,
+    /// Iteration limit for this layer; `None` reuses the base iteration data.
+    pub max_iter: Option<u32>,
+    /// Coloring of this layer; `None` uses the base `--coloring`.
+    pub coloring: Option<Coloring>,
+    pub opacity: f64,
+    pub blend: BlendMode,
+}
+
+impl FromStr for Layer {
+    type Err = String;
+
+    /// Parses `key=value` pairs such as
+    /// `palette=fire,opacity=0.4,blend=normal,max_iter=300,coloring=smooth*de`.
+    fn from_str(s: &str) -> Result<Layer, String> {
+        let mut layer = Layer {
+            palette: Palette::Classic,
+            max_iter: None,
+            coloring: None,
+            opacity: 0.5,
+            blend: BlendMode::Normal,
+        };
+        for pair in s.split(',').filter(|p| !p.trim().is_empty()) {
+            let (key, value) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("layer: expected key=value, got {pair:?}"))?;
+            let value = value.trim();
+            match key.trim() {
+                "palette" => layer.palette = value.parse()?,
+                "max_iter" => {
+                    layer.max_iter = Some(
+                        value
+                            .parse()
+                            .map_err(|_| format!("layer: invalid max_iter {value:?}"))?,
+                    )
+                }
+                "coloring" => layer.coloring = Some(value.parse()?),
+                "opacity" => {
+                    layer.opacity = value
+                        .parse::<f64>()
+                        .map_err(|_| format!("layer: invalid opacity {value:?}"))?
+                        .clamp(0.0, 1.0)
+                }
+                "blend" => layer.blend = value.parse()?,
+                other => return Err(format!("layer: unknown key {other:?}")),
+            }
+        }
+        Ok(layer)
+    }
+}
+
+pub fn composite(
+    base: &mut ImageBuffer<Rgb<u8>, Vec<u8>>,
+    top: &ImageBuffer<Rgb<u8>, Vec<u8>>,
+    blend: BlendMode,
+    opacity: f64,
+) {
+    base.as_mut()
+        .par_iter_mut()
+        .zip(top.as_raw().par_iter())
+        .for_each(|(b, &t)| {
+            let bf = *b as f64 / 255.0;
+            let blended = blend.apply(bf, t as f64 / 255.0);
+            let mixed = bf + (blended - bf) * opacity;
+            *b = (mixed * 255.0).round().clamp(0.0, 255.0) as u8;
+        });
+}
@@ -0,0 +1,221 @@
+use crate::RenderArgs;
+use clap::parser::ValueSource;
+use clap::{ArgAction, ArgMatches, Args};
+use std::ffi::OsString;
+use std::fs;
+
+/// Settings read from a flat TOML file: one `key = value` per line, where the
+/// key is a render option name (`max_iter` or `max-iter`) and the value is a
+/// string, number, boolean or single-line array of those.
+pub type Config = Vec<(String, Vec<String>)>;
+
+pub fn load(path: &str) -> Result<Config, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("read {path:?}: {e}"))?;
+    parse(&text).map_err(|e| format!("{path}: {e}"))
+}
+
+pub fn parse(text: &str) -> Result<Config, String> {
+    let mut config = Config::new();
+    for (lineno, line) in text.lines().enumerate() {
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        let entry = parse_line(line).map_err(|e| format!("line {}: {e}", lineno + 1))?;
+        config.push(entry);
+    }
+    Ok(config)
+}
+
+fn parse_line(line: &str) -> Result<(String, Vec<String>), String> {
+    if line.starts_with('[') {
+        return Err("tables are not supported, keys must be top-level".to_string());
+    }
+    let (key, value) = line
+        .split_once('=')
+        .ok_or_else(|| format!("expected key = value, got {line:?}"))?;
+    let key = key.trim().trim_matches('"').replace('-', "_");
+    let value = value.trim();
+    let values = match value.strip_prefix('[') {
+        Some(inner) => {
+            let inner = inner
+                .strip_suffix(']')
+                .ok_or("arrays must close on the same line")?;
+            split_array(inner)?
+                .iter()
+                .map(|item| parse_scalar(item))
+                .collect::<Result<_, _>>()?
+        }
+        None => vec![parse_scalar(value)?],
+    };
+    Ok((key, values))
+}
+
+fn parse_scalar(value: &str) -> Result<String, String> {
+    let value = value.trim();
+    if let Some(inner) = value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')) {
+        return Ok(inner.to_string());
+    }
+    let Some(inner) = value.strip_prefix('"') else {
+        if value.is_empty() {
+            return Err("missing value".to_string());
+        }
+        return Ok(value.to_string());
+    };
+    let inner = inner
+        .strip_suffix('"')
+        .ok_or_else(|| format!("unterminated string {value:?}"))?;
+    let mut out = String::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('"') => out.push('"'),
+            Some('\\') => out.push('\\'),
+            Some(other) => return Err(format!("unsupported escape \\{other}")),
+            None => return Err(format!("unterminated string {value:?}")),
+        }
+    }
+    Ok(out)
+}
+
+/// Splits array items on commas that are not inside quotes.
+fn split_array(inner: &str) -> Result<Vec<&str>, String> {
+    let mut items = Vec::new();
+    let mut quote = None;
+    let mut escaped = false;
+    let mut start = 0;
+    for (i, c) in inner.char_indices() {
+        match (quote, c) {
+            (Some('"'), '\\') if !escaped => {
+                escaped = true;
+                continue;
+            }
+            (Some(q), _) if c == q && !escaped => quote = None,
+            (None, '"' | '\'') => quote = Some(c),
+            (None, ',') => {
+                items.push(&inner[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+        escaped = false;
+    }
+    if quote.is_some() {
+        return Err("unterminated string in array".to_string());
+    }
+    if !inner[start..].trim().is_empty() {
+        items.push(&inner[start..]);
+    }
+    Ok(items)
+}
+
+fn strip_comment(line: &str) -> &str {
+    let mut quote = None;
+    for (i, c) in line.char_indices() {
+        match (quote, c) {
+            (None, '#') => return &line[..i],
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if c == q => quote = None,
+            _ => {}
+        }
+    }
+    line
+}
+
+/// Appends config values to the command line for every option that neither
+/// the command line nor a `MANDELBROT_*` variable set, so both win over the file.
+pub fn merge_args(
+    argv: Vec<OsString>,
+    matches: &ArgMatches,
+    config: &Config,
+) -> Result<Vec<OsString>, String> {
+    let cmd = RenderArgs::augment_args(clap::Command::new("config"));
+    let mut merged = argv;
+    for (key, values) in config {
+        let arg = cmd
+            .get_arguments()
+            .find(|a| a.get_id() == key.as_str())
+            .ok_or_else(|| format!("config: unknown setting {key:?}"))?;
+        if matches!(
+            matches.value_source(key),
+            Some(ValueSource::CommandLine | ValueSource::EnvVariable)
+        ) {
+            continue;
+        }
+        let long = arg.get_long().unwrap_or(key.as_str());
+        match arg.get_action() {
+            ArgAction::SetTrue => {
+                if values.iter().any(|v| v == "true") {
+                    merged.push(format!("--{long}").into());
+                }
+            }
+            _ => {
+                for value in values {
+                    merged.push(format!("--{long}={value}").into());
+                }
+            }
+        }
+    }
+    Ok(merged)
+}
+
+/// Renders resolved settings as a config file that `--config` reads back.
+pub fn to_toml(settings: &[(String, String)]) -> String {
+    let cmd = RenderArgs::augment_args(clap::Command::new("config"));
+    let mut out = String::from("# mandelbrot-animation configuration\n");
+    let mut done: Vec<&str> = Vec::new();
+    for (key, _) in settings {
+        if done.contains(&key.as_str()) {
+            continue;
+        }
+        done.push(key);
+        let values: Vec<&str> = settings
+            .iter()
+            .filter(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+            .collect();
+        let is_flag = cmd
+            .get_arguments()
+            .any(|a| a.get_id() == key.as_str() && matches!(a.get_action(), ArgAction::SetTrue));
+        let value = if is_flag {
+            values[0].to_string()
+        } else if values.len() == 1 {
+            toml_value(values[0])
+        } else {
+            let items: Vec<String> = values.iter().map(|v| toml_string(v)).collect();
+            format!("[{}]", items.join(", "))
+        };
+        out.push_str(&format!("{key} = {value}\n"));
+    }
+    out
+}
+
+fn toml_value(value: &str) -> String {
+    let digits = value.strip_prefix('-').unwrap_or(value);
+    let numeric = value.parse::<f64>().is_ok_and(f64::is_finite)
+        && digits.starts_with(|c: char| c.is_ascii_digit())
+        && digits.ends_with(|c: char| c.is_ascii_digit())
+        && digits
+            .chars()
+            .all(|c| c.is_ascii_digit() || "+-.eE".contains(c));
+    if numeric {
+        value.to_string()
+    } else {
+        toml_string(value)
+    }
+}
+
+fn toml_string(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t");
+    format!("\"{escaped}\"")
+}
@@ -0,0 +1,78 @@
+use crate::render::IterationBuffer;
+use image::{ImageBuffer, Rgb};
+use rayon::prelude::*;
+use std::collections::VecDeque;
+
+type Frame = ImageBuffer<Rgb<u8>, Vec<u8>>;
+
+/// `--deflicker`: a box filter over the colors of the last few frames.
+///
+/// A pixel only takes in an earlier frame's color where that frame's escape
+/// time there is within `tolerance` of its own, relatively, so filaments
+/// that shimmer in place settle while edges that moved stay sharp. Earlier
+/// frames enter unfiltered, which keeps any frame reproducible from the
+/// frames just before it.
+pub struct Deflicker {
+    frames: usize,
+    tolerance: f64,
+    history: VecDeque<(Vec<f64>, Frame)>,
+}
+
+impl Deflicker {
+    pub fn new(frames: u32, tolerance: f64) -> Deflicker {
+        Deflicker {
+            frames: frames.max(1) as usize,
+            tolerance,
+            history: VecDeque::new(),
+        }
+    }
+
+    /// Blends `img` with the frames seen before, then remembers its own
+    /// colors for the frames after.
+    pub fn apply(&mut self, iterations: &IterationBuffer, img: Frame) -> Frame {
+        if self.frames == 1 {
+            return img;
+        }
+        let history: Vec<_> = self
+            .history
+            .iter()
+            .filter(|(_, earlier)| earlier.dimensions() == img.dimensions())
+            .collect();
+        let mut out = img.clone();
+        out.as_mut()
+            .par_chunks_mut(3)
+            .enumerate()
+            .for_each(|(i, pixel)| {
+                let value = iterations.values[i];
+                let mut sum = [pixel[0] as u32, pixel[1] as u32, pixel[2] as u32];
+                let mut count = 1;
+                for (earlier_values, earlier) in &history {
+                    if !similar(value, earlier_values[i], self.tolerance) {
+                        continue;
+                    }
+                    let color = &earlier.as_raw()[i * 3..i * 3 + 3];
+                    for (s, c) in sum.iter_mut().zip(color) {
+                        *s += *c as u32;
+                    }
+                    count += 1;
+                }
+                for (p, s) in pixel.iter_mut().zip(sum) {
+                    *p = ((s + count / 2) / count) as u8;
+                }
+            });
+        if self.history.len() + 1 >= self.frames {
+            self.history.pop_front();
+        }
+        self.history.push_back((iterations.values.clone(), img));
+        out
+    }
+}
+
+/// Both inside the set, or both escaping at about the same time.
+fn similar(a: f64, b: f64, tolerance: f64) -> bool {
+    match (a < 0.0, b < 0.0) {
+        (true, true) => true,
+        (false, false) => (a - b).abs() <= tolerance * a.max(b),
+        _ => false,
+    }
+}
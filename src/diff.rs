@@ -0,0 +1,200 @@
+use crate::palette::Palette;
+use crate::{encode_png, output};
+use image::{ImageBuffer, Rgb};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(clap::Args, Debug)]
+pub struct DiffArgs {
+    /// Frames of the reference run.
+    #[arg(long, alias = "a")]
+    run_a: String,
+    /// Frames of the run being checked against it.
+    #[arg(long, alias = "b")]
+    run_b: String,
+    /// Largest per-channel difference still counted as unchanged.
+    #[arg(long, default_value_t = 0)]
+    tolerance: u8,
+    /// Where heatmaps of the differing frames and `stats.csv` go.
+    #[arg(long, default_value = "out/diff")]
+    out_dir: String,
+}
+
+struct FrameDiff {
+    changed: u64,
+    max: u8,
+    mean: f64,
+    psnr: f64,
+}
+
+/// Compares every PNG under `--run-a` with the file of the same relative
+/// path under `--run-b`. Fails when any frame differs beyond the tolerance, so it can
+/// gate an optimization that must not change the picture.
+pub fn run(args: DiffArgs) -> Result<(), String> {
+    let (dir_a, dir_b) = (Path::new(&args.run_a), Path::new(&args.run_b));
+    let mut files = Vec::new();
+    collect_pngs(dir_a, Path::new(""), &mut files)?;
+    files.sort();
+    if files.is_empty() {
+        return Err(format!("no PNG frames in {dir_a:?}"));
+    }
+    let out_dir = output::resolve_dir(Path::new(&args.out_dir))?;
+    fs::create_dir_all(&out_dir).map_err(|e| output::describe_error("create", &out_dir, &e))?;
+    let heatmap_palette: Palette = "fire".parse()?;
+
+    let mut csv = String::from("file,changed_pixels,max_diff,mean_diff,psnr_db\n");
+    let (mut differing, mut problems, mut changed_total, mut max_total) = (0, 0, 0u64, 0u8);
+    let mut worst_psnr = f64::INFINITY;
+    for file in &files {
+        let a = load(&dir_a.join(file));
+        let b = load(&dir_b.join(file));
+        let (a, b) = match (a, b) {
+            (Ok(a), Ok(b)) if a.dimensions() == b.dimensions() => (a, b),
+            (Ok(a), Ok(b)) => {
+                println!(
+                    "{}: size differs ({}x{} vs {}x{})",
+                    file.display(),
+                    a.width(),
+                    a.height(),
+                    b.width(),
+                    b.height()
+                );
+                problems += 1;
+                continue;
+            }
+            (Err(e), _) | (_, Err(e)) => {
+                println!("{}: {e}", file.display());
+                problems += 1;
+                continue;
+            }
+        };
+        let diff = compare(&a, &b, args.tolerance);
+        csv.push_str(&format!(
+            "{},{},{},{:.4},{:.2}\n",
+            file.display(),
+            diff.changed,
+            diff.max,
+            diff.mean,
+            diff.psnr
+        ));
+        changed_total += diff.changed;
+        max_total = max_total.max(diff.max);
+        worst_psnr = worst_psnr.min(diff.psnr);
+        if diff.changed == 0 {
+            continue;
+        }
+        differing += 1;
+        let heatmap_path = out_dir.join(file);
+        if let Some(parent) = heatmap_path.parent() {
+            fs::create_dir_all(parent).map_err(|e| output::describe_error("create", parent, &e))?;
+        }
+        let bytes = encode_png(&heatmap(&a, &b, args.tolerance, &heatmap_palette))?;
+        fs::write(&heatmap_path, bytes)
+            .map_err(|e| output::describe_error("save", &heatmap_path, &e))?;
+        println!(
+            "{}: {} pixels changed, max {}, mean {:.4}, PSNR {:.2} dB -> {}",
+            file.display(),
+            diff.changed,
+            diff.max,
+            diff.mean,
+            diff.psnr,
+            heatmap_path.display()
+        );
+    }
+    let stats_path = out_dir.join("stats.csv");
+    fs::write(&stats_path, csv).map_err(|e| output::describe_error("save", &stats_path, &e))?;
+
+    println!(
+        "compared {} frames: {} differ, {} unmatched, {} pixels changed, max diff {}, worst PSNR {:.2} dB",
+        files.len() - problems,
+        differing,
+        problems,
+        changed_total,
+        max_total,
+        worst_psnr
+    );
+    if differing + problems > 0 {
+        return Err(format!(
+            "{} frame(s) differ between {dir_a:?} and {dir_b:?}",
+            differing + problems
+        ));
+    }
+    Ok(())
+}
+
+fn collect_pngs(root: &Path, relative: &Path, out: &mut Vec<PathBuf>) -> Result<(), String> {
+    let dir = root.join(relative);
+    let entries = fs::read_dir(&dir).map_err(|e| format!("read {dir:?}: {e}"))?;
+    for entry in entries.filter_map(Result::ok) {
+        let path = relative.join(entry.file_name());
+        if entry.file_type().is_ok_and(|t| t.is_dir()) {
+            collect_pngs(root, &path, out)?;
+        } else if path.extension().is_some_and(|ext| ext == "png") {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
+
+fn load(path: &Path) -> Result<ImageBuffer<Rgb<u8>, Vec<u8>>, String> {
+    image::open(path)
+        .map(|img| img.to_rgb8())
+        .map_err(|e| format!("read {path:?}: {e}"))
+}
+
+/// Largest channel difference of each pixel.
+fn pixel_diffs<'a>(
+    a: &'a ImageBuffer<Rgb<u8>, Vec<u8>>,
+    b: &'a ImageBuffer<Rgb<u8>, Vec<u8>>,
+) -> impl Iterator<Item = u8> + 'a {
+    a.pixels()
+        .zip(b.pixels())
+        .map(|(pa, pb)| (0..3).map(|c| pa[c].abs_diff(pb[c])).max().unwrap_or(0))
+}
+
+fn compare(
+    a: &ImageBuffer<Rgb<u8>, Vec<u8>>,
+    b: &ImageBuffer<Rgb<u8>, Vec<u8>>,
+    tolerance: u8,
+) -> FrameDiff {
+    let changed = pixel_diffs(a, b).filter(|&d| d > tolerance).count() as u64;
+    let max = pixel_diffs(a, b).max().unwrap_or(0);
+    let (mut abs_sum, mut sq_sum) = (0.0, 0.0);
+    for (x, y) in a.as_raw().iter().zip(b.as_raw()) {
+        let d = *x as f64 - *y as f64;
+        abs_sum += d.abs();
+        sq_sum += d * d;
+    }
+    let samples = a.as_raw().len().max(1) as f64;
+    let mse = sq_sum / samples;
+    FrameDiff {
+        changed,
+        max,
+        mean: abs_sum / samples,
+        psnr: if mse == 0.0 {
+            f64::INFINITY
+        } else {
+            10.0 * (255.0 * 255.0 / mse).log10()
+        },
+    }
+}
+
+/// Unchanged pixels as a dim grayscale copy of `a`; changed ones colored by
+/// how far apart they are, on a square-root scale so small drifts still show.
+fn heatmap(
+    a: &ImageBuffer<Rgb<u8>, Vec<u8>>,
+    b: &ImageBuffer<Rgb<u8>, Vec<u8>>,
+    tolerance: u8,
+    palette: &Palette,
+) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+    let mut img = ImageBuffer::new(a.width(), a.height());
+    for ((out, pa), d) in img.pixels_mut().zip(a.pixels()).zip(pixel_diffs(a, b)) {
+        *out = if d > tolerance {
+            Rgb(palette.color(0.25 + 0.75 * (d as f64 / 255.0).sqrt()))
+        } else {
+            let luma = (0.299 * pa[0] as f64 + 0.587 * pa[1] as f64 + 0.114 * pa[2] as f64) / 4.0;
+            Rgb([luma as u8; 3])
+        };
+    }
+    img
+}
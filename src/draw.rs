@@ -0,0 +1,97 @@
+use crate::composite::BlendMode;
+use image::{ImageBuffer, Rgb};
+
+pub type Rgba = [u8; 4];
+
+/// A color and the way it is combined with the pixels underneath.
+#[derive(Debug, Clone, Copy)]
+pub struct Paint {
+    pub color: Rgba,
+    pub blend: BlendMode,
+}
+
+/// Parses `#rrggbb` or `#rrggbbaa`.
+pub fn parse_rgba(s: &str) -> Result<Rgba, String> {
+    let hex = s.trim().trim_start_matches('#');
+    let value = u32::from_str_radix(hex, 16)
+        .ok()
+        .filter(|_| hex.len() == 6 || hex.len() == 8)
+        .ok_or_else(|| format!("invalid color {s:?}, expected #rrggbb or #rrggbbaa"))?;
+    let value = if hex.len() == 6 {
+        (value << 8) | 0xff
+    } else {
+        value
+    };
+    Ok(value.to_be_bytes())
+}
+
+pub fn blend_pixel(img: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, x: i64, y: i64, paint: Paint) {
+    if x < 0 || y < 0 || x >= img.width() as i64 || y >= img.height() as i64 {
+        return;
+    }
+    let pixel = img.get_pixel_mut(x as u32, y as u32);
+    let alpha = paint.color[3] as f64 / 255.0;
+    for c in 0..3 {
+        let base = pixel[c] as f64 / 255.0;
+        let top = paint.blend.apply(base, paint.color[c] as f64 / 255.0);
+        let blended = base * (1.0 - alpha) + top * alpha;
+        pixel[c] = (blended * 255.0).round() as u8;
+    }
+}
+
+pub fn fill_rect(
+    img: &mut ImageBuffer<Rgb<u8>, Vec<u8>>,
+    x: i64,
+    y: i64,
+    w: u32,
+    h: u32,
+    paint: Paint,
+) {
+    let x0 = x.max(0);
+    let y0 = y.max(0);
+    let x1 = (x + w as i64).min(img.width() as i64);
+    let y1 = (y + h as i64).min(img.height() as i64);
+    for py in y0..y1 {
+        for px in x0..x1 {
+            blend_pixel(img, px, py, paint);
+        }
+    }
+}
+
+pub fn draw_line(
+    img: &mut ImageBuffer<Rgb<u8>, Vec<u8>>,
+    from: (i64, i64),
+    to: (i64, i64),
+    thickness: u32,
+    paint: Paint,
+) {
+    let (mut x, mut y) = from;
+    let dx = (to.0 - x).abs();
+    let dy = -(to.1 - y).abs();
+    let sx = if x < to.0 { 1 } else { -1 };
+    let sy = if y < to.1 { 1 } else { -1 };
+    let offset = (thickness.max(1) / 2) as i64;
+    let mut err = dx + dy;
+    loop {
+        fill_rect(
+            img,
+            x - offset,
+            y - offset,
+            thickness.max(1),
+            thickness.max(1),
+            paint,
+        );
+        if x == to.0 && y == to.1 {
+            break;
+        }
+        let e2 = 2 * err;
+        if e2 >= dy {
+            err += dy;
+            x += sx;
+        }
+        if e2 <= dx {
+            err += dx;
+            y += sy;
+        }
+    }
+}
@@ -0,0 +1,57 @@
+use image::{ImageBuffer, Rgb};
+use std::io::Write;
+use std::process::{Child, ChildStdin, Command, Stdio};
+
+/// An ffmpeg child process fed with raw RGB frames on stdin.
+pub struct FfmpegPipe {
+    child: Child,
+    stdin: Option<ChildStdin>,
+}
+
+impl FfmpegPipe {
+    pub fn spawn(
+        width: u32,
+        height: u32,
+        fps: u32,
+        output_args: &[&str],
+    ) -> Result<FfmpegPipe, String> {
+        let mut child = Command::new("ffmpeg")
+            .args(["-hide_banner", "-loglevel", "error", "-y"])
+            .args(["-f", "rawvideo", "-pix_fmt", "rgb24"])
+            .args(["-s", &format!("{width}x{height}")])
+            .args(["-r", &fps.to_string()])
+            .args(["-i", "-"])
+            .args(output_args)
+            .stdin(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("spawn ffmpeg: {e}"))?;
+        let stdin = child.stdin.take();
+        Ok(FfmpegPipe { child, stdin })
+    }
+
+    pub fn write_frame(&mut self, img: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> Result<(), String> {
+        let stdin = self
+            .stdin
+            .as_mut()
+            .ok_or_else(|| "ffmpeg stdin closed".to_string())?;
+        stdin
+            .write_all(img.as_raw())
+            .map_err(|e| format!("write to ffmpeg: {e}"))
+    }
+
+    pub fn finish(mut self) -> Result<(), String> {
+        drop(self.stdin.take());
+        let status = self
+            .child
+            .wait()
+            .map_err(|e| format!("wait for ffmpeg: {e}"))?;
+        if !status.success() {
+            return Err(format!("ffmpeg exited with {status}"));
+        }
+        Ok(())
+    }
+}
+
+pub fn h264_args(output: &str) -> [&str; 5] {
+    ["-c:v", "libx264", "-pix_fmt", "yuv420p", output]
+}
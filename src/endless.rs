@@ -0,0 +1,130 @@
+use crate::render::{self, Complex, INTERIOR};
+use crate::{RenderArgs, auto_frame_count, exp_lerp, fixed_path};
+use std::sync::Mutex;
+
+/// Deepest zoom an endless leg goes to; beyond this f64 runs out of precision.
+const MIN_ZOOM: f64 = 1e-12;
+/// Side of the low-resolution probes used to look for new targets.
+const PROBE_SIZE: u32 = 48;
+/// Zoom factor between successive refinement probes.
+const REFINE_STEP: f64 = 8.0;
+
+type TargetCache = Option<((u64, u64, u32), Vec<Complex>)>;
+
+/// Targets found so far, shared by every frame of the run.
+static TARGETS: Mutex<TargetCache> = Mutex::new(None);
+
+/// Center, zoom and leg-local `t` of a frame in `--endless` mode.
+///
+/// The first leg zooms from --zoom-start to --zoom-end into the animation's
+/// starting point. Every later leg zooms back out from the previous target,
+/// pans while wide, and dives into a newly found one, so the motion never cuts.
+pub fn view(args: &RenderArgs, frame: u32) -> (Complex, f64, f64) {
+    let top = args.zoom_start;
+    let floor = args.zoom_end.clamp(MIN_ZOOM, top);
+    let half = auto_frame_count(top, floor, args.fps, args.zoom_rate).max(2);
+    if frame < half {
+        let s = frame as f64 / half as f64;
+        return (target(args, 0), exp_lerp(top, floor, s), s);
+    }
+    let f = frame - half;
+    let leg = 1 + (f / (2 * half)) as usize;
+    let p = f % (2 * half);
+    let (a, b) = (target(args, leg - 1), target(args, leg));
+    let u = p as f64 / (2 * half) as f64;
+    if p < half {
+        let zoom = exp_lerp(floor, top, p as f64 / half as f64);
+        (lerp(a, b, 0.5 * zoom / top), zoom, u)
+    } else {
+        let zoom = exp_lerp(top, floor, (p - half) as f64 / half as f64);
+        (lerp(b, a, 0.5 * zoom / top), zoom, u)
+    }
+}
+
+fn lerp(a: Complex, b: Complex, w: f64) -> Complex {
+    Complex {
+        re: a.re + (b.re - a.re) * w,
+        im: a.im + (b.im - a.im) * w,
+    }
+}
+
+/// The `leg`-th target. Found deterministically from the ones before it, so
+/// re-rendering any frame later lands on exactly the same point.
+fn target(args: &RenderArgs, leg: usize) -> Complex {
+    let top = args.zoom_start;
+    let floor = args.zoom_end.clamp(MIN_ZOOM, top);
+    let key = (top.to_bits(), floor.to_bits(), args.max_iter);
+    loop {
+        let earlier = {
+            let mut cache = TARGETS.lock().unwrap_or_else(|e| e.into_inner());
+            if cache.as_ref().is_none_or(|(k, _)| *k != key) {
+                *cache = Some((key, vec![fixed_path(args.fractal)[0]]));
+            }
+            let targets = &cache.as_ref().expect("initialized above").1;
+            if let Some(&target) = targets.get(leg) {
+                return target;
+            }
+            targets.clone()
+        };
+        // Probed without the lock: under --frame-jobs a worker waiting in the
+        // probe's parallel loop may pick up another frame that needs a target
+        // too, and would wait for a lock its own thread holds. The same
+        // targets give the same next one, so whichever is stored first wins.
+        let next = find_target(&earlier, top, floor, args.max_iter);
+        let mut cache = TARGETS.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some((k, targets)) = cache.as_mut()
+            && *k == key
+            && targets.len() == earlier.len()
+        {
+            targets.push(next);
+        }
+    }
+}
+
+/// Looks for a boundary point away from the earlier targets: the slowest
+/// escaping pixel of a wide probe around the last target, then repeatedly the
+/// slowest escaping pixel of a closer probe around that, down to `floor`.
+fn find_target(earlier: &[Complex], top: f64, floor: f64, max_iter: u32) -> Complex {
+    let last = earlier[earlier.len() - 1];
+    let far_enough = |c: Complex| {
+        earlier.iter().all(|e| {
+            let (dx, dy) = (c.re - e.re, c.im - e.im);
+            (dx * dx + dy * dy).sqrt() > 0.25 * top
+        })
+    };
+    let mut center = match slowest_escape(last, top, max_iter, far_enough) {
+        Some(c) => c,
+        None => return last,
+    };
+    let mut zoom = top;
+    while zoom / REFINE_STEP > floor {
+        zoom /= REFINE_STEP;
+        match slowest_escape(center, zoom, max_iter, |_| true) {
+            Some(c) => center = c,
+            None => break,
+        }
+    }
+    center
+}
+
+/// The point of the probe view that escapes last, among those `allowed`.
+fn slowest_escape(
+    center: Complex,
+    zoom: f64,
+    max_iter: u32,
+    allowed: impl Fn(Complex) -> bool,
+) -> Option<Complex> {
+    let probe = render::iterate_frame(PROBE_SIZE, PROBE_SIZE, center, zoom, max_iter, true);
+    let point = render::pixel_mapping(PROBE_SIZE, PROBE_SIZE, center, zoom);
+    let mut best: Option<(f64, Complex)> = None;
+    for (idx, &value) in probe.values.iter().enumerate() {
+        if value == INTERIOR || !value.is_finite() {
+            continue;
+        }
+        let c = point(idx as u32 % PROBE_SIZE, idx as u32 / PROBE_SIZE);
+        if allowed(c) && best.is_none_or(|(v, _)| value > v) {
+            best = Some((value, c));
+        }
+    }
+    best.map(|(_, c)| c)
+}
@@ -0,0 +1,108 @@
+use crate::anomaly::NAN_COLOR;
+use crate::numeric::{ComplexNum, DoubleDouble};
+use crate::render::{self, Complex, IterationBuffer};
+use crate::{FrameInfo, RenderArgs};
+use image::{ImageBuffer, Rgb};
+use rayon::prelude::*;
+
+/// Subdirectory and manifest variant of the error maps.
+pub const LABEL: &str = "error";
+
+/// Errors of at most this many iterations are drawn black.
+const FLOOR: f64 = 1e-6;
+/// Errors of this many iterations and more are drawn white.
+const CEILING: f64 = 10.0;
+/// Pixels off by more than this many iterations count as inaccurate.
+pub const TOLERANCE: f64 = 0.01;
+/// Pixels the frame and the reference disagree on being inside the set.
+const MISMATCH_COLOR: [u8; 3] = [255, 0, 0];
+
+/// How far one frame is from its reference.
+#[derive(Debug, Clone, Copy)]
+pub struct Report {
+    /// Pixels off by more than [`TOLERANCE`] iterations.
+    pub inaccurate: usize,
+    /// Pixels escaping in one and interior in the other.
+    pub mismatched: usize,
+    /// Largest error among the pixels both escape at.
+    pub max_error: f64,
+    pub pixels: usize,
+}
+
+/// Re-iterates every pixel of the frame in double-double precision, from
+/// the plane point the pixel stands for before f64 rounds it, and maps how
+/// far the frame's smooth escape time is from it: black where they agree,
+/// brighter on a log scale up to white at [`CEILING`] iterations, red where
+/// only one of them escaped. This catches both the cascade's resampling and
+/// f64 running out of digits at deep zooms.
+pub fn measure(
+    args: &RenderArgs,
+    info: &FrameInfo,
+    iterations: &IterationBuffer,
+) -> (ImageBuffer<Rgb<u8>, Vec<u8>>, Report) {
+    let (width, height) = (iterations.width, iterations.height);
+    // Offsets from the center stay exact enough in f64; only their sum with
+    // the center needs the extra digits.
+    let offset = render::transformed_mapping(
+        width,
+        height,
+        Complex::ZERO,
+        info.zoom,
+        info.rotation,
+        info.transform,
+        info.warp,
+    );
+    let center: ComplexNum<DoubleDouble> = info.center.cast();
+    let reference = IterationBuffer::from_fn(width, height, iterations.max_iter, |x, y| {
+        let c = center.add(offset(x as f64, y as f64).cast());
+        render::mandelbrot_smooth_in(c, iterations.max_iter, args.derivative_bailout)
+    });
+
+    let mut img: ImageBuffer<Rgb<u8>, Vec<u8>> = ImageBuffer::new(width, height);
+    img.as_mut()
+        .par_chunks_mut(3)
+        .zip(iterations.values.par_iter().zip(&reference.values))
+        .for_each(|(pixel, (&value, &expected))| {
+            pixel.copy_from_slice(&color(value, expected));
+        });
+    let (inaccurate, mismatched, max_error) = iterations
+        .values
+        .par_iter()
+        .zip(&reference.values)
+        .map(|(&value, &expected)| match error(value, expected) {
+            Some(e) => ((e > TOLERANCE) as usize, 0, e),
+            None => (1, 1, 0.0),
+        })
+        .reduce(|| (0, 0, 0.0), |a, b| (a.0 + b.0, a.1 + b.1, a.2.max(b.2)));
+    let report = Report {
+        inaccurate,
+        mismatched,
+        max_error,
+        pixels: iterations.values.len(),
+    };
+    (img, report)
+}
+
+/// Iterations between the two values, `None` when only one escaped.
+fn error(value: f64, expected: f64) -> Option<f64> {
+    match (value < 0.0, expected < 0.0) {
+        (true, true) => Some(0.0),
+        (false, false) => Some((value - expected).abs()),
+        _ => None,
+    }
+}
+
+fn color(value: f64, expected: f64) -> [u8; 3] {
+    if value.is_nan() || expected.is_nan() {
+        return NAN_COLOR;
+    }
+    match error(value, expected) {
+        None => MISMATCH_COLOR,
+        Some(e) if e <= FLOOR => [0, 0, 0],
+        Some(e) => {
+            let level = (e / FLOOR).log10() / (CEILING / FLOOR).log10();
+            let gray = (level.clamp(0.0, 1.0) * 255.0).round() as u8;
+            [gray, gray, gray]
+        }
+    }
+}
@@ -0,0 +1,111 @@
+use crate::{RenderArgs, color_frame, encode_main_frame, fixed_path, frame_info, iterate, threads};
+use std::time::Instant;
+
+#[derive(clap::Args, Debug)]
+pub struct EstimateArgs {
+    #[command(flatten)]
+    render: RenderArgs,
+    /// Number of probe frames spread evenly over the animation.
+    #[arg(long, default_value_t = 5)]
+    probes: u32,
+    /// Linear resolution of each probe relative to the requested frame size.
+    #[arg(long, default_value_t = 0.1)]
+    probe_scale: f64,
+}
+
+pub fn run(args: EstimateArgs) -> Result<(), String> {
+    args.render.start_threads()?;
+    threads::install(move || estimate(args))
+}
+
+fn estimate(args: EstimateArgs) -> Result<(), String> {
+    let render = &args.render;
+    if render.endless {
+        return Err("estimate needs a finite animation, --endless never finishes".to_string());
+    }
+    let total_frames = render.total_frames();
+    let probes = args.probes.clamp(1, total_frames);
+    let scale = args.probe_scale.clamp(0.01, 1.0);
+    let probe_width = ((render.width as f64 * scale).round() as u32).max(1);
+    let probe_height = ((render.height as f64 * scale).round() as u32).max(1);
+    let pixel_ratio =
+        (render.width as f64 * render.height as f64) / (probe_width as f64 * probe_height as f64);
+
+    let path = fixed_path(render.fractal);
+    let mut seconds = 0.0;
+    let mut bytes = 0.0;
+    // Iteration limits of the probes, which differ under --auto-iter.
+    let (mut min_iter, mut max_iter) = (u32::MAX, 0);
+    for k in 0..probes {
+        let frame = if probes <= 1 {
+            0
+        } else {
+            k * (total_frames - 1) / (probes - 1)
+        };
+        let info = frame_info(render, &path, frame);
+        let started = Instant::now();
+        let iterations = iterate(render, &info, probe_width, probe_height, info.max_iter);
+        let img = color_frame(render, &iterations, &info, &render.palette);
+        let elapsed = started.elapsed().as_secs_f64();
+        // Written as a run writes its main frames, so the disk estimate
+        // follows --format and --bit-depth.
+        let encoded = encode_main_frame(render, &iterations, &info, &img)?;
+        println!(
+            "probe frame {frame}: {probe_width}x{probe_height} (max_iter {}) in {:.3}s, {} bytes",
+            info.max_iter,
+            elapsed,
+            encoded.len()
+        );
+        min_iter = min_iter.min(info.max_iter);
+        max_iter = max_iter.max(info.max_iter);
+        seconds += elapsed;
+        bytes += encoded.len() as f64;
+    }
+
+    let threads = rayon::current_num_threads() as f64;
+    let frame_seconds = seconds / probes as f64 * pixel_ratio;
+    let wall_seconds = frame_seconds * total_frames as f64;
+    let cpu_hours = wall_seconds * threads / 3600.0;
+    let disk_bytes = bytes / probes as f64 * pixel_ratio * total_frames as f64;
+
+    let iterations = if min_iter == max_iter {
+        max_iter.to_string()
+    } else {
+        format!("{min_iter}-{max_iter}")
+    };
+    println!();
+    println!(
+        "estimate for {} frames at {}x{} (max_iter {iterations}):",
+        total_frames, render.width, render.height
+    );
+    println!("  per frame:  {}", format_duration(frame_seconds));
+    println!(
+        "  wall time:  {} on {} threads",
+        format_duration(wall_seconds),
+        threads
+    );
+    println!("  cpu time:   {cpu_hours:.2} CPU-hours");
+    println!("  disk usage: {}", format_bytes(disk_bytes));
+    Ok(())
+}
+
+fn format_duration(seconds: f64) -> String {
+    if seconds < 120.0 {
+        format!("{seconds:.1}s")
+    } else if seconds < 7200.0 {
+        format!("{:.1}min", seconds / 60.0)
+    } else {
+        format!("{:.1}h", seconds / 3600.0)
+    }
+}
+
+fn format_bytes(bytes: f64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let mut value = bytes;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    format!("{value:.1} {}", UNITS[unit])
+}
@@ -0,0 +1,255 @@
+use crate::output::{self, FrameWriter};
+use crate::render::{self, Complex};
+use crate::warp::Warp;
+use crate::{
+    FrameInfo, RenderArgs, encode_png, fixed_path, frame_filename, frame_info, jitter,
+    perturbation, threads,
+};
+use image::{ImageBuffer, Rgb};
+use rayon::prelude::*;
+use std::collections::VecDeque;
+use std::f64::consts::TAU;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// Strip rows iterated at a time.
+const BAND_ROWS: usize = 64;
+
+#[derive(clap::Args, Debug)]
+pub struct ExpMapArgs {
+    #[command(flatten)]
+    render: RenderArgs,
+    /// Columns of the strip, one full turn around the center; by default
+    /// enough that its pixels are no coarser than those of the frame corners.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(16..))]
+    strip_width: Option<u32>,
+}
+
+pub fn run(args: ExpMapArgs) -> Result<(), String> {
+    args.render.start_threads()?;
+    threads::install(move || exp_map(args))
+}
+
+/// Renders a pure zoom as one exponential map of the plane around the
+/// center: a strip whose columns go once round the center and whose rows
+/// step down in radius by the same factor each, so every zoom level takes
+/// the same number of rows. Each frame is then resampled from the rows its
+/// radii fall in, which iterates far fewer pixels than rendering the frames
+/// one by one.
+fn exp_map(args: ExpMapArgs) -> Result<(), String> {
+    let render = &args.render;
+    render.check()?;
+    if render.endless {
+        return Err("exp-map needs a finite animation, not --endless".to_string());
+    }
+    let path = fixed_path(render.fractal);
+    let frames: Vec<FrameInfo> = render
+        .frame_range()
+        .map(|frame| frame_info(render, &path, frame))
+        .collect();
+    check(render, &frames)?;
+    let (width, height) = (render.width, render.height);
+    let (first, last) = (&frames[0], &frames[frames.len() - 1]);
+    let (half_w, half_h) = (width as f64 / 2.0, height as f64 / 2.0);
+    let half_min = half_w.min(half_h);
+    let strip_width = args
+        .strip_width
+        .unwrap_or_else(|| (TAU * half_w.hypot(half_h)).ceil() as u32);
+    // From the corners of the first frame to half a pixel of the last.
+    let step = TAU / strip_width as f64;
+    let top = (first.zoom * half_w.hypot(half_h) / half_min).ln();
+    let bottom = (last.zoom / half_min / 2.0).ln();
+    let strip = Strip {
+        width: strip_width as usize,
+        step,
+        top,
+        rows: ((top - bottom) / step).ceil() as usize + 2,
+    };
+    let iterated = strip.width as f64 * strip.rows as f64;
+    let direct = width as f64 * height as f64 * frames.len() as f64;
+    println!(
+        "exp-map: strip {}x{} px for {} frames, {:.0}% of the pixels of rendering them one by one",
+        strip.width,
+        strip.rows,
+        frames.len(),
+        100.0 * iterated / direct
+    );
+
+    // One reference and one iteration limit, the deepest frame's, for all of it.
+    let max_iter = last.max_iter;
+    let reference = perturbation::Reference::new(
+        last.center,
+        render.deep_center.as_ref(),
+        perturbation::pixel_scale(width, height, last.zoom),
+        max_iter,
+        render.orbit_store().as_ref(),
+    );
+    let (palette, phase) = jitter::apply(render, first, &render.palette);
+    let band = |index: usize| {
+        let start = index * BAND_ROWS;
+        let rows = BAND_ROWS.min(strip.rows - start);
+        let iterations = perturbation::iterate(
+            &reference,
+            strip.width as u32,
+            rows as u32,
+            |x, y| strip.offset(x, start as f64 + y),
+            max_iter,
+            render.derivative_bailout,
+        );
+        iterations.colorize_with_phase(palette.as_ref(), phase)
+    };
+
+    let out_dir = output::resolve_dir(Path::new(&render.out_dir))?;
+    fs::create_dir_all(&out_dir).map_err(|e| output::describe_error("create", &out_dir, &e))?;
+    let mut writer = FrameWriter::new(
+        out_dir,
+        render.fallback_dir.as_ref().map(PathBuf::from),
+        !render.non_interactive,
+    );
+    let started = Instant::now();
+    // The bands the current frame samples, from `first_band` on.
+    let mut bands: VecDeque<ImageBuffer<Rgb<u8>, Vec<u8>>> = VecDeque::new();
+    let mut first_band = 0;
+    for (done, info) in frames.iter().enumerate() {
+        let (near, far) = strip.rows_for(info.zoom, half_w, half_h, half_min);
+        while first_band < near / BAND_ROWS && !bands.is_empty() {
+            bands.pop_front();
+            first_band += 1;
+        }
+        if bands.is_empty() {
+            first_band = near / BAND_ROWS;
+        }
+        while (first_band + bands.len()) * BAND_ROWS <= far {
+            bands.push_back(band(first_band + bands.len()));
+        }
+        let offset = perturbation::frame_offsets(
+            width,
+            height,
+            info.zoom,
+            0.0,
+            render::IDENTITY,
+            Warp::None,
+        );
+        let pixel = |row: usize, column: usize| -> [f64; 3] {
+            let band = &bands[row / BAND_ROWS - first_band];
+            let p = band.get_pixel(column as u32, (row % BAND_ROWS) as u32);
+            p.0.map(f64::from)
+        };
+        let mut img: ImageBuffer<Rgb<u8>, Vec<u8>> = ImageBuffer::new(width, height);
+        img.as_mut()
+            .par_chunks_mut(3)
+            .enumerate()
+            .for_each(|(idx, out)| {
+                let (x, y) = (idx as u32 % width, idx as u32 / width);
+                let (u, v) = strip.position(offset(x as f64, y as f64));
+                let v = v.clamp(near as f64, far as f64);
+                let (u0, v0) = (u.floor(), v.floor().min((far - 1) as f64));
+                let (fu, fv) = (u - u0, v - v0);
+                let (u0, v0) = (u0 as usize % strip.width, v0 as usize);
+                let u1 = (u0 + 1) % strip.width;
+                let [a, b, c, d] = [
+                    pixel(v0, u0),
+                    pixel(v0, u1),
+                    pixel(v0 + 1, u0),
+                    pixel(v0 + 1, u1),
+                ];
+                for k in 0..3 {
+                    let top = a[k] + (b[k] - a[k]) * fu;
+                    let bottom = c[k] + (d[k] - c[k]) * fu;
+                    out[k] = (top + (bottom - top) * fv).round() as u8;
+                }
+            });
+        let file = writer.write(&frame_filename(info.index), &encode_png(&img)?)?;
+        println!("frame {}/{} -> {}", done + 1, frames.len(), file.display());
+    }
+    println!(
+        "exp-map: {} frames in {:.1}s",
+        frames.len(),
+        started.elapsed().as_secs_f64()
+    );
+    Ok(())
+}
+
+/// Rejects everything but a pure zoom into a fixed center colored from the
+/// escape times alone.
+fn check(args: &RenderArgs, frames: &[FrameInfo]) -> Result<(), String> {
+    if !args.quadratic_mandelbrot() {
+        return Err("exp-map renders --fractal mandelbrot with --power 2".to_string());
+    }
+    if frames.windows(2).any(|pair| pair[1].zoom >= pair[0].zoom) {
+        return Err("exp-map needs a zoom that goes in every frame".to_string());
+    }
+    if frames.iter().any(|info| info.center != frames[0].center) {
+        return Err(
+            "exp-map needs a fixed center, use --deep-center or a path of one point".to_string(),
+        );
+    }
+    let unsupported = [
+        (!args.coloring.is_smooth(), "--coloring other than smooth"),
+        (
+            frames.iter().any(|info| info.rotation != 0.0),
+            "a rotation track",
+        ),
+        (
+            frames.iter().any(|info| info.transform != render::IDENTITY),
+            "--transform",
+        ),
+        (args.warp != Warp::None, "--warp"),
+        (args.boundary_aa > 1, "--boundary-aa"),
+        (args.refine_max_iter.is_some(), "--refine-max-iter"),
+        (args.deflicker > 1, "--deflicker"),
+        (!args.layer.is_empty(), "--layer"),
+        (args.texture.is_some(), "--texture"),
+        (args.grid, "--grid"),
+        (args.legend.is_some(), "--legend"),
+        (args.stats_hud, "--stats-hud"),
+        (args.overlay.is_some(), "--overlay"),
+        (args.canvas.is_some(), "--canvas"),
+    ];
+    match unsupported.iter().find(|(used, _)| *used) {
+        Some((_, flag)) => Err(format!("exp-map doesn't support {flag}")),
+        None => Ok(()),
+    }
+}
+
+/// The exponential map: column `u` lies at angle `u * step` around the
+/// center, row `v` at radius `exp(top - v * step)`.
+#[derive(Clone, Copy)]
+struct Strip {
+    width: usize,
+    /// Radians per column and natural log of the radius per row.
+    step: f64,
+    /// Log of the radius of row 0.
+    top: f64,
+    rows: usize,
+}
+
+impl Strip {
+    /// Difference from the center of the point at strip column `u`, row `v`.
+    fn offset(&self, u: f64, v: f64) -> Complex {
+        let radius = (self.top - v * self.step).exp();
+        let (sin, cos) = (u * self.step).sin_cos();
+        Complex {
+            re: radius * cos,
+            im: radius * sin,
+        }
+    }
+
+    /// Column and row of the difference `d` from the center, the inverse of
+    /// [`Strip::offset`]; the center itself is infinitely far down.
+    fn position(&self, d: Complex) -> (f64, f64) {
+        let u = d.im.atan2(d.re).rem_euclid(TAU) / self.step;
+        let v = (self.top - d.norm_sqr().sqrt().ln()) / self.step;
+        (u, v)
+    }
+
+    /// First and last row a frame of half-size `zoom` samples; the pixels
+    /// closer to the center than half a pixel take the last one.
+    fn rows_for(&self, zoom: f64, half_w: f64, half_h: f64, half_min: f64) -> (usize, usize) {
+        let row = |radius: f64| (self.top - radius.ln()) / self.step;
+        let near = row(zoom * half_w.hypot(half_h) / half_min).floor().max(0.0) as usize;
+        let far = row(zoom / half_min / 2.0).ceil().max(1.0) as usize;
+        (near.min(self.rows - 2), far.min(self.rows - 1))
+    }
+}
@@ -0,0 +1,327 @@
+use std::str::FromStr;
+
+/// A small arithmetic expression such as `0.1*t + 0.02*sin(tau*t*4)`.
+///
+/// Supports `+ - * / ^`, parentheses, unary minus, the constants `pi`, `tau`
+/// and `e`, named variables supplied at evaluation time and the functions
+/// `sin cos tan abs sqrt exp ln floor fract min max clamp`.
+#[derive(Debug, Clone)]
+pub struct Expr {
+    root: Node,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Num(f64),
+    Var(String),
+    Neg(Box<Node>),
+    Bin(Op, Box<Node>, Box<Node>),
+    Call(Func, Vec<Node>),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Func {
+    Sin,
+    Cos,
+    Tan,
+    Abs,
+    Sqrt,
+    Exp,
+    Ln,
+    Floor,
+    Fract,
+    Min,
+    Max,
+    Clamp,
+}
+
+impl Func {
+    fn lookup(name: &str) -> Option<(Func, usize)> {
+        Some(match name {
+            "sin" => (Func::Sin, 1),
+            "cos" => (Func::Cos, 1),
+            "tan" => (Func::Tan, 1),
+            "abs" => (Func::Abs, 1),
+            "sqrt" => (Func::Sqrt, 1),
+            "exp" => (Func::Exp, 1),
+            "ln" => (Func::Ln, 1),
+            "floor" => (Func::Floor, 1),
+            "fract" => (Func::Fract, 1),
+            "min" => (Func::Min, 2),
+            "max" => (Func::Max, 2),
+            "clamp" => (Func::Clamp, 3),
+            _ => return None,
+        })
+    }
+
+    fn call(self, args: &[f64]) -> f64 {
+        match self {
+            Func::Sin => args[0].sin(),
+            Func::Cos => args[0].cos(),
+            Func::Tan => args[0].tan(),
+            Func::Abs => args[0].abs(),
+            Func::Sqrt => args[0].sqrt(),
+            Func::Exp => args[0].exp(),
+            Func::Ln => args[0].ln(),
+            Func::Floor => args[0].floor(),
+            Func::Fract => args[0] - args[0].floor(),
+            Func::Min => args[0].min(args[1]),
+            Func::Max => args[0].max(args[1]),
+            Func::Clamp => args[0].clamp(args[1].min(args[2]), args[2].max(args[1])),
+        }
+    }
+}
+
+impl FromStr for Expr {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Expr, String> {
+        let parse = || {
+            let mut parser = Parser {
+                tokens: tokenize(s)?,
+                pos: 0,
+            };
+            let root = parser.sum()?;
+            if let Some(token) = parser.peek() {
+                return Err(format!("unexpected {token:?}"));
+            }
+            Ok(Expr { root })
+        };
+        parse().map_err(|e: String| format!("expression {s:?}: {e}"))
+    }
+}
+
+impl Expr {
+    /// Names of the variables the expression refers to, in order of appearance.
+    pub fn variables(&self) -> Vec<&str> {
+        fn walk<'a>(node: &'a Node, out: &mut Vec<&'a str>) {
+            match node {
+                Node::Num(_) => {}
+                Node::Var(name) => {
+                    if !out.contains(&name.as_str()) {
+                        out.push(name);
+                    }
+                }
+                Node::Neg(inner) => walk(inner, out),
+                Node::Bin(_, a, b) => {
+                    walk(a, out);
+                    walk(b, out);
+                }
+                Node::Call(_, args) => args.iter().for_each(|a| walk(a, out)),
+            }
+        }
+        let mut out = Vec::new();
+        walk(&self.root, &mut out);
+        out
+    }
+
+    /// Evaluates with the given variable values; unknown variables read as 0.
+    pub fn eval(&self, vars: &[(&str, f64)]) -> f64 {
+        eval(&self.root, vars)
+    }
+}
+
+fn eval(node: &Node, vars: &[(&str, f64)]) -> f64 {
+    match node {
+        Node::Num(v) => *v,
+        Node::Var(name) => vars
+            .iter()
+            .find(|(k, _)| k == name)
+            .map_or(0.0, |(_, v)| *v),
+        Node::Neg(inner) => -eval(inner, vars),
+        Node::Bin(op, a, b) => {
+            let (a, b) = (eval(a, vars), eval(b, vars));
+            match op {
+                Op::Add => a + b,
+                Op::Sub => a - b,
+                Op::Mul => a * b,
+                Op::Div => a / b,
+                Op::Pow => a.powf(b),
+            }
+        }
+        Node::Call(func, args) => {
+            let values: Vec<f64> = args.iter().map(|a| eval(a, vars)).collect();
+            func.call(&values)
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Token {
+    Num(f64),
+    Ident(String),
+    Sym(char),
+}
+
+pub fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let chars: Vec<char> = s.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() || c == '.' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            // Exponent: 1e-6, 2.5E3
+            if i < chars.len() && (chars[i] == 'e' || chars[i] == 'E') {
+                let mut j = i + 1;
+                if j < chars.len() && (chars[j] == '+' || chars[j] == '-') {
+                    j += 1;
+                }
+                if j < chars.len() && chars[j].is_ascii_digit() {
+                    i = j;
+                    while i < chars.len() && chars[i].is_ascii_digit() {
+                        i += 1;
+                    }
+                }
+            }
+            let text: String = chars[start..i].iter().collect();
+            let value = text
+                .parse()
+                .map_err(|_| format!("invalid number {text:?}"))?;
+            tokens.push(Token::Num(value));
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push(Token::Ident(chars[start..i].iter().collect()));
+        } else if "+-*/^(),".contains(c) {
+            tokens.push(Token::Sym(c));
+            i += 1;
+        } else {
+            return Err(format!("unexpected character {c:?}"));
+        }
+    }
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn eat(&mut self, sym: char) -> bool {
+        if self.peek() == Some(&Token::Sym(sym)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, sym: char) -> Result<(), String> {
+        if self.eat(sym) {
+            Ok(())
+        } else {
+            match self.peek() {
+                Some(token) => Err(format!("expected {sym:?}, found {token:?}")),
+                None => Err(format!("expected {sym:?} at end of expression")),
+            }
+        }
+    }
+
+    fn sum(&mut self) -> Result<Node, String> {
+        let mut node = self.product()?;
+        loop {
+            let op = if self.eat('+') {
+                Op::Add
+            } else if self.eat('-') {
+                Op::Sub
+            } else {
+                return Ok(node);
+            };
+            node = Node::Bin(op, Box::new(node), Box::new(self.product()?));
+        }
+    }
+
+    fn product(&mut self) -> Result<Node, String> {
+        let mut node = self.unary()?;
+        loop {
+            let op = if self.eat('*') {
+                Op::Mul
+            } else if self.eat('/') {
+                Op::Div
+            } else {
+                return Ok(node);
+            };
+            node = Node::Bin(op, Box::new(node), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Node, String> {
+        if self.eat('-') {
+            return Ok(Node::Neg(Box::new(self.unary()?)));
+        }
+        if self.eat('+') {
+            return self.unary();
+        }
+        self.power()
+    }
+
+    /// `^` binds tighter than unary minus on its left and is right-associative.
+    fn power(&mut self) -> Result<Node, String> {
+        let base = self.atom()?;
+        if self.eat('^') {
+            let exponent = self.unary()?;
+            return Ok(Node::Bin(Op::Pow, Box::new(base), Box::new(exponent)));
+        }
+        Ok(base)
+    }
+
+    fn atom(&mut self) -> Result<Node, String> {
+        let token = self.peek().cloned().ok_or("unexpected end of expression")?;
+        self.pos += 1;
+        match token {
+            Token::Num(v) => Ok(Node::Num(v)),
+            Token::Sym('(') => {
+                let node = self.sum()?;
+                self.expect(')')?;
+                Ok(node)
+            }
+            Token::Ident(name) => {
+                if self.eat('(') {
+                    let (func, arity) =
+                        Func::lookup(&name).ok_or_else(|| format!("unknown function {name:?}"))?;
+                    let mut args = vec![self.sum()?];
+                    while self.eat(',') {
+                        args.push(self.sum()?);
+                    }
+                    self.expect(')')?;
+                    if args.len() != arity {
+                        return Err(format!(
+                            "{name} takes {arity} argument(s), got {}",
+                            args.len()
+                        ));
+                    }
+                    return Ok(Node::Call(func, args));
+                }
+                Ok(match name.as_str() {
+                    "pi" => Node::Num(std::f64::consts::PI),
+                    "tau" => Node::Num(std::f64::consts::TAU),
+                    "e" => Node::Num(std::f64::consts::E),
+                    _ => Node::Var(name),
+                })
+            }
+            Token::Sym(c) => Err(format!("unexpected {c:?}")),
+        }
+    }
+}
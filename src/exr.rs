@@ -0,0 +1,111 @@
+use flate2::Compression;
+use flate2::write::ZlibEncoder;
+use rayon::prelude::*;
+use std::io::Write;
+
+/// Scanlines per block of ZIP compression, fixed by the format.
+const BLOCK_LINES: usize = 16;
+/// Compression attribute value of ZIP_COMPRESSION.
+const ZIP_COMPRESSION: u8 = 3;
+/// Channel pixel type of 32-bit floats.
+const FLOAT: i32 = 2;
+
+/// Writes a single-part scanline OpenEXR image of 32-bit float channels,
+/// each `(name, values)` holding one value per pixel in rows from the top.
+pub fn encode(width: u32, height: u32, channels: &[(&str, Vec<f32>)]) -> Vec<u8> {
+    // Readers expect the channels sorted by name, in lists and in lines.
+    let mut channels: Vec<&(&str, Vec<f32>)> = channels.iter().collect();
+    channels.sort_by_key(|(name, _)| *name);
+    let (w, h) = (width as usize, height as usize);
+
+    let mut out = vec![0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0];
+    let mut list = Vec::new();
+    for (name, _) in &channels {
+        list.extend_from_slice(name.as_bytes());
+        list.push(0);
+        list.extend_from_slice(&FLOAT.to_le_bytes());
+        // Not perceptually linear, three reserved bytes, no subsampling.
+        list.extend_from_slice(&[0, 0, 0, 0]);
+        list.extend_from_slice(&1i32.to_le_bytes());
+        list.extend_from_slice(&1i32.to_le_bytes());
+    }
+    list.push(0);
+    let window: Vec<u8> = [0, 0, width as i32 - 1, height as i32 - 1]
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect();
+    attribute(&mut out, "channels", "chlist", &list);
+    attribute(&mut out, "compression", "compression", &[ZIP_COMPRESSION]);
+    attribute(&mut out, "dataWindow", "box2i", &window);
+    attribute(&mut out, "displayWindow", "box2i", &window);
+    attribute(&mut out, "lineOrder", "lineOrder", &[0]);
+    attribute(&mut out, "pixelAspectRatio", "float", &1f32.to_le_bytes());
+    attribute(&mut out, "screenWindowCenter", "v2f", &[0; 8]);
+    attribute(&mut out, "screenWindowWidth", "float", &1f32.to_le_bytes());
+    out.push(0);
+
+    let blocks: Vec<Vec<u8>> = (0..h.div_ceil(BLOCK_LINES))
+        .into_par_iter()
+        .map(|block| {
+            let rows = block * BLOCK_LINES..((block + 1) * BLOCK_LINES).min(h);
+            let mut raw = Vec::with_capacity(rows.len() * w * channels.len() * 4);
+            for y in rows {
+                for (_, values) in &channels {
+                    for value in &values[y * w..(y + 1) * w] {
+                        raw.extend_from_slice(&value.to_le_bytes());
+                    }
+                }
+            }
+            let data = compress(&raw);
+            let mut chunk = Vec::with_capacity(8 + data.len());
+            chunk.extend_from_slice(&((block * BLOCK_LINES) as i32).to_le_bytes());
+            chunk.extend_from_slice(&(data.len() as i32).to_le_bytes());
+            chunk.extend_from_slice(&data);
+            chunk
+        })
+        .collect();
+    let mut offset = (out.len() + 8 * blocks.len()) as u64;
+    for block in &blocks {
+        out.extend_from_slice(&offset.to_le_bytes());
+        offset += block.len() as u64;
+    }
+    for block in blocks {
+        out.extend_from_slice(&block);
+    }
+    out
+}
+
+fn attribute(out: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    for text in [name, kind] {
+        out.extend_from_slice(text.as_bytes());
+        out.push(0);
+    }
+    out.extend_from_slice(&(value.len() as i32).to_le_bytes());
+    out.extend_from_slice(value);
+}
+
+/// The ZIP_COMPRESSION form of a block: the even bytes before the odd ones,
+/// each byte stored as its difference to the one before, then zlib. A block
+/// that doesn't get smaller is stored as it is, which readers tell by size.
+fn compress(raw: &[u8]) -> Vec<u8> {
+    let mut split: Vec<u8> = raw
+        .iter()
+        .step_by(2)
+        .chain(raw.iter().skip(1).step_by(2))
+        .copied()
+        .collect();
+    let mut previous = split.first().copied().unwrap_or(0);
+    for byte in split.iter_mut().skip(1) {
+        let current = *byte;
+        *byte = current.wrapping_sub(previous).wrapping_add(128);
+        previous = current;
+    }
+    let mut zlib = ZlibEncoder::new(Vec::new(), Compression::default());
+    zlib.write_all(&split).expect("write to memory");
+    let data = zlib.finish().expect("write to memory");
+    if data.len() < raw.len() {
+        data
+    } else {
+        raw.to_vec()
+    }
+}
@@ -0,0 +1,264 @@
+use crate::FrameInfo;
+use crate::health;
+use crate::render::{IDENTITY, INTERIOR, IterationBuffer};
+use rayon::prelude::*;
+use std::f64::consts::TAU;
+use std::str::FromStr;
+
+/// Independent chaos-game runs; fixed so the result does not depend on the thread count.
+const CHUNKS: u64 = 256;
+/// Iterations discarded at the start of each run before points land on the attractor.
+const BURN_IN: u32 = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Preset {
+    Sierpinski,
+    Swirl,
+    Spherical,
+}
+
+impl FromStr for Preset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Preset, String> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "sierpinski" => Ok(Preset::Sierpinski),
+            "swirl" => Ok(Preset::Swirl),
+            "spherical" => Ok(Preset::Spherical),
+            _ => Err(format!(
+                "unknown flame preset {s:?}, expected sierpinski, swirl or spherical"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Variation {
+    Linear,
+    Sinusoidal,
+    Spherical,
+    Swirl,
+    Horseshoe,
+}
+
+/// `(x, y) -> variation(a*x + b*y + c, d*x + e*y + f)`, chosen with probability `weight`.
+#[derive(Debug, Clone, Copy)]
+struct Transform {
+    coefs: [f64; 6],
+    weight: f64,
+    variation: Variation,
+}
+
+impl Preset {
+    fn transforms(self) -> Vec<Transform> {
+        let t = |coefs, weight, variation| Transform {
+            coefs,
+            weight,
+            variation,
+        };
+        match self {
+            Preset::Sierpinski => vec![
+                t([0.5, 0.0, -0.5, 0.0, 0.5, 0.5], 1.0, Variation::Linear),
+                t([0.5, 0.0, 0.5, 0.0, 0.5, 0.5], 1.0, Variation::Linear),
+                t([0.5, 0.0, 0.0, 0.0, 0.5, -0.5], 1.0, Variation::Linear),
+            ],
+            Preset::Swirl => vec![
+                t([0.6, -0.35, 0.1, 0.35, 0.6, 0.0], 2.0, Variation::Swirl),
+                t(
+                    [0.45, 0.2, -0.4, -0.2, 0.45, 0.3],
+                    1.0,
+                    Variation::Sinusoidal,
+                ),
+                t([-0.4, 0.0, 0.0, 0.0, -0.4, 0.0], 0.5, Variation::Spherical),
+            ],
+            Preset::Spherical => vec![
+                t([0.7, 0.3, 0.0, -0.3, 0.7, 0.0], 1.0, Variation::Spherical),
+                t([0.5, 0.0, 0.3, 0.0, 0.5, -0.2], 1.0, Variation::Horseshoe),
+                t([0.35, -0.1, -0.45, 0.1, 0.35, 0.45], 0.7, Variation::Linear),
+            ],
+        }
+    }
+}
+
+impl Variation {
+    fn apply(self, x: f64, y: f64) -> (f64, f64) {
+        let r2 = x * x + y * y;
+        match self {
+            Variation::Linear => (x, y),
+            Variation::Sinusoidal => (x.sin(), y.sin()),
+            Variation::Spherical => {
+                let inv = 1.0 / (r2 + 1e-12);
+                (x * inv, y * inv)
+            }
+            Variation::Swirl => {
+                let (s, c) = r2.sin_cos();
+                (x * s - y * c, x * c + y * s)
+            }
+            Variation::Horseshoe => {
+                let r = r2.sqrt() + 1e-12;
+                ((x - y) * (x + y) / r, 2.0 * x * y / r)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Flame {
+    pub preset: Preset,
+    /// Chaos-game points plotted per output pixel.
+    pub samples_per_pixel: u32,
+    /// Turns each transform rotates over the whole animation.
+    pub spin: f64,
+}
+
+impl Flame {
+    /// Renders the flame by chaos-game accumulation and stores log-density as
+    /// the per-pixel value, so the palette, layers and outputs treat it exactly
+    /// like escape-time data. Pixels no point reached are [`INTERIOR`].
+    ///
+    /// `view` places the frame in the plane, as its pixel mapping does except
+    /// for warp, and its `t` in 0..=1 rotates the transforms,
+    /// alternating direction between them.
+    pub fn render(
+        &self,
+        width: u32,
+        height: u32,
+        view: &FrameInfo,
+        max_iter: u32,
+    ) -> IterationBuffer {
+        let (center, zoom, t) = (view.center, view.zoom, view.t);
+        let (spin, samples_per_pixel) = (self.spin, self.samples_per_pixel);
+        let transforms: Vec<Transform> = self
+            .preset
+            .transforms()
+            .into_iter()
+            .enumerate()
+            .map(|(i, tf)| {
+                let direction = if i % 2 == 0 { 1.0 } else { -1.0 };
+                rotate(tf, TAU * spin * t * direction)
+            })
+            .collect();
+        let total_weight: f64 = transforms.iter().map(|tf| tf.weight).sum();
+
+        let w = width as usize;
+        let h = height as usize;
+        let half_min = (w.min(h) as f64) / 2.0;
+        let scale = zoom / half_min;
+        // The inverses of the view's rotation and transform take points back
+        // to pixels.
+        let (sin, cos) = view.rotation.to_radians().sin_cos();
+        let skewed = view.transform != IDENTITY;
+        let [a, b, c, d] = view.transform;
+        let det = a * d - b * c;
+        let inverse = [d / det, -b / det, -c / det, a / det];
+        let samples = w as u64 * h as u64 * samples_per_pixel.max(1) as u64;
+        let per_chunk = samples.div_ceil(CHUNKS);
+
+        let counts = (0..CHUNKS)
+            .into_par_iter()
+            .fold(
+                || vec![0u32; w * h],
+                |mut counts, chunk| {
+                    let mut rng = Rng::new(chunk);
+                    let mut x = rng.next_f64() * 2.0 - 1.0;
+                    let mut y = rng.next_f64() * 2.0 - 1.0;
+                    for i in 0..per_chunk + BURN_IN as u64 {
+                        let mut pick = rng.next_f64() * total_weight;
+                        let tf = transforms
+                            .iter()
+                            .find(|tf| {
+                                pick -= tf.weight;
+                                pick < 0.0
+                            })
+                            .unwrap_or(&transforms[transforms.len() - 1]);
+                        let [a, b, c, d, e, f] = tf.coefs;
+                        (x, y) = tf.variation.apply(a * x + b * y + c, d * x + e * y + f);
+                        if !x.is_finite() || !y.is_finite() {
+                            x = rng.next_f64() * 2.0 - 1.0;
+                            y = rng.next_f64() * 2.0 - 1.0;
+                            continue;
+                        }
+                        if i < BURN_IN as u64 {
+                            continue;
+                        }
+                        let (dx, dy) = (x - center.re, y - center.im);
+                        let (mut dx, mut dy) = (dx * cos + dy * sin, dy * cos - dx * sin);
+                        if skewed {
+                            let [a, b, c, d] = inverse;
+                            (dx, dy) = (a * dx + b * dy, c * dx + d * dy);
+                        }
+                        let px = dx / scale + w as f64 / 2.0;
+                        let py = dy / scale + h as f64 / 2.0;
+                        if px >= 0.0 && py >= 0.0 && px < w as f64 && py < h as f64 {
+                            let idx = py as usize * w + px as usize;
+                            counts[idx] = counts[idx].saturating_add(1);
+                        }
+                    }
+                    health::progressed();
+                    counts
+                },
+            )
+            .reduce(
+                || vec![0u32; w * h],
+                |mut a, b| {
+                    for (x, y) in a.iter_mut().zip(b) {
+                        *x = x.saturating_add(y);
+                    }
+                    a
+                },
+            );
+
+        let peak = counts.iter().copied().max().unwrap_or(0).max(1) as f64;
+        let log_peak = (1.0 + peak).ln();
+        let values = counts
+            .par_iter()
+            .map(|&n| {
+                if n == 0 {
+                    INTERIOR
+                } else {
+                    (1.0 + n as f64).ln() / log_peak * max_iter as f64
+                }
+            })
+            .collect();
+
+        IterationBuffer {
+            width,
+            height,
+            max_iter,
+            values,
+        }
+    }
+}
+
+/// Rotates the whole affine map about the origin.
+fn rotate(tf: Transform, angle: f64) -> Transform {
+    let (s, c) = angle.sin_cos();
+    let [a, b, tx, d, e, ty] = tf.coefs;
+    Transform {
+        coefs: [
+            c * a - s * d,
+            c * b - s * e,
+            c * tx - s * ty,
+            s * a + c * d,
+            s * b + c * e,
+            s * tx + c * ty,
+        ],
+        ..tf
+    }
+}
+
+/// xorshift64*, seeded per chunk so every run of a frame is identical.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    pub fn next_f64(&mut self) -> f64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        (self.0.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
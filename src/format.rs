@@ -0,0 +1,103 @@
+use crate::render::IterationBuffer;
+use crate::{FrameInfo, coloring, encode_png, exr};
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::{ImageBuffer, ImageFormat, Rgb};
+use std::io::Cursor;
+use std::str::FromStr;
+
+/// File format of the main frames of a run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Png,
+    /// Lossy, at --quality.
+    Jpeg,
+    /// Lossless; the encoder at hand has no lossy mode.
+    Webp,
+    Tiff,
+    /// The frame's data rather than its colors, as 32-bit float channels.
+    Exr,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Format, String> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "png" => Ok(Format::Png),
+            "jpeg" | "jpg" => Ok(Format::Jpeg),
+            "webp" => Ok(Format::Webp),
+            "tiff" | "tif" => Ok(Format::Tiff),
+            "exr" => Ok(Format::Exr),
+            other => Err(format!(
+                "unknown format {other:?}, expected png, jpeg, webp, tiff or exr"
+            )),
+        }
+    }
+}
+
+impl Format {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Png => "png",
+            Format::Jpeg => "jpg",
+            Format::Webp => "webp",
+            Format::Tiff => "tiff",
+            Format::Exr => "exr",
+        }
+    }
+
+    /// Whether frames of 16 bits per channel can be written in it.
+    pub fn has_16_bits(self) -> bool {
+        matches!(self, Format::Png | Format::Tiff)
+    }
+}
+
+/// An 8-bit image in `format`, which is not [`Format::Exr`]; `quality`
+/// only matters to JPEG.
+pub fn encode(
+    img: &ImageBuffer<Rgb<u8>, Vec<u8>>,
+    format: Format,
+    quality: u8,
+) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    let encoded = match format {
+        Format::Png | Format::Exr => return encode_png(img),
+        Format::Jpeg => JpegEncoder::new_with_quality(&mut bytes, quality).encode_image(img),
+        Format::Webp => WebPEncoder::new_lossless(&mut bytes).encode(
+            img.as_raw(),
+            img.width(),
+            img.height(),
+            image::ExtendedColorType::Rgb8,
+        ),
+        Format::Tiff => img.write_to(&mut Cursor::new(&mut bytes), ImageFormat::Tiff),
+    };
+    encoded.map_err(|e| format!("encode {}: {e}", format.extension()))?;
+    Ok(bytes)
+}
+
+/// A 16-bit image in `format`, one of those [`Format::has_16_bits`] allows.
+pub fn encode16(img: &ImageBuffer<Rgb<u16>, Vec<u16>>, format: Format) -> Result<Vec<u8>, String> {
+    if format != Format::Tiff {
+        return encode_png(img);
+    }
+    let mut bytes = Vec::new();
+    img.write_to(&mut Cursor::new(&mut bytes), ImageFormat::Tiff)
+        .map_err(|e| format!("encode tiff: {e}"))?;
+    Ok(bytes)
+}
+
+/// An OpenEXR file of the frame's smooth escape times as the `smooth`
+/// channel, -1 inside the set, and with `distance` the distance estimate in
+/// pixels as the `distance` channel, 0 inside the set.
+pub fn encode_exr(iterations: &IterationBuffer, info: &FrameInfo, distance: bool) -> Vec<u8> {
+    let mut channels = vec![(
+        "smooth",
+        iterations.values.iter().map(|&v| v as f32).collect(),
+    )];
+    if distance {
+        let distances = coloring::pixel_distances(iterations, info);
+        channels.push(("distance", distances.iter().map(|&d| d as f32).collect()));
+    }
+    exr::encode(iterations.width, iterations.height, &channels)
+}
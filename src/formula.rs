@@ -0,0 +1,466 @@
+use crate::FRAME_VARIABLES;
+use crate::expr::{self, Token};
+use crate::render::{Complex, INTERIOR, IterationBuffer};
+use std::str::FromStr;
+
+/// Below this local growth rate the smooth escape time falls back to whole
+/// iterations; the estimate of the degree is meaningless there.
+const MIN_DEGREE: f64 = 1.01;
+
+/// `--formula`: a map `z -> f(z, c)` such as `z^2 + c*z + c` or `fold(z)^2 + c`,
+/// iterated from `z = 0` for every pixel `c`.
+///
+/// Supports `+ - * / ^`, parentheses, unary minus, imaginary numbers such as
+/// `0.5i`, the constants `i`, `pi`, `tau` and `e`, the frame variables
+/// `t frame frames zoom time` and the functions
+/// `sin cos exp ln sqrt conj fold re im abs`, where `fold` is `|Re z| + i|Im z|`.
+#[derive(Debug, Clone)]
+pub struct Formula {
+    root: Node,
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Num(Complex),
+    Z,
+    C,
+    Var(String),
+    Neg(Box<Node>),
+    Bin(Op, Box<Node>, Box<Node>),
+    Call(Func, Box<Node>),
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Func {
+    Sin,
+    Cos,
+    Exp,
+    Ln,
+    Sqrt,
+    Conj,
+    Fold,
+    Re,
+    Im,
+    Abs,
+}
+
+impl Func {
+    fn lookup(name: &str) -> Option<Func> {
+        Some(match name {
+            "sin" => Func::Sin,
+            "cos" => Func::Cos,
+            "exp" => Func::Exp,
+            "ln" => Func::Ln,
+            "sqrt" => Func::Sqrt,
+            "conj" => Func::Conj,
+            "fold" => Func::Fold,
+            "re" => Func::Re,
+            "im" => Func::Im,
+            "abs" => Func::Abs,
+            _ => return None,
+        })
+    }
+
+    fn call(self, z: Complex) -> Complex {
+        let real = |re: f64| Complex { re, im: 0.0 };
+        match self {
+            Func::Sin => Complex {
+                re: z.re.sin() * z.im.cosh(),
+                im: z.re.cos() * z.im.sinh(),
+            },
+            Func::Cos => Complex {
+                re: z.re.cos() * z.im.cosh(),
+                im: -z.re.sin() * z.im.sinh(),
+            },
+            Func::Exp => exp(z),
+            Func::Ln => ln(z),
+            Func::Sqrt => {
+                let r = z.norm_sqr().sqrt().sqrt();
+                let (sin, cos) = (z.im.atan2(z.re) / 2.0).sin_cos();
+                Complex {
+                    re: r * cos,
+                    im: r * sin,
+                }
+            }
+            Func::Conj => Complex {
+                re: z.re,
+                im: -z.im,
+            },
+            Func::Fold => Complex {
+                re: z.re.abs(),
+                im: z.im.abs(),
+            },
+            Func::Re => real(z.re),
+            Func::Im => real(z.im),
+            Func::Abs => real(z.norm_sqr().sqrt()),
+        }
+    }
+}
+
+fn exp(z: Complex) -> Complex {
+    let r = z.re.exp();
+    let (sin, cos) = z.im.sin_cos();
+    Complex {
+        re: r * cos,
+        im: r * sin,
+    }
+}
+
+fn ln(z: Complex) -> Complex {
+    Complex {
+        re: z.norm_sqr().ln() / 2.0,
+        im: z.im.atan2(z.re),
+    }
+}
+
+/// `a^b` on the principal branch, with `0^b = 0`.
+fn pow(a: Complex, b: Complex) -> Complex {
+    if a.norm_sqr() == 0.0 {
+        return Complex::ZERO;
+    }
+    exp(b.mul(ln(a)))
+}
+
+/// `a^n` by repeated squaring, exact for the small integer powers formulas use.
+fn pow_int(a: Complex, n: i32) -> Complex {
+    let mut result = Complex { re: 1.0, im: 0.0 };
+    let mut base = a;
+    let mut k = n.unsigned_abs();
+    while k > 0 {
+        if k & 1 == 1 {
+            result = result.mul(base);
+        }
+        base = base.mul(base);
+        k >>= 1;
+    }
+    if n < 0 {
+        Complex { re: 1.0, im: 0.0 }.div(result)
+    } else {
+        result
+    }
+}
+
+impl FromStr for Formula {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Formula, String> {
+        let parse = || {
+            let mut parser = Parser {
+                tokens: expr::tokenize(s)?,
+                pos: 0,
+            };
+            let root = parser.sum()?;
+            if let Some(token) = parser.peek() {
+                return Err(format!("unexpected {token:?}"));
+            }
+            Ok(Formula { root })
+        };
+        parse().map_err(|e: String| format!("formula {s:?}: {e}"))
+    }
+}
+
+/// One step of a compiled formula, run on a stack of complex values.
+#[derive(Debug, Clone, Copy)]
+enum Instr {
+    Const(Complex),
+    Z,
+    C,
+    Neg,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+    PowInt(i32),
+    Call(Func),
+}
+
+/// A formula compiled for one frame: the frame variables are substituted,
+/// every part not depending on `z` or `c` is folded into a constant and
+/// whole powers become multiplications.
+#[derive(Debug, Clone)]
+pub struct Program {
+    code: Vec<Instr>,
+    depth: usize,
+}
+
+impl Formula {
+    pub fn compile(&self, vars: &[(&str, f64)]) -> Program {
+        let mut program = Program {
+            code: Vec::new(),
+            depth: 0,
+        };
+        let mut height = 0;
+        emit(&self.root, vars, &mut program, &mut height);
+        program
+    }
+}
+
+/// The value of `node` if it depends on neither `z` nor `c`.
+fn constant(node: &Node, vars: &[(&str, f64)]) -> Option<Complex> {
+    Some(match node {
+        Node::Num(v) => *v,
+        Node::Z | Node::C => return None,
+        Node::Var(name) => Complex {
+            re: vars
+                .iter()
+                .find(|(k, _)| k == name)
+                .map_or(0.0, |(_, v)| *v),
+            im: 0.0,
+        },
+        Node::Neg(inner) => Complex::ZERO.sub(constant(inner, vars)?),
+        Node::Bin(op, a, b) => {
+            let (a, b) = (constant(a, vars)?, constant(b, vars)?);
+            match op {
+                Op::Add => a.add(b),
+                Op::Sub => a.sub(b),
+                Op::Mul => a.mul(b),
+                Op::Div => a.div(b),
+                Op::Pow => pow(a, b),
+            }
+        }
+        Node::Call(func, arg) => func.call(constant(arg, vars)?),
+    })
+}
+
+fn emit(node: &Node, vars: &[(&str, f64)], program: &mut Program, height: &mut usize) {
+    let push = |program: &mut Program, instr: Instr, height: &mut usize| {
+        match instr {
+            Instr::Const(_) | Instr::Z | Instr::C => *height += 1,
+            Instr::Add | Instr::Sub | Instr::Mul | Instr::Div | Instr::Pow => *height -= 1,
+            Instr::Neg | Instr::PowInt(_) | Instr::Call(_) => {}
+        }
+        program.depth = program.depth.max(*height);
+        program.code.push(instr);
+    };
+    if let Some(value) = constant(node, vars) {
+        push(program, Instr::Const(value), height);
+        return;
+    }
+    match node {
+        Node::Z => push(program, Instr::Z, height),
+        Node::C => push(program, Instr::C, height),
+        Node::Num(_) | Node::Var(_) => unreachable!("folded into a constant"),
+        Node::Neg(inner) => {
+            emit(inner, vars, program, height);
+            push(program, Instr::Neg, height);
+        }
+        Node::Bin(Op::Pow, base, exponent)
+            if constant(exponent, vars)
+                .is_some_and(|e| e.im == 0.0 && e.re.fract() == 0.0 && e.re.abs() <= 64.0) =>
+        {
+            let n = constant(exponent, vars).map_or(1, |e| e.re as i32);
+            emit(base, vars, program, height);
+            push(program, Instr::PowInt(n), height);
+        }
+        Node::Bin(op, a, b) => {
+            emit(a, vars, program, height);
+            emit(b, vars, program, height);
+            let instr = match op {
+                Op::Add => Instr::Add,
+                Op::Sub => Instr::Sub,
+                Op::Mul => Instr::Mul,
+                Op::Div => Instr::Div,
+                Op::Pow => Instr::Pow,
+            };
+            push(program, instr, height);
+        }
+        Node::Call(func, arg) => {
+            emit(arg, vars, program, height);
+            push(program, Instr::Call(*func), height);
+        }
+    }
+}
+
+impl Program {
+    fn step(&self, z: Complex, c: Complex, stack: &mut Vec<Complex>) -> Complex {
+        stack.clear();
+        for instr in &self.code {
+            let value = match *instr {
+                Instr::Const(v) => v,
+                Instr::Z => z,
+                Instr::C => c,
+                Instr::Neg => Complex::ZERO.sub(stack.pop().expect("operand")),
+                Instr::PowInt(n) => pow_int(stack.pop().expect("operand"), n),
+                Instr::Call(func) => func.call(stack.pop().expect("operand")),
+                Instr::Add | Instr::Sub | Instr::Mul | Instr::Div | Instr::Pow => {
+                    let b = stack.pop().expect("operand");
+                    let a = stack.pop().expect("operand");
+                    match instr {
+                        Instr::Add => a.add(b),
+                        Instr::Sub => a.sub(b),
+                        Instr::Mul => a.mul(b),
+                        Instr::Div => a.div(b),
+                        _ => pow(a, b),
+                    }
+                }
+            };
+            stack.push(value);
+        }
+        stack.pop().expect("a formula leaves one value")
+    }
+
+    /// Escape-time values over the view, each pixel a parameter `c` iterated
+    /// from `z = 0` until `|z|` passes `bailout`. The smooth part uses the
+    /// orbit's own growth rate as the degree, so it works without knowing
+    /// the formula's.
+    pub fn iterate(
+        &self,
+        width: u32,
+        height: u32,
+        max_iter: u32,
+        bailout: f64,
+        point: impl Fn(u32, u32) -> Complex + Sync,
+    ) -> IterationBuffer {
+        let bailout = bailout.max(2.0);
+        let (log_bailout, bailout_sqr) = (bailout.ln(), bailout * bailout);
+        IterationBuffer::from_fn(width, height, max_iter, |x, y| {
+            let c = point(x, y);
+            let mut stack = Vec::with_capacity(self.depth);
+            let mut z = Complex::ZERO;
+            let mut log_last = f64::NAN;
+            for iter in 0..max_iter {
+                z = self.step(z, c, &mut stack);
+                let r2 = z.norm_sqr();
+                if !r2.is_finite() {
+                    return iter as f64;
+                }
+                let log_r = r2.ln() / 2.0;
+                if r2 > bailout_sqr {
+                    let degree = log_r / log_last;
+                    if !(log_last > 0.0 && degree > MIN_DEGREE) {
+                        return iter as f64;
+                    }
+                    let smooth = iter as f64 + 1.0 - (log_r / log_bailout).ln() / degree.ln();
+                    return smooth.max(0.0);
+                }
+                log_last = log_r;
+            }
+            INTERIOR
+        })
+    }
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn eat(&mut self, sym: char) -> bool {
+        if self.peek() == Some(&Token::Sym(sym)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn sum(&mut self) -> Result<Node, String> {
+        let mut node = self.product()?;
+        loop {
+            let op = if self.eat('+') {
+                Op::Add
+            } else if self.eat('-') {
+                Op::Sub
+            } else {
+                return Ok(node);
+            };
+            node = Node::Bin(op, Box::new(node), Box::new(self.product()?));
+        }
+    }
+
+    fn product(&mut self) -> Result<Node, String> {
+        let mut node = self.unary()?;
+        loop {
+            let op = if self.eat('*') {
+                Op::Mul
+            } else if self.eat('/') {
+                Op::Div
+            } else {
+                return Ok(node);
+            };
+            node = Node::Bin(op, Box::new(node), Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Node, String> {
+        if self.eat('-') {
+            return Ok(Node::Neg(Box::new(self.unary()?)));
+        }
+        if self.eat('+') {
+            return self.unary();
+        }
+        self.power()
+    }
+
+    /// `^` binds tighter than unary minus on its left and is right-associative.
+    fn power(&mut self) -> Result<Node, String> {
+        let base = self.atom()?;
+        if self.eat('^') {
+            let exponent = self.unary()?;
+            return Ok(Node::Bin(Op::Pow, Box::new(base), Box::new(exponent)));
+        }
+        Ok(base)
+    }
+
+    fn atom(&mut self) -> Result<Node, String> {
+        let token = self.peek().cloned().ok_or("unexpected end of formula")?;
+        self.pos += 1;
+        match token {
+            Token::Num(v) => {
+                if self.peek() == Some(&Token::Ident("i".to_string())) {
+                    self.pos += 1;
+                    return Ok(Node::Num(Complex { re: 0.0, im: v }));
+                }
+                Ok(Node::Num(Complex { re: v, im: 0.0 }))
+            }
+            Token::Sym('(') => {
+                let node = self.sum()?;
+                if !self.eat(')') {
+                    return Err("expected ')'".to_string());
+                }
+                Ok(node)
+            }
+            Token::Ident(name) => {
+                if self.eat('(') {
+                    let func =
+                        Func::lookup(&name).ok_or_else(|| format!("unknown function {name:?}"))?;
+                    let arg = self.sum()?;
+                    if !self.eat(')') {
+                        return Err(format!("{name} takes one argument, expected ')'"));
+                    }
+                    return Ok(Node::Call(func, Box::new(arg)));
+                }
+                let number = |re, im| Ok(Node::Num(Complex { re, im }));
+                match name.as_str() {
+                    "z" => Ok(Node::Z),
+                    "c" => Ok(Node::C),
+                    "i" => number(0.0, 1.0),
+                    "pi" => number(std::f64::consts::PI, 0.0),
+                    "tau" => number(std::f64::consts::TAU, 0.0),
+                    "e" => number(std::f64::consts::E, 0.0),
+                    _ if FRAME_VARIABLES.contains(&name.as_str()) => Ok(Node::Var(name)),
+                    _ => Err(format!(
+                        "unknown variable {name:?}, expected z, c or one of {}",
+                        FRAME_VARIABLES.join(", ")
+                    )),
+                }
+            }
+            Token::Sym(c) => Err(format!("unexpected {c:?}")),
+        }
+    }
+}
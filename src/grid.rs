@@ -0,0 +1,138 @@
+use crate::composite::BlendMode;
+use crate::draw::{self, Paint};
+use crate::font;
+use crate::render::Complex;
+use image::{ImageBuffer, Rgb};
+
+const LINE: Paint = Paint {
+    color: [255, 255, 255, 48],
+    blend: BlendMode::Normal,
+};
+const AXIS: Paint = Paint {
+    color: [255, 255, 255, 160],
+    blend: BlendMode::Normal,
+};
+const TEXT: Paint = Paint {
+    color: [255, 255, 255, 255],
+    blend: BlendMode::Normal,
+};
+const BACKDROP: Paint = Paint {
+    color: [0, 0, 0, 160],
+    blend: BlendMode::Normal,
+};
+
+/// Closest grid lines may get, in text-scale units, before labels would crowd.
+const MIN_SPACING: u32 = 80;
+
+/// Draws grid lines at round coordinates, the real and imaginary axes when
+/// they are in view, and each line's coordinate along the bottom and left
+/// edges. The step is picked per frame so labels stay legible while zooming.
+pub fn draw(img: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, center: Complex, zoom: f64) {
+    let (w, h) = (img.width() as f64, img.height() as f64);
+    let scale = zoom / (w.min(h) / 2.0);
+    if !scale.is_finite() || scale <= 0.0 {
+        return;
+    }
+    let text_scale = (img.height() / 360).max(1);
+    let re_range = (center.re - w / 2.0 * scale, center.re + w / 2.0 * scale);
+    let im_range = (center.im - h / 2.0 * scale, center.im + h / 2.0 * scale);
+    let (step, decimals) = pick_step(scale, re_range, im_range, text_scale);
+    let to_x = |re: f64| ((re - center.re) / scale + w / 2.0).round() as i64;
+    let to_y = |im: f64| ((im - center.im) / scale + h / 2.0).round() as i64;
+    let (width, height) = (img.width() as i64, img.height() as i64);
+
+    let re_lines = lines(re_range, step);
+    let im_lines = lines(im_range, step);
+    for &re in &re_lines {
+        let paint = if re == 0.0 { AXIS } else { LINE };
+        let x = to_x(re);
+        draw::draw_line(img, (x, 0), (x, height - 1), text_scale, paint);
+    }
+    for &im in &im_lines {
+        let paint = if im == 0.0 { AXIS } else { LINE };
+        let y = to_y(im);
+        draw::draw_line(img, (0, y), (width - 1, y), text_scale, paint);
+    }
+
+    let pad = 2 * text_scale as i64;
+    let text_h = 7 * text_scale as i64;
+    // Labels run along the bottom and left edges; skip the ones that would
+    // be cut off or run into the other edge's labels.
+    let re_labels: Vec<(f64, String)> = re_lines
+        .iter()
+        .map(|&v| (v, label(v, decimals, "")))
+        .collect();
+    let im_labels: Vec<(f64, String)> = im_lines
+        .iter()
+        .map(|&v| (v, label(v, decimals, "i")))
+        .collect();
+    let left_column = im_labels
+        .iter()
+        .map(|(_, text)| font::text_width(text, text_scale) as i64 + 4 * pad)
+        .max()
+        .unwrap_or(0);
+    let bottom_row = height - text_h - 4 * pad;
+    for (re, text) in &re_labels {
+        let tw = font::text_width(text, text_scale) as i64;
+        let (x, y) = (to_x(*re) - tw / 2, bottom_row + pad);
+        if x - pad >= left_column && x + tw + pad <= width {
+            backdrop(img, x - pad, y - pad, tw + 2 * pad, text_h + 2 * pad);
+            font::draw_text(img, x, y, text_scale, TEXT, text);
+        }
+    }
+    for (im, text) in &im_labels {
+        let tw = font::text_width(text, text_scale) as i64;
+        let (x, y) = (3 * pad, to_y(*im) - text_h / 2);
+        if y - pad >= 0 && y + text_h + pad <= bottom_row {
+            backdrop(img, x - pad, y - pad, tw + 2 * pad, text_h + 2 * pad);
+            font::draw_text(img, x, y, text_scale, TEXT, text);
+        }
+    }
+}
+
+/// The smallest 1-2-5 step whose lines are far enough apart for their labels.
+fn pick_step(
+    scale: f64,
+    re_range: (f64, f64),
+    im_range: (f64, f64),
+    text_scale: u32,
+) -> (f64, usize) {
+    let mut exponent = (scale * MIN_SPACING as f64).log10().floor() as i32 - 1;
+    loop {
+        for mantissa in [1.0, 2.0, 5.0] {
+            let step = mantissa * 10f64.powi(exponent);
+            let decimals = (-exponent).max(0) as usize;
+            let widest = [re_range.0, re_range.1, im_range.0, im_range.1]
+                .iter()
+                .map(|&v| font::text_width(&label(v, decimals, "i"), text_scale))
+                .max()
+                .unwrap_or(0);
+            let needed = (MIN_SPACING * text_scale).max(widest + 8 * text_scale);
+            if step / scale >= needed as f64 {
+                return (step, decimals);
+            }
+        }
+        exponent += 1;
+    }
+}
+
+/// Multiples of `step` inside `range`.
+fn lines((lo, hi): (f64, f64), step: f64) -> Vec<f64> {
+    let first = (lo / step).ceil() as i64;
+    let last = (hi / step).floor() as i64;
+    (first..=last).map(|k| k as f64 * step).collect()
+}
+
+fn label(value: f64, decimals: usize, suffix: &str) -> String {
+    let text = format!("{value:.decimals$}");
+    // Avoid "-0.00" for the axis itself.
+    let text = match text.strip_prefix('-') {
+        Some(rest) if rest.chars().all(|c| c == '0' || c == '.') => rest.to_string(),
+        _ => text,
+    };
+    format!("{text}{suffix}")
+}
+
+fn backdrop(img: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, x: i64, y: i64, w: i64, h: i64) {
+    draw::fill_rect(img, x, y, w.max(0) as u32, h.max(0) as u32, BACKDROP);
+}
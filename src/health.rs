@@ -0,0 +1,151 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Mutex, Once};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How often the status of a running render is rewritten while it works on
+/// a frame, well inside the default --max-age.
+const HEARTBEAT: Duration = Duration::from_secs(30);
+
+/// The status file last written and what it says, for the heartbeat to
+/// refresh. Writes happen under the lock, so a refresh never lands after a
+/// final state.
+static LATEST: Mutex<Option<(PathBuf, Status)>> = Mutex::new(None);
+
+/// When the render last got anywhere, a frame or a chunk of pixels, in
+/// seconds since the epoch. The heartbeat writes it along with the status,
+/// so a render that hangs keeps a fresh `updated` but a stale `progress`.
+static PROGRESS: AtomicU64 = AtomicU64::new(0);
+
+/// Whether a status file has been written, without which there is nothing
+/// to record progress for.
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Records that the render got further, for the next status write. Does
+/// nothing without --status-file; with one it still reads the clock, so
+/// callers record a row or tile of pixels at a time rather than every chunk.
+pub fn progressed() {
+    if ACTIVE.load(Ordering::Relaxed) {
+        PROGRESS.store(unix_now(), Ordering::Relaxed);
+    }
+}
+
+/// Progress of a render written for `healthcheck`, one `key=value` per line.
+#[derive(Clone, Copy)]
+pub struct Status {
+    pub state: &'static str,
+    pub frame: u32,
+    pub frames: u32,
+}
+
+impl Status {
+    /// Replaces the status file atomically so a concurrent reader never sees
+    /// half of it. While the state is `running` the file keeps being
+    /// rewritten every [`HEARTBEAT`] until the next write, with the time of
+    /// the last [`progressed`] call, so a frame slower than --max-age that
+    /// still finishes chunks doesn't read as a stuck render, but a hung one does.
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        ACTIVE.store(true, Ordering::Relaxed);
+        progressed();
+        static HEARTBEAT_STARTED: Once = Once::new();
+        HEARTBEAT_STARTED.call_once(|| {
+            thread::spawn(heartbeat);
+        });
+        let mut latest = LATEST.lock().expect("status lock");
+        *latest = Some((path.to_path_buf(), *self));
+        self.write_file(path)
+    }
+
+    fn write_file(&self, path: &Path) -> Result<(), String> {
+        let text = format!(
+            "state={}\nframe={}\nframes={}\nupdated={}\nprogress={}\npid={}\n",
+            self.state,
+            self.frame,
+            self.frames,
+            unix_now(),
+            PROGRESS.load(Ordering::Relaxed),
+            std::process::id()
+        );
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, text)
+            .and_then(|()| fs::rename(&tmp, path))
+            .map_err(|e| format!("write status {path:?}: {e}"))
+    }
+}
+
+/// Refreshes the `updated` time of a running render's status for as long
+/// as the process lives.
+fn heartbeat() {
+    loop {
+        thread::sleep(HEARTBEAT);
+        let latest = LATEST.lock().expect("status lock");
+        if let Some((path, status)) = latest.as_ref()
+            && status.state == "running"
+            && let Err(e) = status.write_file(path)
+        {
+            eprintln!("{e}");
+        }
+    }
+}
+
+#[derive(clap::Args, Debug)]
+pub struct HealthcheckArgs {
+    /// Status file written by a render started with --status-file.
+    #[arg(long, default_value = "out/status.txt", env = "MANDELBROT_STATUS_FILE")]
+    status_file: String,
+    /// Seconds without progress after which a running render counts as
+    /// stuck; a running render makes some with every chunk of pixels it
+    /// finishes and writes its status at least every 30 seconds.
+    #[arg(long, default_value_t = 300)]
+    max_age: u64,
+}
+
+/// Succeeds while the render is making progress or has finished; fails when
+/// it failed, was interrupted, has not updated its status in --max-age
+/// seconds, or has updated it without finishing any pixels in that time,
+/// as a hung render would.
+pub fn run(args: HealthcheckArgs) -> Result<(), String> {
+    let text = fs::read_to_string(&args.status_file)
+        .map_err(|e| format!("unhealthy: read {:?}: {e}", args.status_file))?;
+    let get = |key: &str| {
+        text.lines()
+            .find_map(|line| line.strip_prefix(key)?.strip_prefix('='))
+            .unwrap_or("")
+    };
+    let state = get("state");
+    let now = unix_now();
+    let age = now.saturating_sub(get("updated").parse().unwrap_or(0));
+    // Status files of older builds have no progress time.
+    let stalled = match get("progress").parse::<u64>() {
+        Ok(progress) => now.saturating_sub(progress),
+        Err(_) => age,
+    };
+    let progress = format!("frame {}/{}", get("frame"), get("frames"));
+    match state {
+        "done" => {
+            println!("healthy: done, {progress}");
+            Ok(())
+        }
+        "running" if age > args.max_age => Err(format!(
+            "unhealthy: status not updated for {age}s (limit {}s), {progress}",
+            args.max_age
+        )),
+        "running" if stalled > args.max_age => Err(format!(
+            "unhealthy: no progress for {stalled}s (limit {}s), {progress}",
+            args.max_age
+        )),
+        "running" => {
+            println!("healthy: running, {progress}, updated {age}s ago");
+            Ok(())
+        }
+        other => Err(format!("unhealthy: {other}, {progress}")),
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
@@ -0,0 +1,64 @@
+use crate::analyze;
+use crate::composite::BlendMode;
+use crate::draw::{self, Paint};
+use crate::font;
+use crate::render::IterationBuffer;
+use crate::strings;
+use image::{ImageBuffer, Rgb};
+
+const TEXT: Paint = Paint {
+    color: [255, 255, 255, 255],
+    blend: BlendMode::Normal,
+};
+const BACKDROP: Paint = Paint {
+    color: [0, 0, 0, 160],
+    blend: BlendMode::Normal,
+};
+
+/// Draws the frame's interior share, visible area, boundary length and
+/// boundary dimension estimates, as `analyze` computes them, in the top-left
+/// corner.
+pub fn draw(img: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, iterations: &IterationBuffer, zoom: f64) {
+    let pixel = analyze::pixel_size(iterations.width, iterations.height, zoom);
+    let stats = analyze::stats(iterations, pixel);
+    let labels = [
+        strings::text("hud_interior", "interior", &[]),
+        strings::text("hud_area", "area", &[]),
+        strings::text("hud_boundary", "boundary", &[]),
+        strings::text("hud_dimension", "dimension", &[]),
+    ];
+    let values = [
+        format!("{:.2}%", stats.interior_fraction * 100.0),
+        format!("{:.3e}", stats.area),
+        format!("{:.3e}", stats.boundary_length),
+        stats
+            .dimension
+            .map_or("-".to_string(), |d| format!("{d:.3}")),
+    ];
+    // Values line up one space after the longest label, in any language.
+    let label_w = labels.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+    let lines: Vec<String> = labels
+        .iter()
+        .zip(&values)
+        .map(|(label, value)| format!("{label:label_w$} {value}"))
+        .collect();
+    let scale = (img.height() / 360).max(1);
+    let pad = 4 * scale as i64;
+    let line_h = 9 * scale as i64;
+    let text_w = lines
+        .iter()
+        .map(|line| font::text_width(line, scale))
+        .max()
+        .unwrap_or(0) as i64;
+    draw::fill_rect(
+        img,
+        pad,
+        pad,
+        (text_w + 2 * pad) as u32,
+        (lines.len() as i64 * line_h + pad) as u32,
+        BACKDROP,
+    );
+    for (i, line) in lines.iter().enumerate() {
+        font::draw_text(img, 2 * pad, 2 * pad + i as i64 * line_h, scale, TEXT, line);
+    }
+}
@@ -0,0 +1,177 @@
+use crate::letterbox::Canvas;
+use crate::palette::{BUILTIN_NAMES, Palette};
+use crate::perturbation::DeepCenter;
+use crate::{config, output, segments, settings};
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::Path;
+
+#[derive(clap::Args, Debug)]
+pub struct InitArgs {
+    /// Where the config file goes.
+    #[arg(long, default_value = "mandelbrot.toml")]
+    output: String,
+    /// Replace the file if it exists.
+    #[arg(long)]
+    force: bool,
+}
+
+/// Frame sizes offered by number.
+const RESOLUTIONS: [(u32, u32, &str); 5] = [
+    (1920, 1080, "Full HD"),
+    (1280, 720, "HD"),
+    (3840, 2160, "4K UHD"),
+    (1080, 1920, "vertical"),
+    (1080, 1080, "square"),
+];
+
+/// Places that still show structure at the default depth, offered by number.
+const LOCATIONS: [(&str, &str); 3] = [
+    ("seahorse valley", "-0.743643887037151,0.13182590420533"),
+    (
+        "elephant valley spiral",
+        "0.2549870375144766,0.0005679790528465",
+    ),
+    ("mini Mandelbrot", "-0.0452407411,0.9868162204352258"),
+];
+
+/// Asks a few questions on the terminal and writes a config file that
+/// renders an animation with `--config`. Empty answers take the default in
+/// brackets, so answers can also be piped in, one per line.
+pub fn run(args: InitArgs) -> Result<(), String> {
+    let path = Path::new(&args.output);
+    if path.exists() && !args.force {
+        return Err(format!(
+            "{} already exists, pass --force to replace it",
+            path.display()
+        ));
+    }
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+
+    println!("Resolution:");
+    for (i, (w, h, name)) in RESOLUTIONS.iter().enumerate() {
+        println!("  {}) {w}x{h} ({name})", i + 1);
+    }
+    let (width, height) = ask(
+        &mut input,
+        "Number or WIDTHxHEIGHT",
+        "1",
+        |answer| match choice(answer, RESOLUTIONS.len()) {
+            Some(i) => Ok((RESOLUTIONS[i].0, RESOLUTIONS[i].1)),
+            None => answer
+                .parse::<Canvas>()
+                .map(|size| (size.width, size.height))
+                .map_err(|_| format!("expected 1-{} or WIDTHxHEIGHT", RESOLUTIONS.len())),
+        },
+    )?;
+    let fps = ask(&mut input, "Frames per second", "30", |answer| {
+        answer
+            .parse::<u32>()
+            .ok()
+            .filter(|&fps| fps > 0)
+            .ok_or_else(|| "expected a whole number above 0".to_string())
+    })?;
+    let seconds = ask(
+        &mut input,
+        "Duration (e.g. 20s or 1m30s)",
+        "20s",
+        segments::parse_duration,
+    )?;
+
+    println!("Where to zoom in:");
+    for (i, (name, center)) in LOCATIONS.iter().enumerate() {
+        println!("  {}) {name} ({center})", i + 1);
+    }
+    let center = ask(
+        &mut input,
+        "Number or pasted re,im",
+        "1",
+        |answer| match choice(answer, LOCATIONS.len()) {
+            Some(i) => Ok(LOCATIONS[i].1.to_string()),
+            None => answer
+                .parse::<DeepCenter>()
+                .map(|_| answer.to_string())
+                .map_err(|_| format!("expected 1-{} or re,im", LOCATIONS.len())),
+        },
+    )?;
+    let zoom_end = ask(
+        &mut input,
+        "Final zoom, the height of the last view (smaller is deeper)",
+        "1e-10",
+        |answer| {
+            answer
+                .parse::<f64>()
+                .ok()
+                .filter(|&zoom| zoom > 0.0 && zoom < 1.0)
+                .map(|_| answer.to_string())
+                .ok_or_else(|| "expected a number between 0 and 1, e.g. 1e-10".to_string())
+        },
+    )?;
+    let palette = ask(
+        &mut input,
+        &format!("Palette ({} or #rrggbb stops)", BUILTIN_NAMES.join(", ")),
+        "classic",
+        |answer| answer.parse::<Palette>().map(|_| answer.to_string()),
+    )?;
+
+    let frames = ((seconds * fps as f64).round() as u32).max(1);
+    let settings: Vec<(String, String)> = [
+        ("width", width.to_string()),
+        ("height", height.to_string()),
+        ("fps", fps.to_string()),
+        ("frames", frames.to_string()),
+        ("deep_center", center),
+        ("zoom_end", zoom_end),
+        ("palette", palette),
+    ]
+    .into_iter()
+    .map(|(key, value)| (key.to_string(), value))
+    .collect();
+    settings::to_render_args(&settings)?.check()?;
+    fs::write(path, config::to_toml(&settings))
+        .map_err(|e| output::describe_error("write", path, &e))?;
+    println!();
+    println!("{frames} frames -> {}", path.display());
+    println!("render with: mandelbrot-animation --config {}", args.output);
+    Ok(())
+}
+
+/// The index of a 1-based menu answer.
+fn choice(answer: &str, count: usize) -> Option<usize> {
+    answer
+        .parse::<usize>()
+        .ok()
+        .filter(|&n| (1..=count).contains(&n))
+        .map(|n| n - 1)
+}
+
+/// Asks until the answer parses. An empty answer, or the end of the input,
+/// takes `default`.
+fn ask<T>(
+    input: &mut impl BufRead,
+    question: &str,
+    default: &str,
+    parse: impl Fn(&str) -> Result<T, String>,
+) -> Result<T, String> {
+    loop {
+        print!("{question} [{default}]: ");
+        io::stdout().flush().map_err(|e| format!("stdout: {e}"))?;
+        let mut line = String::new();
+        let read = input
+            .read_line(&mut line)
+            .map_err(|e| format!("stdin: {e}"))?;
+        if read == 0 {
+            println!();
+        }
+        let answer = match line.trim() {
+            "" => default,
+            answer => answer,
+        };
+        match parse(answer) {
+            Ok(value) => return Ok(value),
+            Err(e) if read == 0 => return Err(e),
+            Err(e) => println!("  {e}"),
+        }
+    }
+}
@@ -0,0 +1,59 @@
+use crate::flame::Rng;
+use crate::palette::Palette;
+use crate::{FrameInfo, RenderArgs};
+use std::borrow::Cow;
+
+/// The palette and phase of one frame under `--palette-jitter`. Every
+/// `--palette-jitter-every` frames starts a segment with its own look, drawn
+/// from `--palette-seed` and the segment number alone so any frame can be
+/// rendered on its own; frames in between blend from one look to the next.
+pub fn apply<'a>(
+    args: &RenderArgs,
+    info: &FrameInfo,
+    palette: &'a Palette,
+) -> (Cow<'a, Palette>, f64) {
+    if args.palette_jitter == 0.0 {
+        return (Cow::Borrowed(palette), info.palette_phase);
+    }
+    let stops = palette.stops();
+    let every = args.palette_jitter_every.max(1);
+    let segment = info.index / every;
+    let f = (info.index % every) as f64 / every as f64;
+    let blend = f * f * (3.0 - 2.0 * f);
+    let from = Look::draw(args, segment, stops.len());
+    let to = Look::draw(args, segment.saturating_add(1), stops.len());
+    let stops = stops
+        .iter()
+        .zip(from.stops.iter().zip(&to.stops))
+        .map(|(stop, (a, b))| {
+            let mut color = *stop;
+            for ((c, a), b) in color.iter_mut().zip(a).zip(b) {
+                *c = (*c + a + (b - a) * blend).clamp(0.0, 1.0);
+            }
+            color
+        })
+        .collect();
+    let phase = info.palette_phase + from.phase + (to.phase - from.phase) * blend;
+    (Cow::Owned(Palette::Gradient(stops)), phase)
+}
+
+/// The offsets of one segment: a turn of the palette and a shift for every
+/// channel of every stop, each up to `--palette-jitter`. The turn is only
+/// ever forwards, so the dark start of a palette isn't wrapped around to its
+/// bright end across the whole exterior.
+struct Look {
+    phase: f64,
+    stops: Vec<[f64; 3]>,
+}
+
+impl Look {
+    fn draw(args: &RenderArgs, segment: u32, stops: usize) -> Look {
+        let mut rng =
+            Rng::new(args.palette_seed ^ (segment as u64).wrapping_mul(0xD6E8_FEB8_6659_FD93));
+        let amount = args.palette_jitter;
+        let phase = amount * rng.next_f64();
+        let mut offset = || amount * (2.0 * rng.next_f64() - 1.0);
+        let stops = (0..stops).map(|_| [offset(), offset(), offset()]).collect();
+        Look { phase, stops }
+    }
+}
@@ -0,0 +1,100 @@
+use crate::{RenderArgs, compare, settings};
+use image::{GenericImage, ImageBuffer, Rgb};
+use std::str::FromStr;
+
+/// Subdirectory and manifest variant of the multi-view frames.
+pub const LABEL: &str = "views";
+
+/// Black space between the cells of a grid.
+const GUTTER: u32 = 2;
+
+type Frame = ImageBuffer<Rgb<u8>, Vec<u8>>;
+
+/// One `--view`: the run's settings with some of them changed, written
+/// `key=value,key=value` like `--compare` overrides.
+#[derive(Debug, Clone)]
+pub struct View {
+    pub overrides: Vec<(String, String)>,
+}
+
+impl FromStr for View {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<View, String> {
+        let overrides = s
+            .split(',')
+            .filter(|p| !p.trim().is_empty())
+            .map(compare::parse_override)
+            .collect::<Result<Vec<_>, _>>()?;
+        if overrides.is_empty() {
+            return Err("a view needs at least one key=value".to_string());
+        }
+        Ok(View { overrides })
+    }
+}
+
+impl View {
+    pub fn label(&self) -> String {
+        let changes: Vec<String> = self
+            .overrides
+            .iter()
+            .map(|(k, v)| format!("{k}={v}"))
+            .collect();
+        changes.join(" ")
+    }
+
+    /// The settings this view renders with; extra outputs of the run itself
+    /// are left out.
+    pub fn args(&self, settings: &[(String, String)]) -> Result<RenderArgs, String> {
+        let mut merged: Vec<(String, String)> = settings
+            .iter()
+            .filter(|(key, _)| {
+                !["view", "view_columns", "compare"].contains(&key.as_str())
+                    && !self.overrides.iter().any(|(k, _)| k == key)
+            })
+            .cloned()
+            .collect();
+        merged.extend(self.overrides.iter().cloned());
+        let args = settings::to_render_args(&merged).map_err(|e| format!("--view: {e}"))?;
+        args.check()
+            .map_err(|e| format!("--view {}: {e}", self.label()))?;
+        Ok(args)
+    }
+}
+
+/// Lays `cells` out `columns` wide, row by row, each in a slot as large as
+/// the largest cell and captioned in its top-left corner.
+pub fn grid(cells: &[(Frame, String)], columns: u32) -> Frame {
+    let cell_w = cells.iter().map(|(img, _)| img.width()).max().unwrap_or(1);
+    let cell_h = cells.iter().map(|(img, _)| img.height()).max().unwrap_or(1);
+    let columns = columns.clamp(1, (cells.len() as u32).max(1));
+    let rows = (cells.len() as u32).div_ceil(columns).max(1);
+    let mut img = ImageBuffer::new(
+        columns * cell_w + (columns - 1) * GUTTER,
+        rows * cell_h + (rows - 1) * GUTTER,
+    );
+    let scale = (cell_h / 360).max(1);
+    for (i, (cell, label)) in cells.iter().enumerate() {
+        let (x, y) = (
+            (i as u32 % columns) * (cell_w + GUTTER),
+            (i as u32 / columns) * (cell_h + GUTTER),
+        );
+        img.copy_from(cell, x, y).expect("cell fits its slot");
+        if !label.is_empty() {
+            compare::caption(&mut img, x as i64, y as i64, scale, label);
+        }
+    }
+    img
+}
+
+/// The run's own frame followed by each view's, as `grid` cells lettered
+/// A, B, C, ... like `--compare`.
+pub fn views_frame(own: Frame, views: Vec<(&View, Frame)>, columns: Option<u32>) -> Frame {
+    let mut cells = vec![(own, "A".to_string())];
+    for (i, (view, img)) in views.into_iter().enumerate() {
+        let letter = (b'B' + (i % 25) as u8) as char;
+        cells.push((img, format!("{letter}  {}", view.label())));
+    }
+    let columns = columns.unwrap_or_else(|| (cells.len() as f64).sqrt().ceil() as u32);
+    grid(&cells, columns)
+}
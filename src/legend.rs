@@ -0,0 +1,210 @@
+use crate::composite::BlendMode;
+use crate::draw::{self, Paint};
+use crate::font;
+use crate::palette::Palette;
+use image::{ImageBuffer, Rgb};
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Fractions of `max_iter` that get a labelled tick.
+const TICKS: [f64; 5] = [0.0, 0.25, 0.5, 0.75, 1.0];
+
+const TEXT: Paint = Paint {
+    color: [255, 255, 255, 255],
+    blend: BlendMode::Normal,
+};
+const BACKDROP: Paint = Paint {
+    color: [0, 0, 0, 160],
+    blend: BlendMode::Normal,
+};
+
+/// Where the legend strip sits on every frame.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Placement {
+    Bottom,
+    Right,
+}
+
+impl FromStr for Placement {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Placement, String> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "bottom" => Ok(Placement::Bottom),
+            "right" => Ok(Placement::Right),
+            _ => Err(format!(
+                "unknown legend placement {s:?}, expected bottom or right"
+            )),
+        }
+    }
+}
+
+/// Draws the palette as a bar labelled with the iteration counts it stands
+/// for, on a dark box at the frame's edge. `phase` is the frame's --palette-phase.
+pub fn draw(
+    img: &mut ImageBuffer<Rgb<u8>, Vec<u8>>,
+    placement: Placement,
+    palette: &Palette,
+    phase: f64,
+    max_iter: u32,
+) {
+    let style = Style::new((img.height() / 360).max(1), max_iter);
+    let margin = style.margin();
+    let (w, h) = (img.width() as i64, img.height() as i64);
+    match placement {
+        Placement::Bottom => {
+            let length = (w / 3).max(16);
+            let (box_w, box_h) = style.horizontal_size(length);
+            let (x, y) = (w - box_w - 3 * margin, h - box_h - 3 * margin);
+            draw::fill_rect(
+                img,
+                x,
+                y,
+                (box_w + 2 * margin) as u32,
+                (box_h + 2 * margin) as u32,
+                BACKDROP,
+            );
+            style.horizontal(img, (x + margin, y + margin), length, palette, phase);
+        }
+        Placement::Right => {
+            let length = (h / 3).max(16);
+            let (box_w, box_h) = style.vertical_size(length);
+            let (x, y) = (w - box_w - 3 * margin, (h - box_h) / 2 - margin);
+            draw::fill_rect(
+                img,
+                x,
+                y,
+                (box_w + 2 * margin) as u32,
+                (box_h + 2 * margin) as u32,
+                BACKDROP,
+            );
+            style.vertical(img, (x + margin, y + margin), length, palette, phase);
+        }
+    }
+}
+
+/// Writes a horizontal legend on its own, for slides or captions next to the video.
+pub fn write_file(path: &Path, palette: &Palette, max_iter: u32) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("create {parent:?}: {e}"))?;
+    }
+    let style = Style::new(2, max_iter);
+    let margin = style.margin();
+    let length = 512;
+    let (w, h) = style.horizontal_size(length);
+    let mut img: ImageBuffer<Rgb<u8>, Vec<u8>> =
+        ImageBuffer::new((w + 2 * margin) as u32, (h + 2 * margin) as u32);
+    style.horizontal(&mut img, (margin, margin), length, palette, 0.0);
+    img.save(path).map_err(|e| format!("write {path:?}: {e}"))
+}
+
+struct Style {
+    scale: u32,
+    max_iter: u32,
+    /// Width of the widest label, so labels at the ends stay inside the box.
+    label_w: i64,
+}
+
+impl Style {
+    fn new(scale: u32, max_iter: u32) -> Style {
+        Style {
+            scale,
+            max_iter,
+            label_w: font::text_width(&max_iter.to_string(), scale) as i64,
+        }
+    }
+
+    fn margin(&self) -> i64 {
+        4 * self.scale as i64
+    }
+
+    fn text_h(&self) -> i64 {
+        7 * self.scale as i64
+    }
+
+    fn thickness(&self) -> i64 {
+        2 * self.text_h()
+    }
+
+    fn label(&self, tick: f64) -> String {
+        ((tick * self.max_iter as f64).round() as u64).to_string()
+    }
+
+    fn horizontal_size(&self, length: i64) -> (i64, i64) {
+        (
+            length + self.label_w,
+            self.thickness() + 2 * self.margin() + self.text_h(),
+        )
+    }
+
+    fn vertical_size(&self, length: i64) -> (i64, i64) {
+        (
+            self.thickness() + 2 * self.margin() + self.label_w,
+            length + self.text_h(),
+        )
+    }
+
+    fn horizontal(
+        &self,
+        img: &mut ImageBuffer<Rgb<u8>, Vec<u8>>,
+        (x, y): (i64, i64),
+        length: i64,
+        palette: &Palette,
+        phase: f64,
+    ) {
+        let bar_x = x + self.label_w / 2;
+        let thickness = self.thickness();
+        for i in 0..length {
+            let paint = gradient_paint(palette, phase, i, length);
+            draw::fill_rect(img, bar_x + i, y, 1, thickness as u32, paint);
+        }
+        for tick in TICKS {
+            let label = self.label(tick);
+            let tx = bar_x + (tick * (length - 1) as f64) as i64;
+            let tick_y = y + thickness;
+            draw::fill_rect(img, tx, tick_y, self.scale, 2 * self.scale, TEXT);
+            let lw = font::text_width(&label, self.scale) as i64;
+            let ly = tick_y + 2 * self.margin();
+            font::draw_text(img, tx - lw / 2, ly, self.scale, TEXT, &label);
+        }
+    }
+
+    fn vertical(
+        &self,
+        img: &mut ImageBuffer<Rgb<u8>, Vec<u8>>,
+        (x, y): (i64, i64),
+        length: i64,
+        palette: &Palette,
+        phase: f64,
+    ) {
+        let bar_y = y + self.text_h() / 2;
+        let thickness = self.thickness();
+        for i in 0..length {
+            let paint = gradient_paint(palette, phase, i, length);
+            draw::fill_rect(img, x, bar_y + length - 1 - i, thickness as u32, 1, paint);
+        }
+        for tick in TICKS {
+            let label = self.label(tick);
+            let ty = bar_y + ((1.0 - tick) * (length - 1) as f64) as i64;
+            draw::fill_rect(img, x + thickness, ty, 2 * self.scale, self.scale, TEXT);
+            let lx = x + thickness + 2 * self.margin();
+            font::draw_text(img, lx, ty - self.text_h() / 2, self.scale, TEXT, &label);
+        }
+    }
+}
+
+/// Color of step `i` of `steps`, rotated by the phase the same way frames are.
+fn gradient_paint(palette: &Palette, phase: f64, i: i64, steps: i64) -> Paint {
+    let t = i as f64 / (steps - 1).max(1) as f64;
+    let t = if phase == 0.0 {
+        t
+    } else {
+        (t + phase).rem_euclid(1.0)
+    };
+    let [r, g, b] = palette.color(t);
+    Paint {
+        color: [r, g, b, 255],
+        blend: BlendMode::Normal,
+    }
+}
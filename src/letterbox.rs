@@ -0,0 +1,67 @@
+use crate::composite::BlendMode;
+use crate::draw::{self, Paint};
+use image::{ImageBuffer, Rgb};
+use std::str::FromStr;
+
+/// Action-safe and title-safe areas, as fractions of each canvas side.
+const SAFE_AREAS: [f64; 2] = [0.93, 0.90];
+const GUIDE: Paint = Paint {
+    color: [255, 255, 255, 128],
+    blend: BlendMode::Normal,
+};
+
+/// `--canvas`: the size of the delivered picture, e.g. `1920x1080`. The
+/// fractal keeps --width and --height and sits centered on it between black
+/// bars, letterboxed (above and below) or pillarboxed (left and right).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Canvas {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl FromStr for Canvas {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Canvas, String> {
+        let size = s
+            .split_once('x')
+            .and_then(|(w, h)| Some((w.trim().parse().ok()?, h.trim().parse().ok()?)))
+            .filter(|&(w, h)| w > 0 && h > 0);
+        let Some((width, height)) = size else {
+            return Err(format!("invalid canvas {s:?}, expected WIDTHxHEIGHT"));
+        };
+        Ok(Canvas { width, height })
+    }
+}
+
+impl Canvas {
+    /// `img` centered on the canvas.
+    pub fn apply(self, img: ImageBuffer<Rgb<u8>, Vec<u8>>) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+        if img.dimensions() == (self.width, self.height) {
+            return img;
+        }
+        let mut out = ImageBuffer::new(self.width, self.height);
+        let x = self.width.saturating_sub(img.width()) / 2;
+        let y = self.height.saturating_sub(img.height()) / 2;
+        image::imageops::replace(&mut out, &img, x as i64, y as i64);
+        out
+    }
+}
+
+/// Outlines the action-safe and title-safe areas of broadcast pictures.
+pub fn draw_safe_areas(img: &mut ImageBuffer<Rgb<u8>, Vec<u8>>) {
+    let (w, h) = (img.width() as f64, img.height() as f64);
+    for fraction in SAFE_AREAS {
+        let (x0, y0) = (w * (1.0 - fraction) / 2.0, h * (1.0 - fraction) / 2.0);
+        let (x0, y0) = (x0.round() as i64, y0.round() as i64);
+        let (x1, y1) = (img.width() as i64 - 1 - x0, img.height() as i64 - 1 - y0);
+        for (from, to) in [
+            ((x0, y0), (x1, y0)),
+            ((x1, y0), (x1, y1)),
+            ((x1, y1), (x0, y1)),
+            ((x0, y1), (x0, y0)),
+        ] {
+            draw::draw_line(img, from, to, 1, GUIDE);
+        }
+    }
+}
@@ -1,180 +1,2189 @@
-use clap::Parser;
-use image::{ImageBuffer, Rgb};
-use rayon::prelude::*;
+mod analyze;
+mod anomaly;
+mod antialias;
+mod bignum;
+mod buddhabrot;
+mod bundle;
+mod cache;
+mod cascade;
+mod checkpoint;
+mod coloring;
+mod compare;
+mod completions;
+mod composite;
+mod config;
+mod deflicker;
+mod diff;
+mod draw;
+mod encode;
+mod endless;
+mod errormap;
+mod estimate;
+mod expmap;
+mod expr;
+mod exr;
+mod flame;
+mod font;
+mod format;
+mod formula;
+mod grid;
+mod health;
+mod hud;
+mod init;
+mod iterfile;
+mod jitter;
+mod layout;
+mod legend;
+mod letterbox;
+mod manifest;
+mod newton;
+mod numeric;
+mod orbits;
+mod output;
+mod overlay;
+mod pacing;
+mod palette;
+mod panorama;
+mod pathplot;
+mod perturbation;
+mod pipeline;
+mod poster;
+mod preset;
+mod preview;
+mod rational;
+mod recolor;
+mod reframe;
+mod render;
+mod runlog;
+mod scheduler;
+#[cfg(feature = "screensaver")]
+mod screensaver;
+mod segments;
+mod selftest;
+mod settings;
+mod sha256;
+mod signals;
+mod stream;
+mod strings;
+mod subdivide;
+mod swatch;
+mod sweep;
+mod texture;
+mod threads;
+mod timeline;
+mod verify;
+mod warp;
+
+use checkpoint::Checkpoint;
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
+use composite::Layer;
+use expr::Expr;
+use image::{ImageBuffer, ImageFormat, Rgb};
+use manifest::{FrameEntry, Manifest};
+use output::FrameWriter;
+use overlay::Overlay;
+use palette::{NamedPalette, Palette};
+use render::{Complex, IterationBuffer};
+use std::collections::BTreeMap;
+use std::ffi::OsString;
 use std::fs;
-use std::path::PathBuf;
+use std::io::Cursor;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use strings::Strings;
+
+#[derive(Parser, Debug)]
+#[command(name = "mandelbrot-animation")]
+#[command(about = "Render Mandelbrot animation frames.")]
+#[command(args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Read render settings from a TOML file; flags on the command line take precedence.
+    #[arg(long, env = "MANDELBROT_CONFIG")]
+    config: Option<String>,
+    /// Print the fully resolved render settings as TOML and exit.
+    #[arg(long, env = "MANDELBROT_PRINT_CONFIG")]
+    print_config: bool,
+    #[command(flatten)]
+    render: RenderArgs,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Check rendered frames against the hashes in the run manifest.
+    Verify(verify::VerifyArgs),
+    /// Render a few low-resolution probe frames and extrapolate the cost of a full run.
+    Estimate(Box<estimate::EstimateArgs>),
+    /// Render a gradient strip and a sample fractal for a palette definition.
+    Palette(swatch::PaletteArgs),
+    /// Re-color frames from iteration data saved with --save-iterations.
+    Recolor(recolor::RecolorArgs),
+    /// Render a strip that follows the set boundary at a fixed zoom.
+    Panorama(panorama::PanoramaArgs),
+    /// Render one frame of the animation in tiles, for stills too large to hold in memory.
+    Poster(Box<poster::PosterArgs>),
+    /// Draw the camera path and the views of the frames over a wide view of the set.
+    PathPlot(Box<pathplot::PathPlotArgs>),
+    /// Render a pure zoom as one exponential-map strip and resample the frames from it.
+    ExpMap(Box<expmap::ExpMapArgs>),
+    /// Encode rendered frames to H.264, optionally split into numbered segments.
+    Encode(segments::EncodeArgs),
+    /// Crop rendered frames to another aspect ratio around their most detailed region.
+    Reframe(reframe::ReframeArgs),
+    /// Compare two frame directories and write difference heatmaps and statistics.
+    Diff(diff::DiffArgs),
+    /// Render one frame for every combination of values of one or two settings into a labeled mosaic.
+    Sweep(Box<sweep::SweepArgs>),
+    /// Estimate area, boundary length and dimension from iteration data saved with --save-iterations.
+    Analyze(analyze::AnalyzeArgs),
+    /// Exit with 0 while a render started with --status-file is healthy, 1 otherwise.
+    Healthcheck(health::HealthcheckArgs),
+    /// Run quick numerical checks of this build before trusting it with a long render.
+    Selftest,
+    /// Ask a few questions and write a ready-to-run config file.
+    Init(init::InitArgs),
+    /// Print a shell completion script or the manual page.
+    Completions(completions::CompletionsArgs),
+    /// Play a slow endless zoom in the terminal until interrupted.
+    #[cfg(feature = "screensaver")]
+    Screensaver(screensaver::ScreensaverArgs),
+}
+
+#[derive(clap::Args, Debug, Clone)]
+struct RenderArgs {
+    /// What to render: `mandelbrot`, a `julia` set, the `burning-ship`, the `newton`
+    /// fractal of --newton-poly, the `buddhabrot`, the map of a `formula`, an IFS
+    /// `flame` or a `rational` map.
+    #[arg(long, default_value = "mandelbrot", env = "MANDELBROT_FRACTAL")]
+    fractal: Fractal,
+    /// Exponent d of `z^d + c` for `mandelbrot` and `julia`; may be fractional.
+    #[arg(long, default_value_t = 2.0, env = "MANDELBROT_POWER")]
+    power: f64,
+    /// Seed c of `--fractal julia` at the first frame, e.g. `(-0.8+0.156i)`.
+    #[arg(long, default_value = "-0.8+0.156i", value_parser = rational::parse_complex, env = "MANDELBROT_JULIA_C_START")]
+    julia_c_start: Complex,
+    /// Seed c at the last frame, moved to linearly over the animation; defaults to --julia-c-start.
+    #[arg(long, value_parser = rational::parse_complex, env = "MANDELBROT_JULIA_C_END")]
+    julia_c_end: Option<Complex>,
+    /// Polynomial whose roots `--fractal newton` finds; `c` follows the c_re and c_im tracks, 0 without them.
+    #[arg(long, default_value = "z^3 - 1", env = "MANDELBROT_NEWTON_POLY")]
+    newton_poly: rational::Poly,
+    /// Starting points sampled per pixel for `--fractal buddhabrot`; more is smoother and slower.
+    #[arg(long, default_value_t = 10, env = "MANDELBROT_BUDDHABROT_SAMPLES")]
+    buddhabrot_samples: u32,
+    /// Render the Buddhabrot as a Nebulabrot: red, green and blue count the
+    /// orbits escaping within these iteration limits, e.g. `5000,500,50`.
+    #[arg(long, env = "MANDELBROT_NEBULABROT")]
+    nebulabrot: Option<buddhabrot::Bands>,
+    /// Map iterated by `--fractal formula` from z = 0, with each pixel as c,
+    /// e.g. `z^2 + c*z + c`; may use t, frame, frames, zoom and time.
+    #[arg(long, default_value = "z^2 + c", env = "MANDELBROT_FORMULA")]
+    formula: formula::Formula,
+    /// Escape radius for `--fractal formula`.
+    #[arg(long, default_value_t = 100.0, env = "MANDELBROT_FORMULA_BAILOUT")]
+    formula_bailout: f64,
+    /// Transform set used by `--fractal flame`: sierpinski, swirl or spherical.
+    #[arg(long, default_value = "swirl", env = "MANDELBROT_FLAME_PRESET")]
+    flame_preset: flame::Preset,
+    /// Chaos-game points per pixel for `--fractal flame`; more is smoother and slower.
+    #[arg(long, default_value_t = 10, env = "MANDELBROT_FLAME_SAMPLES")]
+    flame_samples: u32,
+    /// Turns each flame transform rotates over the animation.
+    #[arg(long, default_value_t = 0.25, env = "MANDELBROT_FLAME_SPIN")]
+    flame_spin: f64,
+    /// Numerator of the `--fractal rational` map, a polynomial in z and c.
+    #[arg(long, default_value = "z^3 + c", env = "MANDELBROT_NUMERATOR")]
+    numerator: rational::Poly,
+    /// Denominator of the `--fractal rational` map; the default gives z^2 + c/z.
+    #[arg(long, default_value = "z", env = "MANDELBROT_DENOMINATOR")]
+    denominator: rational::Poly,
+    /// `dynamic` (pixels are z0, c animated) or `parameter` (pixels are c, fixed z0).
+    #[arg(long, default_value = "dynamic", env = "MANDELBROT_RATIONAL_PLANE")]
+    rational_plane: rational::Plane,
+    /// Real part of c per frame in the dynamic plane (t, frame, frames, zoom, time).
+    #[arg(long, default_value = "0.05*cos(tau*t)", value_parser = parse_frame_expr, env = "MANDELBROT_RATIONAL_C_RE")]
+    rational_c_re: Expr,
+    /// Imaginary part of c per frame in the dynamic plane.
+    #[arg(long, default_value = "0.05*sin(tau*t)", value_parser = parse_frame_expr, env = "MANDELBROT_RATIONAL_C_IM")]
+    rational_c_im: Expr,
+    /// Starting point in the parameter plane, e.g. `1` or `(0.5-0.2i)`.
+    #[arg(long, default_value = "1", value_parser = rational::parse_complex, env = "MANDELBROT_RATIONAL_Z0")]
+    rational_z0: Complex,
+    /// Escape radius for rational maps.
+    #[arg(long, default_value_t = 100.0, env = "MANDELBROT_RATIONAL_BAILOUT")]
+    rational_bailout: f64,
+    /// Frame size, frame rate and encoder settings for a platform:
+    /// `vertical-1080x1920`, `square-1080` or `4k-uhd`. Explicit options win.
+    #[arg(long, env = "MANDELBROT_PRESET")]
+    preset: Option<preset::Preset>,
+    #[arg(long, default_value_t = 1920, env = "MANDELBROT_WIDTH")]
+    width: u32,
+    #[arg(long, default_value_t = 1080, env = "MANDELBROT_HEIGHT")]
+    height: u32,
+    /// Center the frames on a larger picture of this size, `WIDTHxHEIGHT`,
+    /// between black bars, e.g. 4:3 frames on a `1920x1080` canvas.
+    #[arg(long, env = "MANDELBROT_CANVAS")]
+    canvas: Option<letterbox::Canvas>,
+    /// Number of frames, or `auto` to derive it from the zoom depth and --zoom-rate.
+    #[arg(long, default_value = "300", env = "MANDELBROT_FRAMES")]
+    frames: FrameCount,
+    /// First frame to render, counting from 0; the frames keep their place
+    /// in the whole animation, so several machines can split one run.
+    #[arg(long, default_value_t = 0, env = "MANDELBROT_START_FRAME")]
+    start_frame: u32,
+    /// Last frame to render, inclusive; the last frame of the animation by default.
+    #[arg(long, env = "MANDELBROT_END_FRAME")]
+    end_frame: Option<u32>,
+    #[arg(long, default_value_t = 30, env = "MANDELBROT_FPS")]
+    fps: u32,
+    #[arg(long, default_value_t = 2000, env = "MANDELBROT_MAX_ITER")]
+    max_iter: u32,
+    /// Scale each frame's iteration limit with its zoom depth: --auto-iter-min
+    /// plus --auto-iter-rate for every tenfold zoom past 1, up to --max-iter.
+    #[arg(long, env = "MANDELBROT_AUTO_ITER")]
+    auto_iter: bool,
+    /// Iteration limit of --auto-iter at zoom 1 and wider.
+    #[arg(long, default_value_t = 200, env = "MANDELBROT_AUTO_ITER_MIN")]
+    auto_iter_min: u32,
+    /// Iterations --auto-iter adds per tenfold zoom.
+    #[arg(long, default_value_t = 300, env = "MANDELBROT_AUTO_ITER_RATE")]
+    auto_iter_rate: u32,
+    /// Stop iterating a point as soon as its orbit's derivative shows it is
+    /// interior; faster on frames with a lot of the set visible.
+    #[arg(long, env = "MANDELBROT_DERIVATIVE_BAILOUT")]
+    derivative_bailout: bool,
+    /// Average each frame's colors with those of up to this many frames in
+    /// total, where the escape time barely changed, against flicker in the
+    /// video; 1 disables. Needs --frame-order sequential.
+    #[arg(
+        long,
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u32).range(1..=16),
+        env = "MANDELBROT_DEFLICKER"
+    )]
+    deflicker: u32,
+    /// Largest relative change of the escape time between frames that --deflicker still blends over.
+    #[arg(long, default_value_t = 0.2, env = "MANDELBROT_DEFLICKER_TOLERANCE")]
+    deflicker_tolerance: f64,
+    /// Supersample pixels on the set boundary with an N×N grid; 1 disables. Mandelbrot only.
+    #[arg(
+        long,
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u32).range(1..=8),
+        env = "MANDELBROT_BOUNDARY_AA"
+    )]
+    boundary_aa: u32,
+    /// Re-iterate the pixels still inside the set after the main pass with a
+    /// doubled --max-iter, again and again up to this limit. Mandelbrot only.
+    #[arg(
+        long,
+        conflicts_with = "frame_budget",
+        env = "MANDELBROT_REFINE_MAX_ITER"
+    )]
+    refine_max_iter: Option<u32>,
+    /// Iterate one oversized keyframe per this many frames and resample the
+    /// frames in between from it; 1 iterates every frame. Mandelbrot only.
+    #[arg(
+        long,
+        default_value_t = 1,
+        value_parser = clap::value_parser!(u32).range(1..),
+        conflicts_with = "frame_budget",
+        env = "MANDELBROT_CASCADE"
+    )]
+    cascade: u32,
+    /// What iterates the frames: `auto` picks per frame, or force one of
+    /// `perturbation`, `cascade`, `soa`, `scalar`, `subdivide`, `multibrot`, `julia`, `burning-ship`, `newton`, `buddhabrot`, `formula`, `flame` or `rational`.
+    #[arg(long, default_value = "auto", env = "MANDELBROT_BACKEND")]
+    backend: scheduler::Choice,
+    /// Frames iterated at the same time, each still split across all cores;
+    /// more than 1 keeps many cores busy on small frames. Frames are still
+    /// written, and reported, in order.
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..), env = "MANDELBROT_FRAME_JOBS")]
+    frame_jobs: u32,
+    /// Worker threads of the pixel work, one per core by default; fewer
+    /// leave cores free for other work.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..), env = "MANDELBROT_THREADS")]
+    threads: Option<u32>,
+    /// Run at a lower scheduling priority (nice 10), so other work on the
+    /// machine comes first. Unix only.
+    #[arg(long, env = "MANDELBROT_NICE")]
+    nice: bool,
+    /// Float type of the `soa` and `scalar` backends: `f64`, or `f32` for
+    /// faster previews of shallow zooms.
+    #[arg(long, default_value = "f64", env = "MANDELBROT_PRECISION")]
+    precision: numeric::Precision,
+    /// Render frames `sequential`ly or `bisect` the animation: first, last,
+    /// middle, then ever finer in between.
+    #[arg(
+        long,
+        default_value = "sequential",
+        conflicts_with_all = ["endless", "stream"],
+        env = "MANDELBROT_FRAME_ORDER"
+    )]
+    frame_order: scheduler::Order,
+    /// How to split each frame into parallel jobs: `rows`, `tiles` or `pixels`.
+    #[arg(long, default_value = "pixels", env = "MANDELBROT_CHUNK")]
+    chunk: render::ChunkMode,
+    /// Rows per job, tile side or pixels per job for --chunk.
+    #[arg(long, env = "MANDELBROT_CHUNK_SIZE")]
+    chunk_size: Option<usize>,
+    /// Built-in palette name or comma-separated `#rrggbb` gradient stops.
+    #[arg(long, default_value = "classic", env = "MANDELBROT_PALETTE")]
+    palette: Palette,
+    /// Per-frame palette rotation, e.g. `0.1*t + 0.02*sin(tau*t*4)` (t, frame, frames, zoom, time).
+    #[arg(long, value_parser = parse_frame_expr, env = "MANDELBROT_PALETTE_PHASE")]
+    palette_phase: Option<Expr>,
+    /// How pixels pick their palette color: `smooth`, `histogram`, `de`, `trap`
+    /// or `stripes`; joining several with `*` (e.g. `smooth*de`) shades the
+    /// first by the others.
+    #[arg(long, default_value = "smooth", env = "MANDELBROT_COLORING")]
+    coloring: coloring::Coloring,
+    /// Extra palettes colored from the same iteration data, each written to its own subdirectory.
+    #[arg(long, value_delimiter = ',', env = "MANDELBROT_PALETTES")]
+    palettes: Vec<NamedPalette>,
+    /// Also write every frame side by side with a second rendering that changes
+    /// one setting per use, e.g. `--compare max_iter=4000 --compare palette=fire`.
+    #[arg(long, value_parser = compare::parse_override, env = "MANDELBROT_COMPARE")]
+    compare: Vec<(String, String)>,
+    /// Also write every frame in a grid with other views of it, each changing
+    /// some settings, e.g. `--view palette=fire --view fractal=julia,julia_c_start=-0.4+0.6i`.
+    #[arg(long, env = "MANDELBROT_VIEW")]
+    view: Vec<layout::View>,
+    /// Columns of the --view grid; about square by default.
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..), env = "MANDELBROT_VIEW_COLUMNS")]
+    view_columns: Option<u32>,
+    /// Extra layer composited over every frame, e.g.
+    /// `palette=fire,opacity=0.4,max_iter=300` or `palette=grayscale,coloring=smooth*de`.
+    #[arg(long, env = "MANDELBROT_LAYER")]
+    layer: Vec<Layer>,
+    /// Noise over the complex plane that varies the brightness or the palette
+    /// position, e.g. `amount=0.3` or `target=position,amount=0.1,size=6,seed=2`.
+    #[arg(long, env = "MANDELBROT_TEXTURE")]
+    texture: Option<texture::Texture>,
+    /// Randomly varies the palette's colors and phase by up to this much per
+    /// segment of --palette-jitter-every frames, blending between segments;
+    /// 0 disables.
+    #[arg(long, default_value_t = 0.0, env = "MANDELBROT_PALETTE_JITTER")]
+    palette_jitter: f64,
+    /// Frames per --palette-jitter segment; 1 gives every frame its own look.
+    #[arg(long, default_value_t = 30, value_parser = clap::value_parser!(u32).range(1..), env = "MANDELBROT_PALETTE_JITTER_EVERY")]
+    palette_jitter_every: u32,
+    /// Seed of --palette-jitter; the same seed gives the same looks.
+    #[arg(long, default_value_t = 0, env = "MANDELBROT_PALETTE_SEED")]
+    palette_seed: u64,
+    /// Overlay template with text, rectangles and lines drawn on every frame.
+    #[arg(long, value_parser = Overlay::load, env = "MANDELBROT_OVERLAY")]
+    overlay: Option<Overlay>,
+    /// Translations of console messages, HUD labels and overlay `{text:key}`
+    /// items, one `key = "text"` per line.
+    #[arg(long, value_parser = Strings::load, env = "MANDELBROT_STRINGS")]
+    strings: Option<Strings>,
+    /// Draw complex-plane grid lines, axes and coordinate labels on every frame.
+    #[arg(long, env = "MANDELBROT_GRID")]
+    grid: bool,
+    /// Draw a legend of the main palette against iteration counts on every frame: `bottom` or `right`.
+    #[arg(long, env = "MANDELBROT_LEGEND")]
+    legend: Option<legend::Placement>,
+    /// Also write the legend as a separate image.
+    #[arg(long, env = "MANDELBROT_LEGEND_FILE")]
+    legend_file: Option<String>,
+    /// Draw running estimates of the interior share, area, boundary length and
+    /// boundary dimension in the corner of every frame.
+    #[arg(long, env = "MANDELBROT_STATS_HUD")]
+    stats_hud: bool,
+    /// Reuse iteration data of frames rendered before with exactly the same
+    /// parameters from this directory, and store new frames there.
+    #[arg(long, env = "MANDELBROT_CACHE_DIR")]
+    cache_dir: Option<String>,
+    /// Also write each frame's iteration data to `<out_dir>/iterations` for later recoloring.
+    #[arg(long, env = "MANDELBROT_SAVE_ITERATIONS")]
+    save_iterations: bool,
+    /// File format of the frames: `png`, `jpeg` (lossy, see --quality),
+    /// `webp` (lossless), `tiff`, or `exr` for OpenEXR files of the 32-bit
+    /// float escape times (channel `smooth`, -1 inside the set) to grade and
+    /// composite elsewhere. --palettes, --compare and the other extra images
+    /// stay PNG.
+    #[arg(long, default_value = "png", env = "MANDELBROT_FORMAT")]
+    format: format::Format,
+    /// JPEG quality of --format jpeg frames, 1 to 100.
+    #[arg(long, default_value_t = 90, value_parser = clap::value_parser!(u8).range(1..=100), env = "MANDELBROT_QUALITY")]
+    quality: u8,
+    /// Bits per channel of the PNG frames, 8 or 16. 16 bits take the palette
+    /// colors before they are rounded to 8 bits, which ends the banding of
+    /// slow gradients; they need --coloring smooth and no image effects.
+    #[arg(long, default_value_t = 8, env = "MANDELBROT_BIT_DEPTH")]
+    bit_depth: u8,
+    /// With --format exr, also write each pixel's distance to the set in
+    /// pixels as channel `distance`. Only for --fractal mandelbrot with --power 2.
+    #[arg(long, env = "MANDELBROT_EXR_DISTANCE")]
+    exr_distance: bool,
+    /// Also write a map of each frame's per-pixel error against a slower
+    /// double-double re-iteration to `<out_dir>/error`. Only for --fractal mandelbrot.
+    #[arg(long, env = "MANDELBROT_ERROR_MAP")]
+    error_map: bool,
+    /// Also write a map of how many samples --boundary-aa took of each pixel
+    /// to `<out_dir>/aa-samples`, to tune it by.
+    #[arg(long, env = "MANDELBROT_AA_SAMPLE_MAP")]
+    aa_sample_map: bool,
+    /// What to do with frames that come out all black, one flat color or with
+    /// NaN pixels: `off`, `warn` or `retry` them with a doubled --max-iter.
+    #[arg(long, default_value = "warn", env = "MANDELBROT_ANOMALY")]
+    anomaly: anomaly::Policy,
+    /// How many times `--anomaly retry` doubles the iteration limit of one frame.
+    #[arg(long, default_value_t = 2, env = "MANDELBROT_ANOMALY_RETRIES")]
+    anomaly_retries: u32,
+    /// Time limit per frame, e.g. `30s`; pixels left when it runs out are
+    /// iterated with an eighth of --max-iter. Only for --fractal mandelbrot.
+    #[arg(long, value_parser = segments::parse_duration, env = "MANDELBROT_FRAME_BUDGET")]
+    frame_budget: Option<f64>,
+    /// Keep zooming until stopped: after each dive to --zoom-end, zoom back out
+    /// and dive into a newly found boundary point. Ignores --frames.
+    #[arg(long, conflicts_with = "preview_video", env = "MANDELBROT_ENDLESS")]
+    endless: bool,
+    /// Center of the view in plain decimals with as many digits as the zoom
+    /// needs, `re,im`; replaces the camera path. Deep frames are rendered by
+    /// perturbation around it.
+    #[arg(long, allow_hyphen_values = true, env = "MANDELBROT_DEEP_CENTER")]
+    deep_center: Option<perturbation::DeepCenter>,
+    /// Load the reference orbits of perturbation frames from this directory
+    /// when a run with the same center computed them before, and store the
+    /// ones computed; shards of a render farm can share one.
+    #[arg(long, env = "MANDELBROT_REFERENCE_DIR")]
+    reference_dir: Option<String>,
+    #[arg(long, default_value_t = 1.0, env = "MANDELBROT_ZOOM_START")]
+    zoom_start: f64,
+    #[arg(long, default_value_t = 1e-6, env = "MANDELBROT_ZOOM_END")]
+    zoom_end: f64,
+    /// Zoom speed used by `--frames auto`, in doublings of magnification per second.
+    #[arg(long, default_value_t = 1.0, env = "MANDELBROT_ZOOM_RATE")]
+    zoom_rate: f64,
+    /// Keyframes of one frame property over the animation, overriding how it
+    /// would otherwise move, e.g. `zoom=0:3,0.5:1e-3:in-out,1:1e-6` or
+    /// `rotation=0:0,1:90`. Repeat for more properties.
+    #[arg(long, conflicts_with = "endless", env = "MANDELBROT_TRACK")]
+    track: Vec<timeline::Track>,
+    /// Matrix `a,b,c,d` the view goes through before any rotation, taking a
+    /// pixel's offset (x, y) from the center to (a x + b y, c x + d y), e.g.
+    /// `1,0.5,0,1` to shear or `-1,0,0,1` to mirror it. The tracks
+    /// `transform_a` to `transform_d` animate its entries.
+    #[arg(long, default_value = "1,0,0,1", value_parser = render::parse_transform, env = "MANDELBROT_TRANSFORM")]
+    transform: render::Transform,
+    /// Conformal map the view goes through before --transform and any
+    /// rotation: `tunnel` for the exponential map, which shows many zoom
+    /// levels at once as a tunnel into the center, or `mobius:a,b,c,d` for
+    /// the Möbius transform (a w + b) / (c w + d) with complex coefficients.
+    #[arg(long, default_value = "none", env = "MANDELBROT_WARP")]
+    warp: warp::Warp,
+    #[arg(long, default_value = "out/frames", env = "MANDELBROT_OUT_DIR")]
+    out_dir: String,
+    /// Directory to continue writing frames to if out_dir runs out of space.
+    #[arg(long, env = "MANDELBROT_FALLBACK_DIR")]
+    fallback_dir: Option<String>,
+    /// Append a machine-readable JSON-lines log of the run to this file.
+    #[arg(long, env = "MANDELBROT_LOG_FILE")]
+    log_file: Option<String>,
+    /// Before the full frames, render a low-resolution preview and encode it to this file.
+    #[arg(long, env = "MANDELBROT_PREVIEW_VIDEO")]
+    preview_video: Option<String>,
+    /// Linear resolution of the preview relative to --width/--height.
+    #[arg(long, default_value_t = 0.25, env = "MANDELBROT_PREVIEW_SCALE")]
+    preview_scale: f64,
+    /// Only produce the preview video and skip the full-resolution frames.
+    #[arg(long, requires = "preview_video", env = "MANDELBROT_PREVIEW_ONLY")]
+    preview_only: bool,
+    /// Outline the action-safe and title-safe areas on the preview video.
+    #[arg(long, requires = "preview_video", env = "MANDELBROT_SAFE_AREA")]
+    safe_area: bool,
+    /// Never prompt; keep stdout to one `frame` line per frame and a final `done` line.
+    #[arg(long, env = "MANDELBROT_NON_INTERACTIVE")]
+    non_interactive: bool,
+    /// Keep the run's state in this file for the `healthcheck` subcommand.
+    #[arg(long, env = "MANDELBROT_STATUS_FILE")]
+    status_file: Option<String>,
+    /// After rendering, pack the settings, zoom path and manifest into this
+    /// `.tar.gz` or `.tar` archive for reproducing the run elsewhere.
+    #[arg(long, value_parser = bundle::parse_path, env = "MANDELBROT_BUNDLE")]
+    bundle: Option<String>,
+    /// Also put the rendered frames into the --bundle archive.
+    #[arg(long, requires = "bundle", env = "MANDELBROT_BUNDLE_FRAMES")]
+    bundle_frames: bool,
+    /// Also broadcast the frames live at --fps to an `rtmp://` URL or an `.m3u8` HLS playlist.
+    #[arg(long, value_parser = stream::parse_target, env = "MANDELBROT_STREAM")]
+    stream: Option<String>,
+    /// How far the renderer may run ahead of the live stream, e.g. `10s`; the
+    /// broadcast starts once this much is rendered.
+    #[arg(long, default_value = "5s", value_parser = segments::parse_duration, env = "MANDELBROT_STREAM_LEAD")]
+    stream_lead: f64,
+    /// Pace the --stream at exactly --fps: frames that fall behind lower the
+    /// resolution and iteration limit of the next ones, and frames whose turn
+    /// has already passed are dropped.
+    #[arg(
+        long,
+        requires = "stream",
+        conflicts_with = "encode",
+        env = "MANDELBROT_REALTIME"
+    )]
+    realtime: bool,
+    /// Also encode the frames with ffmpeg while rendering, into numbered
+    /// segments of this video, e.g. `out/zoom.mp4` -> `out/zoom_001.mp4`.
+    /// A rerun skips the segments already finished.
+    #[arg(long, env = "MANDELBROT_ENCODE")]
+    encode: Option<String>,
+    /// Length of each --encode segment, e.g. `30s` or `5m`; a failed ffmpeg
+    /// costs at most this much re-encoding.
+    #[arg(long, default_value = "1m", value_parser = segments::parse_duration, env = "MANDELBROT_ENCODE_SEGMENT")]
+    encode_segment: f64,
+    /// Render every frame again, instead of keeping the frames a run with
+    /// the same settings already left in --out-dir.
+    #[arg(long, alias = "force", env = "MANDELBROT_NO_RESUME")]
+    no_resume: bool,
+    /// Continue the interrupted run that left a checkpoint in --out-dir,
+    /// failing unless it ran with the same settings.
+    #[arg(long, conflicts_with = "no_resume", env = "MANDELBROT_RESUME")]
+    resume: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Fractal {
+    Mandelbrot,
+    Julia,
+    BurningShip,
+    Newton,
+    Buddhabrot,
+    Formula,
+    Flame,
+    Rational,
+}
+
+impl FromStr for Fractal {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Fractal, String> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "mandelbrot" => Ok(Fractal::Mandelbrot),
+            "julia" => Ok(Fractal::Julia),
+            "burning-ship" | "burning_ship" => Ok(Fractal::BurningShip),
+            "newton" => Ok(Fractal::Newton),
+            "buddhabrot" => Ok(Fractal::Buddhabrot),
+            "flame" => Ok(Fractal::Flame),
+            "formula" => Ok(Fractal::Formula),
+            "rational" => Ok(Fractal::Rational),
+            _ => Err(format!(
+                "unknown fractal {s:?}, expected mandelbrot, julia, burning-ship, newton, buddhabrot, formula, flame or rational"
+            )),
+        }
+    }
+}
 
 #[derive(Debug, Clone, Copy)]
-struct Complex {
-    re: f64,
-    im: f64,
+enum FrameCount {
+    Fixed(u32),
+    Auto,
 }
 
-impl Complex {
-    fn add(self, other: Complex) -> Complex {
-        Complex {
-            re: self.re + other.re,
-            im: self.im + other.im,
+impl FromStr for FrameCount {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<FrameCount, String> {
+        if s.eq_ignore_ascii_case("auto") {
+            return Ok(FrameCount::Auto);
         }
+        s.parse()
+            .map(FrameCount::Fixed)
+            .map_err(|_| format!("expected a frame count or `auto`, got {s:?}"))
     }
+}
 
-    fn mul(self, other: Complex) -> Complex {
-        Complex {
-            re: self.re * other.re - self.im * other.im,
-            im: self.re * other.im + self.im * other.re,
+impl RenderArgs {
+    /// Whether the main frames are 8-bit PNGs of the frame as it is shown.
+    fn png_frames(&self) -> bool {
+        self.format == format::Format::Png && self.bit_depth == 8
+    }
+
+    /// Plain `z² + c` Mandelbrot, the set the vectorized kernel, the cascade,
+    /// the frame budget and the orbit-based colorings are written for.
+    fn quadratic_mandelbrot(&self) -> bool {
+        self.fractal == Fractal::Mandelbrot && self.power == 2.0
+    }
+
+    /// Where --reference-dir keeps reference orbits, if anywhere.
+    fn orbit_store(&self) -> Option<orbits::OrbitStore> {
+        self.reference_dir
+            .as_deref()
+            .map(|dir| orbits::OrbitStore::new(Path::new(dir)))
+    }
+
+    /// Starts the worker pool of `--threads` and `--nice` that the work of
+    /// the run goes through, see [`threads::install`].
+    fn start_threads(&self) -> Result<(), String> {
+        threads::configure(self.threads.map(|n| n as usize), self.nice)
+    }
+
+    /// Rejects settings that can't be rendered together.
+    fn check(&self) -> Result<(), String> {
+        self.coloring.check(self)?;
+        for coloring in self
+            .layer
+            .iter()
+            .filter_map(|layer| layer.coloring.as_ref())
+        {
+            coloring.check(self).map_err(|e| format!("--layer: {e}"))?;
+        }
+        timeline::check(&self.track)?;
+        scheduler::check(self, &frame_info(self, &fixed_path(self.fractal), 0))?;
+        if let Some(canvas) = self.canvas
+            && (canvas.width < self.width || canvas.height < self.height)
+        {
+            return Err(format!(
+                "--canvas {}x{} is smaller than the {}x{} frames",
+                canvas.width, canvas.height, self.width, self.height
+            ));
+        }
+        if !(self.power > 1.0 && self.power.is_finite()) {
+            return Err(format!(
+                "--power must be greater than 1, got {}",
+                self.power
+            ));
+        }
+        if self.power != 2.0 && !matches!(self.fractal, Fractal::Mandelbrot | Fractal::Julia) {
+            return Err("--power only applies to --fractal mandelbrot and julia".to_string());
+        }
+        if self.endless && self.power != 2.0 {
+            return Err(
+                "--endless finds its targets on the z² set, so it needs --power 2".to_string(),
+            );
+        }
+        if self.fractal == Fractal::Newton && !self.coloring.is_smooth() {
+            return Err("--fractal newton colors root basins, it takes no --coloring".to_string());
+        }
+        if self.fractal == Fractal::Newton && self.newton_poly.degree() == 0 {
+            return Err("--newton-poly needs at least one z term".to_string());
+        }
+        if self.nebulabrot.is_some() && self.fractal != Fractal::Buddhabrot {
+            return Err("--nebulabrot only applies to --fractal buddhabrot".to_string());
+        }
+        if self.nebulabrot.is_some() && !self.coloring.is_smooth() {
+            return Err("--nebulabrot colors its own channels, it takes no --coloring".to_string());
+        }
+        if self.stats_hud && matches!(self.fractal, Fractal::Flame | Fractal::Buddhabrot) {
+            return Err(
+                "--stats-hud needs escape-time iterations, flames and the Buddhabrot store densities"
+                    .to_string(),
+            );
+        }
+        if self.deep_center.is_some() && !self.quadratic_mandelbrot() {
+            return Err("--deep-center only applies to --fractal mandelbrot".to_string());
+        }
+        if self.deep_center.is_some()
+            && [timeline::Property::CenterRe, timeline::Property::CenterIm]
+                .into_iter()
+                .any(|p| timeline::find(&self.track, p).is_some())
+        {
+            return Err("--deep-center fixes the center, it can't take center tracks".to_string());
+        }
+        if self.deep_center.is_some() && self.endless {
+            return Err("--endless picks its own centers, it can't take --deep-center".to_string());
+        }
+        if self.deflicker > 1 && self.frame_order != scheduler::Order::Sequential {
+            return Err(
+                "--deflicker blends each frame with the ones before it, so it needs --frame-order sequential"
+                    .to_string(),
+            );
+        }
+        if let Some(end) = self.end_frame
+            && end < self.start_frame
+        {
+            return Err(format!(
+                "--end-frame {end} is before --start-frame {}",
+                self.start_frame
+            ));
+        }
+        let last = self.total_frames() - 1;
+        for (flag, frame) in [
+            ("--start-frame", Some(self.start_frame)),
+            ("--end-frame", self.end_frame),
+        ] {
+            if let Some(frame) = frame.filter(|&frame| frame > last) {
+                return Err(format!("{flag} {frame} is past the last frame {last}"));
+            }
+        }
+        if self.encode.is_some() && self.frame_order != scheduler::Order::Sequential {
+            return Err(
+                "--encode writes the video in order, so it needs --frame-order sequential"
+                    .to_string(),
+            );
         }
+        if self.format == format::Format::Exr && self.encode.is_some() {
+            return Err("--encode needs --format png frames to build the video from".to_string());
+        }
+        if self.bit_depth == 16 {
+            self.check_bit_depth()?;
+        } else if self.bit_depth != 8 {
+            return Err(format!(
+                "--bit-depth must be 8 or 16, got {}",
+                self.bit_depth
+            ));
+        }
+        if self.exr_distance {
+            if self.format != format::Format::Exr {
+                return Err("--exr-distance needs --format exr".to_string());
+            }
+            if !self.quadratic_mandelbrot() {
+                return Err("--exr-distance needs --fractal mandelbrot with --power 2".to_string());
+            }
+        }
+        if self.realtime
+            && (self.frame_jobs > 1 || self.frame_order != scheduler::Order::Sequential)
+        {
+            return Err(
+                "--realtime renders frames in turn, so it needs --frame-order sequential without --frame-jobs"
+                    .to_string(),
+            );
+        }
+        if self.auto_iter && self.auto_iter_min > self.max_iter {
+            return Err(format!(
+                "--auto-iter-min {} is above the --max-iter cap {}",
+                self.auto_iter_min, self.max_iter
+            ));
+        }
+        if self.refine_max_iter.is_some() && !self.quadratic_mandelbrot() {
+            return Err("--refine-max-iter only applies to --fractal mandelbrot".to_string());
+        }
+        if self.aa_sample_map && !(self.boundary_aa > 1 && self.quadratic_mandelbrot()) {
+            return Err(
+                "--aa-sample-map needs --boundary-aa above 1 with --fractal mandelbrot and --power 2"
+                    .to_string(),
+            );
+        }
+        if self.error_map && !self.quadratic_mandelbrot() {
+            return Err(
+                "--error-map compares against the z² Mandelbrot, so it needs --fractal mandelbrot and --power 2"
+                    .to_string(),
+            );
+        }
+        if let Some(texture) = self.texture {
+            if self.fractal == Fractal::Flame {
+                return Err(
+                    "--texture lies on the complex plane, which flames aren't drawn on".to_string(),
+                );
+            }
+            if texture.target == texture::Target::Position {
+                if self.fractal == Fractal::Newton || self.nebulabrot.is_some() {
+                    return Err(
+                        "--texture target=position needs escape times, use target=value"
+                            .to_string(),
+                    );
+                }
+                if self.boundary_aa > 1 {
+                    return Err(
+                        "--texture target=position can't be combined with --boundary-aa"
+                            .to_string(),
+                    );
+                }
+            }
+        }
+        if !(0.0..=1.0).contains(&self.palette_jitter) {
+            return Err(format!(
+                "--palette-jitter must be between 0 and 1, got {}",
+                self.palette_jitter
+            ));
+        }
+        if self.grid && timeline::find(&self.track, timeline::Property::Rotation).is_some() {
+            return Err("--grid can't be drawn on a rotated view".to_string());
+        }
+        if self.grid
+            && (self.transform != render::IDENTITY
+                || timeline::TRANSFORM
+                    .iter()
+                    .any(|&(_, property)| timeline::find(&self.track, property).is_some()))
+        {
+            return Err("--grid can't be drawn on a --transform view".to_string());
+        }
+        if self.warp != warp::Warp::None {
+            if self.grid {
+                return Err("--grid can't be drawn on a --warp view".to_string());
+            }
+            if self.fractal == Fractal::Buddhabrot {
+                return Err("--fractal buddhabrot can't be drawn with --warp".to_string());
+            }
+            if self.fractal == Fractal::Flame {
+                return Err("--fractal flame can't be drawn with --warp".to_string());
+            }
+        }
+        Ok(())
     }
 
-    fn norm_sqr(self) -> f64 {
-        self.re * self.re + self.im * self.im
+    /// Rejects settings whose colors don't come straight from the palette,
+    /// which only exist as 8-bit images.
+    fn check_bit_depth(&self) -> Result<(), String> {
+        let unsupported = [
+            (
+                !self.format.has_16_bits(),
+                "--format other than png or tiff",
+            ),
+            (!self.coloring.is_smooth(), "--coloring other than smooth"),
+            (self.fractal == Fractal::Newton, "--fractal newton"),
+            (self.nebulabrot.is_some(), "--nebulabrot"),
+            (self.boundary_aa > 1, "--boundary-aa"),
+            (self.deflicker > 1, "--deflicker"),
+            (!self.layer.is_empty(), "--layer"),
+            (self.texture.is_some(), "--texture"),
+            (self.grid, "--grid"),
+            (self.legend.is_some(), "--legend"),
+            (self.stats_hud, "--stats-hud"),
+            (self.overlay.is_some(), "--overlay"),
+            (self.canvas.is_some(), "--canvas"),
+        ];
+        match unsupported.iter().find(|(used, _)| *used) {
+            Some((_, flag)) => Err(format!("--bit-depth 16 doesn't support {flag}")),
+            None => Ok(()),
+        }
+    }
+
+    /// The frames this run renders, --start-frame to --end-frame.
+    fn frame_range(&self) -> Range<u32> {
+        let end = self
+            .end_frame
+            .map_or(self.total_frames(), |end| end.saturating_add(1));
+        self.start_frame..end
+    }
+
+    fn total_frames(&self) -> u32 {
+        if self.endless {
+            return u32::MAX;
+        }
+        match self.frames {
+            FrameCount::Fixed(frames) => frames.max(1),
+            FrameCount::Auto => {
+                auto_frame_count(self.zoom_start, self.zoom_end, self.fps, self.zoom_rate)
+            }
+        }
     }
 }
 
-#[derive(Parser, Debug)]
-#[command(name = "mandelbrot-animation")]
-#[command(about = "Render Mandelbrot animation frames.")]
-struct Args {
-    #[arg(long, default_value_t = 1920)]
-    width: u32,
-    #[arg(long, default_value_t = 1080)]
-    height: u32,
-    #[arg(long, default_value_t = 300)]
-    frames: u32,
-    #[arg(long, default_value_t = 30)]
-    fps: u32,
-    #[arg(long, default_value_t = 2000)]
-    max_iter: u32,
-    #[arg(long, default_value_t = 1.0)]
-    zoom_start: f64,
-    #[arg(long, default_value_t = 1e-6)]
-    zoom_end: f64,
-    #[arg(long, default_value = "out/frames")]
-    out_dir: String,
+/// Exit status for a failure: `128 + signal` when a signal stopped the run, 1 otherwise.
+/// Usage errors exit with 2 from clap.
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!(
+                "{}",
+                strings::text("error", "Error: {error}", &[("error", &e)])
+            );
+            match signals::received() {
+                Some(sig) => ExitCode::from((128 + sig) as u8),
+                None => ExitCode::FAILURE,
+            }
+        }
+    }
 }
 
-fn main() -> Result<(), String> {
-    let args = Args::parse();
-    let out_dir = PathBuf::from(&args.out_dir);
-    fs::create_dir_all(&out_dir).map_err(|e| format!("create out_dir: {e}"))?;
+fn run() -> Result<(), String> {
+    let mut argv: Vec<OsString> = std::env::args_os().collect();
+    let mut matches = Cli::command().get_matches_from(&argv);
+    if let Some(path) = matches.get_one::<String>("config") {
+        let config = config::load(path)?;
+        argv = config::merge_args(argv, &matches, &config)?;
+        matches = Cli::command().get_matches_from(&argv);
+    }
+    // After --config, so that the file's options win over the preset too.
+    if let Some(preset) = matches.get_one::<preset::Preset>("preset") {
+        argv = config::merge_args(argv, &matches, &preset.config())?;
+        matches = Cli::command().get_matches_from(&argv);
+    }
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    if cli.print_config {
+        print!("{}", config::to_toml(&settings::from_matches(&matches)));
+        return Ok(());
+    }
+    match cli.command {
+        Some(Command::Verify(args)) => verify::run(args),
+        Some(Command::Estimate(args)) => estimate::run(*args),
+        Some(Command::Palette(args)) => swatch::run(args),
+        Some(Command::Recolor(args)) => recolor::run(args),
+        Some(Command::Panorama(args)) => panorama::run(args),
+        Some(Command::Poster(args)) => poster::run(*args),
+        Some(Command::PathPlot(args)) => pathplot::run(*args),
+        Some(Command::ExpMap(args)) => expmap::run(*args),
+        Some(Command::Encode(args)) => segments::run(args),
+        Some(Command::Reframe(args)) => reframe::run(args),
+        Some(Command::Diff(args)) => diff::run(args),
+        Some(Command::Sweep(args)) => {
+            let sub = matches
+                .subcommand_matches("sweep")
+                .expect("sweep was parsed");
+            sweep::run(*args, settings::from_matches(sub))
+        }
+        Some(Command::Analyze(args)) => analyze::run(args),
+        Some(Command::Healthcheck(args)) => health::run(args),
+        Some(Command::Selftest) => selftest::run(),
+        Some(Command::Init(args)) => init::run(args),
+        Some(Command::Completions(args)) => completions::run(args, Cli::command()),
+        #[cfg(feature = "screensaver")]
+        Some(Command::Screensaver(args)) => screensaver::run(args),
+        None => {
+            cli.render.start_threads()?;
+            let settings = settings::from_matches(&matches);
+            threads::install(|| render(cli.render, settings))
+        }
+    }
+}
 
-    let path = fixed_path();
+fn render(args: RenderArgs, settings: Vec<(String, String)>) -> Result<(), String> {
+    signals::install();
+    render::set_chunking(args.chunk, args.chunk_size);
+    if let Some(strings) = &args.strings {
+        strings::set(strings.clone());
+    }
+    args.check()?;
+    if let Some(log_file) = &args.log_file {
+        runlog::init(Path::new(log_file))?;
+    }
+    let fields: Vec<(&str, runlog::Value)> = settings
+        .iter()
+        .map(|(key, value)| (key.as_str(), value.as_str().into()))
+        .collect();
+    runlog::info("run_start", &fields);
 
-    let total_frames = args.frames.max(1);
-    for frame in 0..total_frames {
-        let t = if total_frames <= 1 {
-            0.0
-        } else {
-            frame as f64 / (total_frames - 1) as f64
-        };
-        let path_center = path_position(&path, t);
-        let zoom = exp_lerp(args.zoom_start, args.zoom_end, t);
-        let center = dampened_center(path[0], path_center, zoom, args.zoom_start);
-        let img = render_frame(
-            args.width,
-            args.height,
-            center,
-            zoom,
-            args.max_iter,
-        );
+    if let Some(preview) = &args.preview_video {
+        preview::render_preview(&args, Path::new(preview))?;
+        if args.preview_only {
+            return Ok(());
+        }
+    }
 
-        let filename = format!("frame_{:06}.png", frame);
-        let filepath = out_dir.join(filename);
-        img.save(&filepath)
-            .map_err(|e| format!("save {filepath:?}: {e}"))?;
-        println!(
-            "frame {}/{} -> {}",
-            frame + 1,
-            total_frames,
-            filepath.display()
-        );
+    let started = Instant::now();
+    let result = render_frames(&args, settings);
+    match &result {
+        Ok(frames) => runlog::info(
+            "run_end",
+            &[
+                ("frames", (*frames).into()),
+                ("elapsed_s", started.elapsed().as_secs_f64().into()),
+            ],
+        ),
+        Err(e) => runlog::error("run_failed", &[("error", e.as_str().into())]),
     }
+    let frames = result?;
 
+    if args.non_interactive {
+        println!("done {frames}");
+        return Ok(());
+    }
+    if args.format == format::Format::Exr {
+        return Ok(());
+    }
     println!();
-    println!("ffmpeg example:");
     println!(
-        "ffmpeg -framerate {} -i {}/frame_%06d.png -c:v libx264 -pix_fmt yuv420p out/mandelbrot.mp4",
+        "{}",
+        strings::text("ffmpeg_example", "ffmpeg example:", &[])
+    );
+    println!(
+        "ffmpeg -framerate {} -i {}/frame_%06d.{} {}",
         args.fps,
-        args.out_dir
+        args.out_dir,
+        args.format.extension(),
+        preset::output_args(args.preset, "out/mandelbrot.mp4").join(" ")
     );
 
     Ok(())
 }
 
-fn render_frame(
+fn render_frames(args: &RenderArgs, settings: Vec<(String, String)>) -> Result<u32, String> {
+    let out_dir = output::resolve_dir(Path::new(&args.out_dir))?;
+    fs::create_dir_all(&out_dir).map_err(|e| output::describe_error("create", &out_dir, &e))?;
+
+    let path = fixed_path(args.fractal);
+    let mut writer = FrameWriter::new(
+        out_dir.clone(),
+        args.fallback_dir.as_ref().map(PathBuf::from),
+        !args.non_interactive,
+    );
+    let other = if args.compare.is_empty() {
+        None
+    } else {
+        Some(compare::other_args(&settings, &args.compare)?)
+    };
+    let views = args
+        .view
+        .iter()
+        .map(|view| Ok((view, view.args(&settings)?)))
+        .collect::<Result<Vec<_>, String>>()?;
+    let cache = match &args.cache_dir {
+        Some(dir) => Some(cache::FrameCache::open(
+            Path::new(dir),
+            &settings,
+            args.cascade > 1,
+        )?),
+        None => None,
+    };
+    let mut reused = 0;
+    let mut deflicker = deflicker::Deflicker::new(args.deflicker, args.deflicker_tolerance);
+    if args.resume {
+        let checkpoint = Checkpoint::read(&out_dir)?.ok_or_else(|| {
+            format!(
+                "--resume: no checkpoint in {}, only an interrupted run leaves one",
+                out_dir.display()
+            )
+        })?;
+        if checkpoint.settings != manifest::settings_hash(&settings) {
+            return Err(format!(
+                "--resume: the checkpoint in {} is from a run with other settings",
+                out_dir.display()
+            ));
+        }
+        eprintln!(
+            "{}",
+            strings::text(
+                "resume_checkpoint",
+                "resume: continuing at frame {frame} from the checkpoint in {dir}",
+                &[
+                    ("frame", &checkpoint.next_frame),
+                    ("dir", &out_dir.display())
+                ],
+            )
+        );
+    }
+    let finished = if args.no_resume {
+        BTreeMap::new()
+    } else {
+        Manifest::finished(
+            &out_dir,
+            args.fallback_dir.as_deref().map(Path::new),
+            &settings,
+        )?
+    };
+    let mut manifest = Manifest::create(&mut writer, settings)?;
+    if !finished.is_empty() {
+        runlog::info("resume", &[("frames", finished.len().into())]);
+        eprintln!(
+            "{}",
+            strings::text(
+                "resume",
+                "resume: keeping the {count} frames already in {dir}, --no-resume renders them again",
+                &[("count", &finished.len()), ("dir", &out_dir.display())],
+            )
+        );
+    }
+    if let Some(legend_file) = &args.legend_file {
+        legend::write_file(Path::new(legend_file), &args.palette, args.max_iter)?;
+        eprintln!(
+            "{}",
+            strings::text(
+                "legend_written",
+                "legend -> {path}",
+                &[("path", legend_file)]
+            )
+        );
+    }
+
+    let total_frames = args.total_frames();
+    if let (FrameCount::Fixed(_), false) = (args.frames, args.endless) {
+        let suggested = auto_frame_count(args.zoom_start, args.zoom_end, args.fps, args.zoom_rate);
+        if suggested != total_frames && !args.non_interactive {
+            println!(
+                "{}",
+                strings::text(
+                    "hint_frames_auto",
+                    "hint: --frames auto would render {frames} frames ({rate} doublings/s at {fps} fps)",
+                    &[
+                        ("frames", &suggested),
+                        ("rate", &args.zoom_rate),
+                        ("fps", &args.fps)
+                    ],
+                )
+            );
+        }
+    }
+    let mut live = match &args.stream {
+        Some(target) => Some(stream::LiveStream::start(
+            args.canvas.map_or(args.width, |canvas| canvas.width),
+            args.canvas.map_or(args.height, |canvas| canvas.height),
+            args.fps,
+            target,
+            args.stream_lead,
+        )?),
+        None => None,
+    };
+    let mut encoder = match &args.encode {
+        Some(video) => Some(segments::SegmentEncoder::start(
+            Path::new(video),
+            args.encode_segment,
+            args.fps,
+            args.preset,
+        )?),
+        None => None,
+    };
+    let range = args.frame_range();
+    let planned = range.len() as u32;
+    update_status(args, "running", 0, planned);
+    let mut precision_warned = false;
+    let mut f32_warned = false;
+    let mut done = 0;
+    let mut blended = None;
+    let pacer = args
+        .realtime
+        .then(|| Mutex::new(pacing::Pacer::new(args.fps)));
+    let mut shown = None;
+    let result = pipeline::ordered(
+        args.frame_jobs as usize,
+        args.frame_order.frames(range),
+        |frame| {
+            if finished.contains_key(&frame) {
+                return (frame, None);
+            }
+            let level = match &pacer {
+                Some(pacer) => match pacer.lock().expect("pacer lock").start() {
+                    Some(level) => level,
+                    None => return (frame, None),
+                },
+                None => pacing::Level::FULL,
+            };
+            let iterated = iterate_animation_frame(args, &path, cache.as_ref(), frame, level);
+            (frame, Some(iterated))
+        },
+        |(frame, iterated)| {
+            let Some(iterated) = iterated else {
+                let Some(previous) = finished.get(&frame) else {
+                    // Dropped by --realtime; the stream shows the last frame again.
+                    if let (Some(live), Some(img)) = (&mut live, &shown) {
+                        live.push(img)?;
+                    }
+                    done += 1;
+                    update_status(args, "running", done, planned);
+                    return Ok(());
+                };
+                for entry in &previous.files {
+                    let entry = FrameEntry {
+                        file: manifest.relative(Path::new(&entry.file)),
+                        ..entry.clone()
+                    };
+                    manifest.push_frame(&mut writer, entry)?;
+                }
+                if let Some(anomaly) = &previous.anomaly {
+                    manifest.push_anomaly(&mut writer, anomaly.clone())?;
+                }
+                if let Some(pixels) = previous.degraded {
+                    manifest.push_degraded(&mut writer, frame, pixels)?;
+                }
+                if let Some(encoder) = &mut encoder
+                    && encoder.needs(frame)
+                    && let Some(entry) = previous.files.iter().find(|e| e.variant.is_none())
+                {
+                    let file = PathBuf::from(&entry.file);
+                    let img = image::open(&file)
+                        .map_err(|e| format!("read {file:?}: {e}"))?
+                        .to_rgb8();
+                    encoder.push(frame, &img, &file)?;
+                }
+                done += 1;
+                update_status(args, "running", done, planned);
+                return Ok(());
+            };
+            let Iterated {
+                frame,
+                info,
+                backend,
+                started: frame_started,
+                reused: from_cache,
+                checked,
+            } = iterated?;
+            if !precision_warned && backend != "perturbation" && scheduler::past_f64(args, &info) {
+                precision_warned = true;
+                runlog::warn(
+                    "frame_past_precision",
+                    &[("frame", frame.into()), ("backend", backend.into())],
+                );
+                eprintln!(
+                    "{}",
+                    strings::text(
+                        "frame_past_precision",
+                        "warning: frame {frame} is zoomed past f64 precision, the {backend} backend renders it blocky",
+                        &[("frame", &frame), ("backend", &backend)],
+                    )
+                );
+            }
+            if !f32_warned
+                && args.precision == numeric::Precision::F32
+                && matches!(backend, "soa" | "scalar")
+                && scheduler::past_f32(args, &info)
+            {
+                f32_warned = true;
+                runlog::warn("frame_past_f32", &[("frame", frame.into())]);
+                eprintln!(
+                    "{}",
+                    strings::text(
+                        "frame_past_f32",
+                        "warning: frame {frame} is zoomed past f32 precision and renders blocky, use --precision f64",
+                        &[("frame", &frame)],
+                    )
+                );
+            }
+            if from_cache {
+                reused += 1;
+            }
+            if let Some(pixels) = checked.degraded {
+                manifest.push_degraded(&mut writer, frame, pixels)?;
+            }
+            if let Some(anomaly) = checked.anomaly {
+                manifest.push_anomaly(&mut writer, anomaly)?;
+            }
+            let (iterations, max_iter) = (checked.iterations, checked.max_iter);
+            if args.deflicker > 1 && frame > 0 && blended != Some(frame - 1) {
+                // The frames before come from the last run or lie before
+                // --start-frame; blend as if this run had rendered them.
+                for k in frame.saturating_sub(args.deflicker - 1)..frame {
+                    let info = frame_info(args, &path, k);
+                    let earlier = iterate(args, &info, args.width, args.height, info.max_iter);
+                    deflicker.apply(&earlier, base_colors(args, &earlier, &info, &args.palette));
+                }
+            }
+            let retried;
+            let args = if max_iter == args.max_iter {
+                args
+            } else {
+                retried = RenderArgs {
+                    max_iter,
+                    ..args.clone()
+                };
+                &retried
+            };
+
+            if args.save_iterations {
+                let iter_file =
+                    Path::new(iterfile::ITERATIONS_DIR).join(iterfile::iteration_filename(frame));
+                writer.write(&iter_file.to_string_lossy(), &iterfile::encode(&iterations))?;
+            }
+
+            let filename = frame_filename(frame);
+            let img = deflicker.apply(
+                &iterations,
+                base_colors(args, &iterations, &info, &args.palette),
+            );
+            blended = Some(frame);
+            let img = decorate(args, img, &iterations, &info, &args.palette);
+            let bytes = encode_main_frame(args, &iterations, &info, &img)?;
+            let filepath = save_frame_file(
+                &mut writer,
+                &mut manifest,
+                frame,
+                None,
+                &Path::new(&filename)
+                    .with_extension(args.format.extension())
+                    .to_string_lossy(),
+                &bytes,
+            )?;
+            if let Some(live) = &mut live {
+                live.push(&img)?;
+                if pacer.is_some() {
+                    shown = Some(img.clone());
+                }
+            }
+            if let Some(encoder) = &mut encoder {
+                encoder.push(frame, &img, &filepath)?;
+            }
+            for variant in &args.palettes {
+                let img = color_frame(args, &iterations, &info, &variant.palette);
+                let variant_file = Path::new(&variant.label).join(&filename);
+                save_frame(
+                    &mut writer,
+                    &mut manifest,
+                    frame,
+                    Some(&variant.label),
+                    &variant_file.to_string_lossy(),
+                    &img,
+                )?;
+            }
+            if let Some(other) = &other {
+                let right = render_animation_frame(other, &path, frame, &other.palette);
+                let img = compare::side_by_side(&img, &right, &args.compare);
+                let compare_file = Path::new(compare::LABEL).join(&filename);
+                save_frame(
+                    &mut writer,
+                    &mut manifest,
+                    frame,
+                    Some(compare::LABEL),
+                    &compare_file.to_string_lossy(),
+                    &img,
+                )?;
+            }
+            if !views.is_empty() {
+                let others = views
+                    .iter()
+                    .map(|(view, view_args)| {
+                        let img =
+                            render_animation_frame(view_args, &path, frame, &view_args.palette);
+                        (*view, img)
+                    })
+                    .collect();
+                let img = layout::views_frame(img.clone(), others, args.view_columns);
+                let views_file = Path::new(layout::LABEL).join(&filename);
+                save_frame(
+                    &mut writer,
+                    &mut manifest,
+                    frame,
+                    Some(layout::LABEL),
+                    &views_file.to_string_lossy(),
+                    &img,
+                )?;
+            }
+            if args.error_map {
+                let (img, report) = errormap::measure(args, &info, &iterations);
+                let error_file = Path::new(errormap::LABEL).join(&filename);
+                save_frame(
+                    &mut writer,
+                    &mut manifest,
+                    frame,
+                    Some(errormap::LABEL),
+                    &error_file.to_string_lossy(),
+                    &img,
+                )?;
+                runlog::info(
+                    "error_map",
+                    &[
+                        ("frame", frame.into()),
+                        ("inaccurate_pixels", report.inaccurate.into()),
+                        ("mismatched_pixels", report.mismatched.into()),
+                        ("max_error", report.max_error.into()),
+                    ],
+                );
+                if !args.non_interactive {
+                    println!(
+                        "error map: {} of {} pixels off by more than {} iterations ({} inside/outside mismatches), max {:.3e}",
+                        report.inaccurate,
+                        report.pixels,
+                        errormap::TOLERANCE,
+                        report.mismatched,
+                        report.max_error
+                    );
+                }
+            }
+            if args.aa_sample_map {
+                let (img, report) = antialias::sample_map(&iterations, args, &info);
+                let samples_file = Path::new(antialias::LABEL).join(&filename);
+                save_frame(
+                    &mut writer,
+                    &mut manifest,
+                    frame,
+                    Some(antialias::LABEL),
+                    &samples_file.to_string_lossy(),
+                    &img,
+                )?;
+                runlog::info(
+                    "aa_samples",
+                    &[
+                        ("frame", frame.into()),
+                        ("boundary_pixels", report.boundary.into()),
+                        ("samples", report.samples.into()),
+                    ],
+                );
+                if !args.non_interactive {
+                    println!(
+                        "aa samples: {} of {} pixels on the boundary, {:.2} samples per pixel",
+                        report.boundary,
+                        report.pixels,
+                        report.samples as f64 / report.pixels as f64
+                    );
+                }
+            }
+            runlog::info(
+                "frame",
+                &[
+                    ("frame", frame.into()),
+                    ("backend", backend.into()),
+                    ("zoom", info.zoom.into()),
+                    ("center_re", info.center.re.into()),
+                    ("center_im", info.center.im.into()),
+                    (
+                        "duration_ms",
+                        (frame_started.elapsed().as_millis() as u64).into(),
+                    ),
+                ],
+            );
+            let (number, path) = (frame + 1, filepath.display());
+            if args.non_interactive {
+                // The protocol lines stay in English for the programs reading them.
+                if args.endless {
+                    println!("frame {number} -> {path}");
+                } else {
+                    println!("frame {number}/{total_frames} -> {path}");
+                }
+            } else if args.endless {
+                println!(
+                    "{}",
+                    strings::text(
+                        "frame_written_endless",
+                        "frame {frame} -> {path}",
+                        &[("frame", &number), ("path", &path)],
+                    )
+                );
+            } else {
+                println!(
+                    "{}",
+                    strings::text(
+                        "frame_written",
+                        "frame {frame}/{frames} -> {path}",
+                        &[
+                            ("frame", &number),
+                            ("frames", &total_frames),
+                            ("path", &path)
+                        ],
+                    )
+                );
+            }
+            done += 1;
+            update_status(args, "running", done, planned);
+            if let Some(pacer) = &pacer {
+                pacer.lock().expect("pacer lock").finish();
+            }
+            Ok(())
+        },
+    );
+    let result = match live {
+        Some(live) => result.and(live.finish()),
+        None => result,
+    };
+    if let Some(pacer) = pacer {
+        pacer.into_inner().expect("pacer lock").report();
+    }
+    // An interrupted run leaves its last segment out of the list, to be
+    // encoded whole again by the rerun.
+    let result = match encoder {
+        Some(encoder) => result.and_then(|()| encoder.finish()),
+        None => result,
+    };
+    let written = manifest
+        .frames
+        .iter()
+        .filter(|f| f.variant.is_none())
+        .count() as u32;
+    if result.is_err() && signals::received().is_some() {
+        // The frames before are all in the manifest, for the rerun to keep.
+        if let Some(next) = args
+            .frame_order
+            .frames(args.frame_range())
+            .nth(done as usize)
+        {
+            match Checkpoint::new(next, &manifest.settings).write(&out_dir) {
+                Ok(path) => eprintln!(
+                    "{}",
+                    strings::text(
+                        "checkpoint_written",
+                        "checkpoint -> {path}, --resume continues at frame {frame}",
+                        &[("path", &path.display()), ("frame", &next)],
+                    )
+                ),
+                Err(e) => eprintln!("{e}"),
+            }
+        }
+    } else if result.is_ok() {
+        Checkpoint::remove(&out_dir)?;
+    }
+    let state = match (&result, signals::received()) {
+        (Ok(()), _) => "done",
+        (Err(_), Some(_)) => "interrupted",
+        (Err(_), None) => "failed",
+    };
+    update_status(args, state, written, planned);
+    if cache.is_some() {
+        eprintln!(
+            "{}",
+            strings::text(
+                "cache_reused",
+                "cache: reused {reused} of {frames} frames",
+                &[("reused", &reused), ("frames", &written)],
+            )
+        );
+    }
+
+    if let Some(bundle) = &args.bundle {
+        let bundle = Path::new(bundle);
+        match bundle::export(args, &manifest, bundle) {
+            Ok(()) => eprintln!(
+                "{}",
+                strings::text(
+                    "bundle_written",
+                    "bundle -> {path}",
+                    &[("path", &bundle.display())]
+                )
+            ),
+            Err(e) if result.is_ok() => return Err(e),
+            Err(e) => eprintln!("{e}"),
+        }
+    }
+    result.map(|()| planned)
+}
+
+/// One frame's iterations, from the cache or iterated and checked. Under
+/// --frame-jobs several of these are made at once, ahead of the frame that
+/// is being written.
+struct Iterated {
+    frame: u32,
+    info: FrameInfo,
+    backend: &'static str,
+    started: Instant,
+    reused: bool,
+    checked: Checked,
+}
+
+/// The buffer of [`iterate_checked`], the limit it used and what the
+/// manifest has to record about it.
+struct Checked {
+    iterations: IterationBuffer,
+    max_iter: u32,
+    degraded: Option<usize>,
+    anomaly: Option<manifest::AnomalyEntry>,
+}
+
+/// Everything about a frame that doesn't depend on the frames before it:
+/// its view and its iterations. Frames that come out as-is, without
+/// retries or degradation, are stored in the cache.
+fn iterate_animation_frame(
+    args: &RenderArgs,
+    path: &[Complex],
+    cache: Option<&cache::FrameCache>,
+    frame: u32,
+    level: pacing::Level,
+) -> Result<Iterated, String> {
+    if let Some(sig) = signals::received() {
+        return Err(format!("interrupted by signal {sig} before frame {frame}"));
+    }
+    let started = Instant::now();
+    let info = frame_info(args, path, frame);
+    let backend = scheduler::backend_for(args, &info).name();
+    let keyed;
+    let args = if info.max_iter == args.max_iter {
+        args
+    } else {
+        keyed = RenderArgs {
+            max_iter: info.max_iter,
+            ..args.clone()
+        };
+        &keyed
+    };
+    // A refined frame keeps the limit its last pass reached.
+    let cached = cache.and_then(|c| c.load(&info)).filter(|b| {
+        (b.width, b.height) == (args.width, args.height)
+            && (b.max_iter == args.max_iter || args.refine_max_iter.is_some())
+    });
+    let (reused, checked) = match cached {
+        Some(iterations) => (
+            true,
+            Checked {
+                iterations,
+                max_iter: args.max_iter,
+                degraded: None,
+                anomaly: None,
+            },
+        ),
+        None if !level.is_full() => (false, iterate_reduced(args, &info, frame, level)),
+        None => {
+            let checked = iterate_checked(args, &info, frame, started);
+            if let Some(cache) = cache
+                && checked.max_iter == args.max_iter
+                && checked.degraded.is_none()
+                && anomaly::scan(&checked.iterations).is_none()
+                && let Err(e) = cache.store(&info, &checked.iterations)
+            {
+                eprintln!("warning: {e}");
+            }
+            (false, checked)
+        }
+    };
+    Ok(Iterated {
+        frame,
+        info,
+        backend,
+        started,
+        reused,
+        checked,
+    })
+}
+
+/// Iterates a frame at a lower --realtime `level`, stretched to full size.
+fn iterate_reduced(
+    args: &RenderArgs,
+    info: &FrameInfo,
+    frame: u32,
+    level: pacing::Level,
+) -> Checked {
+    let (width, height) = level.size(args.width, args.height);
+    let max_iter = level.max_iter(args.max_iter);
+    let reduced = iterate(args, info, width, height, max_iter);
+    runlog::info(
+        "frame_reduced",
+        &[
+            ("frame", frame.into()),
+            ("width", width.into()),
+            ("height", height.into()),
+            ("max_iter", max_iter.into()),
+        ],
+    );
+    Checked {
+        iterations: pacing::upscale(&reduced, args.width, args.height, args.max_iter),
+        max_iter: args.max_iter,
+        degraded: Some(args.width as usize * args.height as usize),
+        anomaly: None,
+    }
+}
+
+/// Iterates a frame, within --frame-budget if one is set, and checks it for
+/// anomalies, re-iterating with a doubled limit under `--anomaly retry`.
+fn iterate_checked(args: &RenderArgs, info: &FrameInfo, frame: u32, started: Instant) -> Checked {
+    let mut max_iter = args.max_iter;
+    let mut iterations = match (args.frame_budget, args.fractal) {
+        // The budgeted pass iterates f64 points, which deep frames are past.
+        (Some(seconds), _)
+            if args.quadratic_mandelbrot()
+                && scheduler::backend_for(args, info).name() != "perturbation" =>
+        {
+            let budget = render::Budget {
+                deadline: started + Duration::from_secs_f64(seconds),
+                reduced_iter: (max_iter / BUDGET_ITER_DIVISOR).max(1),
+            };
+            let (iterations, degraded) = render::iterate_points_within(
+                args.width,
+                args.height,
+                max_iter,
+                args.derivative_bailout,
+                info.pixel_mapping(args.width, args.height),
+                &budget,
+            );
+            if degraded > 0 {
+                runlog::warn(
+                    "frame_over_budget",
+                    &[
+                        ("frame", frame.into()),
+                        ("degraded_pixels", degraded.into()),
+                        ("reduced_max_iter", budget.reduced_iter.into()),
+                    ],
+                );
+                eprintln!(
+                    "warning: frame {frame} ran over --frame-budget, {degraded} pixels used --max-iter {}",
+                    budget.reduced_iter
+                );
+                return Checked {
+                    iterations,
+                    max_iter,
+                    degraded: Some(degraded),
+                    anomaly: None,
+                };
+            }
+            iterations
+        }
+        _ => iterate(args, info, args.width, args.height, max_iter),
+    };
+    let unchecked = |iterations, max_iter| Checked {
+        iterations,
+        max_iter,
+        degraded: None,
+        anomaly: None,
+    };
+    if args.anomaly == anomaly::Policy::Off {
+        return unchecked(iterations, max_iter);
+    }
+    let Some(first) = anomaly::scan(&iterations) else {
+        return unchecked(iterations, max_iter);
+    };
+    let mut found = Some(first);
+    if args.anomaly == anomaly::Policy::Retry {
+        for _ in 0..args.anomaly_retries {
+            max_iter = max_iter.max(iterations.max_iter).saturating_mul(2);
+            iterations = iterate(args, info, args.width, args.height, max_iter);
+            found = anomaly::scan(&iterations);
+            if found.is_none() {
+                break;
+            }
+        }
+    }
+    let kind = found.unwrap_or(first).to_string();
+    let resolved = found.is_none();
+    runlog::warn(
+        "frame_anomaly",
+        &[
+            ("frame", frame.into()),
+            ("kind", kind.as_str().into()),
+            ("max_iter", max_iter.into()),
+            ("resolved", resolved.into()),
+        ],
+    );
+    if resolved {
+        eprintln!(
+            "{}",
+            strings::text(
+                "anomaly_resolved",
+                "warning: frame {frame} was {kind}, re-rendered with --max-iter {max_iter}",
+                &[("frame", &frame), ("kind", &first), ("max_iter", &max_iter)],
+            )
+        );
+    } else {
+        eprintln!(
+            "{}",
+            strings::text(
+                "anomaly",
+                "warning: frame {frame} is {kind} (max_iter {max_iter})",
+                &[("frame", &frame), ("kind", &kind), ("max_iter", &max_iter)],
+            )
+        );
+    }
+    Checked {
+        iterations,
+        max_iter,
+        degraded: None,
+        anomaly: Some(manifest::AnomalyEntry {
+            index: frame,
+            max_iter,
+            resolved,
+            kind,
+        }),
+    }
+}
+
+fn update_status(args: &RenderArgs, state: &'static str, frame: u32, frames: u32) {
+    let Some(path) = &args.status_file else {
+        return;
+    };
+    let status = health::Status {
+        state,
+        frame,
+        frames,
+    };
+    if let Err(e) = status.write(Path::new(path)) {
+        runlog::warn("status_write_failed", &[("error", e.as_str().into())]);
+        eprintln!("{e}");
+    }
+}
+
+fn save_frame(
+    writer: &mut FrameWriter,
+    manifest: &mut Manifest,
+    frame: u32,
+    variant: Option<&str>,
+    filename: &str,
+    img: &ImageBuffer<Rgb<u8>, Vec<u8>>,
+) -> Result<PathBuf, String> {
+    let bytes = encode_png(img)?;
+    save_frame_file(writer, manifest, frame, variant, filename, &bytes)
+}
+
+/// Writes an encoded frame file and records it in the log and manifest.
+fn save_frame_file(
+    writer: &mut FrameWriter,
+    manifest: &mut Manifest,
+    frame: u32,
+    variant: Option<&str>,
+    filename: &str,
+    bytes: &[u8],
+) -> Result<PathBuf, String> {
+    let filepath = writer.write(filename, bytes)?;
+    let file = manifest.relative(&filepath);
+    let sha256 = sha256::hex_digest(bytes);
+    runlog::info(
+        "frame_file",
+        &[
+            ("frame", frame.into()),
+            ("variant", variant.unwrap_or("").into()),
+            ("file", file.as_str().into()),
+            ("sha256", sha256.as_str().into()),
+        ],
+    );
+    manifest.push_frame(
+        writer,
+        FrameEntry {
+            index: frame,
+            variant: variant.map(str::to_string),
+            file,
+            sha256,
+        },
+    )?;
+    Ok(filepath)
+}
+
+fn frame_filename(frame: u32) -> String {
+    format!("frame_{:06}.png", frame)
+}
+
+fn encode_png<P>(img: &ImageBuffer<P, Vec<P::Subpixel>>) -> Result<Vec<u8>, String>
+where
+    P: image::PixelWithColorType,
+    [P::Subpixel]: image::EncodableLayout,
+{
+    let mut bytes = Vec::new();
+    img.write_to(&mut Cursor::new(&mut bytes), ImageFormat::Png)
+        .map_err(|e| format!("encode png: {e}"))?;
+    Ok(bytes)
+}
+
+/// The main frame file in --format: `img` encoded, or made from the
+/// iterations for --format exr and --bit-depth 16, whose plain palette
+/// colors are the only ones with 16 bits.
+fn encode_main_frame(
+    args: &RenderArgs,
+    iterations: &IterationBuffer,
+    info: &FrameInfo,
+    img: &ImageBuffer<Rgb<u8>, Vec<u8>>,
+) -> Result<Vec<u8>, String> {
+    if args.format == format::Format::Exr {
+        return Ok(format::encode_exr(iterations, info, args.exr_distance));
+    }
+    if args.bit_depth == 16 {
+        let (palette, phase) = jitter::apply(args, info, &args.palette);
+        let img = iterations.colorize16_with_phase(palette.as_ref(), phase);
+        return format::encode16(&img, args.format);
+    }
+    format::encode(img, args.format, args.quality)
+}
+
+fn render_animation_frame(
+    args: &RenderArgs,
+    path: &[Complex],
+    frame: u32,
+    palette: &Palette,
+) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+    let info = frame_info(args, path, frame);
+    let iterations = iterate(args, &info, args.width, args.height, info.max_iter);
+    color_frame(args, &iterations, &info, palette)
+}
+
+/// The main frame of `frame` as a run writes it: under --deflicker blended
+/// with the frames before it, which are rendered again for it.
+fn render_main_frame(
+    args: &RenderArgs,
+    path: &[Complex],
+    frame: u32,
+) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+    let mut deflicker = deflicker::Deflicker::new(args.deflicker, args.deflicker_tolerance);
+    let mut img = None;
+    for k in frame.saturating_sub(args.deflicker - 1)..=frame {
+        let info = frame_info(args, path, k);
+        let iterations = iterate(args, &info, args.width, args.height, info.max_iter);
+        let blended = deflicker.apply(
+            &iterations,
+            base_colors(args, &iterations, &info, &args.palette),
+        );
+        if k == frame {
+            img = Some(decorate(args, blended, &iterations, &info, &args.palette));
+        }
+    }
+    img.expect("the range ends at frame")
+}
+
+/// Escape-time or flame density data for one frame at the given resolution.
+/// With --refine-max-iter the buffer's `max_iter` is the limit refinement reached.
+fn iterate(
+    args: &RenderArgs,
+    info: &FrameInfo,
     width: u32,
     height: u32,
+    max_iter: u32,
+) -> IterationBuffer {
+    let backend = scheduler::backend_for(args, info);
+    let mut iterations = backend.iterate(args, info, width, height, max_iter);
+    let Some(cap) = args.refine_max_iter.filter(|_| args.quadratic_mandelbrot()) else {
+        return iterations;
+    };
+    if backend.name() == "perturbation" {
+        // Pixels past f64 precision need the longer orbit, around the same center.
+        let reference = scheduler::reference(args, info, width, height, cap);
+        let offset = perturbation::frame_offsets(
+            width,
+            height,
+            info.zoom,
+            info.rotation,
+            info.transform,
+            info.warp,
+        );
+        render::refine_interior(&mut iterations, cap, |x, y, limit| {
+            reference.escape(offset(x as f64, y as f64), limit, args.derivative_bailout)
+        });
+    } else {
+        let point = info.pixel_mapping(width, height);
+        render::refine_interior(&mut iterations, cap, |x, y, limit| {
+            render::mandelbrot_smooth(point(x, y), limit, args.derivative_bailout)
+        });
+    }
+    iterations
+}
+
+/// Where the camera is for one frame, plus the values overlays can display.
+#[derive(Debug, Clone, Copy)]
+struct FrameInfo {
+    index: u32,
+    total: u32,
+    t: f64,
     center: Complex,
     zoom: f64,
+    /// Degrees, counter-clockwise around `center`.
+    rotation: f64,
+    /// Applied to the view before `rotation`.
+    transform: render::Transform,
+    /// Applied to the view before `transform`.
+    warp: warp::Warp,
     max_iter: u32,
-) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
-    let mut img: ImageBuffer<Rgb<u8>, Vec<u8>> = ImageBuffer::new(width, height);
-    let buf = img.as_mut();
-    let w = width as usize;
-    let h = height as usize;
-    let half_min = (w.min(h) as f64) / 2.0;
-    let scale = zoom / half_min;
-
-    buf.par_chunks_mut(3)
-        .enumerate()
-        .for_each(|(idx, pixel)| {
-            let x = (idx % w) as f64;
-            let y = (idx / w) as f64;
-            let cx = (x - (w as f64 / 2.0)) * scale + center.re;
-            let cy = (y - (h as f64 / 2.0)) * scale + center.im;
-            let c = Complex { re: cx, im: cy };
-            let color = mandelbrot_color(c, max_iter);
-            pixel[0] = color[0];
-            pixel[1] = color[1];
-            pixel[2] = color[2];
-        });
+    palette_phase: f64,
+    fps: u32,
+}
 
-    img
+/// Pixels past --frame-budget are iterated with `max_iter / BUDGET_ITER_DIVISOR`.
+const BUDGET_ITER_DIVISOR: u32 = 8;
+
+/// Variables available to per-frame expressions such as --palette-phase.
+const FRAME_VARIABLES: [&str; 5] = ["t", "frame", "frames", "zoom", "time"];
+
+impl FrameInfo {
+    fn variables(&self) -> [(&'static str, f64); 5] {
+        [
+            ("t", self.t),
+            ("frame", self.index as f64),
+            ("frames", self.total as f64),
+            ("zoom", self.zoom),
+            ("time", self.index as f64 / self.fps.max(1) as f64),
+        ]
+    }
+
+    /// Maps pixel coordinates of a `width`×`height` image of this view to the plane.
+    fn pixel_mapping(&self, width: u32, height: u32) -> impl Fn(u32, u32) -> Complex + Sync {
+        let point = self.subpixel_mapping(width, height);
+        move |x, y| point(x as f64, y as f64)
+    }
+
+    fn subpixel_mapping(&self, width: u32, height: u32) -> impl Fn(f64, f64) -> Complex + Sync {
+        render::transformed_mapping(
+            width,
+            height,
+            self.center,
+            self.zoom,
+            self.rotation,
+            self.transform,
+            self.warp,
+        )
+    }
 }
 
-fn mandelbrot_color(c: Complex, max_iter: u32) -> [u8; 3] {
-    let mut z = Complex { re: 0.0, im: 0.0 };
-    let mut iter = 0;
+fn parse_frame_expr(s: &str) -> Result<Expr, String> {
+    let expr: Expr = s.parse()?;
+    if let Some(unknown) = expr
+        .variables()
+        .into_iter()
+        .find(|v| !FRAME_VARIABLES.contains(v))
+    {
+        return Err(format!(
+            "unknown variable {unknown:?}, expected one of {}",
+            FRAME_VARIABLES.join(", ")
+        ));
+    }
+    Ok(expr)
+}
+
+fn frame_info(args: &RenderArgs, path: &[Complex], frame: u32) -> FrameInfo {
+    let total_frames = args.total_frames();
+    let (t, center, zoom) = if args.endless {
+        let (center, zoom, t) = endless::view(args, frame);
+        (t, center, zoom)
+    } else {
+        let t = if total_frames <= 1 {
+            0.0
+        } else {
+            frame as f64 / (total_frames - 1) as f64
+        };
+        let path_center = path_position(path, t);
+        let zoom = track_or(args, timeline::Property::Zoom, t, || {
+            exp_lerp(args.zoom_start, args.zoom_end, t)
+        });
+        let center = dampened_center(path[0], path_center, zoom, args.zoom_start);
+        let center = args
+            .deep_center
+            .as_ref()
+            .map_or(center, |deep| deep.approx());
+        let center = Complex {
+            re: track_or(args, timeline::Property::CenterRe, t, || center.re),
+            im: track_or(args, timeline::Property::CenterIm, t, || center.im),
+        };
+        (t, center, zoom)
+    };
+    let mut info = FrameInfo {
+        index: frame,
+        total: total_frames,
+        t,
+        center,
+        zoom,
+        rotation: track_or(args, timeline::Property::Rotation, t, || 0.0),
+        transform: timeline::TRANSFORM
+            .map(|(i, property)| track_or(args, property, t, || args.transform[i])),
+        warp: args.warp,
+        max_iter: track_or(args, timeline::Property::MaxIter, t, || {
+            auto_max_iter(args, zoom) as f64
+        })
+        .round() as u32,
+        palette_phase: 0.0,
+        fps: args.fps,
+    };
+    info.palette_phase = track_or(args, timeline::Property::PalettePhase, t, || {
+        args.palette_phase
+            .as_ref()
+            .map_or(0.0, |expr| expr.eval(&info.variables()))
+    });
+    info
+}
 
-    while iter < max_iter && z.norm_sqr() <= 4.0 {
-        z = z.mul(z).add(c);
-        iter += 1;
+/// The iteration limit for a frame at `zoom`: --max-iter, or with
+/// --auto-iter one that grows with the logarithm of the magnification.
+fn auto_max_iter(args: &RenderArgs, zoom: f64) -> u32 {
+    if !args.auto_iter {
+        return args.max_iter;
     }
+    let decades = (1.0 / zoom).log10().max(0.0);
+    (args.auto_iter_min as f64 + args.auto_iter_rate as f64 * decades)
+        .min(args.max_iter as f64)
+        .round() as u32
+}
 
-    if iter >= max_iter {
-        return [0, 0, 0];
+/// Degrees a rotation track turns the view over the frame, from halfway
+/// since the previous frame to halfway to the next; 0 without one.
+fn rotation_sweep(args: &RenderArgs, info: &FrameInfo) -> f64 {
+    let Some(track) = timeline::find(&args.track, timeline::Property::Rotation) else {
+        return 0.0;
+    };
+    if info.total <= 1 {
+        return 0.0;
     }
+    let half = 0.5 / (info.total - 1) as f64;
+    track.value(info.t + half) - track.value(info.t - half)
+}
 
-    let zn = z.norm_sqr().sqrt();
-    let smooth = iter as f64 + 1.0 - (zn.ln().ln() / 2.0_f64.ln());
-    let t = (smooth / max_iter as f64).clamp(0.0, 1.0);
-    palette_color(t)
+/// The value of the `property` track at `t`, or `default()` without one.
+fn track_or(
+    args: &RenderArgs,
+    property: timeline::Property,
+    t: f64,
+    default: impl FnOnce() -> f64,
+) -> f64 {
+    timeline::find(&args.track, property).map_or_else(default, |track| track.value(t))
 }
 
-fn palette_color(t: f64) -> [u8; 3] {
-    let hue = (360.0 * (0.65 + 2.2 * t)) % 360.0;
-    let sat = 0.95;
-    let val = (0.25 + 0.85 * t).clamp(0.0, 1.0);
-    hsv_to_rgb(hue, sat, val)
+/// Colors one frame with `palette`, composites the extra layers over it and draws the overlay.
+fn color_frame(
+    args: &RenderArgs,
+    iterations: &IterationBuffer,
+    info: &FrameInfo,
+    palette: &Palette,
+) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+    let img = base_colors(args, iterations, info, palette);
+    decorate(args, img, iterations, info, palette)
 }
 
-fn hsv_to_rgb(h: f64, s: f64, v: f64) -> [u8; 3] {
-    let h = (h % 360.0 + 360.0) % 360.0;
-    let c = v * s;
-    let x = c * (1.0 - ((h / 60.0) % 2.0 - 1.0).abs());
-    let m = v - c;
-    let (r1, g1, b1) = match h {
-        h if h < 60.0 => (c, x, 0.0),
-        h if h < 120.0 => (x, c, 0.0),
-        h if h < 180.0 => (0.0, c, x),
-        h if h < 240.0 => (0.0, x, c),
-        h if h < 300.0 => (x, 0.0, c),
-        _ => (c, 0.0, x),
+/// The fractal itself: the iterations colored, boundary-smoothed and with
+/// the --layer images composited over them.
+fn base_colors(
+    args: &RenderArgs,
+    iterations: &IterationBuffer,
+    info: &FrameInfo,
+    palette: &Palette,
+) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+    let (palette, phase) = jitter::apply(args, info, palette);
+    let palette = palette.as_ref();
+    let (w, h) = (iterations.width, iterations.height);
+    let pixel = info.zoom / (w.min(h) as f64 / 2.0);
+    let mut img = shade_textured(args, iterations, info, &args.coloring, palette, phase);
+    if args.boundary_aa > 1 && args.quadratic_mandelbrot() {
+        antialias::smooth_boundary(&mut img, iterations, args, info, palette, phase);
+    }
+    for layer in &args.layer {
+        let coloring = layer.coloring.as_ref().unwrap_or(&args.coloring);
+        let top = match layer.max_iter {
+            Some(max_iter) if max_iter != iterations.max_iter => {
+                let top = iterate(args, info, w, h, max_iter);
+                shade_textured(args, &top, info, coloring, &layer.palette, phase)
+            }
+            _ => shade_textured(args, iterations, info, coloring, &layer.palette, phase),
+        };
+        composite::composite(&mut img, &top, layer.blend, layer.opacity);
+    }
+    if let Some(texture) = args.texture.filter(|t| t.target == texture::Target::Value) {
+        texture.shade(&mut img, pixel, info.pixel_mapping(w, h));
+    }
+    img
+}
+
+/// [`shade`] with the escape times spread by a `--texture target=position`.
+fn shade_textured(
+    args: &RenderArgs,
+    iterations: &IterationBuffer,
+    info: &FrameInfo,
+    coloring: &coloring::Coloring,
+    palette: &Palette,
+    phase: f64,
+) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+    let Some(texture) = args
+        .texture
+        .filter(|t| t.target == texture::Target::Position)
+    else {
+        return shade(args, iterations, info, coloring, palette, phase);
     };
-    [
-        ((r1 + m) * 255.0).clamp(0.0, 255.0) as u8,
-        ((g1 + m) * 255.0).clamp(0.0, 255.0) as u8,
-        ((b1 + m) * 255.0).clamp(0.0, 255.0) as u8,
-    ]
+    let (w, h) = (iterations.width, iterations.height);
+    let pixel = info.zoom / (w.min(h) as f64 / 2.0);
+    let shifted = texture.shift(iterations, pixel, info.pixel_mapping(w, h));
+    shade(args, &shifted, info, coloring, palette, phase)
+}
+
+/// Grid, legend, statistics and overlay drawn over the fractal, then the
+/// --canvas bars around it.
+fn decorate(
+    args: &RenderArgs,
+    mut img: ImageBuffer<Rgb<u8>, Vec<u8>>,
+    iterations: &IterationBuffer,
+    info: &FrameInfo,
+    palette: &Palette,
+) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+    let (palette, phase) = jitter::apply(args, info, palette);
+    let palette = palette.as_ref();
+    if args.grid {
+        let center = match args.fractal {
+            Fractal::Mandelbrot
+            | Fractal::Julia
+            | Fractal::BurningShip
+            | Fractal::Newton
+            | Fractal::Buddhabrot
+            | Fractal::Formula => info.center,
+            Fractal::Flame | Fractal::Rational => Complex::ZERO,
+        };
+        grid::draw(&mut img, center, info.zoom);
+    }
+    if let Some(placement) = args.legend {
+        legend::draw(&mut img, placement, palette, phase, iterations.max_iter);
+    }
+    if args.stats_hud {
+        hud::draw(&mut img, iterations, info.zoom);
+    }
+    if let Some(overlay) = &args.overlay {
+        overlay.apply(&mut img, info);
+    }
+    match args.canvas {
+        Some(canvas) => canvas.apply(img),
+        None => img,
+    }
+}
+
+/// Colors the iterations alone: root basins for Newton, `coloring` otherwise.
+fn shade(
+    args: &RenderArgs,
+    iterations: &IterationBuffer,
+    info: &FrameInfo,
+    coloring: &coloring::Coloring,
+    palette: &Palette,
+    phase: f64,
+) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+    match args.fractal {
+        Fractal::Newton => newton::colorize(iterations, palette, phase),
+        Fractal::Buddhabrot if args.nebulabrot.is_some() => buddhabrot::colorize_bands(iterations),
+        _ => coloring.colorize(iterations, info, palette, phase),
+    }
 }
 
 fn exp_lerp(a: f64, b: f64, t: f64) -> f64 {
@@ -184,6 +2193,15 @@ fn exp_lerp(a: f64, b: f64, t: f64) -> f64 {
     a * (b / a).powf(t)
 }
 
+fn auto_frame_count(zoom_start: f64, zoom_end: f64, fps: u32, zoom_rate: f64) -> u32 {
+    if zoom_start <= 0.0 || zoom_end <= 0.0 || zoom_rate <= 0.0 {
+        return 1;
+    }
+    let doublings = (zoom_start / zoom_end).log2().abs();
+    let seconds = doublings / zoom_rate;
+    ((seconds * fps as f64).round() as u32).max(1)
+}
+
 fn dampened_center(base: Complex, target: Complex, zoom: f64, zoom_start: f64) -> Complex {
     let ratio = if zoom_start > 0.0 {
         (zoom / zoom_start).clamp(0.0, 1.0)
@@ -196,7 +2214,24 @@ fn dampened_center(base: Complex, target: Complex, zoom: f64, zoom_start: f64) -
     }
 }
 
-fn fixed_path() -> Vec<Complex> {
+/// The default camera path of `fractal`; the zoom closes in on the first point.
+fn fixed_path(fractal: Fractal) -> Vec<Complex> {
+    if fractal == Fractal::Newton {
+        // Where the basins of z^3 - 1 meet, the default polynomial.
+        return vec![Complex::ZERO];
+    }
+    if matches!(fractal, Fractal::Buddhabrot | Fractal::Formula) {
+        // The middle of the whole set; its orbits spread well beyond any deep view,
+        // and a formula's set is unknown beforehand.
+        return vec![Complex { re: -0.5, im: 0.0 }];
+    }
+    if fractal == Fractal::BurningShip {
+        // The edge of the period-3 mini ship on the antenna, left of the main ship.
+        return vec![Complex {
+            re: -1.7491580505371092,
+            im: -0.00819146728515625,
+        }];
+    }
     vec![
         Complex {
             re: -0.743643887037151,
@@ -0,0 +1,516 @@
+use crate::RenderArgs;
+use crate::output::FrameWriter;
+use crate::{output, settings, sha256};
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+pub const MANIFEST_FILE: &str = "manifest.txt";
+
+/// Settings that change how a run goes but none of the files it writes, so
+/// a rerun that differs from the last one only in these resumes its frames.
+const RUN_ONLY: [&str; 25] = [
+    "start_frame",
+    "end_frame",
+    "chunk",
+    "chunk_size",
+    "frame_order",
+    "frame_jobs",
+    "threads",
+    "nice",
+    "out_dir",
+    "fallback_dir",
+    "log_file",
+    "preview_video",
+    "preview_scale",
+    "preview_only",
+    "non_interactive",
+    "status_file",
+    "bundle",
+    "bundle_frames",
+    "stream",
+    "stream_lead",
+    "encode",
+    "encode_segment",
+    "no_resume",
+    "resume",
+    "reference_dir",
+];
+
+#[derive(Clone)]
+pub struct FrameEntry {
+    pub index: u32,
+    /// Label of the extra palette this file was colored with, if any.
+    pub variant: Option<String>,
+    /// Relative to the manifest's directory, or absolute for a file elsewhere.
+    pub file: String,
+    pub sha256: String,
+}
+
+/// A frame that looked broken after rendering, and the iteration limit it ended up with.
+#[derive(Clone)]
+pub struct AnomalyEntry {
+    pub index: u32,
+    pub max_iter: u32,
+    pub resolved: bool,
+    pub kind: String,
+}
+
+pub struct Manifest {
+    pub settings: Vec<(String, String)>,
+    pub frames: Vec<FrameEntry>,
+    pub anomalies: Vec<AnomalyEntry>,
+    /// Frames that ran over --frame-budget, with the number of degraded pixels.
+    pub degraded: Vec<(u32, usize)>,
+    /// Where the manifest is; it follows the frames to --fallback-dir.
+    dir: PathBuf,
+    /// Open for appending while a render records its frames.
+    log: Option<File>,
+}
+
+/// A frame an earlier render finished, with what its manifest said about it.
+/// The files are paths to open, not relative to any manifest.
+pub struct Finished {
+    pub files: Vec<FrameEntry>,
+    pub anomaly: Option<AnomalyEntry>,
+    pub degraded: Option<usize>,
+}
+
+impl Manifest {
+    /// Starts the manifest of a new render in the directory of `writer`: the
+    /// settings are written right away and every frame is appended as soon
+    /// as its file is in place, so the manifest on disk always lists exactly
+    /// the frames that are complete. Writes go through `writer`, pausing or
+    /// moving to --fallback-dir like the frames when the disk fills up.
+    pub fn create(
+        writer: &mut FrameWriter,
+        settings: Vec<(String, String)>,
+    ) -> Result<Manifest, String> {
+        let mut manifest = Manifest {
+            settings,
+            frames: Vec::new(),
+            anomalies: Vec::new(),
+            degraded: Vec::new(),
+            dir: writer.dir().to_path_buf(),
+            log: None,
+        };
+        writer.attempt(MANIFEST_FILE, |path| manifest.rewrite(path))?;
+        Ok(manifest)
+    }
+
+    /// The directory the manifest is in, which its relative files are in too.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// How the manifest refers to the file at `path`: relative to its
+    /// directory if it is in there, absolute otherwise.
+    pub fn relative(&self, path: &Path) -> String {
+        relative_to(&self.dir, path)
+    }
+
+    pub fn push_frame(
+        &mut self,
+        writer: &mut FrameWriter,
+        entry: FrameEntry,
+    ) -> Result<(), String> {
+        let line = frame_line(&entry);
+        self.frames.push(entry);
+        self.append(writer, &line)
+    }
+
+    pub fn push_anomaly(
+        &mut self,
+        writer: &mut FrameWriter,
+        anomaly: AnomalyEntry,
+    ) -> Result<(), String> {
+        let line = anomaly_line(&anomaly);
+        self.anomalies.push(anomaly);
+        self.append(writer, &line)
+    }
+
+    pub fn push_degraded(
+        &mut self,
+        writer: &mut FrameWriter,
+        index: u32,
+        pixels: usize,
+    ) -> Result<(), String> {
+        self.degraded.push((index, pixels));
+        self.append(writer, &format!("degraded {index} {pixels}\n"))
+    }
+
+    /// Appends `line`, already recorded in memory, to the manifest on disk.
+    /// When the frames have moved to another directory, or the disk fills
+    /// up and `writer` moves them, the whole manifest is written there
+    /// instead and appended to from then on.
+    fn append(&mut self, writer: &mut FrameWriter, line: &str) -> Result<(), String> {
+        if self.log.is_none() {
+            return Ok(());
+        }
+        writer.attempt(MANIFEST_FILE, |path| {
+            if path.parent() != Some(self.dir.as_path()) {
+                return self.rewrite(path);
+            }
+            let log = self.log.as_mut().expect("manifest open for appending");
+            let len = log.metadata()?.len();
+            log.write_all(line.as_bytes())
+                .and_then(|()| log.sync_data())
+                .inspect_err(|_| {
+                    // A partial line would run into the next one.
+                    let _ = log.set_len(len);
+                })
+        })?;
+        Ok(())
+    }
+
+    /// Writes the whole manifest to `path`, moving it there from its
+    /// directory so far, and opens it for appending.
+    fn rewrite(&mut self, path: &Path) -> io::Result<()> {
+        let dir = path.parent().unwrap_or(Path::new(""));
+        if dir != self.dir {
+            for entry in &mut self.frames {
+                entry.file = relative_to(dir, &self.dir.join(&entry.file));
+            }
+            self.dir = dir.to_path_buf();
+        }
+        let tmp = output::tmp_path(path);
+        fs::write(&tmp, self.text())
+            .and_then(|()| fs::rename(&tmp, path))
+            .inspect_err(|_| {
+                let _ = fs::remove_file(&tmp);
+            })?;
+        self.log = Some(OpenOptions::new().append(true).open(path)?);
+        Ok(())
+    }
+
+    /// The manifest file as it would be written from scratch.
+    fn text(&self) -> String {
+        let mut out = String::from("# mandelbrot-animation run manifest\n");
+        for (key, value) in &self.settings {
+            out.push_str(&format!("setting {key} {value}\n"));
+        }
+        for entry in &self.frames {
+            out.push_str(&frame_line(entry));
+        }
+        for anomaly in &self.anomalies {
+            out.push_str(&anomaly_line(anomaly));
+        }
+        for (index, pixels) in &self.degraded {
+            out.push_str(&format!("degraded {index} {pixels}\n"));
+        }
+        out
+    }
+
+    pub fn read(dir: &Path) -> Result<Manifest, String> {
+        let path = dir.join(MANIFEST_FILE);
+        let text = fs::read_to_string(&path).map_err(|e| format!("read {path:?}: {e}"))?;
+        let mut manifest = Manifest {
+            settings: Vec::new(),
+            frames: Vec::new(),
+            anomalies: Vec::new(),
+            degraded: Vec::new(),
+            dir: dir.to_path_buf(),
+            log: None,
+        };
+        // A crash while appending can leave a partial last line; it names no complete frame.
+        let complete = match text.rfind('\n') {
+            Some(end) => &text[..=end],
+            None => "",
+        };
+        for (lineno, line) in complete.lines().enumerate() {
+            let bad_line = || format!("{path:?}:{}: malformed line", lineno + 1);
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (kind, rest) = line.split_once(' ').ok_or_else(bad_line)?;
+            match kind {
+                "setting" => {
+                    let (key, value) = rest.split_once(' ').unwrap_or((rest, ""));
+                    manifest.settings.push((key.to_string(), value.to_string()));
+                }
+                "frame" | "variant" => {
+                    let mut parts = rest.splitn(if kind == "frame" { 3 } else { 4 }, ' ');
+                    let variant = if kind == "variant" {
+                        Some(parts.next().ok_or_else(bad_line)?.to_string())
+                    } else {
+                        None
+                    };
+                    let index = parts
+                        .next()
+                        .and_then(|v| v.parse().ok())
+                        .ok_or_else(bad_line)?;
+                    let sha256 = parts.next().ok_or_else(bad_line)?.to_string();
+                    let file = parts.next().ok_or_else(bad_line)?.to_string();
+                    manifest.frames.push(FrameEntry {
+                        index,
+                        variant,
+                        file,
+                        sha256,
+                    });
+                }
+                "anomaly" => {
+                    let mut parts = rest.splitn(4, ' ');
+                    let mut number = || parts.next().and_then(|v| v.parse().ok());
+                    let index = number().ok_or_else(bad_line)?;
+                    let max_iter = number().ok_or_else(bad_line)?;
+                    let resolved = parts.next().ok_or_else(bad_line)? == "resolved";
+                    let kind = parts.next().unwrap_or("").to_string();
+                    manifest.anomalies.push(AnomalyEntry {
+                        index,
+                        max_iter,
+                        resolved,
+                        kind,
+                    });
+                }
+                "degraded" => {
+                    let (index, pixels) = rest.split_once(' ').ok_or_else(bad_line)?;
+                    let index = index.parse().map_err(|_| bad_line())?;
+                    let pixels = pixels.parse().map_err(|_| bad_line())?;
+                    manifest.degraded.push((index, pixels));
+                }
+                _ => return Err(bad_line()),
+            }
+        }
+        Ok(manifest)
+    }
+
+    /// The frames the last render into `dir` finished, if it ran with the
+    /// same `settings`: those its manifest lists with every file still in
+    /// place. Listed files are complete, since each is only added once it
+    /// has its final name. A manifest that followed the frames to
+    /// `fallback` counts too. Empty without a manifest or with other settings.
+    pub fn finished(
+        dir: &Path,
+        fallback: Option<&Path>,
+        settings: &[(String, String)],
+    ) -> Result<BTreeMap<u32, Finished>, String> {
+        let mut finished = BTreeMap::new();
+        for dir in [Some(dir), fallback].into_iter().flatten() {
+            if !dir.join(MANIFEST_FILE).is_file() {
+                continue;
+            }
+            let previous = Manifest::read(dir)?;
+            if output_settings(&previous.settings) != output_settings(settings) {
+                continue;
+            }
+            let mut found = BTreeMap::new();
+            for mut entry in previous.frames {
+                entry.file = dir.join(&entry.file).display().to_string();
+                found
+                    .entry(entry.index)
+                    .or_insert_with(|| Finished {
+                        files: Vec::new(),
+                        anomaly: None,
+                        degraded: None,
+                    })
+                    .files
+                    .push(entry);
+            }
+            found.retain(|index, frame: &mut Finished| {
+                !finished.contains_key(index)
+                    && frame.files.iter().any(|e| e.variant.is_none())
+                    && frame.files.iter().all(|e| Path::new(&e.file).is_file())
+            });
+            for anomaly in previous.anomalies {
+                if let Some(frame) = found.get_mut(&anomaly.index) {
+                    frame.anomaly = Some(anomaly);
+                }
+            }
+            for (index, pixels) in previous.degraded {
+                if let Some(frame) = found.get_mut(&index) {
+                    frame.degraded = Some(pixels);
+                }
+            }
+            finished.append(&mut found);
+        }
+        Ok(finished)
+    }
+
+    pub fn render_args(&self) -> Result<RenderArgs, String> {
+        settings::to_render_args(&self.settings)
+    }
+
+    /// Whether --frame-budget cut the quality of part of a frame, which makes
+    /// it depend on timing and impossible to re-render exactly.
+    pub fn is_degraded(&self, index: u32) -> bool {
+        self.degraded.iter().any(|&(i, _)| i == index)
+    }
+
+    /// Iteration limit a frame was rendered with, if an anomaly retry changed it.
+    pub fn frame_max_iter(&self, index: u32) -> Option<u32> {
+        self.anomalies
+            .iter()
+            .find(|a| a.index == index)
+            .map(|a| a.max_iter)
+    }
+}
+
+fn frame_line(entry: &FrameEntry) -> String {
+    match &entry.variant {
+        None => format!("frame {} {} {}\n", entry.index, entry.sha256, entry.file),
+        Some(label) => format!(
+            "variant {label} {} {} {}\n",
+            entry.index, entry.sha256, entry.file
+        ),
+    }
+}
+
+fn anomaly_line(anomaly: &AnomalyEntry) -> String {
+    format!(
+        "anomaly {} {} {} {}\n",
+        anomaly.index,
+        anomaly.max_iter,
+        if anomaly.resolved {
+            "resolved"
+        } else {
+            "unresolved"
+        },
+        anomaly.kind
+    )
+}
+
+/// `path` relative to `dir` if it is in there, absolute otherwise.
+fn relative_to(dir: &Path, path: &Path) -> String {
+    match path.strip_prefix(dir) {
+        Ok(relative) => relative.display().to_string(),
+        Err(_) => std::path::absolute(path)
+            .unwrap_or_else(|_| path.to_path_buf())
+            .display()
+            .to_string(),
+    }
+}
+
+/// A digest of the settings that decide what a run writes, the same for
+/// two runs exactly when [`Manifest::finished`] would resume one from the other.
+pub fn settings_hash(settings: &[(String, String)]) -> String {
+    let text: String = output_settings(settings)
+        .iter()
+        .map(|(key, value)| format!("{key}={value}\n"))
+        .collect();
+    sha256::hex_digest(text.as_bytes())
+}
+
+/// The settings that decide what a run writes, in a fixed order.
+fn output_settings(settings: &[(String, String)]) -> Vec<&(String, String)> {
+    let mut relevant: Vec<_> = settings
+        .iter()
+        .filter(|(key, _)| !RUN_ONLY.contains(&key.as_str()))
+        .collect();
+    // A stable sort, which keeps repeated options such as --layer in order.
+    relevant.sort_by(|a, b| a.0.cmp(&b.0));
+    relevant
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Empty `out` and `fallback` directories for one test.
+    fn scratch(name: &str) -> (PathBuf, PathBuf) {
+        let root =
+            std::env::temp_dir().join(format!("mandelbrot-manifest-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        let (out, fallback) = (root.join("out"), root.join("fallback"));
+        fs::create_dir_all(&out).unwrap();
+        (out, fallback)
+    }
+
+    fn settings() -> Vec<(String, String)> {
+        vec![("max_iter".to_string(), "100".to_string())]
+    }
+
+    fn entry(index: u32, file: &str) -> FrameEntry {
+        FrameEntry {
+            index,
+            variant: None,
+            file: file.to_string(),
+            sha256: "0".repeat(64),
+        }
+    }
+
+    /// `/dev/full` fails every write with ENOSPC.
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn full_disk_moves_the_manifest_to_the_fallback_dir() {
+        let (out, fallback) = scratch("append");
+        let mut writer = FrameWriter::new(out.clone(), Some(fallback.clone()), false);
+        let mut manifest = Manifest::create(&mut writer, settings()).unwrap();
+        manifest
+            .push_frame(&mut writer, entry(0, "frame_000000.png"))
+            .unwrap();
+        manifest.log = Some(OpenOptions::new().append(true).open("/dev/full").unwrap());
+        manifest
+            .push_frame(&mut writer, entry(1, "frame_000001.png"))
+            .unwrap();
+
+        let fallback = fs::canonicalize(&fallback).unwrap();
+        assert_eq!(manifest.dir(), fallback);
+        let moved = Manifest::read(&fallback).unwrap();
+        let files: Vec<_> = moved
+            .frames
+            .iter()
+            .map(|e| PathBuf::from(&e.file))
+            .collect();
+        let out = std::path::absolute(&out).unwrap();
+        assert_eq!(
+            files,
+            [out.join("frame_000000.png"), out.join("frame_000001.png")]
+        );
+        assert_eq!(moved.settings, settings());
+        assert_eq!(Manifest::read(&out).unwrap().frames.len(), 1);
+        let _ = fs::remove_dir_all(out.parent().unwrap());
+    }
+
+    /// A run whose frames moved to --fallback-dir part way, and the rerun
+    /// that keeps them.
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn resume_keeps_the_frames_in_the_fallback_dir() {
+        let (out, fallback) = scratch("resume");
+        let mut writer = FrameWriter::new(out.clone(), Some(fallback.clone()), false);
+        let mut manifest = Manifest::create(&mut writer, settings()).unwrap();
+        std::os::unix::fs::symlink("/dev/full", out.join("frame_000001.png.tmp")).unwrap();
+        for index in 0..3 {
+            let filename = format!("frame_{index:06}.png");
+            let path = writer.write(&filename, &[index as u8]).unwrap();
+            let file = manifest.relative(&path);
+            manifest
+                .push_frame(&mut writer, entry(index, &file))
+                .unwrap();
+        }
+        assert!(out.join("frame_000000.png").is_file());
+        assert!(fallback.join("frame_000002.png").is_file());
+
+        let finished = Manifest::finished(&out, Some(&fallback), &settings()).unwrap();
+        assert_eq!(finished.keys().copied().collect::<Vec<_>>(), [0, 1, 2]);
+        let mut writer = FrameWriter::new(out.clone(), Some(fallback.clone()), false);
+        let mut resumed = Manifest::create(&mut writer, settings()).unwrap();
+        for frame in finished.values() {
+            let entry = &frame.files[0];
+            let file = resumed.relative(Path::new(&entry.file));
+            resumed
+                .push_frame(
+                    &mut writer,
+                    FrameEntry {
+                        file,
+                        ..entry.clone()
+                    },
+                )
+                .unwrap();
+        }
+        let manifest = Manifest::read(&out).unwrap();
+        for (entry, byte) in manifest.frames.iter().zip(0u8..) {
+            assert_eq!(fs::read(out.join(&entry.file)).unwrap(), [byte]);
+        }
+        assert_eq!(manifest.frames.len(), 3);
+
+        let other = [("max_iter".to_string(), "200".to_string())];
+        assert!(
+            Manifest::finished(&out, Some(&fallback), &other)
+                .unwrap()
+                .is_empty()
+        );
+        let _ = fs::remove_dir_all(out.parent().unwrap());
+    }
+}
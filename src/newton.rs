@@ -0,0 +1,165 @@
+use crate::anomaly::NAN_COLOR;
+use crate::palette::Palette;
+use crate::rational::{self, Poly};
+use crate::render::{Complex, INTERIOR, IterationBuffer};
+use image::{ImageBuffer, Rgb};
+use rayon::prelude::*;
+
+/// A point has converged once it is this close to a root.
+const TOLERANCE: f64 = 1e-6;
+/// Convergence speed is stored as a fraction of `max_iter` below this, so
+/// it never spills into the next basin index.
+const MAX_FRACTION: f64 = 0.999_999;
+/// Iterations past the frame's fastest point at which a basin is drawn at
+/// half brightness.
+const HALF_BRIGHTNESS_ITER: f64 = 8.0;
+/// Palette step between the colors of consecutive roots; the golden ratio
+/// keeps them apart for any number of roots.
+const BASIN_STEP: f64 = 0.618_033_988_749_895;
+
+/// Newton's method on a polynomial whose coefficients may use `c`.
+///
+/// Each pixel's value is `basin + speed`: the index of the root it converged
+/// to plus the smooth iteration count it took over `max_iter`. Points that
+/// haven't settled on a root after `max_iter` steps are interior.
+pub struct Newton<'a> {
+    pub poly: &'a Poly,
+    pub c: Complex,
+}
+
+impl Newton<'_> {
+    pub fn iterate(
+        &self,
+        width: u32,
+        height: u32,
+        max_iter: u32,
+        point: impl Fn(u32, u32) -> Complex + Sync,
+    ) -> IterationBuffer {
+        let coefs = self.poly.at_c(self.c);
+        let derivative: Vec<Complex> = coefs
+            .iter()
+            .enumerate()
+            .skip(1)
+            .map(|(k, a)| {
+                a.mul(Complex {
+                    re: k as f64,
+                    im: 0.0,
+                })
+            })
+            .collect();
+        let roots = roots(&coefs);
+        let log_tolerance = TOLERANCE.ln();
+
+        IterationBuffer::from_fn(width, height, max_iter, |x, y| {
+            let mut z = point(x, y);
+            let mut last = f64::MAX;
+            for iter in 0..max_iter {
+                if let Some((basin, distance)) = roots
+                    .iter()
+                    .map(|r| z.sub(*r).norm_sqr().sqrt())
+                    .enumerate()
+                    .find(|(_, d)| *d < TOLERANCE)
+                {
+                    // Where between the last two steps the distance crossed the tolerance.
+                    let fraction = if iter == 0 || distance <= 0.0 {
+                        0.0
+                    } else {
+                        ((last.ln() - log_tolerance) / (last.ln() - distance.ln())).clamp(0.0, 1.0)
+                    };
+                    let speed = (iter as f64 - 1.0 + fraction).max(0.0) / max_iter as f64;
+                    return basin as f64 + speed.min(MAX_FRACTION);
+                }
+                let slope = rational::horner(&derivative, z);
+                if slope.norm_sqr() == 0.0 {
+                    return INTERIOR;
+                }
+                last = roots
+                    .iter()
+                    .map(|r| z.sub(*r).norm_sqr().sqrt())
+                    .fold(f64::MAX, f64::min);
+                z = z.sub(rational::horner(&coefs, z).div(slope));
+                if !z.norm_sqr().is_finite() {
+                    return INTERIOR;
+                }
+            }
+            INTERIOR
+        })
+    }
+}
+
+/// Roots of the polynomial with dense `coefs`, lowest power first, found
+/// all at once by Durand–Kerner iteration.
+pub fn roots(coefs: &[Complex]) -> Vec<Complex> {
+    let Some(degree) = coefs.iter().rposition(|k| k.norm_sqr() > 0.0) else {
+        return Vec::new();
+    };
+    let monic: Vec<Complex> = coefs[..=degree]
+        .iter()
+        .map(|k| k.div(coefs[degree]))
+        .collect();
+    let seed = Complex { re: 0.4, im: 0.9 };
+    let mut roots = Vec::with_capacity(degree);
+    let mut guess = Complex { re: 1.0, im: 0.0 };
+    for _ in 0..degree {
+        roots.push(guess);
+        guess = guess.mul(seed);
+    }
+    for _ in 0..1000 {
+        let mut moved: f64 = 0.0;
+        for i in 0..degree {
+            let others = (0..degree)
+                .filter(|j| *j != i)
+                .fold(Complex { re: 1.0, im: 0.0 }, |acc, j| {
+                    acc.mul(roots[i].sub(roots[j]))
+                });
+            let step = rational::horner(&monic, roots[i]).div(others);
+            if step.norm_sqr().is_finite() {
+                roots[i] = roots[i].sub(step);
+                moved = moved.max(step.norm_sqr());
+            }
+        }
+        if moved < 1e-30 {
+            break;
+        }
+    }
+    roots
+}
+
+/// Colors every basin with its own palette color, darker the longer its
+/// points took to converge compared to the fastest point of the frame, so
+/// deep frames whose points all need many steps stay visible. Points that
+/// never converged are black.
+pub fn colorize(
+    iterations: &IterationBuffer,
+    palette: &Palette,
+    phase: f64,
+) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+    let max_iter = iterations.max_iter as f64;
+    let steps = |value: f64| value.fract() * max_iter;
+    let fastest = iterations
+        .values
+        .par_iter()
+        .filter(|v| **v >= 0.0)
+        .map(|v| steps(*v))
+        .reduce(|| f64::MAX, f64::min);
+    let mut img: ImageBuffer<Rgb<u8>, Vec<u8>> =
+        ImageBuffer::new(iterations.width, iterations.height);
+    img.as_mut()
+        .par_chunks_mut(3)
+        .zip(iterations.values.par_iter())
+        .for_each(|(pixel, &value)| {
+            let color = if value.is_nan() {
+                NAN_COLOR
+            } else if value < 0.0 {
+                [0, 0, 0]
+            } else {
+                let t = (value.floor() * BASIN_STEP + phase).rem_euclid(1.0);
+                let brightness = 0.5f64.powf((steps(value) - fastest) / HALF_BRIGHTNESS_ITER);
+                palette
+                    .color(t)
+                    .map(|c| (c as f64 * brightness).round() as u8)
+            };
+            pixel.copy_from_slice(&color);
+        });
+    img
+}
@@ -0,0 +1,223 @@
+use std::fmt::Debug;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+use std::str::FromStr;
+
+/// A real number type the iteration kernels can be written against, so the
+/// same kernel runs in single, double or double-double precision.
+pub trait Real:
+    Copy
+    + Debug
+    + Send
+    + Sync
+    + PartialOrd
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+{
+    const ZERO: Self;
+    const ONE: Self;
+
+    fn from_f64(value: f64) -> Self;
+    fn to_f64(self) -> f64;
+}
+
+impl Real for f32 {
+    const ZERO: f32 = 0.0;
+    const ONE: f32 = 1.0;
+
+    fn from_f64(value: f64) -> f32 {
+        value as f32
+    }
+
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+}
+
+impl Real for f64 {
+    const ZERO: f64 = 0.0;
+    const ONE: f64 = 1.0;
+
+    fn from_f64(value: f64) -> f64 {
+        value
+    }
+
+    fn to_f64(self) -> f64 {
+        self
+    }
+}
+
+/// `--precision`: the float type the per-pixel Mandelbrot kernels iterate in.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Precision {
+    /// Twice as many points per vector instruction, for previews of shallow zooms.
+    F32,
+    #[default]
+    F64,
+}
+
+impl FromStr for Precision {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Precision, String> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "f32" => Ok(Precision::F32),
+            "f64" => Ok(Precision::F64),
+            _ => Err(format!("unknown precision {s:?}, expected f32 or f64")),
+        }
+    }
+}
+
+/// An unevaluated sum `hi + lo` of two f64 with `|lo| <= ulp(hi) / 2`,
+/// giving about 106 bits of mantissa.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct DoubleDouble {
+    hi: f64,
+    lo: f64,
+}
+
+impl DoubleDouble {
+    fn renormalized(hi: f64, lo: f64) -> DoubleDouble {
+        let s = hi + lo;
+        DoubleDouble {
+            hi: s,
+            lo: lo - (s - hi),
+        }
+    }
+}
+
+/// Exact sum of two f64 as a rounded value and its error.
+fn two_sum(a: f64, b: f64) -> (f64, f64) {
+    let s = a + b;
+    let v = s - a;
+    (s, (a - (s - v)) + (b - v))
+}
+
+/// Exact product of two f64 as a rounded value and its error.
+fn two_prod(a: f64, b: f64) -> (f64, f64) {
+    let p = a * b;
+    (p, a.mul_add(b, -p))
+}
+
+impl Add for DoubleDouble {
+    type Output = DoubleDouble;
+
+    fn add(self, other: DoubleDouble) -> DoubleDouble {
+        let (s, e) = two_sum(self.hi, other.hi);
+        let (t, f) = two_sum(self.lo, other.lo);
+        let hi_lo = DoubleDouble::renormalized(s, e + t);
+        DoubleDouble::renormalized(hi_lo.hi, hi_lo.lo + f)
+    }
+}
+
+impl Neg for DoubleDouble {
+    type Output = DoubleDouble;
+
+    fn neg(self) -> DoubleDouble {
+        DoubleDouble {
+            hi: -self.hi,
+            lo: -self.lo,
+        }
+    }
+}
+
+impl Sub for DoubleDouble {
+    type Output = DoubleDouble;
+
+    fn sub(self, other: DoubleDouble) -> DoubleDouble {
+        self + -other
+    }
+}
+
+impl Mul for DoubleDouble {
+    type Output = DoubleDouble;
+
+    fn mul(self, other: DoubleDouble) -> DoubleDouble {
+        let (p, e) = two_prod(self.hi, other.hi);
+        DoubleDouble::renormalized(p, e + (self.hi * other.lo + self.lo * other.hi))
+    }
+}
+
+impl Div for DoubleDouble {
+    type Output = DoubleDouble;
+
+    fn div(self, other: DoubleDouble) -> DoubleDouble {
+        // Long division by the leading part, refined once on the remainder.
+        let q1 = self.hi / other.hi;
+        let r = self - other * DoubleDouble::from_f64(q1);
+        let q2 = r.hi / other.hi;
+        let r = r - other * DoubleDouble::from_f64(q2);
+        let q3 = r.hi / other.hi;
+        DoubleDouble::renormalized(q1, q2) + DoubleDouble::from_f64(q3)
+    }
+}
+
+impl Real for DoubleDouble {
+    const ZERO: DoubleDouble = DoubleDouble { hi: 0.0, lo: 0.0 };
+    const ONE: DoubleDouble = DoubleDouble { hi: 1.0, lo: 0.0 };
+
+    fn from_f64(value: f64) -> DoubleDouble {
+        DoubleDouble { hi: value, lo: 0.0 }
+    }
+
+    fn to_f64(self) -> f64 {
+        self.hi + self.lo
+    }
+}
+
+/// A complex number over any [`Real`]; `render::Complex` is the f64 one.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ComplexNum<R> {
+    pub re: R,
+    pub im: R,
+}
+
+impl<R: Real> ComplexNum<R> {
+    pub const ZERO: ComplexNum<R> = ComplexNum {
+        re: R::ZERO,
+        im: R::ZERO,
+    };
+
+    pub fn add(self, other: ComplexNum<R>) -> ComplexNum<R> {
+        ComplexNum {
+            re: self.re + other.re,
+            im: self.im + other.im,
+        }
+    }
+
+    pub fn sub(self, other: ComplexNum<R>) -> ComplexNum<R> {
+        ComplexNum {
+            re: self.re - other.re,
+            im: self.im - other.im,
+        }
+    }
+
+    pub fn mul(self, other: ComplexNum<R>) -> ComplexNum<R> {
+        ComplexNum {
+            re: self.re * other.re - self.im * other.im,
+            im: self.re * other.im + self.im * other.re,
+        }
+    }
+
+    pub fn div(self, other: ComplexNum<R>) -> ComplexNum<R> {
+        let denom = other.norm_sqr();
+        ComplexNum {
+            re: (self.re * other.re + self.im * other.im) / denom,
+            im: (self.im * other.re - self.re * other.im) / denom,
+        }
+    }
+
+    pub fn norm_sqr(self) -> R {
+        self.re * self.re + self.im * self.im
+    }
+
+    /// The same number in another precision, rounded through f64.
+    pub fn cast<S: Real>(self) -> ComplexNum<S> {
+        ComplexNum {
+            re: S::from_f64(self.re.to_f64()),
+            im: S::from_f64(self.im.to_f64()),
+        }
+    }
+}
@@ -0,0 +1,108 @@
+use crate::render::Complex;
+use crate::{output, sha256};
+use flate2::Compression;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+const MAGIC: &[u8; 4] = b"MBRO";
+const FORMAT: &str = "mandelbrot-orbit-1";
+
+/// Reference orbits of perturbation frames, stored under a hash of the
+/// center and the precision they were computed in, so re-renders and farm
+/// shards of the same deep zoom load them instead of computing them again.
+/// An orbit serves every frame around its center that needs at most as many
+/// iterations, since a shorter one is the same points cut off earlier.
+pub struct OrbitStore {
+    dir: PathBuf,
+}
+
+impl OrbitStore {
+    pub fn new(dir: &Path) -> OrbitStore {
+        OrbitStore {
+            dir: dir.to_path_buf(),
+        }
+    }
+
+    /// `center` is the exact center as text, the decimals of --deep-center
+    /// or the bits of an f64 center.
+    fn path(&self, center: &str, limbs: usize) -> PathBuf {
+        let key = format!("{FORMAT}\ncenter={center}\nlimbs={limbs}\n");
+        let hash = sha256::hex_digest(key.as_bytes());
+        self.dir.join(format!("{hash}.orbit"))
+    }
+
+    /// The stored orbit of `center`, cut to `max_iter` steps, if it runs that
+    /// long or escapes sooner. Unreadable entries are treated as missing and
+    /// get computed again.
+    pub fn load(&self, center: &str, limbs: usize, max_iter: u32) -> Option<Vec<Complex>> {
+        let mut orbit = decode(&fs::read(self.path(center, limbs)).ok()?)?;
+        let steps = max_iter.max(1) as usize;
+        let escaped = orbit.last().is_some_and(|z| z.norm_sqr() > 4.0);
+        if orbit.len() <= steps && !escaped {
+            return None;
+        }
+        orbit.truncate(steps + 1);
+        Some(orbit)
+    }
+
+    /// Stores `orbit` unless one at least as long is there already.
+    pub fn store(&self, center: &str, limbs: usize, orbit: &[Complex]) -> Result<(), String> {
+        let path = self.path(center, limbs);
+        if let Ok(bytes) = fs::read(&path)
+            && decode(&bytes).is_some_and(|stored| stored.len() >= orbit.len())
+        {
+            return Ok(());
+        }
+        fs::create_dir_all(&self.dir)
+            .map_err(|e| output::describe_error("create", &self.dir, &e))?;
+        let tmp = output::tmp_path(&path);
+        fs::write(&tmp, encode(orbit))
+            .and_then(|()| fs::rename(&tmp, &path))
+            .map_err(|e| {
+                let _ = fs::remove_file(&tmp);
+                output::describe_error("save", &path, &e)
+            })
+    }
+}
+
+/// Header and deflated little-endian f64 pairs, the orbit exactly as
+/// computed so a loaded one renders the same pixels.
+fn encode(orbit: &[Complex]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&(orbit.len() as u32).to_le_bytes());
+    let mut encoder = DeflateEncoder::new(bytes, Compression::fast());
+    for z in orbit {
+        encoder
+            .write_all(&z.re.to_le_bytes())
+            .and_then(|()| encoder.write_all(&z.im.to_le_bytes()))
+            .expect("writing to a Vec cannot fail");
+    }
+    encoder.finish().expect("writing to a Vec cannot fail")
+}
+
+fn decode(bytes: &[u8]) -> Option<Vec<Complex>> {
+    if bytes.len() < 8 || &bytes[..4] != MAGIC {
+        return None;
+    }
+    let len = u32::from_le_bytes(bytes[4..8].try_into().unwrap()) as usize;
+    let mut raw = Vec::new();
+    DeflateDecoder::new(&bytes[8..])
+        .read_to_end(&mut raw)
+        .ok()?;
+    if len == 0 || raw.len() != len * 16 {
+        return None;
+    }
+    let value = |chunk: &[u8]| f64::from_le_bytes(chunk.try_into().unwrap());
+    Some(
+        raw.chunks_exact(16)
+            .map(|chunk| Complex {
+                re: value(&chunk[..8]),
+                im: value(&chunk[8..]),
+            })
+            .collect(),
+    )
+}
@@ -0,0 +1,403 @@
+use crate::runlog;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+const TRANSIENT_RETRIES: u32 = 3;
+
+pub struct FrameWriter {
+    dir: PathBuf,
+    fallback_dir: Option<PathBuf>,
+    /// Whether a full disk may be resolved by asking on the terminal.
+    interactive: bool,
+}
+
+impl FrameWriter {
+    pub fn new(dir: PathBuf, fallback_dir: Option<PathBuf>, interactive: bool) -> FrameWriter {
+        FrameWriter {
+            dir,
+            fallback_dir,
+            interactive,
+        }
+    }
+
+    /// The directory frames go to, --out-dir until the disk fills up.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Writes a frame, pausing or switching directories when the disk fills up.
+    /// The data goes to a temporary name first and is renamed into place once
+    /// it is on disk, so a crash never leaves a truncated frame behind.
+    /// Returns the path the frame was actually written to.
+    pub fn write(&mut self, filename: &str, bytes: &[u8]) -> Result<PathBuf, String> {
+        self.attempt(filename, |filepath| {
+            let tmp = tmp_path(filepath);
+            let written = match filepath.parent() {
+                Some(parent) => fs::create_dir_all(parent),
+                None => Ok(()),
+            }
+            .and_then(|()| write_synced(&tmp, bytes))
+            .and_then(|()| fs::rename(&tmp, filepath));
+            if written.is_err() {
+                let _ = fs::remove_file(&tmp);
+            }
+            written
+        })
+    }
+
+    /// Runs `write` on `filename` in the current directory until it works,
+    /// retrying transient errors and pausing or switching directories when
+    /// the disk fills up, so `write` may see a path in another directory
+    /// than the last time. A failed `write` must leave no partial data.
+    /// Returns the path of the successful write.
+    pub fn attempt(
+        &mut self,
+        filename: &str,
+        mut write: impl FnMut(&Path) -> io::Result<()>,
+    ) -> Result<PathBuf, String> {
+        let mut attempts = 0;
+        loop {
+            let filepath = self.dir.join(filename);
+            let err = match write(&filepath) {
+                Ok(()) => return Ok(filepath),
+                Err(e) => e,
+            };
+            if is_transient(&err) && attempts < TRANSIENT_RETRIES {
+                attempts += 1;
+                runlog::warn(
+                    "transient_write_error",
+                    &[
+                        ("file", filepath.display().to_string().into()),
+                        ("error", err.to_string().into()),
+                        ("attempt", attempts.into()),
+                    ],
+                );
+                eprintln!(
+                    "retrying {} after transient error: {err}",
+                    filepath.display()
+                );
+                thread::sleep(Duration::from_secs(1 << attempts));
+                continue;
+            }
+            if !is_disk_full(&err) {
+                return Err(describe_error("save", &filepath, &err));
+            }
+            runlog::warn(
+                "disk_full",
+                &[
+                    ("file", filepath.display().to_string().into()),
+                    ("error", err.to_string().into()),
+                ],
+            );
+
+            if let Some(fallback) = self.fallback_dir.take() {
+                eprintln!(
+                    "disk full while writing {}; switching output to {}",
+                    filepath.display(),
+                    fallback.display()
+                );
+                self.switch_dir(&fallback)?;
+                continue;
+            }
+            self.prompt_disk_full(&filepath, &err)?;
+        }
+    }
+
+    fn switch_dir(&mut self, dir: &Path) -> Result<(), String> {
+        let dir = resolve_dir(dir)?;
+        fs::create_dir_all(&dir).map_err(|e| describe_error("create", &dir, &e))?;
+        self.dir = fs::canonicalize(&dir).map_err(|e| describe_error("resolve", &dir, &e))?;
+        runlog::warn(
+            "output_dir_switched",
+            &[("dir", self.dir.display().to_string().into())],
+        );
+        Ok(())
+    }
+
+    fn prompt_disk_full(&mut self, filepath: &Path, err: &io::Error) -> Result<(), String> {
+        let stdin = io::stdin();
+        if !self.interactive || !stdin.is_terminal() {
+            return Err(describe_error("save", filepath, err));
+        }
+        eprintln!("disk full while writing {}: {err}", filepath.display());
+        eprint!(
+            "free some space and press Enter to retry, type a directory to continue there, or 'q' to abort: "
+        );
+        let _ = io::stderr().flush();
+
+        let mut line = String::new();
+        stdin
+            .lock()
+            .read_line(&mut line)
+            .map_err(|e| format!("read stdin: {e}"))?;
+        match line.trim() {
+            "" => Ok(()),
+            "q" => Err(format!("aborted: disk full while writing {filepath:?}")),
+            dir => self.switch_dir(Path::new(dir)),
+        }
+    }
+}
+
+/// `frame_000001.png` -> `frame_000001.png.tmp`
+pub fn tmp_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".tmp");
+    PathBuf::from(name)
+}
+
+fn write_synced(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let mut file = fs::File::create(path)?;
+    file.write_all(bytes)?;
+    file.sync_all()
+}
+
+fn is_disk_full(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::StorageFull | io::ErrorKind::QuotaExceeded
+    )
+}
+
+fn is_transient(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::Interrupted
+            | io::ErrorKind::TimedOut
+            | io::ErrorKind::ResourceBusy
+            | io::ErrorKind::NetworkDown
+            | io::ErrorKind::StaleNetworkFileHandle
+    )
+}
+
+pub fn describe_error(action: &str, path: &Path, err: &io::Error) -> String {
+    let mut msg = format!("{action} {}: {err} ({:?})", path.display(), err.kind());
+    if err.kind() == io::ErrorKind::InvalidFilename {
+        msg.push_str("; the path is too long or contains characters the filesystem rejects");
+    }
+    msg
+}
+
+/// Normalizes an output directory so later joins keep working on every platform.
+#[cfg(not(windows))]
+pub fn resolve_dir(dir: &Path) -> Result<PathBuf, String> {
+    Ok(dir.to_path_buf())
+}
+
+/// Normalizes an output directory to an absolute verbatim (`\\?\`) path so
+/// frame paths are not limited to MAX_PATH, including on UNC shares.
+#[cfg(windows)]
+pub fn resolve_dir(dir: &Path) -> Result<PathBuf, String> {
+    check_windows_names(&dir.to_string_lossy())?;
+    let absolute = std::path::absolute(dir).map_err(|e| describe_error("resolve", dir, &e))?;
+    Ok(PathBuf::from(to_verbatim(&absolute.to_string_lossy())))
+}
+
+/// Where a Windows path starts from. The paths are handled as text so the
+/// rules can be checked on any platform.
+#[derive(Debug, PartialEq)]
+enum Root<'a> {
+    /// `C:`
+    Disk(char),
+    /// `\\server\share`
+    Unc(&'a str, &'a str),
+    /// `\\?\` or `\\.\`, which Windows takes as it is.
+    Verbatim,
+    Relative,
+}
+
+/// The root of a Windows path and the rest of it after the root.
+fn split_root(path: &str) -> (Root<'_>, &str) {
+    if path.starts_with(r"\\?\") || path.starts_with(r"\\.\") {
+        return (Root::Verbatim, path);
+    }
+    let bytes = path.as_bytes();
+    if bytes.len() >= 2 && bytes[1] == b':' && bytes[0].is_ascii_alphabetic() {
+        return (
+            Root::Disk(bytes[0].to_ascii_uppercase() as char),
+            &path[2..],
+        );
+    }
+    if let Some(rest) = path.strip_prefix(r"\\").or_else(|| path.strip_prefix("//")) {
+        let mut parts = rest.splitn(3, ['\\', '/']);
+        if let (Some(server), Some(share)) = (parts.next(), parts.next())
+            && !server.is_empty()
+            && !share.is_empty()
+        {
+            return (Root::Unc(server, share), parts.next().unwrap_or(""));
+        }
+    }
+    (Root::Relative, path)
+}
+
+/// The named components of the part of a path after its root.
+fn names(rest: &str) -> impl Iterator<Item = &str> {
+    rest.split(['\\', '/'])
+        .filter(|name| !name.is_empty() && *name != ".")
+}
+
+/// `C:\out\frames` -> `\\?\C:\out\frames` and `\\nas\share\out` ->
+/// `\\?\UNC\nas\share\out` for an absolute path, with `/` turned into `\`
+/// and `..` resolved, which verbatim paths don't do themselves. Other paths
+/// come back unchanged.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn to_verbatim(path: &str) -> String {
+    let (root, rest) = split_root(path);
+    let mut out = match root {
+        Root::Disk(drive) => format!(r"\\?\{drive}:"),
+        Root::Unc(server, share) => format!(r"\\?\UNC\{server}\{share}"),
+        Root::Verbatim | Root::Relative => return path.to_string(),
+    };
+    let mut kept: Vec<&str> = Vec::new();
+    for name in names(rest) {
+        if name == ".." {
+            kept.pop();
+        } else {
+            kept.push(name);
+        }
+    }
+    for name in kept {
+        out.push('\\');
+        out.push_str(name);
+    }
+    out
+}
+
+/// Rejects paths with a component Windows can't create: a reserved device
+/// name, a trailing dot or space, or a character it doesn't allow in names.
+/// Verbatim paths bypass these rules in Windows, so they pass as they are.
+#[cfg_attr(not(windows), allow(dead_code))]
+fn check_windows_names(path: &str) -> Result<(), String> {
+    let rest = match split_root(path) {
+        (Root::Verbatim, _) => return Ok(()),
+        (_, rest) => rest,
+    };
+    for name in names(rest).filter(|name| *name != "..") {
+        let stem = name
+            .split('.')
+            .next()
+            .unwrap_or("")
+            .trim_end()
+            .to_ascii_uppercase();
+        let numbered = (stem.starts_with("COM") || stem.starts_with("LPT"))
+            && stem.len() == 4
+            && matches!(stem.as_bytes()[3], b'1'..=b'9');
+        if numbered || matches!(stem.as_str(), "CON" | "PRN" | "AUX" | "NUL") {
+            return Err(format!(
+                "output path {path} uses the reserved Windows device name {name:?}"
+            ));
+        }
+        if name.ends_with('.') || name.ends_with(' ') {
+            return Err(format!(
+                "output path {path} has a component ending in a dot or space: {name:?}"
+            ));
+        }
+        if let Some(c) = name
+            .chars()
+            .find(|c| r#"<>:"|?*"#.contains(*c) || c.is_control())
+        {
+            return Err(format!(
+                "output path {path} contains the character {c:?}, which Windows does not allow"
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reserved_device_names() {
+        for path in [
+            r"out\CON",
+            r"C:\frames\NUL.txt",
+            "out/com1",
+            r"\\nas\share\LPT9.png",
+        ] {
+            let err = check_windows_names(path).unwrap_err();
+            assert!(
+                err.contains("reserved Windows device name"),
+                "{path}: {err}"
+            );
+        }
+        for path in [
+            r"out\CONSOLE",
+            r"C:\frames\COM0",
+            "out/nul2/LPT",
+            r"C:\..\COM",
+        ] {
+            assert_eq!(check_windows_names(path), Ok(()), "{path}");
+        }
+    }
+
+    #[test]
+    fn trailing_dots_and_spaces() {
+        for path in [r"out\frames.", r"C:\render \frames", "out/a./b"] {
+            let err = check_windows_names(path).unwrap_err();
+            assert!(err.contains("ending in a dot or space"), "{path}: {err}");
+        }
+        for path in [r"out\.\frames", r"C:\out\..\frames", r"\\?\C:\frames."] {
+            assert_eq!(check_windows_names(path), Ok(()), "{path}");
+        }
+    }
+
+    #[test]
+    fn forbidden_characters() {
+        let err = check_windows_names(r"C:\out\a:b").unwrap_err();
+        assert!(err.contains("':'"), "{err}");
+        assert!(check_windows_names("out/what?").is_err());
+        assert_eq!(check_windows_names(r"C:\out\frames"), Ok(()));
+    }
+
+    #[test]
+    fn verbatim_disk_paths() {
+        let long = format!(r"C:\render\{}\frames", "x".repeat(300));
+        assert_eq!(to_verbatim(&long), format!(r"\\?\{long}"));
+        assert_eq!(to_verbatim(r"c:\out/frames\"), r"\\?\C:\out\frames");
+        assert_eq!(to_verbatim(r"C:\out\..\frames\.\a"), r"\\?\C:\frames\a");
+    }
+
+    #[test]
+    fn verbatim_unc_paths() {
+        assert_eq!(
+            to_verbatim(r"\\nas\renders\job\frames"),
+            r"\\?\UNC\nas\renders\job\frames"
+        );
+        assert_eq!(to_verbatim("//nas/renders/job"), r"\\?\UNC\nas\renders\job");
+        assert_eq!(to_verbatim(r"\\nas\renders"), r"\\?\UNC\nas\renders");
+    }
+
+    #[test]
+    fn other_paths_stay_as_they_are() {
+        for path in [
+            r"\\?\C:\out",
+            r"\\?\UNC\nas\share",
+            r"\\.\pipe\x",
+            r"out\frames",
+            r"\\nas",
+        ] {
+            assert_eq!(to_verbatim(path), path);
+        }
+    }
+
+    #[test]
+    fn describe_error_messages() {
+        let err = io::Error::from(io::ErrorKind::NotFound);
+        let msg = describe_error("save", Path::new("out/frame_000001.png"), &err);
+        assert!(msg.starts_with("save out/frame_000001.png: "), "{msg}");
+        assert!(msg.ends_with("(NotFound)"), "{msg}");
+        assert!(!msg.contains("too long"));
+
+        let err = io::Error::from(io::ErrorKind::InvalidFilename);
+        let msg = describe_error("create", Path::new("out"), &err);
+        assert!(msg.starts_with("create out: "), "{msg}");
+        assert!(
+            msg.ends_with("(InvalidFilename); the path is too long or contains characters the filesystem rejects"),
+            "{msg}"
+        );
+    }
+}
@@ -0,0 +1,342 @@
+use crate::FrameInfo;
+use crate::composite::BlendMode;
+use crate::draw::{self, Paint};
+use crate::font;
+use crate::strings;
+use image::{ImageBuffer, Rgb};
+use std::fs;
+
+/// Declarative per-frame overlay loaded from a template file.
+///
+/// Each non-empty line is one item; `#` starts a comment:
+///
+/// ```text
+/// rect x=16 y=16 w=420 h=64 color=#00000080
+/// text x=28 y=28 size=3 color=#ffffff "zoom {zoom}  frame {frame}/{frames}"
+/// text x=-28 y=-40 size=2 align=right "{elapsed}"
+/// text x=28 y=-40 size=2 "{text:magnification} {magnification}"
+/// line x1=0 y1=-4 x2=-1 y2=-4 width=2 color=#ffffff40
+/// ```
+///
+/// Negative coordinates count from the right/bottom edge; `blend=multiply` (or
+/// `screen`, `overlay`, `add`) changes how an item combines with the frame.
+/// `{text:key}` is the `key` entry of `--strings`, or `key` itself without one,
+/// so one template serves every language.
+#[derive(Debug, Clone)]
+pub struct Overlay {
+    items: Vec<Item>,
+}
+
+#[derive(Debug, Clone)]
+enum Item {
+    Text {
+        x: i64,
+        y: i64,
+        size: u32,
+        align: Align,
+        paint: Paint,
+        template: Vec<Segment>,
+    },
+    Rect {
+        x: i64,
+        y: i64,
+        w: u32,
+        h: u32,
+        paint: Paint,
+    },
+    Line {
+        from: (i64, i64),
+        to: (i64, i64),
+        width: u32,
+        paint: Paint,
+    },
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Align {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Debug, Clone)]
+enum Segment {
+    Literal(String),
+    Var(Var),
+}
+
+#[derive(Debug, Clone)]
+enum Var {
+    Frame,
+    Frames,
+    T,
+    Zoom,
+    Magnification,
+    Center,
+    Re,
+    Im,
+    Elapsed,
+    Fps,
+    Text(String),
+}
+
+impl Overlay {
+    pub fn load(path: &str) -> Result<Overlay, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("read {path:?}: {e}"))?;
+        Overlay::parse(&text).map_err(|e| format!("{path}: {e}"))
+    }
+
+    pub fn parse(text: &str) -> Result<Overlay, String> {
+        let mut items = Vec::new();
+        for (lineno, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let item = parse_item(line).map_err(|e| format!("line {}: {e}", lineno + 1))?;
+            items.push(item);
+        }
+        Ok(Overlay { items })
+    }
+
+    pub fn apply(&self, img: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, info: &FrameInfo) {
+        let w = img.width() as i64;
+        let h = img.height() as i64;
+        let resolve = |v: i64, size: i64| if v < 0 { size + v } else { v };
+        for item in &self.items {
+            match item {
+                Item::Text {
+                    x,
+                    y,
+                    size,
+                    align,
+                    paint,
+                    template,
+                } => {
+                    let text = expand(template, info);
+                    let text_w = font::text_width(&text, *size) as i64;
+                    let x = resolve(*x, w);
+                    let x = match align {
+                        Align::Left => x,
+                        Align::Center => x - text_w / 2,
+                        Align::Right => x - text_w,
+                    };
+                    font::draw_text(img, x, resolve(*y, h), *size, *paint, &text);
+                }
+                Item::Rect {
+                    x,
+                    y,
+                    w: rw,
+                    h: rh,
+                    paint,
+                } => draw::fill_rect(img, resolve(*x, w), resolve(*y, h), *rw, *rh, *paint),
+                Item::Line {
+                    from,
+                    to,
+                    width,
+                    paint,
+                } => draw::draw_line(
+                    img,
+                    (resolve(from.0, w), resolve(from.1, h)),
+                    (resolve(to.0, w), resolve(to.1, h)),
+                    *width,
+                    *paint,
+                ),
+            }
+        }
+    }
+}
+
+fn parse_item(line: &str) -> Result<Item, String> {
+    let tokens = tokenize(line)?;
+    let (kind, rest) = tokens.split_first().ok_or("empty item")?;
+    let mut attrs = Vec::new();
+    let mut text = None;
+    for token in rest {
+        match token {
+            Token::Quoted(s) => text = Some(s.clone()),
+            Token::Word(word) => {
+                let (key, value) = word
+                    .split_once('=')
+                    .ok_or_else(|| format!("expected key=value, got {word:?}"))?;
+                attrs.push((key.to_string(), value.to_string()));
+            }
+        }
+    }
+    let get = |key: &str| {
+        attrs
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    };
+    let int = |key: &str, default: i64| -> Result<i64, String> {
+        get(key).map_or(Ok(default), |v| {
+            v.parse()
+                .map_err(|_| format!("{key}: invalid number {v:?}"))
+        })
+    };
+    let paint = Paint {
+        color: get("color").map_or(Ok([255, 255, 255, 255]), draw::parse_rgba)?,
+        blend: get("blend").map_or(Ok(BlendMode::Normal), str::parse)?,
+    };
+
+    match kind {
+        Token::Word(k) if k == "text" => {
+            let text = text.ok_or("text item needs a quoted string")?;
+            let align = match get("align").unwrap_or("left") {
+                "left" => Align::Left,
+                "center" => Align::Center,
+                "right" => Align::Right,
+                other => return Err(format!("unknown align {other:?}")),
+            };
+            Ok(Item::Text {
+                x: int("x", 0)?,
+                y: int("y", 0)?,
+                size: int("size", 2)?.max(1) as u32,
+                align,
+                paint,
+                template: parse_template(&text)?,
+            })
+        }
+        Token::Word(k) if k == "rect" => Ok(Item::Rect {
+            x: int("x", 0)?,
+            y: int("y", 0)?,
+            w: int("w", 0)?.max(0) as u32,
+            h: int("h", 0)?.max(0) as u32,
+            paint,
+        }),
+        Token::Word(k) if k == "line" => Ok(Item::Line {
+            from: (int("x1", 0)?, int("y1", 0)?),
+            to: (int("x2", 0)?, int("y2", 0)?),
+            width: int("width", 1)?.max(1) as u32,
+            paint,
+        }),
+        _ => Err(format!(
+            "unknown item {kind:?}, expected text, rect or line"
+        )),
+    }
+}
+
+#[derive(Debug)]
+enum Token {
+    Word(String),
+    Quoted(String),
+}
+
+fn tokenize(line: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = line.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' {
+            chars.next();
+            let mut s = String::new();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => s.extend(chars.next()),
+                    Some(c) => s.push(c),
+                    None => return Err("unterminated string".to_string()),
+                }
+            }
+            tokens.push(Token::Quoted(s));
+        } else {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() {
+                    break;
+                }
+                word.push(c);
+                chars.next();
+            }
+            tokens.push(Token::Word(word));
+        }
+    }
+    Ok(tokens)
+}
+
+fn parse_template(text: &str) -> Result<Vec<Segment>, String> {
+    let mut segments = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find('{') {
+        if start > 0 {
+            segments.push(Segment::Literal(rest[..start].to_string()));
+        }
+        let end = rest[start..]
+            .find('}')
+            .ok_or_else(|| format!("unclosed variable in {text:?}"))?;
+        let name = &rest[start + 1..start + end];
+        let var = match name {
+            "frame" => Var::Frame,
+            "frames" => Var::Frames,
+            "t" => Var::T,
+            "zoom" => Var::Zoom,
+            "magnification" => Var::Magnification,
+            "center" => Var::Center,
+            "re" => Var::Re,
+            "im" => Var::Im,
+            "elapsed" => Var::Elapsed,
+            "fps" => Var::Fps,
+            _ => match name.strip_prefix("text:") {
+                Some(key) => Var::Text(key.to_string()),
+                None => return Err(format!("unknown variable {{{name}}}")),
+            },
+        };
+        segments.push(Segment::Var(var));
+        rest = &rest[start + end + 1..];
+    }
+    if !rest.is_empty() {
+        segments.push(Segment::Literal(rest.to_string()));
+    }
+    Ok(segments)
+}
+
+fn expand(template: &[Segment], info: &FrameInfo) -> String {
+    let digits = coordinate_digits(info.zoom);
+    let mut out = String::new();
+    for segment in template {
+        match segment {
+            Segment::Literal(s) => out.push_str(s),
+            Segment::Var(var) => out.push_str(&match var {
+                Var::Frame => info.index.to_string(),
+                Var::Frames => info.total.to_string(),
+                Var::T => format!("{:.3}", info.t),
+                Var::Zoom => format!("{:.3e}", info.zoom),
+                Var::Magnification => format_magnification(1.0 / info.zoom),
+                Var::Center => {
+                    let sign = if info.center.im < 0.0 { '-' } else { '+' };
+                    format!(
+                        "{:.digits$} {sign} {:.digits$}i",
+                        info.center.re,
+                        info.center.im.abs()
+                    )
+                }
+                Var::Re => format!("{:.digits$}", info.center.re),
+                Var::Im => format!("{:.digits$}", info.center.im),
+                Var::Elapsed => format_elapsed(info.index as f64 / info.fps.max(1) as f64),
+                Var::Fps => info.fps.to_string(),
+                Var::Text(key) => strings::text(key, key, &[]),
+            }),
+        }
+    }
+    out
+}
+
+/// Enough decimal places to tell neighbouring frames apart at this zoom.
+pub fn coordinate_digits(zoom: f64) -> usize {
+    ((-zoom.log10()).ceil() as i64 + 4).clamp(4, 17) as usize
+}
+
+pub fn format_magnification(m: f64) -> String {
+    if m < 1e4 {
+        format!("{m:.1}x")
+    } else {
+        format!("{m:.2e}x")
+    }
+}
+
+fn format_elapsed(seconds: f64) -> String {
+    let minutes = (seconds / 60.0).floor();
+    format!("{:02}:{:05.2}", minutes as u64, seconds - minutes * 60.0)
+}
@@ -0,0 +1,159 @@
+use crate::render::IterationBuffer;
+use crate::runlog;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Linear resolution and share of the iteration limit of each quality
+/// level, from full quality down.
+const LEVELS: [(f64, f64); 5] = [
+    (1.0, 1.0),
+    (0.75, 0.75),
+    (0.5, 0.5),
+    (0.35, 0.35),
+    (0.25, 0.25),
+];
+/// Share of the frame interval a frame may take when [`Pacer`] considers
+/// raising the quality, so the better frames still leave some slack.
+const HEADROOM: f64 = 0.8;
+/// Frames in a row that must have room for the next level up before the
+/// quality rises, so it doesn't flip back and forth every frame.
+const CALM_FRAMES: u32 = 3;
+
+/// A quality level of [`Pacer`]; the first is full quality.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Level(usize);
+
+impl Level {
+    pub const FULL: Level = Level(0);
+
+    pub fn is_full(self) -> bool {
+        self == Level::FULL
+    }
+
+    /// Size to iterate a `width`x`height` frame at.
+    pub fn size(self, width: u32, height: u32) -> (u32, u32) {
+        let scale = LEVELS[self.0].0;
+        let side = |n: u32| ((n as f64 * scale).round() as u32).max(1);
+        (side(width), side(height))
+    }
+
+    pub fn max_iter(self, max_iter: u32) -> u32 {
+        ((max_iter as f64 * LEVELS[self.0].1).round() as u32).max(1)
+    }
+
+    /// Time a frame takes at this level relative to full quality.
+    fn cost(self) -> f64 {
+        let (scale, iterations) = LEVELS[self.0];
+        scale * scale * iterations
+    }
+}
+
+/// Keeps frames coming at exactly the frame rate: each frame has a slot of
+/// one interval on a fixed clock and is handed on when its slot ends. The
+/// quality drops while frames run over their slot and comes back once
+/// they fit again; a frame whose slot is over before it starts is dropped.
+pub struct Pacer {
+    interval: Duration,
+    /// Start of the next slot, once the first one has begun.
+    next_slot: Option<Instant>,
+    started: Instant,
+    level: Level,
+    calm: u32,
+    shown: u64,
+    degraded: u64,
+    dropped: u64,
+}
+
+impl Pacer {
+    pub fn new(fps: u32) -> Pacer {
+        Pacer {
+            interval: Duration::from_secs_f64(1.0 / fps.max(1) as f64),
+            next_slot: None,
+            started: Instant::now(),
+            level: Level::FULL,
+            calm: 0,
+            shown: 0,
+            degraded: 0,
+            dropped: 0,
+        }
+    }
+
+    /// Moves to the next slot and returns the level to render its frame
+    /// at, or `None` if the slot is already over and the frame is dropped.
+    pub fn start(&mut self) -> Option<Level> {
+        let now = Instant::now();
+        let slot = *self.next_slot.get_or_insert(now);
+        self.next_slot = Some(slot + self.interval);
+        if now >= slot + self.interval {
+            self.dropped += 1;
+            return None;
+        }
+        self.started = now;
+        Some(self.level)
+    }
+
+    /// Called once the frame of the slot [`Pacer::start`] began is done:
+    /// adjusts the level by how long it took, then waits for the slot to end.
+    pub fn finish(&mut self) {
+        let took = self.started.elapsed();
+        self.shown += 1;
+        if !self.level.is_full() {
+            self.degraded += 1;
+        }
+        let Level(level) = self.level;
+        if took > self.interval && level + 1 < LEVELS.len() {
+            self.level = Level(level + 1);
+            self.calm = 0;
+        } else if level > 0 {
+            let better = Level(level - 1);
+            let expected = took.as_secs_f64() * better.cost() / self.level.cost();
+            if expected < self.interval.as_secs_f64() * HEADROOM {
+                self.calm += 1;
+            } else {
+                self.calm = 0;
+            }
+            if self.calm >= CALM_FRAMES {
+                self.level = better;
+                self.calm = 0;
+            }
+        }
+        if let Some(end) = self.next_slot
+            && let Some(rest) = end.checked_duration_since(Instant::now())
+        {
+            thread::sleep(rest);
+        }
+    }
+
+    /// Logs and reports on stderr how many frames kept up at full quality.
+    pub fn report(&self) {
+        runlog::info(
+            "realtime",
+            &[
+                ("shown", self.shown.into()),
+                ("degraded", self.degraded.into()),
+                ("dropped", self.dropped.into()),
+            ],
+        );
+        eprintln!(
+            "realtime: {} frames shown, {} at reduced quality, {} dropped",
+            self.shown, self.degraded, self.dropped
+        );
+    }
+}
+
+/// Stretches the iterations of a reduced level to `width`x`height`, each
+/// pixel taking the value of the one it falls on. The buffer keeps the full
+/// `max_iter`, so its colors match those of full-quality frames.
+pub fn upscale(
+    iterations: &IterationBuffer,
+    width: u32,
+    height: u32,
+    max_iter: u32,
+) -> IterationBuffer {
+    let (sw, sh) = (iterations.width, iterations.height);
+    IterationBuffer::from_fn(width, height, max_iter, |x, y| {
+        let sx = (x as u64 * sw as u64 / width as u64) as u32;
+        let sy = (y as u64 * sh as u64 / height as u64) as u32;
+        iterations.values[(sy * sw + sx) as usize]
+    })
+}
@@ -0,0 +1,154 @@
+use crate::sha256;
+use std::str::FromStr;
+
+#[derive(Debug, Clone)]
+pub enum Palette {
+    Classic,
+    Gradient(Vec<[f64; 3]>),
+}
+
+/// Stops the classic palette is sampled at where a gradient is needed.
+const CLASSIC_STOPS: usize = 64;
+
+pub const BUILTIN_NAMES: [&str; 5] = ["classic", "ultra", "fire", "ocean", "grayscale"];
+
+impl FromStr for Palette {
+    type Err = String;
+
+    /// Accepts a built-in name or a list of `#rrggbb` stops separated by `,` or `:`.
+    fn from_str(s: &str) -> Result<Palette, String> {
+        let stops = match s.trim().to_ascii_lowercase().as_str() {
+            "classic" => return Ok(Palette::Classic),
+            "ultra" => "#000764,#206bcb,#edffff,#ffaa00,#000200".to_string(),
+            "fire" => "#000000,#5c0a00,#d62800,#ff9a00,#ffef9e,#ffffff".to_string(),
+            "ocean" => "#00041a,#002a5c,#0077b6,#48cae4,#caf0f8".to_string(),
+            "grayscale" => "#000000,#ffffff".to_string(),
+            _ if s.contains([',', ':']) || s.trim().starts_with('#') => s.to_string(),
+            _ => {
+                return Err(format!(
+                    "unknown palette {s:?}, expected one of {} or #rrggbb stops",
+                    BUILTIN_NAMES.join(", ")
+                ));
+            }
+        };
+        let colors = stops
+            .split([',', ':'])
+            .map(parse_hex_color)
+            .collect::<Result<Vec<_>, _>>()?;
+        if colors.len() < 2 {
+            return Err(format!(
+                "palette {s:?} needs at least two colors (or one of: {})",
+                BUILTIN_NAMES.join(", ")
+            ));
+        }
+        Ok(Palette::Gradient(colors))
+    }
+}
+
+/// A palette plus a filesystem-safe label, used when several palettes are written per frame.
+#[derive(Debug, Clone)]
+pub struct NamedPalette {
+    pub label: String,
+    pub palette: Palette,
+}
+
+impl FromStr for NamedPalette {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<NamedPalette, String> {
+        let palette = s.parse()?;
+        let name = s.trim().to_ascii_lowercase();
+        let label = if BUILTIN_NAMES.contains(&name.as_str()) {
+            name
+        } else {
+            format!("custom-{}", &sha256::hex_digest(name.as_bytes())[..8])
+        };
+        Ok(NamedPalette { label, palette })
+    }
+}
+
+impl Palette {
+    pub fn color(&self, t: f64) -> [u8; 3] {
+        match self {
+            Palette::Classic => classic_color(t),
+            Palette::Gradient(stops) => gradient_rgb(stops, t).map(|c| (c * 255.0).round() as u8),
+        }
+    }
+
+    /// [`Palette::color`] at 16 bits per channel, from the same curve
+    /// before it is rounded to 8 bits.
+    pub fn color16(&self, t: f64) -> [u16; 3] {
+        let rgb = match self {
+            Palette::Classic => classic_rgb(t),
+            Palette::Gradient(stops) => gradient_rgb(stops, t),
+        };
+        rgb.map(|c| (c * 65535.0).round().clamp(0.0, 65535.0) as u16)
+    }
+
+    /// The palette as gradient stops; the classic curve is sampled.
+    pub fn stops(&self) -> Vec<[f64; 3]> {
+        match self {
+            Palette::Classic => (0..CLASSIC_STOPS)
+                .map(|i| {
+                    classic_color(i as f64 / (CLASSIC_STOPS - 1) as f64).map(|c| c as f64 / 255.0)
+                })
+                .collect(),
+            Palette::Gradient(stops) => stops.clone(),
+        }
+    }
+}
+
+fn parse_hex_color(s: &str) -> Result<[f64; 3], String> {
+    let hex = s.trim().trim_start_matches('#');
+    let value = u32::from_str_radix(hex, 16)
+        .ok()
+        .filter(|_| hex.len() == 6)
+        .ok_or_else(|| format!("invalid color {s:?}, expected #rrggbb"))?;
+    Ok([
+        ((value >> 16) & 0xff) as f64 / 255.0,
+        ((value >> 8) & 0xff) as f64 / 255.0,
+        (value & 0xff) as f64 / 255.0,
+    ])
+}
+
+/// Channels between 0 and 1 of the gradient at `t`.
+fn gradient_rgb(stops: &[[f64; 3]], t: f64) -> [f64; 3] {
+    let scaled = t.clamp(0.0, 1.0) * (stops.len() - 1) as f64;
+    let idx = (scaled.floor() as usize).min(stops.len() - 2);
+    let f = scaled - idx as f64;
+    let a = stops[idx];
+    let b = stops[idx + 1];
+    [
+        a[0] + (b[0] - a[0]) * f,
+        a[1] + (b[1] - a[1]) * f,
+        a[2] + (b[2] - a[2]) * f,
+    ]
+}
+
+fn classic_color(t: f64) -> [u8; 3] {
+    classic_rgb(t).map(|c| (c * 255.0).clamp(0.0, 255.0) as u8)
+}
+
+/// Channels between 0 and 1 of the classic curve at `t`.
+fn classic_rgb(t: f64) -> [f64; 3] {
+    let hue = (360.0 * (0.65 + 2.2 * t)) % 360.0;
+    let sat = 0.95;
+    let val = (0.25 + 0.85 * t).clamp(0.0, 1.0);
+    hsv_to_rgb(hue, sat, val)
+}
+
+fn hsv_to_rgb(h: f64, s: f64, v: f64) -> [f64; 3] {
+    let h = (h % 360.0 + 360.0) % 360.0;
+    let c = v * s;
+    let x = c * (1.0 - ((h / 60.0) % 2.0 - 1.0).abs());
+    let m = v - c;
+    let (r1, g1, b1) = match h {
+        h if h < 60.0 => (c, x, 0.0),
+        h if h < 120.0 => (x, c, 0.0),
+        h if h < 180.0 => (0.0, c, x),
+        h if h < 240.0 => (0.0, x, c),
+        h if h < 300.0 => (x, 0.0, c),
+        _ => (c, 0.0, x),
+    };
+    [r1 + m, g1 + m, b1 + m]
+}
@@ -0,0 +1,149 @@
+use crate::encode::{self, FfmpegPipe};
+use crate::palette::Palette;
+use crate::render::{self, Complex};
+use image::imageops;
+use std::f64::consts::TAU;
+use std::fs;
+use std::path::Path;
+
+/// Dense samples taken along the boundary before resampling by arc length.
+const PATH_SAMPLES: usize = 20_000;
+
+#[derive(clap::Args, Debug)]
+pub struct PanoramaArgs {
+    /// Height of the strip in pixels; its width follows from the path length.
+    #[arg(long, default_value_t = 512)]
+    height: u32,
+    /// Half the strip height in the complex plane, matching --zoom-start of the animation.
+    #[arg(long, default_value_t = 0.02)]
+    zoom: f64,
+    #[arg(long, default_value_t = 1000)]
+    max_iter: u32,
+    /// Built-in palette name or comma-separated `#rrggbb` gradient stops.
+    #[arg(long, default_value = "classic")]
+    palette: Palette,
+    /// Where along the main cardioid the strip starts, as a fraction of one loop.
+    #[arg(long, default_value_t = 0.05)]
+    start: f64,
+    /// Where along the main cardioid the strip ends, as a fraction of one loop.
+    #[arg(long, default_value_t = 0.95)]
+    end: f64,
+    /// Upper bound on the strip width; the zoom is not changed, the path is cut short.
+    #[arg(long, default_value_t = 32768)]
+    max_width: u32,
+    #[arg(long, default_value = "out/panorama.png")]
+    out: String,
+    /// Also encode a video that scrolls a window across the strip.
+    #[arg(long)]
+    video: Option<String>,
+    #[arg(long, default_value_t = 1280)]
+    video_width: u32,
+    /// Scroll speed of the video in pixels per frame.
+    #[arg(long, default_value_t = 4)]
+    speed: u32,
+    #[arg(long, default_value_t = 30)]
+    fps: u32,
+}
+
+/// Unrolls the neighbourhood of the main cardioid boundary into one image.
+///
+/// Every column samples the plane along the boundary normal at an arc length
+/// proportional to its x position, so the strip has no tile seams and the
+/// boundary runs through the middle row with the exterior at the top.
+pub fn run(args: PanoramaArgs) -> Result<(), String> {
+    let height = args.height.max(2);
+    let pixel = args.zoom / (height as f64 / 2.0);
+    let path = arc_length_path(args.start, args.end, pixel, args.max_width.max(1));
+    let width = path.len() as u32;
+    println!(
+        "panorama: {width}x{height} px, {:.4} units of boundary",
+        width as f64 * pixel
+    );
+
+    let half = height as f64 / 2.0;
+    let iterations = render::iterate_points(width, height, args.max_iter, false, |x, y| {
+        let (point, normal) = path[x as usize];
+        let offset = (half - y as f64) * pixel;
+        Complex {
+            re: point.re + normal.re * offset,
+            im: point.im + normal.im * offset,
+        }
+    });
+    let img = iterations.colorize(&args.palette);
+
+    let out = Path::new(&args.out);
+    if let Some(parent) = out.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("create {parent:?}: {e}"))?;
+    }
+    img.save(out).map_err(|e| format!("save {out:?}: {e}"))?;
+    println!("panorama -> {}", out.display());
+
+    if let Some(video) = &args.video {
+        if let Some(parent) = Path::new(video).parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("create {parent:?}: {e}"))?;
+        }
+        let window_w = args.video_width.min(width) & !1;
+        let window_h = height & !1;
+        if window_w == 0 {
+            return Err("panorama is too narrow for a video".to_string());
+        }
+        let speed = args.speed.max(1);
+        let frames = (width - window_w) / speed + 1;
+        let mut pipe = FfmpegPipe::spawn(window_w, window_h, args.fps, &encode::h264_args(video))?;
+        for frame in 0..frames {
+            let window = imageops::crop_imm(&img, frame * speed, 0, window_w, window_h).to_image();
+            pipe.write_frame(&window)?;
+        }
+        pipe.finish()?;
+        println!("panorama video ({frames} frames) -> {video}");
+    }
+    Ok(())
+}
+
+/// Main cardioid boundary `e^(iθ)/2 - e^(2iθ)/4` at `θ = TAU * s`.
+fn cardioid(s: f64) -> Complex {
+    let theta = TAU * s;
+    Complex {
+        re: theta.cos() / 2.0 - (2.0 * theta).cos() / 4.0,
+        im: theta.sin() / 2.0 - (2.0 * theta).sin() / 4.0,
+    }
+}
+
+/// One `(point, outward unit normal)` per column, spaced `step` apart along the curve.
+fn arc_length_path(start: f64, end: f64, step: f64, max_columns: u32) -> Vec<(Complex, Complex)> {
+    let samples: Vec<Complex> = (0..=PATH_SAMPLES)
+        .map(|i| cardioid(start + (end - start) * i as f64 / PATH_SAMPLES as f64))
+        .collect();
+
+    let mut columns = Vec::new();
+    let mut travelled = 0.0;
+    let mut next = 0.0;
+    for pair in samples.windows(2) {
+        let (a, b) = (pair[0], pair[1]);
+        let (dx, dy) = (b.re - a.re, b.im - a.im);
+        let len = (dx * dx + dy * dy).sqrt();
+        if len == 0.0 {
+            continue;
+        }
+        // Counter-clockwise traversal: the exterior is on the right of the tangent.
+        let direction = if end >= start { 1.0 } else { -1.0 };
+        let normal = Complex {
+            re: dy / len * direction,
+            im: -dx / len * direction,
+        };
+        while next <= travelled + len {
+            let f = (next - travelled) / len;
+            let point = Complex {
+                re: a.re + dx * f,
+                im: a.im + dy * f,
+            };
+            columns.push((point, normal));
+            if columns.len() as u32 >= max_columns {
+                return columns;
+            }
+            next += step;
+        }
+        travelled += len;
+    }
+    columns
+}
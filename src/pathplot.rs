@@ -0,0 +1,325 @@
+use crate::composite::BlendMode;
+use crate::draw::{self, Paint};
+use crate::letterbox::Canvas;
+use crate::render::{self, Complex};
+use crate::warp::Warp;
+use crate::{
+    FrameInfo, RenderArgs, encode_png, fixed_path, font, frame_info, iterate, output, rational,
+    threads,
+};
+use image::{ImageBuffer, Rgb};
+use std::fs;
+use std::path::Path;
+
+const VIEW: Paint = Paint {
+    color: [255, 255, 255, 160],
+    blend: BlendMode::Normal,
+};
+const CAMERA: Paint = Paint {
+    color: [255, 214, 0, 255],
+    blend: BlendMode::Normal,
+};
+const KEYFRAME: Paint = Paint {
+    color: [255, 64, 64, 255],
+    blend: BlendMode::Normal,
+};
+const TEXT: Paint = Paint {
+    color: [255, 255, 255, 255],
+    blend: BlendMode::Normal,
+};
+/// Room left around everything drawn when the plot picks its own view.
+const MARGIN: f64 = 1.1;
+
+#[derive(clap::Args, Debug)]
+pub struct PathPlotArgs {
+    #[command(flatten)]
+    render: RenderArgs,
+    /// Size of the plot, `WIDTHxHEIGHT`.
+    #[arg(long, default_value = "1280x720")]
+    size: Canvas,
+    /// Center of the plotted part of the plane, e.g. `-0.75+0.1i`; by
+    /// default the middle of everything drawn.
+    #[arg(long, value_parser = rational::parse_complex)]
+    center: Option<Complex>,
+    /// Half the height of the plotted part of the plane; by default
+    /// everything drawn fits.
+    #[arg(long)]
+    zoom: Option<f64>,
+    /// Outline the view of every this many frames, and of the last one.
+    #[arg(long, default_value_t = 30, value_parser = clap::value_parser!(u32).range(1..))]
+    every: u32,
+    /// A `.png`, or an `.svg` with the marks drawn as vectors over the set.
+    #[arg(long, default_value = "out/path.png")]
+    output: String,
+}
+
+/// What the plot draws over the set, in plot pixels.
+struct Marks {
+    /// Corners of the views of every --every-th frame, with its number.
+    views: Vec<(u32, [(f64, f64); 4])>,
+    /// The center of every frame in order.
+    camera: Vec<(f64, f64)>,
+    keyframes: Vec<(f64, f64)>,
+}
+
+pub fn run(args: PathPlotArgs) -> Result<(), String> {
+    args.render.start_threads()?;
+    threads::install(move || plot(args))
+}
+
+/// Draws where the animation goes before it is rendered: the views of
+/// some of its frames outlined, and the camera path through their centers,
+/// over a wide view of the set.
+fn plot(args: PathPlotArgs) -> Result<(), String> {
+    let render = &args.render;
+    render.check()?;
+    if render.endless {
+        return Err(
+            "path-plot needs a finite animation, --endless picks its path while rendering"
+                .to_string(),
+        );
+    }
+    let svg = match Path::new(&args.output).extension() {
+        Some(ext) if ext.eq_ignore_ascii_case("svg") => true,
+        Some(ext) if ext.eq_ignore_ascii_case("png") => false,
+        _ => return Err(format!("{}: expected a .png or .svg file", args.output)),
+    };
+    let path = fixed_path(render.fractal);
+    let frames: Vec<FrameInfo> = render
+        .frame_range()
+        .map(|frame| frame_info(render, &path, frame))
+        .collect();
+    let last = frames.len() - 1;
+    let views: Vec<(u32, [Complex; 4])> = frames
+        .iter()
+        .enumerate()
+        .filter(|&(i, _)| i % args.every as usize == 0 || i == last)
+        .map(|(_, info)| (info.index, corners(render, info)))
+        .collect();
+    let keyframes = match &render.deep_center {
+        Some(deep) => vec![deep.approx()],
+        None => path,
+    };
+
+    let (width, height) = (args.size.width, args.size.height);
+    let points = views
+        .iter()
+        .flat_map(|(_, corners)| corners)
+        .chain(&keyframes)
+        .copied()
+        .chain(frames.iter().map(|info| info.center));
+    let (center, zoom) = fit(points, width, height);
+    let view = FrameInfo {
+        center: args.center.unwrap_or(center),
+        zoom: args.zoom.unwrap_or(zoom),
+        rotation: 0.0,
+        transform: render::IDENTITY,
+        warp: Warp::None,
+        ..frames[0]
+    };
+    let scale = view.zoom / (width.min(height) as f64 / 2.0);
+    let to_pixel = |c: &Complex| {
+        (
+            (c.re - view.center.re) / scale + width as f64 / 2.0,
+            (c.im - view.center.im) / scale + height as f64 / 2.0,
+        )
+    };
+    let marks = Marks {
+        views: views
+            .iter()
+            .map(|(frame, corners)| (*frame, corners.each_ref().map(to_pixel)))
+            .collect(),
+        camera: frames.iter().map(|info| to_pixel(&info.center)).collect(),
+        keyframes: keyframes.iter().map(to_pixel).collect(),
+    };
+
+    let mut img = iterate(render, &view, width, height, view.max_iter).colorize(&render.palette);
+    let thickness = (height / 360).max(1);
+    let out = Path::new(&args.output);
+    if let Some(parent) = out.parent() {
+        fs::create_dir_all(parent).map_err(|e| output::describe_error("create", parent, &e))?;
+    }
+    let bytes = if svg {
+        to_svg(&encode_png(&img)?, &marks, width, height, thickness).into_bytes()
+    } else {
+        draw_marks(&mut img, &marks, thickness);
+        encode_png(&img)?
+    };
+    fs::write(out, bytes).map_err(|e| output::describe_error("write", out, &e))?;
+    println!(
+        "path-plot: {} frames, {} views outlined, {} keyframes -> {}",
+        frames.len(),
+        marks.views.len(),
+        marks.keyframes.len(),
+        out.display()
+    );
+    Ok(())
+}
+
+/// Corners of the part of the plane a frame shows, clockwise from the top left.
+fn corners(args: &RenderArgs, info: &FrameInfo) -> [Complex; 4] {
+    let (w, h) = (args.width, args.height);
+    let point = info.subpixel_mapping(w, h);
+    let (w, h) = (w as f64, h as f64);
+    [point(0.0, 0.0), point(w, 0.0), point(w, h), point(0.0, h)]
+}
+
+/// Center and zoom of a `width`x`height` view holding all `points`.
+fn fit(points: impl Iterator<Item = Complex>, width: u32, height: u32) -> (Complex, f64) {
+    let (mut lo, mut hi) = (
+        Complex {
+            re: f64::INFINITY,
+            im: f64::INFINITY,
+        },
+        Complex {
+            re: f64::NEG_INFINITY,
+            im: f64::NEG_INFINITY,
+        },
+    );
+    for p in points {
+        (lo.re, lo.im) = (lo.re.min(p.re), lo.im.min(p.im));
+        (hi.re, hi.im) = (hi.re.max(p.re), hi.im.max(p.im));
+    }
+    let center = Complex {
+        re: (lo.re + hi.re) / 2.0,
+        im: (lo.im + hi.im) / 2.0,
+    };
+    let scale = ((hi.re - lo.re) / width as f64).max((hi.im - lo.im) / height as f64) * MARGIN;
+    let zoom = scale * width.min(height) as f64 / 2.0;
+    (center, if zoom > 0.0 { zoom } else { 1.0 })
+}
+
+fn draw_marks(img: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, marks: &Marks, thickness: u32) {
+    let size = (img.width() as f64, img.height() as f64);
+    let line = |img: &mut ImageBuffer<Rgb<u8>, Vec<u8>>, a, b, paint| {
+        if let Some((a, b)) = clip(a, b, size) {
+            draw::draw_line(img, pixel(a), pixel(b), thickness, paint);
+        }
+    };
+    for (frame, corners) in &marks.views {
+        for (i, &corner) in corners.iter().enumerate() {
+            line(img, corner, corners[(i + 1) % 4], VIEW);
+        }
+        // Only on views wide enough to hold it, so the smallest don't
+        // bury the path under their numbers.
+        let label = format!("frame {frame}");
+        let span = (corners[1].0 - corners[0].0).hypot(corners[1].1 - corners[0].1);
+        let (x, y) = pixel(corners[0]);
+        if span > 2.0 * font::text_width(&label, thickness) as f64 {
+            let pad = 2 * thickness as i64;
+            font::draw_text(img, x + pad, y + pad, thickness, TEXT, &label);
+        }
+    }
+    for pair in marks.camera.windows(2) {
+        line(img, pair[0], pair[1], CAMERA);
+    }
+    let side = 5 * thickness;
+    for &keyframe in &marks.keyframes {
+        let (x, y) = pixel(keyframe);
+        let half = side as i64 / 2;
+        draw::fill_rect(img, x - half, y - half, side, side, KEYFRAME);
+    }
+}
+
+/// The same marks as [`draw_marks`], as SVG over the `background` PNG.
+fn to_svg(background: &[u8], marks: &Marks, width: u32, height: u32, thickness: u32) -> String {
+    let color = |paint: Paint| {
+        let [r, g, b, a] = paint.color;
+        format!(
+            "#{r:02x}{g:02x}{b:02x}\" stroke-opacity=\"{:.3}",
+            a as f64 / 255.0
+        )
+    };
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" viewBox=\"0 0 {width} {height}\">\n"
+    );
+    svg.push_str(&format!(
+        "<image width=\"{width}\" height=\"{height}\" href=\"data:image/png;base64,{}\"/>\n",
+        base64(background)
+    ));
+    for (frame, corners) in &marks.views {
+        let points: Vec<String> = corners
+            .iter()
+            .map(|(x, y)| format!("{x:.2},{y:.2}"))
+            .collect();
+        svg.push_str(&format!(
+            "<polygon points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"{thickness}\"><title>frame {frame}</title></polygon>\n",
+            points.join(" "),
+            color(VIEW)
+        ));
+    }
+    let points: Vec<String> = marks
+        .camera
+        .iter()
+        .map(|(x, y)| format!("{x:.2},{y:.2}"))
+        .collect();
+    svg.push_str(&format!(
+        "<polyline points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"{thickness}\"/>\n",
+        points.join(" "),
+        color(CAMERA)
+    ));
+    let side = 5 * thickness;
+    for (x, y) in &marks.keyframes {
+        let [r, g, b, _] = KEYFRAME.color;
+        svg.push_str(&format!(
+            "<rect x=\"{:.2}\" y=\"{:.2}\" width=\"{side}\" height=\"{side}\" fill=\"#{r:02x}{g:02x}{b:02x}\"/>\n",
+            x - side as f64 / 2.0,
+            y - side as f64 / 2.0
+        ));
+    }
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn pixel((x, y): (f64, f64)) -> (i64, i64) {
+    (x.round() as i64, y.round() as i64)
+}
+
+/// The part of the segment from `a` to `b` inside a `size` image, so the
+/// outlines of views far larger than the plot don't take millions of steps.
+fn clip(a: (f64, f64), b: (f64, f64), size: (f64, f64)) -> Option<((f64, f64), (f64, f64))> {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let (mut t0, mut t1) = (0.0f64, 1.0f64);
+    for (p, q) in [
+        (-dx, a.0 + 1.0),
+        (dx, size.0 - a.0),
+        (-dy, a.1 + 1.0),
+        (dy, size.1 - a.1),
+    ] {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None;
+            }
+            continue;
+        }
+        let t = q / p;
+        if p < 0.0 {
+            t0 = t0.max(t);
+        } else {
+            t1 = t1.min(t);
+        }
+    }
+    (t0 <= t1).then_some((
+        (a.0 + t0 * dx, a.1 + t0 * dy),
+        (a.0 + t1 * dx, a.1 + t1 * dy),
+    ))
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
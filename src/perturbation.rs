@@ -0,0 +1,394 @@
+use crate::bignum::Fixed;
+use crate::orbits::OrbitStore;
+use crate::render::{self, Complex, DERIVATIVE_EPSILON, INTERIOR, IterationBuffer, Transform};
+use crate::warp::Warp;
+use std::str::FromStr;
+
+/// Frames whose pixels are closer than this, relative to the center's
+/// distance from the origin, are past what f64 pixel coordinates resolve.
+pub const DEEP_SCALE: f64 = 1e-12;
+/// Bits beyond the pixel spacing the reference orbit is computed with.
+const GUARD_BITS: f64 = 64.0;
+/// Largest difference, relative to the difference itself, the series may
+/// have from iterating the probes before iterations can no longer be skipped.
+/// Barely above f64 rounding: filaments amplify any larger error into
+/// speckles of pixels escaping hundreds of iterations off.
+const SERIES_TOLERANCE: f64 = 1e-13;
+/// Probes per side of the lattice the series is checked on, so a feature
+/// inside the frame that the series misses shows up at some probe rather
+/// than as a glitched blob.
+const SERIES_PROBES: u32 = 5;
+
+/// `--deep-center`: a center given with more digits than an f64 keeps,
+/// `re,im` in plain decimals.
+#[derive(Debug, Clone)]
+pub struct DeepCenter {
+    re: String,
+    im: String,
+}
+
+impl FromStr for DeepCenter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<DeepCenter, String> {
+        let (re, im) = s
+            .split_once(',')
+            .ok_or_else(|| format!("invalid center {s:?}, expected re,im"))?;
+        Fixed::parse(re, 2)?;
+        Fixed::parse(im, 2)?;
+        Ok(DeepCenter {
+            re: re.trim().to_string(),
+            im: im.trim().to_string(),
+        })
+    }
+}
+
+impl DeepCenter {
+    /// The nearest f64 point, where the rest of the frame's view sits.
+    pub fn approx(&self) -> Complex {
+        Complex {
+            re: self.re.parse().unwrap_or(0.0),
+            im: self.im.parse().unwrap_or(0.0),
+        }
+    }
+
+    fn fixed(&self, limbs: usize) -> (Fixed, Fixed) {
+        (
+            Fixed::parse(&self.re, limbs).unwrap_or_else(|_| Fixed::zero(limbs)),
+            Fixed::parse(&self.im, limbs).unwrap_or_else(|_| Fixed::zero(limbs)),
+        )
+    }
+}
+
+/// The orbit of the view's center, computed in as much precision as the
+/// pixel spacing needs and rounded to f64 point by point. Every pixel then
+/// only iterates its small difference from it in f64.
+pub struct Reference {
+    orbit: Vec<Complex>,
+    series: Series,
+}
+
+/// The first `skip` iterations of every pixel as a cubic in its `dc`:
+/// `delta = a dc + b dc^2 + c dc^3`.
+#[derive(Debug, Clone, Copy)]
+struct Series {
+    skip: usize,
+    a: Complex,
+    b: Complex,
+    c: Complex,
+}
+
+impl Series {
+    const NONE: Series = Series {
+        skip: 0,
+        a: Complex::ZERO,
+        b: Complex::ZERO,
+        c: Complex::ZERO,
+    };
+
+    fn delta(&self, dc: Complex) -> Complex {
+        let dc2 = dc.mul(dc);
+        self.a
+            .mul(dc)
+            .add(self.b.mul(dc2))
+            .add(self.c.mul(dc2.mul(dc)))
+    }
+}
+
+impl Reference {
+    /// The orbit of `deep`, or of `center` without it, for `max_iter` steps or
+    /// until it escapes. With `orbits` it is loaded from there when stored
+    /// before, and stored there when computed.
+    pub fn new(
+        center: Complex,
+        deep: Option<&DeepCenter>,
+        scale: f64,
+        max_iter: u32,
+        orbits: Option<&OrbitStore>,
+    ) -> Reference {
+        let depth = -scale.abs().max(f64::MIN_POSITIVE).log2();
+        let limbs = ((depth.max(0.0) + GUARD_BITS) / 64.0).ceil() as usize + 1;
+        let key = match deep {
+            Some(deep) => format!("{},{}", deep.re, deep.im),
+            None => format!("{:x},{:x}", center.re.to_bits(), center.im.to_bits()),
+        };
+        let orbit = match orbits.and_then(|orbits| orbits.load(&key, limbs, max_iter)) {
+            Some(orbit) => orbit,
+            None => {
+                let orbit = orbit(center, deep, limbs, max_iter);
+                if let Some(orbits) = orbits
+                    && let Err(e) = orbits.store(&key, limbs, &orbit)
+                {
+                    eprintln!("warning: {e}");
+                }
+                orbit
+            }
+        };
+        Reference {
+            orbit,
+            series: Series::NONE,
+        }
+    }
+
+    /// Finds how many early iterations all points around the `probes` (see
+    /// [`probes`]) share closely enough to compute by a series instead, so
+    /// [`Reference::escape`] skips them.
+    ///
+    /// The series is carried along the orbit while iterating the probes
+    /// themselves matches it within [`SERIES_TOLERANCE`], and stops before
+    /// any probe escapes or needs rebasing.
+    pub fn approximate(&mut self, probes: &[Complex], max_iter: u32) {
+        let one = Complex { re: 1.0, im: 0.0 };
+        let mut series = Series::NONE;
+        let mut deltas = vec![Complex::ZERO; probes.len()];
+        let last = (self.orbit.len() - 1).min(max_iter as usize);
+        for m in 0..last {
+            let two_z = self.orbit[m].add(self.orbit[m]);
+            let next = Series {
+                skip: m + 1,
+                a: two_z.mul(series.a).add(one),
+                b: two_z.mul(series.b).add(series.a.mul(series.a)),
+                c: two_z
+                    .mul(series.c)
+                    .add(series.a.mul(series.b).add(series.a.mul(series.b))),
+            };
+            let valid = probes.iter().zip(&mut deltas).all(|(dc, delta)| {
+                *delta = two_z.add(*delta).mul(*delta).add(*dc);
+                let z = self.orbit[m + 1].add(*delta);
+                let error = next.delta(*dc).sub(*delta).norm_sqr();
+                // Written so that overflowing terms fail the check too.
+                z.norm_sqr() <= 4.0
+                    && z.norm_sqr() >= delta.norm_sqr()
+                    && error <= SERIES_TOLERANCE * SERIES_TOLERANCE * delta.norm_sqr()
+            });
+            if !valid {
+                break;
+            }
+            series = next;
+        }
+        self.series = series;
+    }
+
+    /// Smooth escape time of the point `dc` away from the reference, the
+    /// same value [`render::mandelbrot_smooth`] gives where f64 suffices.
+    ///
+    /// Whenever the full orbit comes closer to 0 than its difference from
+    /// the reference, or the reference ends, the difference is rebased onto
+    /// the start of the reference orbit, so one reference serves the whole
+    /// frame without glitches.
+    ///
+    /// The iterations [`Reference::approximate`] found shared are skipped,
+    /// except with `derivative_bailout`, which needs the derivative along
+    /// them.
+    pub fn escape(&self, dc: Complex, max_iter: u32, derivative_bailout: bool) -> f64 {
+        let orbit = &self.orbit;
+        let series = match derivative_bailout {
+            true => Series::NONE,
+            false => self.series,
+        };
+        let mut m = series.skip.min(max_iter as usize);
+        let mut delta = match m {
+            0 => Complex::ZERO,
+            _ => series.delta(dc),
+        };
+        let mut z = orbit[m].add(delta);
+        let mut dz = Complex { re: 1.0, im: 0.0 };
+        let mut iter = m as u32;
+        while iter < max_iter && z.norm_sqr() <= 4.0 {
+            if derivative_bailout && iter > 0 {
+                dz = z.add(z).mul(dz);
+                if dz.norm_sqr() < DERIVATIVE_EPSILON {
+                    return INTERIOR;
+                }
+            }
+            if m + 1 >= orbit.len() || z.norm_sqr() < delta.norm_sqr() {
+                delta = z;
+                m = 0;
+            }
+            // (Z + delta)^2 + C + dc less Z^2 + C.
+            delta = orbit[m].add(orbit[m]).add(delta).mul(delta).add(dc);
+            m += 1;
+            z = orbit[m].add(delta);
+            iter += 1;
+        }
+        if iter >= max_iter {
+            return INTERIOR;
+        }
+        let zn = z.norm_sqr().sqrt();
+        iter as f64 + 1.0 - (zn.ln().ln() / 2.0_f64.ln())
+    }
+}
+
+/// The orbit of `deep`, or of `center` without it, in fixed point with
+/// `limbs` and rounded to f64 point by point.
+fn orbit(center: Complex, deep: Option<&DeepCenter>, limbs: usize, max_iter: u32) -> Vec<Complex> {
+    let (c_re, c_im) = match deep {
+        Some(deep) => deep.fixed(limbs),
+        None => (
+            Fixed::from_f64(center.re, limbs),
+            Fixed::from_f64(center.im, limbs),
+        ),
+    };
+    let (mut re, mut im) = (Fixed::zero(limbs), Fixed::zero(limbs));
+    let mut orbit = vec![Complex::ZERO];
+    for _ in 0..max_iter.max(1) {
+        let (re2, im2) = (re.mul(&re), im.mul(&im));
+        let cross = re.mul(&im);
+        im = cross.add(&cross).add(&c_im);
+        re = re2.sub(&im2).add(&c_re);
+        let z = Complex {
+            re: re.to_f64(),
+            im: im.to_f64(),
+        };
+        orbit.push(z);
+        if z.norm_sqr() > 4.0 {
+            break;
+        }
+    }
+    orbit
+}
+
+/// A whole frame by perturbation around the center of `reference`, with
+/// pixels placed by `offset`, as from [`frame_offsets`].
+pub fn iterate(
+    reference: &Reference,
+    width: u32,
+    height: u32,
+    offset: impl Fn(f64, f64) -> Complex + Sync,
+    max_iter: u32,
+    derivative_bailout: bool,
+) -> IterationBuffer {
+    IterationBuffer::from_fn(width, height, max_iter, |x, y| {
+        reference.escape(offset(x as f64, y as f64), max_iter, derivative_bailout)
+    })
+}
+
+/// Maps pixel coordinates to their difference from the frame's center.
+pub fn frame_offsets(
+    width: u32,
+    height: u32,
+    zoom: f64,
+    rotation: f64,
+    transform: Transform,
+    warp: Warp,
+) -> impl Fn(f64, f64) -> Complex + Sync {
+    render::transformed_mapping(
+        width,
+        height,
+        Complex::ZERO,
+        zoom,
+        rotation,
+        transform,
+        warp,
+    )
+}
+
+/// A lattice of points across the frame, corners included, as differences
+/// from its center.
+pub fn probes(
+    width: u32,
+    height: u32,
+    zoom: f64,
+    rotation: f64,
+    transform: Transform,
+    warp: Warp,
+) -> Vec<Complex> {
+    let offset = frame_offsets(width, height, zoom, rotation, transform, warp);
+    let n = SERIES_PROBES;
+    let at = |k: u32, size: u32| k as f64 * (size - 1) as f64 / (n - 1) as f64;
+    (0..n)
+        .flat_map(|j| (0..n).map(move |i| (i, j)))
+        .map(|(i, j)| offset(at(i, width), at(j, height)))
+        .collect()
+}
+
+/// Distance between neighboring pixels in the plane.
+pub fn pixel_scale(width: u32, height: u32, zoom: f64) -> f64 {
+    zoom / (width.min(height) as f64 / 2.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render::IDENTITY;
+
+    /// Escape time of `deep + offset` iterated in fixed point all the way,
+    /// as the reference orbit is, the value perturbation has to match.
+    fn direct(deep: &DeepCenter, offset: Complex, limbs: usize, max_iter: u32) -> f64 {
+        let (re0, im0) = deep.fixed(limbs);
+        let c_re = re0.add(&Fixed::from_f64(offset.re, limbs));
+        let c_im = im0.add(&Fixed::from_f64(offset.im, limbs));
+        let (mut re, mut im) = (Fixed::zero(limbs), Fixed::zero(limbs));
+        for iter in 1..max_iter {
+            let (re2, im2) = (re.mul(&re), im.mul(&im));
+            let cross = re.mul(&im);
+            im = cross.add(&cross).add(&c_im);
+            re = re2.sub(&im2).add(&c_re);
+            let z = Complex {
+                re: re.to_f64(),
+                im: im.to_f64(),
+            };
+            if z.norm_sqr() > 4.0 {
+                let zn = z.norm_sqr().sqrt();
+                return iter as f64 + 1.0 - (zn.ln().ln() / 2.0_f64.ln());
+            }
+        }
+        INTERIOR
+    }
+
+    /// The center is the nucleus of a period-998 minibrot, so the reference
+    /// orbit never escapes while every pixel's orbit comes close to 0 where
+    /// the reference's doesn't: where Pauldelbrot's criterion finds glitches
+    /// that need secondary references without rebasing.
+    #[test]
+    fn deep_frame_has_no_glitch_blobs() {
+        let deep: DeepCenter = "-0.74364388703715887077806454349364257504760996,\
+                                0.13182590420531229282109735487476726526298860"
+            .parse()
+            .unwrap();
+        let (width, height, zoom, max_iter) = (32, 24, 1e-12, 6000);
+        let scale = pixel_scale(width, height, zoom);
+        assert!(scale < DEEP_SCALE);
+        let mut reference = Reference::new(deep.approx(), Some(&deep), scale, max_iter, None);
+        reference.approximate(
+            &probes(width, height, zoom, 0.0, IDENTITY, Warp::None),
+            max_iter,
+        );
+        let offset = frame_offsets(width, height, zoom, 0.0, IDENTITY, Warp::None);
+        let limbs = ((-scale.log2() + GUARD_BITS) / 64.0).ceil() as usize + 1;
+        let wrong: Vec<Vec<bool>> = (0..height)
+            .map(|y| {
+                (0..width)
+                    .map(|x| {
+                        let dc = offset(x as f64, y as f64);
+                        let perturbed = reference.escape(dc, max_iter, false);
+                        let expected = direct(&deep, dc, limbs, max_iter);
+                        match (perturbed == INTERIOR, expected == INTERIOR) {
+                            (false, false) => (perturbed - expected).abs() >= 1.0,
+                            (inside, expected_inside) => inside != expected_inside,
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        // A few pixels this close to the minibrot escape at a different time
+        // for any change in the last bits of their offset, but a glitch is a
+        // blob of neighboring pixels that all went wrong together.
+        let count = wrong.iter().flatten().filter(|&&wrong| wrong).count();
+        assert!(
+            count * 50 <= (width * height) as usize,
+            "{count} pixels differ"
+        );
+        for (y, row) in wrong.iter().enumerate() {
+            for (x, _) in row.iter().enumerate().filter(|(_, wrong)| **wrong) {
+                let neighbors = (y.saturating_sub(1)..(y + 2).min(wrong.len()))
+                    .flat_map(|ny| {
+                        (x.saturating_sub(1)..(x + 2).min(row.len())).map(move |nx| (nx, ny))
+                    })
+                    .filter(|&(nx, ny)| (nx, ny) != (x, y) && wrong[ny][nx])
+                    .count();
+                assert_eq!(neighbors, 0, "pixels around {x},{y} differ together");
+            }
+        }
+    }
+}
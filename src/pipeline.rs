@@ -0,0 +1,178 @@
+use crate::threads;
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
+use std::sync::{Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// How many items past the oldest unfinished one each thread may start on,
+/// so one slow item can't leave the others piling up unfinished results.
+const LEAD_PER_JOB: usize = 2;
+/// How long [`receive`] waits before it looks for pool work again.
+const IDLE_WAIT: Duration = Duration::from_millis(1);
+
+/// Runs `work` on the items of `order`, up to `jobs` of them at once on
+/// threads of their own, and hands the results to `finish` one by one in
+/// the order of `order`, however they complete. The threads do their work
+/// in the pool of [`threads::install`]. Stops at the first error from
+/// `finish`; items already started are finished and dropped.
+pub fn ordered<T: Send, R: Send>(
+    jobs: usize,
+    order: impl Iterator<Item = T> + Send,
+    work: impl Fn(T) -> R + Sync,
+    mut finish: impl FnMut(R) -> Result<(), String>,
+) -> Result<(), String> {
+    if jobs <= 1 {
+        for item in order {
+            finish(work(item))?;
+        }
+        return Ok(());
+    }
+    let window = jobs * LEAD_PER_JOB;
+    let queue = Mutex::new(order.enumerate());
+    let finished = (Mutex::new(0), Condvar::new());
+    let stop = AtomicBool::new(false);
+    thread::scope(|scope| {
+        let (sender, receiver) = mpsc::channel();
+        let mut started = Ok(());
+        for job in 0..jobs {
+            let sender = sender.clone();
+            let (queue, finished, stop, work) = (&queue, &finished, &stop, &work);
+            let spawned = thread::Builder::new()
+                .name(format!("frame-{job}"))
+                .spawn_scoped(scope, move || {
+                    loop {
+                        let Some((seq, item)) = queue.lock().expect("queue lock").next() else {
+                            return;
+                        };
+                        let (done, ready) = finished;
+                        let mut done = done.lock().expect("progress lock");
+                        while seq >= *done + window && !stop.load(Ordering::Relaxed) {
+                            done = ready.wait(done).expect("progress lock");
+                        }
+                        drop(done);
+                        if stop.load(Ordering::Relaxed) {
+                            return;
+                        }
+                        let result = threads::install(|| work(item));
+                        if sender.send((seq, result)).is_err() {
+                            return;
+                        }
+                    }
+                });
+            if let Err(e) = spawned {
+                started = Err(format!("start frame thread: {e}"));
+                break;
+            }
+        }
+        drop(sender);
+        let mut pending = BTreeMap::new();
+        let mut next = 0;
+        let result = started.and_then(|()| {
+            while let Some((seq, result)) = receive(&receiver) {
+                pending.insert(seq, result);
+                while let Some(result) = pending.remove(&next) {
+                    finish(result)?;
+                    next += 1;
+                    *finished.0.lock().expect("progress lock") = next;
+                    finished.1.notify_all();
+                }
+            }
+            Ok(())
+        });
+        // Under the lock, so no thread checks `stop` and then misses the wakeup.
+        let done = finished.0.lock().expect("progress lock");
+        stop.store(true, Ordering::Relaxed);
+        drop(done);
+        finished.1.notify_all();
+        result
+    })
+}
+
+/// The next result from the threads, `None` once they have all stopped.
+/// Called from a pool worker, as under [`threads::install`], it helps the
+/// pool with its work while it waits rather than holding a worker idle.
+fn receive<R>(receiver: &mpsc::Receiver<R>) -> Option<R> {
+    loop {
+        match receiver.try_recv() {
+            Ok(result) => return Some(result),
+            Err(mpsc::TryRecvError::Disconnected) => return None,
+            Err(mpsc::TryRecvError::Empty) => {}
+        }
+        if rayon::yield_now() == Some(rayon::Yield::Executed) {
+            continue;
+        }
+        match receiver.recv_timeout(IDLE_WAIT) {
+            Ok(result) => return Some(result),
+            Err(mpsc::RecvTimeoutError::Disconnected) => return None,
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Cli, fixed_path, frame_info, iterate};
+    use clap::Parser;
+    use std::time::Duration;
+
+    /// Iterates every frame of a run of `flags` through [`ordered`] with four
+    /// frame jobs, failing if that doesn't finish in time.
+    fn render_with_frame_jobs(flags: &[&str]) {
+        let args = Cli::try_parse_from(["mandelbrot-animation"].iter().chain(flags))
+            .expect("valid flags")
+            .render;
+        threads::configure(Some(4), false).expect("worker pool");
+        let (done, finished) = mpsc::channel();
+        thread::spawn(move || {
+            let path = fixed_path(args.fractal);
+            let frames = args.end_frame.unwrap_or(args.total_frames() - 1) + 1;
+            let work = |frame| {
+                let info = frame_info(&args, &path, frame);
+                iterate(&args, &info, args.width, args.height, info.max_iter);
+            };
+            let _ = done.send(ordered(4, 0..frames, work, |()| Ok(())));
+        });
+        let result = finished
+            .recv_timeout(Duration::from_secs(120))
+            .expect("frames deadlocked on a shared lock");
+        assert_eq!(result, Ok(()));
+    }
+
+    #[test]
+    fn frame_jobs_share_cascade_keyframes() {
+        render_with_frame_jobs(&[
+            "--cascade",
+            "4",
+            "--frames",
+            "64",
+            "--width",
+            "96",
+            "--height",
+            "54",
+        ]);
+    }
+
+    #[test]
+    fn frame_jobs_share_endless_targets() {
+        render_with_frame_jobs(&[
+            "--endless",
+            "--zoom-end",
+            "1e-4",
+            "--fps",
+            "4",
+            "--zoom-rate",
+            "4",
+            "--width",
+            "64",
+            "--height",
+            "48",
+            "--end-frame",
+            "120",
+            "--max-iter",
+            "200",
+        ]);
+    }
+}
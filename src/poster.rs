@@ -0,0 +1,243 @@
+use crate::render::{self, IterationBuffer};
+use crate::{RenderArgs, fixed_path, frame_info, jitter, output, perturbation, scheduler, threads};
+use flate2::Compression;
+use flate2::Crc;
+use flate2::write::ZlibEncoder;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::Instant;
+
+/// Bytes of image data per IDAT chunk.
+const IDAT_SIZE: usize = 1 << 16;
+
+#[derive(clap::Args, Debug)]
+pub struct PosterArgs {
+    #[command(flatten)]
+    render: RenderArgs,
+    /// Frame of the animation to render at --width x --height.
+    #[arg(long, default_value_t = 0)]
+    frame: u32,
+    /// Side of the square tiles the frame is computed in; one row of tiles
+    /// is in memory at a time.
+    #[arg(long, default_value_t = 1024, value_parser = clap::value_parser!(u32).range(16..))]
+    tile: u32,
+    #[arg(long, default_value = "out/poster.png")]
+    output: String,
+}
+
+pub fn run(args: PosterArgs) -> Result<(), String> {
+    args.render.start_threads()?;
+    threads::install(move || poster(args))
+}
+
+/// Renders one frame far larger than fits in memory whole: a row of tiles
+/// at a time, each row colored and compressed into the PNG before the next
+/// is computed. Pixels come out as in a full-size frame of the animation.
+fn poster(args: PosterArgs) -> Result<(), String> {
+    let render = &args.render;
+    render.check()?;
+    check(render)?;
+    if !render.endless && args.frame >= render.total_frames() {
+        return Err(format!(
+            "--frame {} is past the last frame {}",
+            args.frame,
+            render.total_frames() - 1
+        ));
+    }
+    let (width, height, tile) = (render.width, render.height, args.tile);
+    let info = frame_info(render, &fixed_path(render.fractal), args.frame);
+    let backend = scheduler::backend_for(render, &info).name();
+    if !matches!(backend, "perturbation" | "soa" | "scalar") {
+        return Err(format!(
+            "poster can't split --backend {backend} frames into tiles, use soa, scalar or perturbation"
+        ));
+    }
+    let reference = (backend == "perturbation")
+        .then(|| scheduler::reference(render, &info, width, height, info.max_iter));
+    let offset = perturbation::frame_offsets(
+        width,
+        height,
+        info.zoom,
+        info.rotation,
+        info.transform,
+        info.warp,
+    );
+    let point = info.pixel_mapping(width, height);
+    let (palette, phase) = jitter::apply(render, &info, &render.palette);
+    let tiles = width.div_ceil(tile) * height.div_ceil(tile);
+    println!("poster: {width}x{height} px in {tiles} tiles of {tile} px ({backend})");
+
+    let out = Path::new(&args.output);
+    if let Some(parent) = out.parent() {
+        fs::create_dir_all(parent).map_err(|e| output::describe_error("create", parent, &e))?;
+    }
+    let tmp = output::tmp_path(out);
+    let file = fs::File::create(&tmp).map_err(|e| output::describe_error("create", &tmp, &e))?;
+    let started = Instant::now();
+    let written = (|| {
+        let mut png = RowWriter::new(BufWriter::new(file), width, height)?;
+        let mut band = Vec::new();
+        for y0 in (0..height).step_by(tile as usize) {
+            let rows = tile.min(height - y0);
+            band.clear();
+            band.resize(width as usize * rows as usize * 3, 0);
+            for x0 in (0..width).step_by(tile as usize) {
+                let columns = tile.min(width - x0);
+                let iterations = match &reference {
+                    Some(reference) => {
+                        IterationBuffer::from_fn(columns, rows, info.max_iter, |x, y| {
+                            let at = offset((x0 + x) as f64, (y0 + y) as f64);
+                            reference.escape(at, info.max_iter, render.derivative_bailout)
+                        })
+                    }
+                    None => render::iterate_points_in(
+                        render.precision,
+                        columns,
+                        rows,
+                        info.max_iter,
+                        render.derivative_bailout,
+                        |x, y| point(x0 + x, y0 + y),
+                    ),
+                };
+                let img = iterations.colorize_with_phase(palette.as_ref(), phase);
+                let line = columns as usize * 3;
+                for (y, row) in img.as_raw().chunks(line).enumerate() {
+                    let at = (y * width as usize + x0 as usize) * 3;
+                    band[at..at + line].copy_from_slice(row);
+                }
+            }
+            png.write_rows(&band)?;
+            println!("rows {}/{height}", y0 + rows);
+        }
+        png.finish()?
+            .into_inner()
+            .map_err(|e| e.into_error())?
+            .sync_all()
+    })();
+    if let Err(e) = written.and_then(|()| fs::rename(&tmp, out)) {
+        let _ = fs::remove_file(&tmp);
+        return Err(output::describe_error("save", out, &e));
+    }
+    println!(
+        "poster -> {} in {:.1}s",
+        out.display(),
+        started.elapsed().as_secs_f64()
+    );
+    Ok(())
+}
+
+/// Rejects settings whose pixels depend on more of the frame than their
+/// own tile, or on the frame as one image.
+fn check(args: &RenderArgs) -> Result<(), String> {
+    if !args.quadratic_mandelbrot() {
+        return Err("poster renders --fractal mandelbrot with --power 2".to_string());
+    }
+    let unsupported = [
+        (!args.coloring.is_smooth(), "--coloring other than smooth"),
+        (args.boundary_aa > 1, "--boundary-aa"),
+        (args.refine_max_iter.is_some(), "--refine-max-iter"),
+        (args.deflicker > 1, "--deflicker"),
+        (!args.layer.is_empty(), "--layer"),
+        (args.texture.is_some(), "--texture"),
+        (args.grid, "--grid"),
+        (args.legend.is_some(), "--legend"),
+        (args.stats_hud, "--stats-hud"),
+        (args.overlay.is_some(), "--overlay"),
+        (args.canvas.is_some(), "--canvas"),
+    ];
+    match unsupported.iter().find(|(used, _)| *used) {
+        Some((_, flag)) => Err(format!("poster doesn't support {flag}")),
+        None => Ok(()),
+    }
+}
+
+/// Writes an 8-bit RGB PNG a band of rows at a time.
+struct RowWriter<W: Write> {
+    zlib: ZlibEncoder<Idat<W>>,
+    row_bytes: usize,
+    filtered: Vec<u8>,
+}
+
+impl<W: Write> RowWriter<W> {
+    fn new(mut out: W, width: u32, height: u32) -> io::Result<RowWriter<W>> {
+        out.write_all(b"\x89PNG\r\n\x1a\n")?;
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&width.to_be_bytes());
+        header.extend_from_slice(&height.to_be_bytes());
+        // 8 bits per channel, RGB, deflate, adaptive filtering, no interlace.
+        header.extend_from_slice(&[8, 2, 0, 0, 0]);
+        write_chunk(&mut out, b"IHDR", &header)?;
+        let idat = Idat {
+            out,
+            pending: Vec::with_capacity(IDAT_SIZE),
+        };
+        Ok(RowWriter {
+            zlib: ZlibEncoder::new(idat, Compression::default()),
+            row_bytes: width as usize * 3,
+            filtered: Vec::new(),
+        })
+    }
+
+    /// Appends whole rows of RGB bytes, each with the Sub filter, which
+    /// needs no row but its own.
+    fn write_rows(&mut self, rgb: &[u8]) -> io::Result<()> {
+        for row in rgb.chunks(self.row_bytes) {
+            self.filtered.clear();
+            self.filtered.push(1);
+            self.filtered.extend_from_slice(&row[..3]);
+            self.filtered
+                .extend(row.windows(4).map(|w| w[3].wrapping_sub(w[0])));
+            self.zlib.write_all(&self.filtered)?;
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> io::Result<W> {
+        let mut idat = self.zlib.finish()?;
+        idat.flush_chunk()?;
+        write_chunk(&mut idat.out, b"IEND", &[])?;
+        Ok(idat.out)
+    }
+}
+
+/// Cuts the compressed stream into IDAT chunks as it arrives.
+struct Idat<W: Write> {
+    out: W,
+    pending: Vec<u8>,
+}
+
+impl<W: Write> Idat<W> {
+    fn flush_chunk(&mut self) -> io::Result<()> {
+        if !self.pending.is_empty() {
+            write_chunk(&mut self.out, b"IDAT", &self.pending)?;
+            self.pending.clear();
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for Idat<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = buf.len().min(IDAT_SIZE - self.pending.len());
+        self.pending.extend_from_slice(&buf[..n]);
+        if self.pending.len() == IDAT_SIZE {
+            self.flush_chunk()?;
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+fn write_chunk(out: &mut impl Write, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    let mut crc = Crc::new();
+    crc.update(kind);
+    crc.update(data);
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    out.write_all(kind)?;
+    out.write_all(data)?;
+    out.write_all(&crc.sum().to_be_bytes())
+}
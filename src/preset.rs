@@ -0,0 +1,92 @@
+use crate::config::Config;
+use crate::encode;
+use std::str::FromStr;
+
+/// `--preset`: frame size, frame rate and encoder settings for a delivery
+/// target. The zoom spans the shorter side of the frame, so a vertical or
+/// square frame shows the same detail across as a landscape one does in
+/// height, with more of the path above and below.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    /// 9:16 for Shorts, Reels and TikTok.
+    Vertical,
+    /// 1:1 for feeds.
+    Square,
+    /// 3840x2160 at 60 fps.
+    Uhd,
+}
+
+impl FromStr for Preset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Preset, String> {
+        match s {
+            "vertical-1080x1920" => Ok(Preset::Vertical),
+            "square-1080" => Ok(Preset::Square),
+            "4k-uhd" => Ok(Preset::Uhd),
+            other => Err(format!(
+                "unknown preset {other:?}, expected vertical-1080x1920, square-1080 or 4k-uhd"
+            )),
+        }
+    }
+}
+
+impl Preset {
+    /// The render settings the preset stands for, applied like a config file
+    /// beneath the command line, the environment and --config.
+    pub fn config(self) -> Config {
+        let (width, height, fps) = match self {
+            Preset::Vertical => (1080, 1920, 30),
+            Preset::Square => (1080, 1080, 30),
+            Preset::Uhd => (3840, 2160, 60),
+        };
+        [("width", width), ("height", height), ("fps", fps)]
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), vec![value.to_string()]))
+            .collect()
+    }
+
+    /// ffmpeg output options: H.264 in the quality the platforms re-encode
+    /// well from, with the index up front so uploads can start playing early.
+    fn encoder_args(self) -> &'static [&'static str] {
+        match self {
+            Preset::Vertical | Preset::Square => &[
+                "-c:v",
+                "libx264",
+                "-profile:v",
+                "high",
+                "-crf",
+                "18",
+                "-pix_fmt",
+                "yuv420p",
+                "-movflags",
+                "+faststart",
+            ],
+            Preset::Uhd => &[
+                "-c:v",
+                "libx264",
+                "-preset",
+                "slow",
+                "-crf",
+                "16",
+                "-pix_fmt",
+                "yuv420p",
+                "-movflags",
+                "+faststart",
+            ],
+        }
+    }
+}
+
+/// ffmpeg output options for `output`, tuned for `preset` if there is one.
+pub fn output_args(preset: Option<Preset>, output: &str) -> Vec<&str> {
+    match preset {
+        Some(preset) => preset
+            .encoder_args()
+            .iter()
+            .copied()
+            .chain([output])
+            .collect(),
+        None => encode::h264_args(output).to_vec(),
+    }
+}
@@ -0,0 +1,52 @@
+use crate::encode::{self, FfmpegPipe};
+use crate::letterbox::{self, Canvas};
+use crate::{RenderArgs, fixed_path, frame_info, iterate};
+use std::fs;
+use std::path::Path;
+
+/// Renders the whole animation at reduced resolution straight into an H.264 file,
+/// on the --canvas scaled alike and with --safe-area guides drawn over it.
+pub fn render_preview(args: &RenderArgs, output: &Path) -> Result<(), String> {
+    if let Some(parent) = output.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("create {parent:?}: {e}"))?;
+    }
+    let scale = args.preview_scale.clamp(0.01, 1.0);
+    let width = even_dimension(args.width, scale);
+    let height = even_dimension(args.height, scale);
+    let canvas = args
+        .canvas
+        .map_or(Canvas { width, height }, |canvas| Canvas {
+            width: even_dimension(canvas.width, scale),
+            height: even_dimension(canvas.height, scale),
+        });
+    let output = output.to_string_lossy();
+    let mut pipe = FfmpegPipe::spawn(
+        canvas.width,
+        canvas.height,
+        args.fps,
+        &encode::h264_args(&output),
+    )?;
+
+    let path = fixed_path(args.fractal);
+    let total_frames = args.total_frames();
+    for frame in 0..total_frames {
+        let info = frame_info(args, &path, frame);
+        let img = iterate(args, &info, width, height, info.max_iter).colorize(&args.palette);
+        let mut img = canvas.apply(img);
+        if args.safe_area {
+            letterbox::draw_safe_areas(&mut img);
+        }
+        pipe.write_frame(&img)?;
+        if (frame + 1) % 10 == 0 || frame + 1 == total_frames {
+            eprintln!("preview {}/{}", frame + 1, total_frames);
+        }
+    }
+    pipe.finish()?;
+    eprintln!("preview video -> {output}");
+    Ok(())
+}
+
+fn even_dimension(size: u32, scale: f64) -> u32 {
+    let scaled = (size as f64 * scale).round() as u32;
+    (scaled / 2 * 2).max(2)
+}
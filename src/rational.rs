@@ -0,0 +1,266 @@
+use crate::render::{Complex, INTERIOR, IterationBuffer};
+use std::str::FromStr;
+
+/// Below this `|denominator|^2` the orbit is treated as having hit a pole.
+const POLE_EPSILON: f64 = 1e-24;
+
+/// A polynomial in `z` whose coefficients may contain the parameter `c`, e.g.
+/// `z^3 + c`, `0.5*z^2 - (0.2+0.1i)*z + c*z` or `-i*c`.
+#[derive(Debug, Clone)]
+pub struct Poly {
+    terms: Vec<Term>,
+}
+
+/// `coef * c^c_pow * z^z_pow`
+#[derive(Debug, Clone, Copy)]
+struct Term {
+    coef: Complex,
+    c_pow: u32,
+    z_pow: u32,
+}
+
+impl Poly {
+    pub fn degree(&self) -> u32 {
+        self.terms.iter().map(|t| t.z_pow).max().unwrap_or(0)
+    }
+
+    /// Dense coefficients in `z` for a fixed `c`, lowest power first.
+    pub fn at_c(&self, c: Complex) -> Vec<Complex> {
+        let mut coefs = vec![Complex::ZERO; self.degree() as usize + 1];
+        for term in &self.terms {
+            let mut k = term.coef;
+            for _ in 0..term.c_pow {
+                k = k.mul(c);
+            }
+            coefs[term.z_pow as usize] = coefs[term.z_pow as usize].add(k);
+        }
+        coefs
+    }
+}
+
+pub fn horner(coefs: &[Complex], z: Complex) -> Complex {
+    coefs
+        .iter()
+        .rev()
+        .fold(Complex::ZERO, |acc, &k| acc.mul(z).add(k))
+}
+
+impl FromStr for Poly {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Poly, String> {
+        parse_poly(s).map_err(|e| format!("polynomial {s:?}: {e}"))
+    }
+}
+
+fn parse_poly(s: &str) -> Result<Poly, String> {
+    let chars: Vec<char> = s.chars().filter(|c| !c.is_whitespace()).collect();
+    if chars.is_empty() {
+        return Err("empty".to_string());
+    }
+    let mut terms = Vec::new();
+    let mut pos = 0;
+    while pos < chars.len() {
+        let mut sign = 1.0;
+        if pos > 0 || matches!(chars[pos], '+' | '-') {
+            match chars[pos] {
+                '+' => {}
+                '-' => sign = -1.0,
+                other => return Err(format!("expected + or -, found {other:?}")),
+            }
+            pos += 1;
+        }
+        let end = term_end(&chars, pos);
+        let mut term = parse_term(&chars[pos..end].iter().collect::<String>())?;
+        term.coef = Complex {
+            re: term.coef.re * sign,
+            im: term.coef.im * sign,
+        };
+        terms.push(term);
+        pos = end;
+    }
+    Ok(Poly { terms })
+}
+
+/// Index of the `+`/`-` that ends the term starting at `start`, skipping
+/// signs inside parentheses, after `e` exponents and right after `*` or `^`.
+fn term_end(chars: &[char], start: usize) -> usize {
+    let mut depth = 0;
+    for i in start..chars.len() {
+        match chars[i] {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            '+' | '-' if depth == 0 && i > start => {
+                let prev = chars[i - 1];
+                let exponent = matches!(prev, 'e' | 'E')
+                    && i >= 2
+                    && (chars[i - 2].is_ascii_digit() || chars[i - 2] == '.');
+                if !exponent && prev != '*' && prev != '^' {
+                    return i;
+                }
+            }
+            _ => {}
+        }
+    }
+    chars.len()
+}
+
+fn parse_term(text: &str) -> Result<Term, String> {
+    let mut term = Term {
+        coef: Complex { re: 1.0, im: 0.0 },
+        c_pow: 0,
+        z_pow: 0,
+    };
+    for factor in text.split('*') {
+        let (base, power) = match factor.split_once('^') {
+            Some((base, power)) => (
+                base,
+                power
+                    .parse::<u32>()
+                    .map_err(|_| format!("invalid power {power:?}"))?,
+            ),
+            None => (factor, 1),
+        };
+        match base {
+            "" => return Err(format!("empty factor in {text:?}")),
+            "z" => term.z_pow += power,
+            "c" => term.c_pow += power,
+            _ => {
+                let mut k = parse_complex(base)?;
+                if power != 1 {
+                    let base_k = k;
+                    for _ in 1..power {
+                        k = k.mul(base_k);
+                    }
+                }
+                term.coef = term.coef.mul(k);
+            }
+        }
+    }
+    Ok(term)
+}
+
+/// Parses `0.5`, `-2i`, `i`, `(0.1-0.3i)` or `0.1-0.3i`.
+pub fn parse_complex(s: &str) -> Result<Complex, String> {
+    let s: String = s.chars().filter(|c| !c.is_whitespace()).collect();
+    let s = s
+        .strip_prefix('(')
+        .and_then(|v| v.strip_suffix(')'))
+        .unwrap_or(&s);
+    let invalid = || format!("invalid complex number {s:?}");
+    let imaginary = |part: &str| -> Result<f64, String> {
+        match part {
+            "" | "+" => Ok(1.0),
+            "-" => Ok(-1.0),
+            _ => part.parse().map_err(|_| invalid()),
+        }
+    };
+    let Some(body) = s.strip_suffix('i') else {
+        return Ok(Complex {
+            re: s.parse().map_err(|_| invalid())?,
+            im: 0.0,
+        });
+    };
+    // The sign that splits real and imaginary parts, ignoring a leading sign and exponents.
+    let split = body.char_indices().rev().find(|&(i, c)| {
+        matches!(c, '+' | '-') && i > 0 && !matches!(body.as_bytes()[i - 1], b'e' | b'E')
+    });
+    match split {
+        Some((i, _)) => Ok(Complex {
+            re: body[..i].parse().map_err(|_| invalid())?,
+            im: imaginary(&body[i..])?,
+        }),
+        None => Ok(Complex {
+            re: 0.0,
+            im: imaginary(body)?,
+        }),
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Plane {
+    /// Every pixel is a starting point `z0` for one fixed `c` (a Julia-type set).
+    Dynamic,
+    /// Every pixel is a parameter `c`, iterated from one fixed `z0`.
+    Parameter,
+}
+
+impl FromStr for Plane {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Plane, String> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "dynamic" => Ok(Plane::Dynamic),
+            "parameter" => Ok(Plane::Parameter),
+            _ => Err(format!(
+                "unknown plane {s:?}, expected dynamic or parameter"
+            )),
+        }
+    }
+}
+
+/// `z -> numerator(z, c) / denominator(z, c)`, e.g. `(z^3 + c) / z` for `z^2 + c/z`.
+#[derive(Debug, Clone)]
+pub struct RationalMap<'a> {
+    pub numerator: &'a Poly,
+    pub denominator: &'a Poly,
+    pub plane: Plane,
+    /// Fixed parameter in the dynamic plane.
+    pub c: Complex,
+    /// Fixed starting point in the parameter plane.
+    pub z0: Complex,
+    pub bailout: f64,
+}
+
+impl RationalMap<'_> {
+    /// Escape-time values of the map at the point `point` gives each pixel.
+    /// Orbits that grow past the bailout or land on a pole of the denominator
+    /// count as escaped.
+    pub fn iterate(
+        &self,
+        width: u32,
+        height: u32,
+        max_iter: u32,
+        point: impl Fn(u32, u32) -> Complex + Sync,
+    ) -> IterationBuffer {
+        let fixed = match self.plane {
+            Plane::Dynamic => Some((self.numerator.at_c(self.c), self.denominator.at_c(self.c))),
+            Plane::Parameter => None,
+        };
+        let degree = self.numerator.degree() as f64 - self.denominator.degree() as f64;
+        let log_bailout = self.bailout.max(2.0).ln();
+        let bailout_sqr = self.bailout.max(2.0).powi(2);
+
+        IterationBuffer::from_fn(width, height, max_iter, |x, y| {
+            let p = point(x, y);
+            let per_pixel;
+            let (num, den, mut z) = match &fixed {
+                Some((num, den)) => (num, den, p),
+                None => {
+                    per_pixel = (self.numerator.at_c(p), self.denominator.at_c(p));
+                    (&per_pixel.0, &per_pixel.1, self.z0)
+                }
+            };
+            for iter in 0..max_iter {
+                let d = horner(den, z);
+                if d.norm_sqr() < POLE_EPSILON {
+                    return iter as f64;
+                }
+                z = horner(num, z).div(d);
+                let r2 = z.norm_sqr();
+                if !r2.is_finite() {
+                    return iter as f64;
+                }
+                if r2 > bailout_sqr {
+                    if degree <= 1.0 {
+                        return iter as f64;
+                    }
+                    let log_r = r2.ln() / 2.0;
+                    let smooth = iter as f64 + 1.0 - (log_r / log_bailout).ln() / degree.ln();
+                    return smooth.max(0.0);
+                }
+            }
+            INTERIOR
+        })
+    }
+}
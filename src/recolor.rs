@@ -0,0 +1,99 @@
+use crate::deflicker::Deflicker;
+use crate::iterfile;
+use crate::manifest::Manifest;
+use crate::palette::Palette;
+use crate::{RenderArgs, base_colors, decorate, encode_png, fixed_path, frame_info, output};
+use std::fs;
+use std::path::Path;
+
+#[derive(clap::Args, Debug)]
+pub struct RecolorArgs {
+    /// Frames of a run rendered with --save-iterations.
+    #[arg(long, default_value = "out/frames")]
+    run_dir: String,
+    /// Built-in palette name or comma-separated `#rrggbb` gradient stops,
+    /// in place of the run's; every other setting comes from its manifest.
+    #[arg(long, default_value = "classic")]
+    palette: Palette,
+    /// Gamma of the recolored frames: every channel becomes
+    /// `255 * (c / 255)^(1 / gamma)`, so values above 1 brighten the darker
+    /// colors and values below 1 deepen them.
+    #[arg(long, default_value_t = 1.0)]
+    gamma: f64,
+    /// Where the recolored frames go.
+    #[arg(long, default_value = "out/recolored")]
+    out_dir: String,
+}
+
+pub fn run(args: RecolorArgs) -> Result<(), String> {
+    if !(args.gamma > 0.0 && args.gamma.is_finite()) {
+        return Err(format!("--gamma must be positive, got {}", args.gamma));
+    }
+    let dir = output::resolve_dir(Path::new(&args.run_dir))?;
+    let manifest = Manifest::read(&dir)?;
+    let render_args = RenderArgs {
+        palette: args.palette.clone(),
+        ..manifest.render_args()?
+    };
+    let src = dir.join(iterfile::ITERATIONS_DIR);
+    let mut files: Vec<_> = fs::read_dir(&src)
+        .map_err(|e| format!("read {src:?}: {e}"))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "iter"))
+        .collect();
+    files.sort();
+    if files.is_empty() {
+        return Err(format!("no .iter files in {src:?}"));
+    }
+
+    let out_dir = output::resolve_dir(Path::new(&args.out_dir))?;
+    fs::create_dir_all(&out_dir).map_err(|e| output::describe_error("create", &out_dir, &e))?;
+    let path = fixed_path(render_args.fractal);
+    let new_deflicker = || Deflicker::new(render_args.deflicker, render_args.deflicker_tolerance);
+    let mut deflicker = new_deflicker();
+    let mut previous = None;
+    for (i, file) in files.iter().enumerate() {
+        let index: u32 = file
+            .file_stem()
+            .and_then(|stem| stem.to_str()?.strip_prefix("frame_")?.parse().ok())
+            .ok_or_else(|| format!("{file:?}: can't tell which frame this is"))?;
+        let mut frame_args = render_args.clone();
+        if let Some(max_iter) = manifest.frame_max_iter(index) {
+            frame_args.max_iter = max_iter;
+        }
+        let iterations = iterfile::load(file)?;
+        let info = frame_info(&frame_args, &path, index);
+        // --deflicker blends with the frames just before; after a gap in the
+        // saved frames it starts over.
+        if previous.is_some_and(|p: u32| p + 1 != index) {
+            deflicker = new_deflicker();
+        }
+        previous = Some(index);
+        let img = deflicker.apply(
+            &iterations,
+            base_colors(&frame_args, &iterations, &info, &frame_args.palette),
+        );
+        let mut img = decorate(&frame_args, img, &iterations, &info, &frame_args.palette);
+        if args.gamma != 1.0 {
+            let curve = gamma_curve(args.gamma);
+            for channel in img.iter_mut() {
+                *channel = curve[*channel as usize];
+            }
+        }
+        let filepath = out_dir.join(file.with_extension("png").file_name().unwrap());
+        let bytes = encode_png(&img)?;
+        fs::write(&filepath, bytes).map_err(|e| output::describe_error("save", &filepath, &e))?;
+        println!(
+            "recolor {}/{} -> {}",
+            i + 1,
+            files.len(),
+            filepath.display()
+        );
+    }
+    Ok(())
+}
+
+/// Each channel value after `gamma`, looked up rather than computed per pixel.
+fn gamma_curve(gamma: f64) -> [u8; 256] {
+    std::array::from_fn(|c| (255.0 * (c as f64 / 255.0).powf(1.0 / gamma)).round() as u8)
+}
@@ -0,0 +1,201 @@
+use crate::manifest::{FrameEntry, Manifest};
+use crate::output::FrameWriter;
+use crate::{encode_png, output, sha256};
+use image::{ImageBuffer, Rgb, imageops};
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+#[derive(clap::Args, Debug)]
+pub struct ReframeArgs {
+    /// Directory holding the frames and their run manifest.
+    #[arg(long, default_value = "out/frames")]
+    run_dir: String,
+    /// Aspect ratio of the crop, `W:H`.
+    #[arg(long, default_value = "9:16")]
+    aspect: Aspect,
+    /// Frames on either side whose crop positions are averaged, so the crop
+    /// glides along with the detail instead of jumping; 0 disables.
+    #[arg(long, default_value_t = 15)]
+    smoothing: u32,
+    /// Where the cropped frames and their manifest go.
+    #[arg(long, alias = "output", default_value = "out/vertical")]
+    out_dir: String,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Aspect {
+    width: u32,
+    height: u32,
+}
+
+impl FromStr for Aspect {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Aspect, String> {
+        let ratio = s
+            .split_once(':')
+            .and_then(|(w, h)| Some((w.trim().parse().ok()?, h.trim().parse().ok()?)))
+            .filter(|&(w, h)| w > 0 && h > 0);
+        let Some((width, height)) = ratio else {
+            return Err(format!("invalid aspect {s:?}, expected W:H such as 9:16"));
+        };
+        Ok(Aspect { width, height })
+    }
+}
+
+/// Crops every main frame of a run to `--aspect` where it is most detailed,
+/// without rendering anything again. The crop spans the full height (or
+/// width) of the frames and slides along the other side; its position per
+/// frame is the window with the most local contrast, smoothed over time.
+pub fn run(args: ReframeArgs) -> Result<(), String> {
+    let dir = output::resolve_dir(Path::new(&args.run_dir))?;
+    let source = Manifest::read(&dir)?;
+    let mut frames: Vec<&FrameEntry> = source
+        .frames
+        .iter()
+        .filter(|f| f.variant.is_none())
+        .collect();
+    frames.sort_by_key(|f| f.index);
+    if frames.is_empty() {
+        return Err(format!("no frames in {dir:?}"));
+    }
+
+    // First pass: where each frame wants its crop.
+    let mut size = None;
+    let mut starts = Vec::with_capacity(frames.len());
+    for entry in &frames {
+        let img = load(&dir.join(&entry.file))?;
+        let (crop, horizontal) = *size.get_or_insert_with(|| crop_size(&img, args.aspect));
+        if !crop_fits(&img, crop) {
+            return Err(format!(
+                "{}: {}x{} differs from the first frame",
+                entry.file,
+                img.width(),
+                img.height()
+            ));
+        }
+        let profile = detail_profile(&img, horizontal);
+        let window = if horizontal { crop.0 } else { crop.1 };
+        starts.push(best_window(&profile, window as usize) as f64);
+    }
+    let ((crop_w, crop_h), horizontal) = size.expect("at least one frame");
+    let starts = smooth(&starts, args.smoothing as usize);
+
+    // Second pass: crop and write.
+    let out_dir = output::resolve_dir(Path::new(&args.out_dir))?;
+    fs::create_dir_all(&out_dir).map_err(|e| output::describe_error("create", &out_dir, &e))?;
+    let mut writer = FrameWriter::new(out_dir.clone(), None, true);
+    let mut manifest = Manifest::create(&mut writer, source.settings.clone())?;
+    for (i, (entry, start)) in frames.iter().zip(&starts).enumerate() {
+        let img = load(&dir.join(&entry.file))?;
+        let start = start.round() as u32;
+        let (x, y) = if horizontal { (start, 0) } else { (0, start) };
+        let cropped = imageops::crop_imm(&img, x, y, crop_w, crop_h).to_image();
+        let file = Path::new(&entry.file)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| entry.file.clone());
+        let bytes = encode_png(&cropped)?;
+        let filepath = writer.write(&file, &bytes)?;
+        manifest.push_frame(
+            &mut writer,
+            FrameEntry {
+                index: entry.index,
+                variant: None,
+                file: manifest.relative(&filepath),
+                sha256: sha256::hex_digest(&bytes),
+            },
+        )?;
+        println!(
+            "reframe {}/{} -> {}",
+            i + 1,
+            frames.len(),
+            filepath.display()
+        );
+    }
+    println!(
+        "{} frames cropped to {crop_w}x{crop_h} -> {}",
+        frames.len(),
+        out_dir.display()
+    );
+    Ok(())
+}
+
+fn load(path: &Path) -> Result<ImageBuffer<Rgb<u8>, Vec<u8>>, String> {
+    Ok(image::open(path)
+        .map_err(|e| format!("read {path:?}: {e}"))?
+        .to_rgb8())
+}
+
+/// The largest crop of `aspect` inside the frame, kept to even sides for
+/// H.264, and whether it slides horizontally.
+fn crop_size(img: &ImageBuffer<Rgb<u8>, Vec<u8>>, aspect: Aspect) -> ((u32, u32), bool) {
+    let (w, h) = img.dimensions();
+    let even = |side: u64, limit: u32| ((side as u32).min(limit) / 2 * 2).max(1);
+    // aspect.width / aspect.height < w / h: narrower than the frame.
+    if aspect.width as u64 * h as u64 <= aspect.height as u64 * w as u64 {
+        let crop_w = h as u64 * aspect.width as u64 / aspect.height as u64;
+        ((even(crop_w, w), h), true)
+    } else {
+        let crop_h = w as u64 * aspect.height as u64 / aspect.width as u64;
+        ((w, even(crop_h, h)), false)
+    }
+}
+
+fn crop_fits(img: &ImageBuffer<Rgb<u8>, Vec<u8>>, crop: (u32, u32)) -> bool {
+    img.width() >= crop.0 && img.height() >= crop.1
+}
+
+/// Local contrast summed per column (`horizontal`) or per row: how much the
+/// brightness changes towards the next pixel right and down.
+fn detail_profile(img: &ImageBuffer<Rgb<u8>, Vec<u8>>, horizontal: bool) -> Vec<f64> {
+    let (w, h) = img.dimensions();
+    let luma = |x: u32, y: u32| {
+        let [r, g, b] = img.get_pixel(x, y).0;
+        0.299 * r as f64 + 0.587 * g as f64 + 0.114 * b as f64
+    };
+    let mut profile = vec![0.0; if horizontal { w } else { h } as usize];
+    for y in 0..h {
+        for x in 0..w {
+            let here = luma(x, y);
+            let mut detail = 0.0;
+            if x + 1 < w {
+                detail += (luma(x + 1, y) - here).abs();
+            }
+            if y + 1 < h {
+                detail += (luma(x, y + 1) - here).abs();
+            }
+            profile[if horizontal { x } else { y } as usize] += detail;
+        }
+    }
+    profile
+}
+
+/// Start of the `window` consecutive entries with the largest sum; the one
+/// nearest the middle among equals, so flat frames stay centered.
+fn best_window(profile: &[f64], window: usize) -> usize {
+    let positions = profile.len().saturating_sub(window) + 1;
+    let middle = (positions - 1) as f64 / 2.0;
+    let mut sum: f64 = profile[..window.min(profile.len())].iter().sum();
+    let mut best = (sum, -middle.abs(), 0);
+    for start in 1..positions {
+        sum += profile[start + window - 1] - profile[start - 1];
+        let candidate = (sum, -(start as f64 - middle).abs(), start);
+        if candidate.0 > best.0 || (candidate.0 == best.0 && candidate.1 > best.1) {
+            best = candidate;
+        }
+    }
+    best.2
+}
+
+/// Centered moving average over `radius` entries on either side, fewer at
+/// the ends.
+fn smooth(values: &[f64], radius: usize) -> Vec<f64> {
+    (0..values.len())
+        .map(|i| {
+            let window = &values[i.saturating_sub(radius)..(i + radius + 1).min(values.len())];
+            window.iter().sum::<f64>() / window.len() as f64
+        })
+        .collect()
+}
@@ -0,0 +1,826 @@
+use crate::anomaly::NAN_COLOR;
+use crate::health;
+use crate::numeric::{ComplexNum, Precision, Real};
+use crate::palette::Palette;
+use crate::warp::Warp;
+use image::{ImageBuffer, Rgb};
+use rayon::prelude::*;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::time::Instant;
+
+/// Points of the plane as every frame computation uses them.
+pub type Complex = ComplexNum<f64>;
+
+/// How per-pixel work is split into parallel jobs. Smaller jobs balance load
+/// better across many cores; larger ones cost less scheduling overhead.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ChunkMode {
+    /// `size` rows per job (default 1).
+    Rows,
+    /// Square tiles of `size` pixels a side (default 32).
+    Tiles,
+    /// Runs of `size` pixels; without a size, rayon splits adaptively.
+    #[default]
+    Pixels,
+}
+
+impl FromStr for ChunkMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<ChunkMode, String> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "rows" => Ok(ChunkMode::Rows),
+            "tiles" => Ok(ChunkMode::Tiles),
+            "pixels" => Ok(ChunkMode::Pixels),
+            _ => Err(format!(
+                "unknown chunking {s:?}, expected rows, tiles or pixels"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Chunking {
+    mode: ChunkMode,
+    size: Option<usize>,
+}
+
+const DEFAULT_TILE: usize = 32;
+
+static CHUNKING: OnceLock<Chunking> = OnceLock::new();
+
+/// Chooses the work splitting used by every later frame computation.
+/// Only the first call takes effect; the default is adaptive splitting that
+/// keeps the pixels of one lane group of either precision together.
+pub fn set_chunking(mode: ChunkMode, size: Option<usize>) {
+    let _ = CHUNKING.set(Chunking {
+        mode,
+        size: size.map(|s| s.max(1)),
+    });
+}
+
+/// Value stored for points that never escaped within `max_iter`.
+pub const INTERIOR: f64 = -1.0;
+
+/// Computes a row-major frame in parallel, split up the way [`set_chunking`]
+/// chose. `span(start, out)` fills `out` with the values of the pixels from
+/// index `start` on; spans never wrap across rows in tile mode. Every
+/// finished row, or tile in tile mode, counts as progress for the
+/// `healthcheck`.
+fn fill(width: u32, height: u32, span: impl Fn(usize, &mut [f64]) + Sync) -> Vec<f64> {
+    let w = width as usize;
+    let row_span = |start: usize, out: &mut [f64]| {
+        span(start, out);
+        if start / w.max(1) != (start + out.len()) / w.max(1) {
+            health::progressed();
+        }
+    };
+    let mut values = vec![0.0; w * height as usize];
+    match CHUNKING.get().copied().unwrap_or_default() {
+        Chunking {
+            mode: ChunkMode::Pixels,
+            size: None,
+        } => values
+            .par_chunks_mut(LANES_F32)
+            .enumerate()
+            .for_each(|(i, chunk)| row_span(i * LANES_F32, chunk)),
+        Chunking {
+            mode: ChunkMode::Pixels,
+            size: Some(size),
+        } => values
+            .par_chunks_mut(size)
+            .enumerate()
+            .for_each(|(i, chunk)| row_span(i * size, chunk)),
+        Chunking {
+            mode: ChunkMode::Rows,
+            size,
+        } => {
+            let rows = size.unwrap_or(1);
+            values
+                .par_chunks_mut((w * rows).max(1))
+                .enumerate()
+                .for_each(|(i, chunk)| row_span(i * w * rows, chunk));
+        }
+        Chunking {
+            mode: ChunkMode::Tiles,
+            size,
+        } => {
+            let side = size.unwrap_or(DEFAULT_TILE);
+            let (tiles_x, tiles_y) = (w.div_ceil(side), (height as usize).div_ceil(side));
+            let tiles: Vec<(usize, usize, Vec<f64>)> = (0..tiles_x * tiles_y)
+                .into_par_iter()
+                .map(|tile| {
+                    let (x0, y0) = (tile % tiles_x * side, tile / tiles_x * side);
+                    let x1 = (x0 + side).min(w);
+                    let y1 = (y0 + side).min(height as usize);
+                    let mut block = vec![0.0; (x1 - x0) * (y1 - y0)];
+                    for (row, line) in block.chunks_mut(x1 - x0).enumerate() {
+                        span((y0 + row) * w + x0, line);
+                    }
+                    health::progressed();
+                    (x0, y0, block)
+                })
+                .collect();
+            for (x0, y0, block) in tiles {
+                let tile_w = side.min(w - x0);
+                for (row, line) in block.chunks(tile_w).enumerate() {
+                    let start = (y0 + row) * w + x0;
+                    values[start..start + tile_w].copy_from_slice(line);
+                }
+            }
+        }
+    }
+    values
+}
+
+/// The point of the plane behind every pixel of a frame, with real and
+/// imaginary parts in separate arrays so kernels can stream through them.
+pub struct PlanePoints {
+    pub width: u32,
+    pub height: u32,
+    pub re: Vec<f64>,
+    pub im: Vec<f64>,
+}
+
+impl PlanePoints {
+    pub fn from_mapping(
+        width: u32,
+        height: u32,
+        point: impl Fn(u32, u32) -> Complex + Sync,
+    ) -> PlanePoints {
+        let w = width as usize;
+        let len = w * height as usize;
+        let (mut re, mut im) = (vec![0.0; len], vec![0.0; len]);
+        re.par_iter_mut()
+            .zip(im.par_iter_mut())
+            .enumerate()
+            .for_each(|(idx, (re, im))| {
+                let c = point((idx % w) as u32, (idx / w) as u32);
+                (*re, *im) = (c.re, c.im);
+            });
+        PlanePoints {
+            width,
+            height,
+            re,
+            im,
+        }
+    }
+}
+
+/// Smooth escape-time values for one frame, kept separate from coloring so a
+/// single iteration pass can be colored any number of times.
+pub struct IterationBuffer {
+    pub width: u32,
+    pub height: u32,
+    pub max_iter: u32,
+    pub values: Vec<f64>,
+}
+
+impl IterationBuffer {
+    /// Fills a buffer in parallel from a per-pixel escape-time function.
+    pub fn from_fn(
+        width: u32,
+        height: u32,
+        max_iter: u32,
+        value: impl Fn(u32, u32) -> f64 + Sync,
+    ) -> IterationBuffer {
+        let w = width as usize;
+        let values = fill(width, height, |start, out| {
+            for (i, v) in out.iter_mut().enumerate() {
+                let idx = start + i;
+                *v = value((idx % w) as u32, (idx / w) as u32);
+            }
+        });
+        IterationBuffer {
+            width,
+            height,
+            max_iter,
+            values,
+        }
+    }
+
+    /// Iterates every point of `points` with the Mandelbrot kernel, handing
+    /// it whole runs of consecutive points at a time.
+    pub fn from_points(
+        points: &PlanePoints,
+        precision: Precision,
+        max_iter: u32,
+        derivative_bailout: bool,
+    ) -> IterationBuffer {
+        let values = fill(points.width, points.height, |start, out| {
+            let end = start + out.len();
+            let (re, im) = (&points.re[start..end], &points.im[start..end]);
+            match precision {
+                Precision::F32 => {
+                    iterate_slice::<f32, LANES_F32>(re, im, out, max_iter, derivative_bailout)
+                }
+                Precision::F64 => {
+                    iterate_slice::<f64, LANES>(re, im, out, max_iter, derivative_bailout)
+                }
+            }
+        });
+        IterationBuffer {
+            width: points.width,
+            height: points.height,
+            max_iter,
+            values,
+        }
+    }
+
+    pub fn colorize(&self, palette: &Palette) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+        self.colorize_with_phase(palette, 0.0)
+    }
+
+    /// Colors with the palette rotated by `phase`, wrapping around its ends.
+    pub fn colorize_with_phase(
+        &self,
+        palette: &Palette,
+        phase: f64,
+    ) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+        let mut img: ImageBuffer<Rgb<u8>, Vec<u8>> = ImageBuffer::new(self.width, self.height);
+        img.as_mut()
+            .par_chunks_mut(3)
+            .zip(self.values.par_iter())
+            .for_each(|(pixel, &value)| {
+                pixel.copy_from_slice(&self.color_of(value, palette, phase));
+            });
+        img
+    }
+
+    /// [`IterationBuffer::colorize_with_phase`] at 16 bits per channel.
+    pub fn colorize16_with_phase(
+        &self,
+        palette: &Palette,
+        phase: f64,
+    ) -> ImageBuffer<Rgb<u16>, Vec<u16>> {
+        let mut img: ImageBuffer<Rgb<u16>, Vec<u16>> = ImageBuffer::new(self.width, self.height);
+        img.as_mut()
+            .par_chunks_mut(3)
+            .zip(self.values.par_iter())
+            .for_each(|(pixel, &value)| {
+                let color = if value.is_nan() {
+                    NAN_COLOR.map(|c| c as u16 * 257)
+                } else if value < 0.0 {
+                    [0, 0, 0]
+                } else {
+                    let t = (value / self.max_iter as f64).clamp(0.0, 1.0);
+                    palette.color16(if phase == 0.0 {
+                        t
+                    } else {
+                        (t + phase).rem_euclid(1.0)
+                    })
+                };
+                pixel.copy_from_slice(&color);
+            });
+        img
+    }
+
+    /// Color of a single escape-time value of this buffer.
+    pub fn color_of(&self, value: f64, palette: &Palette, phase: f64) -> [u8; 3] {
+        if value.is_nan() {
+            NAN_COLOR
+        } else if value < 0.0 {
+            [0, 0, 0]
+        } else {
+            let t = (value / self.max_iter as f64).clamp(0.0, 1.0);
+            if phase == 0.0 {
+                palette.color(t)
+            } else {
+                palette.color((t + phase).rem_euclid(1.0))
+            }
+        }
+    }
+}
+
+pub fn render_frame(
+    width: u32,
+    height: u32,
+    center: Complex,
+    zoom: f64,
+    max_iter: u32,
+    palette: &Palette,
+) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+    iterate_frame(width, height, center, zoom, max_iter, false).colorize(palette)
+}
+
+pub fn iterate_frame(
+    width: u32,
+    height: u32,
+    center: Complex,
+    zoom: f64,
+    max_iter: u32,
+    derivative_bailout: bool,
+) -> IterationBuffer {
+    iterate_points(
+        width,
+        height,
+        max_iter,
+        derivative_bailout,
+        pixel_mapping(width, height, center, zoom),
+    )
+}
+
+/// Time limit for one frame: pixels started after `deadline` are iterated
+/// only up to `reduced_iter` so the frame still finishes close to on time.
+pub struct Budget {
+    pub deadline: Instant,
+    pub reduced_iter: u32,
+}
+
+/// Like [`iterate_points`], but degrades the pixels still left once the
+/// budget runs out. Also returns how many pixels were degraded.
+pub fn iterate_points_within(
+    width: u32,
+    height: u32,
+    max_iter: u32,
+    derivative_bailout: bool,
+    point: impl Fn(u32, u32) -> Complex + Sync,
+    budget: &Budget,
+) -> (IterationBuffer, usize) {
+    let over = AtomicBool::new(false);
+    let degraded = AtomicUsize::new(0);
+    let iterations = IterationBuffer::from_fn(width, height, max_iter, |x, y| {
+        if !over.load(Ordering::Relaxed) && Instant::now() >= budget.deadline {
+            over.store(true, Ordering::Relaxed);
+        }
+        if over.load(Ordering::Relaxed) {
+            degraded.fetch_add(1, Ordering::Relaxed);
+            mandelbrot_smooth(point(x, y), budget.reduced_iter, derivative_bailout)
+        } else {
+            mandelbrot_smooth(point(x, y), max_iter, derivative_bailout)
+        }
+    });
+    (iterations, degraded.into_inner())
+}
+
+/// Re-iterates the pixels still interior after a pass with twice the limit,
+/// and again, until none is left, a pass frees none or the limit reaches
+/// `cap`. Exterior escape times don't depend on the limit, so the buffer ends
+/// up as if iterated with its new `max_iter` throughout. `escape` gives the
+/// smooth escape time of a pixel for a limit.
+pub fn refine_interior(
+    iterations: &mut IterationBuffer,
+    cap: u32,
+    escape: impl Fn(u32, u32, u32) -> f64 + Sync,
+) {
+    let w = iterations.width as usize;
+    let mut pending: Vec<usize> = (0..iterations.values.len())
+        .filter(|i| iterations.values[*i] == INTERIOR)
+        .collect();
+    while !pending.is_empty() && iterations.max_iter < cap {
+        let max_iter = iterations.max_iter.saturating_mul(2).min(cap);
+        let values: Vec<f64> = pending
+            .par_iter()
+            .map(|i| escape((i % w) as u32, (i / w) as u32, max_iter))
+            .collect();
+        for (i, value) in pending.iter().zip(values) {
+            iterations.values[*i] = value;
+        }
+        iterations.max_iter = max_iter;
+        let before = pending.len();
+        pending.retain(|i| iterations.values[*i] == INTERIOR);
+        if pending.len() == before {
+            break;
+        }
+    }
+}
+
+/// Maps pixel coordinates to the plane so that `zoom` is half the shorter image side.
+pub fn pixel_mapping(
+    width: u32,
+    height: u32,
+    center: Complex,
+    zoom: f64,
+) -> impl Fn(u32, u32) -> Complex + Sync {
+    let point = subpixel_mapping(width, height, center, zoom);
+    move |x, y| point(x as f64, y as f64)
+}
+
+/// [`pixel_mapping`] for fractional pixel coordinates.
+pub fn subpixel_mapping(
+    width: u32,
+    height: u32,
+    center: Complex,
+    zoom: f64,
+) -> impl Fn(f64, f64) -> Complex + Sync {
+    rotated_mapping(width, height, center, zoom, 0.0)
+}
+
+/// [`subpixel_mapping`] with the view turned `degrees` counter-clockwise
+/// around `center`. A zero angle maps every pixel to exactly the same point.
+pub fn rotated_mapping(
+    width: u32,
+    height: u32,
+    center: Complex,
+    zoom: f64,
+    degrees: f64,
+) -> impl Fn(f64, f64) -> Complex + Sync {
+    transformed_mapping(width, height, center, zoom, degrees, IDENTITY, Warp::None)
+}
+
+/// A 2×2 matrix `[a, b, c, d]` applied to a pixel's offset from the frame
+/// center before any rotation, taking `(x, y)` to `(a x + b y, c x + d y)`.
+pub type Transform = [f64; 4];
+
+pub const IDENTITY: Transform = [1.0, 0.0, 0.0, 1.0];
+
+/// Parses the four entries of a [`Transform`], row by row: `a,b,c,d`.
+pub fn parse_transform(s: &str) -> Result<Transform, String> {
+    let entries = s
+        .split(',')
+        .map(|entry| entry.trim().parse::<f64>())
+        .collect::<Result<Vec<_>, _>>()
+        .ok()
+        .and_then(|entries| <Transform>::try_from(entries).ok())
+        .filter(|entries| entries.iter().all(|v| v.is_finite()))
+        .ok_or_else(|| format!("invalid transform {s:?}, expected four numbers a,b,c,d"))?;
+    let [a, b, c, d] = entries;
+    if a * d - b * c == 0.0 {
+        return Err(format!(
+            "transform {s:?} flattens the view onto a line, its determinant is 0"
+        ));
+    }
+    Ok(entries)
+}
+
+/// [`rotated_mapping`] with each pixel's offset from the center taken
+/// through `warp` and then `transform` first. [`Warp::None`] and the
+/// identity change no pixel's point.
+pub fn transformed_mapping(
+    width: u32,
+    height: u32,
+    center: Complex,
+    zoom: f64,
+    degrees: f64,
+    transform: Transform,
+    warp: Warp,
+) -> impl Fn(f64, f64) -> Complex + Sync {
+    let half_min = (width.min(height) as f64) / 2.0;
+    let scale = zoom / half_min;
+    let (half_w, half_h) = (width as f64 / 2.0, height as f64 / 2.0);
+    let (sin, cos) = degrees.to_radians().sin_cos();
+    let [a, b, c, d] = transform;
+    let skewed = transform != IDENTITY;
+    move |x, y| {
+        let (mut dx, mut dy) = ((x - half_w) * scale, (y - half_h) * scale);
+        if warp != Warp::None {
+            let offset = Complex {
+                re: (x - half_w) / half_min,
+                im: (y - half_h) / half_min,
+            };
+            let warped = warp.apply(offset, half_w / half_min);
+            (dx, dy) = (warped.re * zoom, warped.im * zoom);
+        }
+        if skewed {
+            (dx, dy) = (a * dx + b * dy, c * dx + d * dy);
+        }
+        Complex {
+            re: (dx * cos - dy * sin) + center.re,
+            im: (dx * sin + dy * cos) + center.im,
+        }
+    }
+}
+
+/// Iterates an arbitrary mapping from pixel coordinates to points of the plane.
+///
+/// With `derivative_bailout`, orbits whose derivative collapses towards zero
+/// stop early as interior instead of running all `max_iter` iterations.
+pub fn iterate_points(
+    width: u32,
+    height: u32,
+    max_iter: u32,
+    derivative_bailout: bool,
+    point: impl Fn(u32, u32) -> Complex + Sync,
+) -> IterationBuffer {
+    iterate_points_in(
+        Precision::F64,
+        width,
+        height,
+        max_iter,
+        derivative_bailout,
+        point,
+    )
+}
+
+/// [`iterate_points`] carried out in `precision`.
+pub fn iterate_points_in(
+    precision: Precision,
+    width: u32,
+    height: u32,
+    max_iter: u32,
+    derivative_bailout: bool,
+    point: impl Fn(u32, u32) -> Complex + Sync,
+) -> IterationBuffer {
+    let points = PlanePoints::from_mapping(width, height, point);
+    IterationBuffer::from_points(&points, precision, max_iter, derivative_bailout)
+}
+
+/// f64 points [`iterate_slice`] iterates together. Two SSE2 registers' worth;
+/// more lanes run out of registers on the baseline x86-64 target.
+pub const LANES: usize = 4;
+/// The same two registers hold twice as many f32 points.
+pub const LANES_F32: usize = 8;
+
+/// Escape-time values of the points `re[i] + im[i]·i`, written to `out[i]`,
+/// computed in the precision of `R`, `N` points at a time; the last few go
+/// through [`mandelbrot_smooth_in`] one by one.
+pub fn iterate_slice<R: Real, const N: usize>(
+    re: &[f64],
+    im: &[f64],
+    out: &mut [f64],
+    max_iter: u32,
+    derivative_bailout: bool,
+) {
+    let mut re = re.chunks_exact(N);
+    let mut im = im.chunks_exact(N);
+    let mut out = out.chunks_exact_mut(N);
+    for ((cr, ci), out) in (&mut re).zip(&mut im).zip(&mut out) {
+        escape_lanes::<R, N>(cr, ci, out, max_iter, derivative_bailout);
+    }
+    let rest = re.remainder().iter().zip(im.remainder());
+    for ((&re, &im), out) in rest.zip(out.into_remainder()) {
+        let c = Complex { re, im };
+        *out = if in_main_bulbs(c) {
+            INTERIOR
+        } else {
+            mandelbrot_smooth_in::<R>(c.cast(), max_iter, derivative_bailout)
+        };
+    }
+}
+
+/// [`mandelbrot_smooth_in`] for `N` points in lockstep. The arithmetic is
+/// written out over whole arrays so the compiler emits vector instructions.
+/// A lane that escapes or is found interior gets its value and is parked on
+/// NaN, which fails every later test, so the common step needs no masks.
+/// Every lane passes the same tests at the same step as the scalar loop, so
+/// the values are identical.
+fn escape_lanes<R: Real, const N: usize>(
+    re: &[f64],
+    im: &[f64],
+    out: &mut [f64],
+    max_iter: u32,
+    derivative_bailout: bool,
+) {
+    let cr: [R; N] = std::array::from_fn(|l| R::from_f64(re[l]));
+    let ci: [R; N] = std::array::from_fn(|l| R::from_f64(im[l]));
+    let (mut zr, mut zi) = ([R::ZERO; N], [R::ZERO; N]);
+    let (mut dzr, mut dzi) = ([R::ONE; N], [R::ZERO; N]);
+    let nan = R::from_f64(f64::NAN);
+    let mut finish = |lane: usize, value: f64, zr: &mut [R; N], zi: &mut [R; N]| {
+        out[lane] = value;
+        (zr[lane], zi[lane]) = (nan, nan);
+    };
+    for lane in 0..N {
+        if in_main_bulbs(Complex {
+            re: re[lane],
+            im: im[lane],
+        }) {
+            finish(lane, INTERIOR, &mut zr, &mut zi);
+        }
+    }
+    // Brent's cycle check; all lanes start together, so they share its schedule.
+    let (mut saved_r, mut saved_i) = ([R::ZERO; N], [R::ZERO; N]);
+    let (mut steps, mut interval) = (0u32, 1u32);
+    let mut iter = 0;
+    // 4 is exact in every precision, so comparing in `R` is comparing in f64.
+    let four = R::from_f64(4.0);
+    // Until it finishes a lane's orbit stays within |z| <= 2 and never NaN.
+    let parked = |z: R| z.partial_cmp(&z).is_none();
+    let mut left = zr.iter().filter(|&&z| !parked(z)).count();
+    while left > 0 {
+        if iter >= max_iter {
+            let live: [bool; N] = std::array::from_fn(|l| !parked(zr[l]));
+            for lane in (0..N).filter(|&l| live[l]) {
+                finish(lane, INTERIOR, &mut zr, &mut zi);
+            }
+            return;
+        }
+        let norm: [R; N] = std::array::from_fn(|l| zr[l] * zr[l] + zi[l] * zi[l]);
+        if any_lane::<N>(|l| norm[l] > four) {
+            for lane in (0..N).filter(|&l| norm[l] > four) {
+                let zn = norm[lane].to_f64().sqrt();
+                finish(
+                    lane,
+                    iter as f64 + 1.0 - (zn.ln().ln() / 2.0_f64.ln()),
+                    &mut zr,
+                    &mut zi,
+                );
+                left -= 1;
+            }
+        }
+        if derivative_bailout && iter > 0 {
+            for lane in 0..N {
+                let (tr, ti) = (zr[lane] + zr[lane], zi[lane] + zi[lane]);
+                (dzr[lane], dzi[lane]) = (
+                    tr * dzr[lane] - ti * dzi[lane],
+                    tr * dzi[lane] + ti * dzr[lane],
+                );
+            }
+            let dnorm: [f64; N] =
+                std::array::from_fn(|l| (dzr[l] * dzr[l] + dzi[l] * dzi[l]).to_f64());
+            if any_lane::<N>(|l| dnorm[l] < DERIVATIVE_EPSILON) {
+                for lane in (0..N).filter(|&l| dnorm[l] < DERIVATIVE_EPSILON) {
+                    finish(lane, INTERIOR, &mut zr, &mut zi);
+                    left -= 1;
+                }
+            }
+        }
+        for lane in 0..N {
+            (zr[lane], zi[lane]) = (
+                zr[lane] * zr[lane] - zi[lane] * zi[lane] + cr[lane],
+                zr[lane] * zi[lane] + zi[lane] * zr[lane] + ci[lane],
+            );
+        }
+        iter += 1;
+        let repeats: [bool; N] =
+            std::array::from_fn(|l| (zr[l] == saved_r[l]) & (zi[l] == saved_i[l]));
+        if any_lane::<N>(|l| repeats[l]) {
+            for lane in (0..N).filter(|&l| repeats[l]) {
+                finish(lane, INTERIOR, &mut zr, &mut zi);
+                left -= 1;
+            }
+        }
+        steps += 1;
+        if steps == interval {
+            (saved_r, saved_i) = (zr, zi);
+            steps = 0;
+            interval = interval.saturating_mul(2);
+        }
+    }
+}
+
+/// Whether `test` holds in any of `N` lanes, without branching per lane, so
+/// the tests stay vector instructions.
+fn any_lane<const N: usize>(test: impl Fn(usize) -> bool) -> bool {
+    let mut any = 0u8;
+    for lane in 0..N {
+        any |= test(lane) as u8;
+    }
+    any != 0
+}
+
+/// Below this `|dz_n/dz_1|^2` the orbit is taken to be converging to an
+/// attracting cycle, so the point is interior.
+pub const DERIVATIVE_EPSILON: f64 = 1e-12;
+
+/// Points of the main cardioid and the period-2 bulb are answered without
+/// iterating; they make up most of the interior of shallow frames.
+pub fn mandelbrot_smooth(c: Complex, max_iter: u32, derivative_bailout: bool) -> f64 {
+    if in_main_bulbs(c) {
+        return INTERIOR;
+    }
+    mandelbrot_smooth_in(c, max_iter, derivative_bailout)
+}
+
+/// Inside the main cardioid or the period-2 bulb, where no orbit escapes.
+pub fn in_main_bulbs(c: Complex) -> bool {
+    let y2 = c.im * c.im;
+    let q = (c.re - 0.25) * (c.re - 0.25) + y2;
+    q * (q + c.re - 0.25) <= 0.25 * y2 || (c.re + 1.0) * (c.re + 1.0) + y2 <= 0.0625
+}
+
+/// [`mandelbrot_smooth`] carried out in the precision of `R`; only the final
+/// smoothing step is done in f64.
+pub fn mandelbrot_smooth_in<R: Real>(
+    c: ComplexNum<R>,
+    max_iter: u32,
+    derivative_bailout: bool,
+) -> f64 {
+    escape_smooth_in(ComplexNum::ZERO, c, max_iter, derivative_bailout)
+}
+
+/// Smooth escape time of `z0` under `z² + c` for the Julia set of `c`.
+pub fn julia_smooth(z0: Complex, c: Complex, max_iter: u32, derivative_bailout: bool) -> f64 {
+    escape_smooth_in(z0, c, max_iter, derivative_bailout)
+}
+
+/// Smooth escape time of `z0` under `z^power + c`: the Multibrot set of
+/// `power` from `z0 = 0`, its Julia sets otherwise. Integer powers multiply,
+/// fractional ones go through polar form on the principal branch.
+pub fn multibrot_smooth(z0: Complex, c: Complex, power: f64, max_iter: u32) -> f64 {
+    // Below 2 the set reaches past |z| = 2; 2^(1/(d-1)) bounds it for any d > 1.
+    let radius = 2.0_f64.max(2.0_f64.powf(1.0 / (power - 1.0)));
+    let escape = radius * radius;
+    let integer = power.fract() == 0.0 && power <= 64.0;
+    let mut z = z0;
+    let mut cycle = CycleCheck::new(z0);
+    let mut iter = 0;
+    while iter < max_iter && z.norm_sqr() <= escape {
+        let zd = if integer {
+            (1..power as u32).fold(z, |acc, _| acc.mul(z))
+        } else {
+            let r = z.norm_sqr().sqrt().powf(power);
+            let (sin, cos) = (z.im.atan2(z.re) * power).sin_cos();
+            Complex {
+                re: r * cos,
+                im: r * sin,
+            }
+        };
+        z = zd.add(c);
+        iter += 1;
+        if cycle.repeats(z) {
+            return INTERIOR;
+        }
+    }
+    if iter >= max_iter {
+        return INTERIOR;
+    }
+    let zn = z.norm_sqr().sqrt();
+    iter as f64 + 1.0 - (zn.ln().ln() / power.ln())
+}
+
+/// Smooth escape time of `c` for the Burning Ship, which folds `z` into the
+/// first quadrant before squaring: `(|Re z| + i|Im z|)² + c`.
+pub fn burning_ship_smooth(c: Complex, max_iter: u32) -> f64 {
+    let mut z = Complex::ZERO;
+    let mut cycle = CycleCheck::new(z);
+    let mut iter = 0;
+    while iter < max_iter && z.norm_sqr() <= 4.0 {
+        let folded = Complex {
+            re: z.re.abs(),
+            im: z.im.abs(),
+        };
+        z = folded.mul(folded).add(c);
+        iter += 1;
+        if cycle.repeats(z) {
+            return INTERIOR;
+        }
+    }
+    if iter >= max_iter {
+        return INTERIOR;
+    }
+    let zn = z.norm_sqr().sqrt();
+    iter as f64 + 1.0 - (zn.ln().ln() / 2.0_f64.ln())
+}
+
+/// Brent's cycle detection along an orbit: one remembered point, compared
+/// with every later one and moved up at doubling intervals. Only exact
+/// repeats count; an orbit that comes back to a value bit for bit goes
+/// round that cycle forever, so it would have reached `max_iter` anyway.
+struct CycleCheck<T> {
+    saved: T,
+    steps: u32,
+    interval: u32,
+}
+
+impl<T: Copy + PartialEq> CycleCheck<T> {
+    fn new(start: T) -> CycleCheck<T> {
+        CycleCheck {
+            saved: start,
+            steps: 0,
+            interval: 1,
+        }
+    }
+
+    /// Whether `z`, the next point of the orbit, closes a cycle.
+    fn repeats(&mut self, z: T) -> bool {
+        if z == self.saved {
+            return true;
+        }
+        self.steps += 1;
+        if self.steps == self.interval {
+            self.saved = z;
+            self.steps = 0;
+            self.interval = self.interval.saturating_mul(2);
+        }
+        false
+    }
+}
+
+fn escape_smooth_in<R: Real>(
+    z0: ComplexNum<R>,
+    c: ComplexNum<R>,
+    max_iter: u32,
+    derivative_bailout: bool,
+) -> f64 {
+    let mut z = z0;
+    let mut dz = ComplexNum {
+        re: R::ONE,
+        im: R::ZERO,
+    };
+    let mut cycle = CycleCheck::new(z0);
+    let mut iter = 0;
+
+    while iter < max_iter && z.norm_sqr().to_f64() <= 4.0 {
+        if derivative_bailout && iter > 0 {
+            dz = z.add(z).mul(dz);
+            if dz.norm_sqr().to_f64() < DERIVATIVE_EPSILON {
+                return INTERIOR;
+            }
+        }
+        z = z.mul(z).add(c);
+        iter += 1;
+        if cycle.repeats(z) {
+            return INTERIOR;
+        }
+    }
+
+    if iter >= max_iter {
+        return INTERIOR;
+    }
+
+    let zn = z.norm_sqr().to_f64().sqrt();
+    iter as f64 + 1.0 - (zn.ln().ln() / 2.0_f64.ln())
+}
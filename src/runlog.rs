@@ -0,0 +1,153 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+static LOG: OnceLock<Mutex<File>> = OnceLock::new();
+
+pub enum Value {
+    Int(i64),
+    Float(f64),
+    Str(String),
+    Bool(bool),
+}
+
+impl From<u32> for Value {
+    fn from(v: u32) -> Value {
+        Value::Int(v as i64)
+    }
+}
+
+impl From<u64> for Value {
+    fn from(v: u64) -> Value {
+        Value::Int(v as i64)
+    }
+}
+
+impl From<usize> for Value {
+    fn from(v: usize) -> Value {
+        Value::Int(v as i64)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(v: f64) -> Value {
+        Value::Float(v)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(v: bool) -> Value {
+        Value::Bool(v)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(v: &str) -> Value {
+        Value::Str(v.to_string())
+    }
+}
+
+impl From<String> for Value {
+    fn from(v: String) -> Value {
+        Value::Str(v)
+    }
+}
+
+/// Opens the run log in append mode; events are written as one JSON object per line.
+pub fn init(path: &Path) -> Result<(), String> {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|e| format!("open log file {path:?}: {e}"))?;
+    LOG.set(Mutex::new(file))
+        .map_err(|_| "log file already initialized".to_string())
+}
+
+pub fn info(event: &str, fields: &[(&str, Value)]) {
+    write("info", event, fields);
+}
+
+pub fn warn(event: &str, fields: &[(&str, Value)]) {
+    write("warn", event, fields);
+}
+
+pub fn error(event: &str, fields: &[(&str, Value)]) {
+    write("error", event, fields);
+}
+
+fn write(level: &str, event: &str, fields: &[(&str, Value)]) {
+    let Some(log) = LOG.get() else {
+        return;
+    };
+    let mut line = format!(
+        "{{\"ts\":\"{}\",\"level\":\"{level}\",\"event\":{}",
+        timestamp(),
+        json_string(event)
+    );
+    for (key, value) in fields {
+        line.push(',');
+        line.push_str(&json_string(key));
+        line.push(':');
+        match value {
+            Value::Int(v) => line.push_str(&v.to_string()),
+            Value::Float(v) if v.is_finite() => line.push_str(&format!("{v:e}")),
+            Value::Float(_) => line.push_str("null"),
+            Value::Str(v) => line.push_str(&json_string(v)),
+            Value::Bool(v) => line.push_str(&v.to_string()),
+        }
+    }
+    line.push_str("}\n");
+    if let Ok(mut file) = log.lock() {
+        let _ = file.write_all(line.as_bytes());
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// RFC 3339 UTC timestamp with millisecond precision.
+fn timestamp() -> String {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let secs = now.as_secs();
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+
+    // Civil-from-days conversion (Howard Hinnant's algorithm).
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z",
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60,
+        now.subsec_millis()
+    )
+}
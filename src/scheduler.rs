@@ -0,0 +1,645 @@
+use crate::numeric::Precision;
+use crate::render::{self, Complex, IterationBuffer};
+use crate::warp::Warp;
+use crate::{
+    Fractal, FrameInfo, RenderArgs, buddhabrot, cascade, flame, newton, perturbation, rational,
+    subdivide, timeline, track_or,
+};
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::str::FromStr;
+
+/// Something that can turn a frame's view into iteration data.
+pub trait Backend: Sync {
+    fn name(&self) -> &'static str;
+
+    /// Whether this backend can iterate the frame of `info` with these settings.
+    fn supports(&self, args: &RenderArgs, info: &FrameInfo) -> bool;
+
+    fn iterate(
+        &self,
+        args: &RenderArgs,
+        info: &FrameInfo,
+        width: u32,
+        height: u32,
+        max_iter: u32,
+    ) -> IterationBuffer;
+}
+
+/// Every backend, in the order `--backend auto` prefers them; `scalar` and
+/// `subdivide` are never reached that way, they support nothing `soa` doesn't.
+const BACKENDS: [&dyn Backend; 13] = [
+    &Perturbation,
+    &Cascade,
+    &Vectorized,
+    &Scalar,
+    &Subdivide,
+    &Multibrot,
+    &Julia,
+    &BurningShip,
+    &Newton,
+    &Buddhabrot,
+    &Formula,
+    &FlameBackend,
+    &Rational,
+];
+
+/// `--backend`: let the scheduler pick per frame, or always use one backend.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Choice {
+    Auto,
+    Fixed(&'static str),
+}
+
+impl FromStr for Choice {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Choice, String> {
+        let name = s.trim().to_ascii_lowercase();
+        if name == "auto" {
+            return Ok(Choice::Auto);
+        }
+        BACKENDS
+            .iter()
+            .find(|b| b.name() == name)
+            .map(|b| Choice::Fixed(b.name()))
+            .ok_or_else(|| {
+                let names: Vec<&str> = BACKENDS.iter().map(|b| b.name()).collect();
+                format!(
+                    "unknown backend {name:?}, expected auto or one of {}",
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// The backend that iterates this frame. A fixed backend that can't handle
+/// a particular frame (a rotated one for `cascade`) leaves it to `auto`.
+pub fn backend_for(args: &RenderArgs, info: &FrameInfo) -> &'static dyn Backend {
+    if let Choice::Fixed(name) = args.backend
+        && let Some(backend) = BACKENDS
+            .iter()
+            .find(|b| b.name() == name && b.supports(args, info))
+    {
+        return *backend;
+    }
+    BACKENDS
+        .iter()
+        .find(|b| b.supports(args, info))
+        .copied()
+        .expect("every fractal has a backend")
+}
+
+/// Rejects a fixed backend that can't render the chosen fractal at all.
+pub fn check(args: &RenderArgs, info: &FrameInfo) -> Result<(), String> {
+    match args.backend {
+        Choice::Fixed(name) if backend_for(args, info).name() != name => Err(format!(
+            "--backend {name} can't render this --fractal with these settings"
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// In which order the frames of a run are rendered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Order {
+    Sequential,
+    /// First and last frame, then the middle one, then the middles of the
+    /// halves and so on, so an interrupted run still covers the whole animation.
+    Bisect,
+}
+
+impl FromStr for Order {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Order, String> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "sequential" => Ok(Order::Sequential),
+            "bisect" => Ok(Order::Bisect),
+            other => Err(format!(
+                "unknown frame order {other:?}, expected sequential or bisect"
+            )),
+        }
+    }
+}
+
+impl Order {
+    /// The frames of `range` in this order; `Bisect` splits the range alone.
+    pub fn frames(self, range: Range<u32>) -> Box<dyn Iterator<Item = u32> + Send> {
+        match self {
+            Order::Sequential => Box::new(range),
+            Order::Bisect => {
+                let start = range.start;
+                Box::new(
+                    bisect(range.len() as u32)
+                        .into_iter()
+                        .map(move |k| start + k),
+                )
+            }
+        }
+    }
+}
+
+fn bisect(total: u32) -> Vec<u32> {
+    if total <= 2 {
+        return (0..total).collect();
+    }
+    let mut order = vec![0, total - 1];
+    let mut pending = VecDeque::from([(0, total - 1)]);
+    while let Some((lo, hi)) = pending.pop_front() {
+        if hi - lo < 2 {
+            continue;
+        }
+        let mid = lo + (hi - lo) / 2;
+        order.push(mid);
+        pending.push_back((lo, mid));
+        pending.push_back((mid, hi));
+    }
+    order
+}
+
+/// Deep Mandelbrot frames, one high-precision orbit of the center and f64
+/// differences from it for the pixels, see [`perturbation::Reference`].
+struct Perturbation;
+
+impl Backend for Perturbation {
+    fn name(&self) -> &'static str {
+        "perturbation"
+    }
+
+    /// Only deep frames under `auto`; forced, any frame it can render.
+    fn supports(&self, args: &RenderArgs, info: &FrameInfo) -> bool {
+        args.quadratic_mandelbrot()
+            && (args.backend == Choice::Fixed(self.name()) || deep(args, info))
+    }
+
+    fn iterate(
+        &self,
+        args: &RenderArgs,
+        info: &FrameInfo,
+        width: u32,
+        height: u32,
+        max_iter: u32,
+    ) -> IterationBuffer {
+        perturbation::iterate(
+            &reference(args, info, width, height, max_iter),
+            width,
+            height,
+            perturbation::frame_offsets(
+                width,
+                height,
+                info.zoom,
+                info.rotation,
+                info.transform,
+                info.warp,
+            ),
+            max_iter,
+            args.derivative_bailout,
+        )
+    }
+}
+
+/// Whether f64 pixel coordinates no longer resolve the frame, or its
+/// center has more digits than they keep.
+fn deep(args: &RenderArgs, info: &FrameInfo) -> bool {
+    args.deep_center.is_some() || past_f64(args, info)
+}
+
+/// Whether neighboring pixels of the frame are too close for f64 to tell
+/// their coordinates apart well; backends other than perturbation render
+/// such frames blocky.
+pub fn past_f64(args: &RenderArgs, info: &FrameInfo) -> bool {
+    let scale = perturbation::pixel_scale(args.width, args.height, info.zoom);
+    scale < perturbation::DEEP_SCALE * info.center.norm_sqr().sqrt()
+}
+
+/// [`DEEP_SCALE`](perturbation::DEEP_SCALE) for f32 pixel coordinates:
+/// neighboring pixels about four f32 steps apart. A few frames later they
+/// round onto the same coordinates and the frame falls apart into blocks.
+const DEEP_SCALE_F32: f64 = 5e-7;
+
+/// [`past_f64`] for `--precision f32`, which gets there after four or five
+/// tenfold zooms at 1080p.
+pub fn past_f32(args: &RenderArgs, info: &FrameInfo) -> bool {
+    let scale = perturbation::pixel_scale(args.width, args.height, info.zoom);
+    scale < DEEP_SCALE_F32 * info.center.norm_sqr().sqrt()
+}
+
+/// The reference orbit of the frame's center, long enough for `max_iter`,
+/// with the series for the iterations its pixels share.
+pub fn reference(
+    args: &RenderArgs,
+    info: &FrameInfo,
+    width: u32,
+    height: u32,
+    max_iter: u32,
+) -> perturbation::Reference {
+    let mut reference = perturbation::Reference::new(
+        info.center,
+        args.deep_center.as_ref(),
+        perturbation::pixel_scale(width, height, info.zoom),
+        max_iter,
+        args.orbit_store().as_ref(),
+    );
+    reference.approximate(
+        &perturbation::probes(
+            width,
+            height,
+            info.zoom,
+            info.rotation,
+            info.transform,
+            info.warp,
+        ),
+        max_iter,
+    );
+    reference
+}
+
+/// Resamples frames from shared keyframes, see [`cascade::iterate`].
+struct Cascade;
+
+impl Backend for Cascade {
+    fn name(&self) -> &'static str {
+        "cascade"
+    }
+
+    fn supports(&self, args: &RenderArgs, info: &FrameInfo) -> bool {
+        args.quadratic_mandelbrot()
+            && args.cascade > 1
+            && info.rotation == 0.0
+            && info.transform == render::IDENTITY
+            && info.warp == Warp::None
+    }
+
+    fn iterate(
+        &self,
+        args: &RenderArgs,
+        info: &FrameInfo,
+        width: u32,
+        height: u32,
+        max_iter: u32,
+    ) -> IterationBuffer {
+        cascade::iterate(args, info, width, height, max_iter)
+    }
+}
+
+/// Mandelbrot pixels gathered into plane coordinate arrays and iterated a
+/// slice at a time.
+struct Vectorized;
+
+impl Backend for Vectorized {
+    fn name(&self) -> &'static str {
+        "soa"
+    }
+
+    fn supports(&self, args: &RenderArgs, _info: &FrameInfo) -> bool {
+        args.quadratic_mandelbrot()
+    }
+
+    fn iterate(
+        &self,
+        args: &RenderArgs,
+        info: &FrameInfo,
+        width: u32,
+        height: u32,
+        max_iter: u32,
+    ) -> IterationBuffer {
+        render::iterate_points_in(
+            args.precision,
+            width,
+            height,
+            max_iter,
+            args.derivative_bailout,
+            info.pixel_mapping(width, height),
+        )
+    }
+}
+
+/// Mandelbrot pixels iterated one at a time; slower, kept as a reference
+/// for the other backends.
+struct Scalar;
+
+impl Backend for Scalar {
+    fn name(&self) -> &'static str {
+        "scalar"
+    }
+
+    fn supports(&self, args: &RenderArgs, _info: &FrameInfo) -> bool {
+        args.quadratic_mandelbrot()
+    }
+
+    fn iterate(
+        &self,
+        args: &RenderArgs,
+        info: &FrameInfo,
+        width: u32,
+        height: u32,
+        max_iter: u32,
+    ) -> IterationBuffer {
+        let point = info.pixel_mapping(width, height);
+        IterationBuffer::from_fn(width, height, max_iter, |x, y| {
+            let c = point(x, y);
+            match args.precision {
+                Precision::F32 if !render::in_main_bulbs(c) => {
+                    render::mandelbrot_smooth_in::<f32>(c.cast(), max_iter, args.derivative_bailout)
+                }
+                _ => render::mandelbrot_smooth(c, max_iter, args.derivative_bailout),
+            }
+        })
+    }
+}
+
+/// Mandelbrot frames by rectangle subdivision, filling uniform rectangles
+/// from their borders; see [`subdivide::iterate`]. Not exact in the
+/// exterior, so only used when asked for.
+struct Subdivide;
+
+impl Backend for Subdivide {
+    fn name(&self) -> &'static str {
+        "subdivide"
+    }
+
+    fn supports(&self, args: &RenderArgs, _info: &FrameInfo) -> bool {
+        args.quadratic_mandelbrot()
+    }
+
+    fn iterate(
+        &self,
+        args: &RenderArgs,
+        info: &FrameInfo,
+        width: u32,
+        height: u32,
+        max_iter: u32,
+    ) -> IterationBuffer {
+        subdivide::iterate(args, info, width, height, max_iter)
+    }
+}
+
+/// `z^d + c` for `--power` other than 2, iterated one pixel at a time.
+struct Multibrot;
+
+impl Backend for Multibrot {
+    fn name(&self) -> &'static str {
+        "multibrot"
+    }
+
+    fn supports(&self, args: &RenderArgs, _info: &FrameInfo) -> bool {
+        args.fractal == Fractal::Mandelbrot && args.power != 2.0
+    }
+
+    fn iterate(
+        &self,
+        args: &RenderArgs,
+        info: &FrameInfo,
+        width: u32,
+        height: u32,
+        max_iter: u32,
+    ) -> IterationBuffer {
+        let point = info.pixel_mapping(width, height);
+        IterationBuffer::from_fn(width, height, max_iter, |x, y| {
+            render::multibrot_smooth(Complex::ZERO, point(x, y), args.power, max_iter)
+        })
+    }
+}
+
+/// The Julia set of a per-frame seed, following the Mandelbrot camera path.
+struct Julia;
+
+impl Backend for Julia {
+    fn name(&self) -> &'static str {
+        "julia"
+    }
+
+    fn supports(&self, args: &RenderArgs, _info: &FrameInfo) -> bool {
+        args.fractal == Fractal::Julia
+    }
+
+    fn iterate(
+        &self,
+        args: &RenderArgs,
+        info: &FrameInfo,
+        width: u32,
+        height: u32,
+        max_iter: u32,
+    ) -> IterationBuffer {
+        let (start, end) = (
+            args.julia_c_start,
+            args.julia_c_end.unwrap_or(args.julia_c_start),
+        );
+        let c = Complex {
+            re: track_or(args, timeline::Property::CRe, info.t, || {
+                start.re + (end.re - start.re) * info.t
+            }),
+            im: track_or(args, timeline::Property::CIm, info.t, || {
+                start.im + (end.im - start.im) * info.t
+            }),
+        };
+        let point = info.pixel_mapping(width, height);
+        IterationBuffer::from_fn(width, height, max_iter, |x, y| {
+            if args.power == 2.0 {
+                render::julia_smooth(point(x, y), c, max_iter, args.derivative_bailout)
+            } else {
+                render::multibrot_smooth(point(x, y), c, args.power, max_iter)
+            }
+        })
+    }
+}
+
+/// The Burning Ship, on the same camera path machinery with its own default path.
+struct BurningShip;
+
+impl Backend for BurningShip {
+    fn name(&self) -> &'static str {
+        "burning-ship"
+    }
+
+    fn supports(&self, args: &RenderArgs, _info: &FrameInfo) -> bool {
+        args.fractal == Fractal::BurningShip
+    }
+
+    fn iterate(
+        &self,
+        _args: &RenderArgs,
+        info: &FrameInfo,
+        width: u32,
+        height: u32,
+        max_iter: u32,
+    ) -> IterationBuffer {
+        let point = info.pixel_mapping(width, height);
+        IterationBuffer::from_fn(width, height, max_iter, |x, y| {
+            render::burning_ship_smooth(point(x, y), max_iter)
+        })
+    }
+}
+
+/// Newton's method on --newton-poly, pixels as starting points.
+struct Newton;
+
+impl Backend for Newton {
+    fn name(&self) -> &'static str {
+        "newton"
+    }
+
+    fn supports(&self, args: &RenderArgs, _info: &FrameInfo) -> bool {
+        args.fractal == Fractal::Newton
+    }
+
+    fn iterate(
+        &self,
+        args: &RenderArgs,
+        info: &FrameInfo,
+        width: u32,
+        height: u32,
+        max_iter: u32,
+    ) -> IterationBuffer {
+        newton::Newton {
+            poly: &args.newton_poly,
+            c: Complex {
+                re: track_or(args, timeline::Property::CRe, info.t, || 0.0),
+                im: track_or(args, timeline::Property::CIm, info.t, || 0.0),
+            },
+        }
+        .iterate(width, height, max_iter, info.pixel_mapping(width, height))
+    }
+}
+
+/// Orbit densities of the Mandelbrot set, accumulated from random starting points.
+struct Buddhabrot;
+
+impl Backend for Buddhabrot {
+    fn name(&self) -> &'static str {
+        "buddhabrot"
+    }
+
+    fn supports(&self, args: &RenderArgs, _info: &FrameInfo) -> bool {
+        args.fractal == Fractal::Buddhabrot
+    }
+
+    fn iterate(
+        &self,
+        args: &RenderArgs,
+        info: &FrameInfo,
+        width: u32,
+        height: u32,
+        max_iter: u32,
+    ) -> IterationBuffer {
+        buddhabrot::Buddhabrot {
+            samples_per_pixel: args.buddhabrot_samples,
+            bands: args.nebulabrot,
+        }
+        .render(width, height, info, max_iter)
+    }
+}
+
+/// The --formula map, compiled once per frame.
+struct Formula;
+
+impl Backend for Formula {
+    fn name(&self) -> &'static str {
+        "formula"
+    }
+
+    fn supports(&self, args: &RenderArgs, _info: &FrameInfo) -> bool {
+        args.fractal == Fractal::Formula
+    }
+
+    fn iterate(
+        &self,
+        args: &RenderArgs,
+        info: &FrameInfo,
+        width: u32,
+        height: u32,
+        max_iter: u32,
+    ) -> IterationBuffer {
+        args.formula.compile(&info.variables()).iterate(
+            width,
+            height,
+            max_iter,
+            args.formula_bailout,
+            info.pixel_mapping(width, height),
+        )
+    }
+}
+
+// Flames and rational maps ignore the Mandelbrot camera path and stay
+// centered on the origin, but take the rest of the view.
+
+struct FlameBackend;
+
+impl Backend for FlameBackend {
+    fn name(&self) -> &'static str {
+        "flame"
+    }
+
+    fn supports(&self, args: &RenderArgs, _info: &FrameInfo) -> bool {
+        args.fractal == Fractal::Flame
+    }
+
+    fn iterate(
+        &self,
+        args: &RenderArgs,
+        info: &FrameInfo,
+        width: u32,
+        height: u32,
+        max_iter: u32,
+    ) -> IterationBuffer {
+        flame::Flame {
+            preset: args.flame_preset,
+            samples_per_pixel: args.flame_samples,
+            spin: args.flame_spin,
+        }
+        .render(width, height, &origin_view(info), max_iter)
+    }
+}
+
+struct Rational;
+
+impl Backend for Rational {
+    fn name(&self) -> &'static str {
+        "rational"
+    }
+
+    fn supports(&self, args: &RenderArgs, _info: &FrameInfo) -> bool {
+        args.fractal == Fractal::Rational
+    }
+
+    fn iterate(
+        &self,
+        args: &RenderArgs,
+        info: &FrameInfo,
+        width: u32,
+        height: u32,
+        max_iter: u32,
+    ) -> IterationBuffer {
+        let vars = info.variables();
+        rational::RationalMap {
+            numerator: &args.numerator,
+            denominator: &args.denominator,
+            plane: args.rational_plane,
+            c: Complex {
+                re: track_or(args, timeline::Property::CRe, info.t, || {
+                    args.rational_c_re.eval(&vars)
+                }),
+                im: track_or(args, timeline::Property::CIm, info.t, || {
+                    args.rational_c_im.eval(&vars)
+                }),
+            },
+            z0: args.rational_z0,
+            bailout: args.rational_bailout,
+        }
+        .iterate(
+            width,
+            height,
+            max_iter,
+            origin_view(info).pixel_mapping(width, height),
+        )
+    }
+}
+
+/// The view of `info` moved to the origin.
+fn origin_view(info: &FrameInfo) -> FrameInfo {
+    FrameInfo {
+        center: Complex::ZERO,
+        ..*info
+    }
+}
@@ -0,0 +1,136 @@
+use crate::pacing::{self, Level, Pacer};
+use crate::palette::Palette;
+use crate::{fixed_path, frame_info, iterate, settings, signals};
+use std::io::{self, Write};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(clap::Args, Debug)]
+pub struct ScreensaverArgs {
+    #[arg(long, default_value_t = 12)]
+    fps: u32,
+    /// Zoom speed in doublings per second; slow by default.
+    #[arg(long, default_value_t = 0.3)]
+    zoom_rate: f64,
+    /// Depth of each dive before zooming back out to the next point.
+    #[arg(long, default_value_t = 1e-10)]
+    zoom_end: f64,
+    #[arg(long, default_value_t = 600)]
+    max_iter: u32,
+    #[arg(long, default_value = "ultra")]
+    palette: Palette,
+    /// Stop after this many frames instead of running until interrupted.
+    #[arg(long)]
+    frames: Option<u32>,
+    /// Keep exactly --fps: frames that fall behind lower the resolution and
+    /// iteration limit of the next ones, and frames whose turn has already
+    /// passed are skipped.
+    #[arg(long)]
+    realtime: bool,
+}
+
+/// Plays an endless zoom in the terminal with 24-bit color half blocks, two
+/// pixels per character cell, resizing with the terminal, until Ctrl-C.
+pub fn run(args: ScreensaverArgs) -> Result<(), String> {
+    signals::install();
+    let render = settings::to_render_args(&[
+        ("endless".to_string(), "true".to_string()),
+        ("fps".to_string(), args.fps.max(1).to_string()),
+        ("zoom_rate".to_string(), args.zoom_rate.to_string()),
+        ("zoom_end".to_string(), args.zoom_end.to_string()),
+        ("max_iter".to_string(), args.max_iter.to_string()),
+        ("derivative_bailout".to_string(), "true".to_string()),
+    ])?;
+    let path = fixed_path(render.fractal);
+    let interval = Duration::from_secs_f64(1.0 / args.fps.max(1) as f64);
+    let mut out = io::stdout().lock();
+    // Alternate screen, hidden cursor.
+    write!(out, "\x1b[?1049h\x1b[?25l").map_err(|e| format!("write terminal: {e}"))?;
+
+    let mut result = Ok(());
+    let mut frame = 0;
+    let mut pacer = args.realtime.then(|| Pacer::new(args.fps));
+    while signals::received().is_none() && args.frames.is_none_or(|n| frame < n) {
+        let started = Instant::now();
+        let level = match &mut pacer {
+            Some(pacer) => match pacer.start() {
+                Some(level) => level,
+                None => {
+                    frame += 1;
+                    continue;
+                }
+            },
+            None => Level::FULL,
+        };
+        let (cols, rows) = terminal_size();
+        let info = frame_info(&render, &path, frame);
+        let (width, height) = level.size(cols, rows * 2);
+        let mut iterations = iterate(
+            &render,
+            &info,
+            width,
+            height,
+            level.max_iter(render.max_iter),
+        );
+        if !level.is_full() {
+            iterations = pacing::upscale(&iterations, cols, rows * 2, render.max_iter);
+        }
+        let img = iterations.colorize(&args.palette);
+        let mut text = String::from("\x1b[H");
+        for y in 0..rows {
+            for x in 0..cols {
+                let [tr, tg, tb] = img.get_pixel(x, 2 * y).0;
+                let [br, bg, bb] = img.get_pixel(x, 2 * y + 1).0;
+                text.push_str(&format!(
+                    "\x1b[38;2;{tr};{tg};{tb}m\x1b[48;2;{br};{bg};{bb}m\u{2580}"
+                ));
+            }
+            text.push_str("\x1b[0m");
+            if y + 1 < rows {
+                text.push_str("\r\n");
+            }
+        }
+        if let Err(e) = out.write_all(text.as_bytes()).and_then(|()| out.flush()) {
+            result = Err(format!("write terminal: {e}"));
+            break;
+        }
+        frame += 1;
+        match &mut pacer {
+            Some(pacer) => pacer.finish(),
+            None => {
+                if let Some(rest) = interval.checked_sub(started.elapsed()) {
+                    thread::sleep(rest);
+                }
+            }
+        }
+    }
+
+    let _ = write!(out, "\x1b[0m\x1b[?25h\x1b[?1049l");
+    let _ = out.flush();
+    drop(out);
+    if let Some(pacer) = &pacer {
+        pacer.report();
+    }
+    result
+}
+
+/// Character cells of the terminal, from the tty or `COLUMNS`/`LINES`.
+fn terminal_size() -> (u32, u32) {
+    #[cfg(unix)]
+    {
+        // SAFETY: TIOCGWINSZ only fills in the winsize struct passed to it.
+        let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+        let ok = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0;
+        if ok && size.ws_col > 0 && size.ws_row > 0 {
+            return (size.ws_col as u32, size.ws_row as u32);
+        }
+    }
+    let env = |name: &str, default: u32| {
+        std::env::var(name)
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .filter(|&v| v > 0)
+            .unwrap_or(default)
+    };
+    (env("COLUMNS", 80), env("LINES", 24))
+}
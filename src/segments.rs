@@ -0,0 +1,413 @@
+use crate::encode::FfmpegPipe;
+use crate::manifest::{FrameEntry, Manifest};
+use crate::preset::{self, Preset};
+use crate::{output, runlog, strings};
+use image::{ImageBuffer, Rgb};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Times ffmpeg is started again on one segment before the run gives up.
+const ENCODER_RESTARTS: u32 = 3;
+
+#[derive(clap::Args, Debug)]
+pub struct EncodeArgs {
+    /// Directory holding the frames and their run manifest.
+    #[arg(long, default_value = "out/frames")]
+    run_dir: String,
+    /// Directory the video goes to, as `mandelbrot.mp4`.
+    #[arg(long, default_value = "out")]
+    out_dir: String,
+    /// Video file instead of `mandelbrot.mp4` in --out-dir, e.g.
+    /// `out/zoom.mkv`; segments are numbered after its name.
+    #[arg(long)]
+    output: Option<String>,
+    /// Encode the frames of this --palettes label instead of the main palette.
+    #[arg(long)]
+    variant: Option<String>,
+    /// Frame rate; defaults to the --fps the frames were rendered for.
+    #[arg(long)]
+    fps: Option<u32>,
+    /// Split the video into numbered files of this length, e.g. `30s`, `2m` or `1m30s`.
+    #[arg(long, value_parser = parse_duration)]
+    segment_length: Option<f64>,
+    /// Only (re-)encode these 1-based segment numbers, e.g. `3,4`.
+    #[arg(long, value_delimiter = ',')]
+    segments: Vec<u32>,
+}
+
+/// Encodes the frames listed in a run manifest, optionally as numbered segments
+/// plus an ffmpeg concat list that joins them without re-encoding.
+pub fn run(args: EncodeArgs) -> Result<(), String> {
+    let dir = output::resolve_dir(Path::new(&args.run_dir))?;
+    let manifest = Manifest::read(&dir)?;
+    let fps = match args.fps {
+        Some(fps) => fps,
+        None => manifest.render_args()?.fps,
+    }
+    .max(1);
+    // Encoder settings of the run's --preset, if the manifest records one.
+    let preset = manifest.render_args().ok().and_then(|render| render.preset);
+
+    let mut frames: Vec<&FrameEntry> = manifest
+        .frames
+        .iter()
+        .filter(|f| f.variant == args.variant)
+        .collect();
+    frames.sort_by_key(|f| f.index);
+    if frames.is_empty() {
+        return Err(format!("no frames in {dir:?} for the selected palette"));
+    }
+
+    let output = &match &args.output {
+        Some(file) => PathBuf::from(file),
+        None => output::resolve_dir(Path::new(&args.out_dir))?.join("mandelbrot.mp4"),
+    };
+    if let Some(parent) = output.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("create {parent:?}: {e}"))?;
+    }
+    let Some(seconds) = args.segment_length else {
+        encode_frames(&dir, &frames, fps, preset, output)?;
+        println!("{} frames -> {}", frames.len(), output.display());
+        return Ok(());
+    };
+
+    let per_segment = ((seconds * fps as f64).round() as usize).max(1);
+    let chunks: Vec<&[&FrameEntry]> = frames.chunks(per_segment).collect();
+    let mut list = String::new();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let number = i as u32 + 1;
+        let path = segment_path(output, number);
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        list.push_str(&format!("file '{}'\n", name.replace('\'', "'\\''")));
+        if !args.segments.is_empty() && !args.segments.contains(&number) {
+            continue;
+        }
+        encode_frames(&dir, chunk, fps, preset, &path)?;
+        println!(
+            "segment {number}/{}: frames {}-{} -> {}",
+            chunks.len(),
+            chunk[0].index,
+            chunk[chunk.len() - 1].index,
+            path.display()
+        );
+    }
+    let list_path = list_path(output);
+    fs::write(&list_path, list).map_err(|e| format!("write {list_path:?}: {e}"))?;
+    println!(
+        "join with: ffmpeg -f concat -safe 0 -i {} -c copy {}",
+        list_path.display(),
+        output.display()
+    );
+    Ok(())
+}
+
+fn encode_frames(
+    dir: &Path,
+    frames: &[&FrameEntry],
+    fps: u32,
+    preset: Option<Preset>,
+    output: &Path,
+) -> Result<(), String> {
+    let mut pipe: Option<FfmpegPipe> = None;
+    for entry in frames {
+        let path = dir.join(&entry.file);
+        let img = image::open(&path)
+            .map_err(|e| format!("read {path:?}: {e}"))?
+            .to_rgb8();
+        let pipe = match &mut pipe {
+            Some(pipe) => pipe,
+            None => pipe.insert(FfmpegPipe::spawn(
+                img.width(),
+                img.height(),
+                fps,
+                &preset::output_args(preset, &output.to_string_lossy()),
+            )?),
+        };
+        pipe.write_frame(&img)?;
+    }
+    match pipe {
+        Some(pipe) => pipe.finish(),
+        None => Ok(()),
+    }
+}
+
+/// Encodes the frames of a run into numbered segments while they are
+/// rendered, like `encode --segment-length` does afterwards. A segment is
+/// added to the concat list once ffmpeg has finished it, so a rerun skips the
+/// segments in the list and one ffmpeg failure costs only the segment it
+/// was writing: ffmpeg is started again on it and fed its frames back from
+/// the saved PNG files.
+pub struct SegmentEncoder {
+    output: PathBuf,
+    fps: u32,
+    preset: Option<Preset>,
+    per_segment: u32,
+    finished: Vec<u32>,
+    current: Option<Segment>,
+}
+
+/// The segment being encoded: its frames so far and the ffmpeg writing it.
+struct Segment {
+    number: u32,
+    frames: Vec<PathBuf>,
+    pipe: Option<FfmpegPipe>,
+    restarts: u32,
+}
+
+impl SegmentEncoder {
+    pub fn start(
+        output: &Path,
+        seconds: f64,
+        fps: u32,
+        preset: Option<Preset>,
+    ) -> Result<SegmentEncoder, String> {
+        if let Some(parent) = output.parent() {
+            fs::create_dir_all(parent).map_err(|e| output::describe_error("create", parent, &e))?;
+        }
+        let fps = fps.max(1);
+        let finished = finished_segments(output)?;
+        if !finished.is_empty() {
+            eprintln!(
+                "{}",
+                strings::text(
+                    "encode_resumed",
+                    "encode: skipping the {count} segments already in {list}",
+                    &[
+                        ("count", &finished.len()),
+                        ("list", &list_path(output).display()),
+                    ],
+                )
+            );
+        }
+        Ok(SegmentEncoder {
+            output: output.to_path_buf(),
+            fps,
+            preset,
+            per_segment: ((seconds * fps as f64).round() as u32).max(1),
+            finished,
+            current: None,
+        })
+    }
+
+    /// Whether the segment of `frame` still has to be encoded.
+    pub fn needs(&self, frame: u32) -> bool {
+        !self.finished.contains(&(frame / self.per_segment + 1))
+    }
+
+    /// Encodes frame `frame`, saved as `file`, unless its segment is done.
+    /// Finishes the segment with its last frame.
+    pub fn push(
+        &mut self,
+        frame: u32,
+        img: &ImageBuffer<Rgb<u8>, Vec<u8>>,
+        file: &Path,
+    ) -> Result<(), String> {
+        if !self.needs(frame) {
+            return Ok(());
+        }
+        let number = frame / self.per_segment + 1;
+        if self.current.as_ref().is_some_and(|s| s.number != number) {
+            self.close()?;
+        }
+        let segment = self.current.get_or_insert_with(|| Segment {
+            number,
+            frames: Vec::new(),
+            pipe: None,
+            restarts: 0,
+        });
+        segment.frames.push(file.to_path_buf());
+        let path = segment_path(&self.output, number);
+        let video = path.to_string_lossy();
+        let args = preset::output_args(self.preset, &video);
+        let written = match &mut segment.pipe {
+            Some(pipe) => pipe.write_frame(img),
+            None => FfmpegPipe::spawn(img.width(), img.height(), self.fps, &args)
+                .and_then(|pipe| segment.pipe.insert(pipe).write_frame(img)),
+        };
+        if let Err(e) = written {
+            segment.restart(&e, self.fps, &args)?;
+        }
+        if (frame + 1).is_multiple_of(self.per_segment) {
+            self.close()?;
+        }
+        Ok(())
+    }
+
+    /// Finishes the last segment, however short, at the end of the run.
+    pub fn finish(mut self) -> Result<(), String> {
+        self.close()
+    }
+
+    fn close(&mut self) -> Result<(), String> {
+        let Some(mut segment) = self.current.take() else {
+            return Ok(());
+        };
+        let path = segment_path(&self.output, segment.number);
+        let video = path.to_string_lossy();
+        let args = preset::output_args(self.preset, &video);
+        while let Some(pipe) = segment.pipe.take() {
+            if let Err(e) = pipe.finish() {
+                segment.restart(&e, self.fps, &args)?;
+            }
+        }
+        self.finished.push(segment.number);
+        self.finished.sort_unstable();
+        let mut list = String::new();
+        for &number in &self.finished {
+            let path = segment_path(&self.output, number);
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            list.push_str(&format!("file '{}'\n", name.replace('\'', "'\\''")));
+        }
+        let list_path = list_path(&self.output);
+        let tmp = output::tmp_path(&list_path);
+        fs::write(&tmp, list)
+            .and_then(|()| fs::rename(&tmp, &list_path))
+            .map_err(|e| output::describe_error("write", &list_path, &e))?;
+        eprintln!(
+            "{}",
+            strings::text(
+                "segment_encoded",
+                "segment {segment} -> {path}",
+                &[("segment", &segment.number), ("path", &path.display())],
+            )
+        );
+        Ok(())
+    }
+}
+
+impl Segment {
+    /// Starts ffmpeg on the segment again after `error` and feeds it the
+    /// segment's frames so far, up to [`ENCODER_RESTARTS`] times.
+    fn restart(&mut self, error: &str, fps: u32, args: &[&str]) -> Result<(), String> {
+        let mut error = error.to_string();
+        loop {
+            if let Some(pipe) = self.pipe.take() {
+                let _ = pipe.finish();
+            }
+            if self.restarts == ENCODER_RESTARTS {
+                return Err(format!(
+                    "segment {}: ffmpeg failed {} times, last: {error}",
+                    self.number,
+                    ENCODER_RESTARTS + 1
+                ));
+            }
+            self.restarts += 1;
+            runlog::warn(
+                "encoder_restart",
+                &[
+                    ("segment", self.number.into()),
+                    ("error", error.as_str().into()),
+                    ("attempt", self.restarts.into()),
+                ],
+            );
+            eprintln!(
+                "ffmpeg failed on segment {}: {error}; starting it again from frame 1 of the segment",
+                self.number
+            );
+            match self.refeed(fps, args) {
+                Ok(pipe) => {
+                    self.pipe = Some(pipe);
+                    return Ok(());
+                }
+                Err(e) => error = e,
+            }
+        }
+    }
+
+    fn refeed(&self, fps: u32, args: &[&str]) -> Result<FfmpegPipe, String> {
+        let mut pipe: Option<FfmpegPipe> = None;
+        for path in &self.frames {
+            let img = image::open(path)
+                .map_err(|e| format!("read {path:?}: {e}"))?
+                .to_rgb8();
+            let pipe = match &mut pipe {
+                Some(pipe) => pipe,
+                None => pipe.insert(FfmpegPipe::spawn(img.width(), img.height(), fps, args)?),
+            };
+            pipe.write_frame(&img)?;
+        }
+        pipe.ok_or_else(|| "no frames to encode".to_string())
+    }
+}
+
+/// The segments the concat list of `output` names that are on disk.
+fn finished_segments(output: &Path) -> Result<Vec<u32>, String> {
+    let list_path = list_path(output);
+    let list = match fs::read_to_string(&list_path) {
+        Ok(list) => list,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(output::describe_error("read", &list_path, &e)),
+    };
+    let mut finished: Vec<u32> = list
+        .lines()
+        .filter_map(|line| {
+            let name = line.trim().strip_prefix("file '")?.strip_suffix('\'')?;
+            segment_number(output, &name.replace("'\\''", "'"))
+        })
+        .filter(|&number| segment_path(output, number).is_file())
+        .collect();
+    finished.sort_unstable();
+    finished.dedup();
+    Ok(finished)
+}
+
+/// `zoom_003.mp4` -> 3, for segments of `out/zoom.mp4`.
+fn segment_number(output: &Path, name: &str) -> Option<u32> {
+    let stem = output.file_stem()?.to_string_lossy();
+    let rest = name.strip_prefix(&*stem)?.strip_prefix('_')?;
+    let digits = match output.extension() {
+        Some(ext) => rest
+            .strip_suffix(&*ext.to_string_lossy())?
+            .strip_suffix('.')?,
+        None => rest,
+    };
+    digits.parse().ok()
+}
+
+/// `out/zoom.mp4` -> `out/zoom_segments.txt`
+fn list_path(output: &Path) -> PathBuf {
+    output.with_file_name(format!(
+        "{}_segments.txt",
+        output.file_stem().unwrap_or_default().to_string_lossy()
+    ))
+}
+
+/// `out/zoom.mp4` -> `out/zoom_003.mp4`
+fn segment_path(output: &Path, number: u32) -> PathBuf {
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match output.extension() {
+        Some(ext) => format!("{stem}_{number:03}.{}", ext.to_string_lossy()),
+        None => format!("{stem}_{number:03}"),
+    };
+    output.with_file_name(name)
+}
+
+/// Parses durations such as `30s`, `2m`, `1h`, `1m30s`, `1.5m` or plain seconds (`90`).
+pub fn parse_duration(s: &str) -> Result<f64, String> {
+    let invalid = || format!("invalid duration {s:?}, expected e.g. 30s, 2m or 1m30s");
+    let s = s.trim();
+    if let Ok(seconds) = s.parse::<f64>() {
+        return (seconds > 0.0).then_some(seconds).ok_or_else(invalid);
+    }
+    let mut total = 0.0;
+    let mut number = String::new();
+    for c in s.chars() {
+        if c.is_ascii_digit() || c == '.' {
+            number.push(c);
+            continue;
+        }
+        let unit = match c {
+            'h' => 3600.0,
+            'm' => 60.0,
+            's' => 1.0,
+            _ => return Err(invalid()),
+        };
+        let value: f64 = number.parse().map_err(|_| invalid())?;
+        total += value * unit;
+        number.clear();
+    }
+    if !number.is_empty() || total <= 0.0 {
+        return Err(invalid());
+    }
+    Ok(total)
+}
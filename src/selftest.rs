@@ -0,0 +1,217 @@
+use crate::numeric::{DoubleDouble, Real};
+use crate::palette::{BUILTIN_NAMES, NamedPalette, Palette};
+use crate::render::{self, Complex, INTERIOR, IterationBuffer};
+use crate::{Cli, config, expr::Expr, iterfile, settings, sha256};
+use clap::CommandFactory;
+use std::fs;
+
+type Check = fn() -> Result<(), String>;
+
+const CHECKS: [(&str, Check); 8] = [
+    ("kernel reference values", kernel_reference),
+    ("numeric backends agree", numeric_backends),
+    ("derivative bailout agrees", derivative_bailout),
+    ("f32 iteration files agree with f64", iteration_precision),
+    ("palette round trips", palette_round_trip),
+    ("config round trip", config_round_trip),
+    ("expressions", expressions),
+    ("sha256 test vector", sha256_vector),
+];
+
+/// Runs quick numerical checks of this build and fails if any of them do.
+pub fn run() -> Result<(), String> {
+    let mut failed = 0;
+    for (name, check) in CHECKS {
+        match check() {
+            Ok(()) => println!("ok   {name}"),
+            Err(e) => {
+                println!("FAIL {name}: {e}");
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(format!(
+            "{failed} of {} self-test checks failed",
+            CHECKS.len()
+        ));
+    }
+    println!("all {} self-test checks passed", CHECKS.len());
+    Ok(())
+}
+
+fn escape_value(c: Complex, max_iter: u32) -> f64 {
+    render::iterate_points(1, 1, max_iter, false, |_, _| c).values[0]
+}
+
+fn kernel_reference() -> Result<(), String> {
+    let smooth = |iter: f64, z: f64| iter + 1.0 - z.ln().ln() / 2.0_f64.ln();
+    let cases = [
+        (Complex { re: 0.0, im: 0.0 }, INTERIOR),
+        (Complex { re: -1.0, im: 0.0 }, INTERIOR),
+        (Complex { re: -2.0, im: 0.0 }, INTERIOR),
+        // 0 -> 1 -> 2 -> 5
+        (Complex { re: 1.0, im: 0.0 }, smooth(3.0, 5.0)),
+        // 0 -> -2.5
+        (Complex { re: -2.5, im: 0.0 }, smooth(1.0, 2.5)),
+        // 0 -> 2i
+        (
+            Complex { re: 0.0, im: 2.0 },
+            smooth(2.0, 2.0 * 5.0_f64.sqrt()),
+        ),
+    ];
+    for (c, expected) in cases {
+        let value = escape_value(c, 1000);
+        if (value - expected).abs() > 1e-9 {
+            return Err(format!(
+                "c = {}{:+}i gave {value}, expected {expected}",
+                c.re, c.im
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn numeric_backends() -> Result<(), String> {
+    let one = DoubleDouble::ONE;
+    let tiny = (one + DoubleDouble::from_f64(1e-20)) - one;
+    if (tiny.to_f64() - 1e-20).abs() > 1e-35 {
+        return Err(format!(
+            "double-double (1 + 1e-20) - 1 gave {}",
+            tiny.to_f64()
+        ));
+    }
+    let three = DoubleDouble::from_f64(3.0);
+    let residue = (one / three) * three - one;
+    if residue.to_f64().abs() > 1e-30 {
+        return Err(format!(
+            "double-double (1/3)*3 - 1 gave {}",
+            residue.to_f64()
+        ));
+    }
+    let points = [
+        Complex { re: 1.0, im: 0.0 },
+        Complex { re: -2.5, im: 0.0 },
+        Complex { re: 0.3, im: 0.5 },
+        Complex { re: -0.75, im: 0.1 },
+    ];
+    for c in points {
+        let reference = render::mandelbrot_smooth(c, 1000, false);
+        let single = render::mandelbrot_smooth_in::<f32>(c.cast(), 1000, false);
+        let double_double = render::mandelbrot_smooth_in::<DoubleDouble>(c.cast(), 1000, false);
+        if (single - reference).abs() > 1e-3 || (double_double - reference).abs() > 1e-9 {
+            return Err(format!(
+                "c = {}{:+}i gave f64 {reference}, f32 {single}, double-double {double_double}",
+                c.re, c.im
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn reference_frame(derivative_bailout: bool) -> IterationBuffer {
+    render::iterate_frame(
+        96,
+        64,
+        Complex { re: -0.5, im: 0.0 },
+        1.5,
+        4000,
+        derivative_bailout,
+    )
+}
+
+fn derivative_bailout() -> Result<(), String> {
+    let plain = reference_frame(false);
+    let fast = reference_frame(true);
+    let differ = plain
+        .values
+        .iter()
+        .zip(&fast.values)
+        .filter(|(a, b)| a != b)
+        .count();
+    if differ * 1000 > plain.values.len() {
+        return Err(format!("{differ} of {} pixels differ", plain.values.len()));
+    }
+    Ok(())
+}
+
+fn iteration_precision() -> Result<(), String> {
+    let frame = reference_frame(false);
+    let path = std::env::temp_dir().join(format!("selftest-{}.iter", std::process::id()));
+    fs::write(&path, iterfile::encode(&frame)).map_err(|e| format!("write {path:?}: {e}"))?;
+    let loaded = iterfile::load(&path);
+    let _ = fs::remove_file(&path);
+    let loaded = loaded?;
+    let header = |b: &IterationBuffer| (b.width, b.height, b.max_iter);
+    if header(&loaded) != header(&frame) {
+        return Err("header does not round-trip".to_string());
+    }
+    for (a, b) in frame.values.iter().zip(&loaded.values) {
+        if (a - b).abs() > a.abs() * 1e-6 {
+            return Err(format!("{a} was stored as {b}"));
+        }
+    }
+    Ok(())
+}
+
+fn palette_round_trip() -> Result<(), String> {
+    for name in BUILTIN_NAMES {
+        let named: NamedPalette = name.parse()?;
+        if named.label != name {
+            return Err(format!("{name} is labeled {:?}", named.label));
+        }
+    }
+    let palette: Palette = "#102030,#808080,#a0b0c0".parse()?;
+    let ends = (palette.color(0.0), palette.color(0.5), palette.color(1.0));
+    if ends != ([0x10, 0x20, 0x30], [0x80, 0x80, 0x80], [0xa0, 0xb0, 0xc0]) {
+        return Err(format!("gradient stops come back as {ends:?}"));
+    }
+    Ok(())
+}
+
+fn config_round_trip() -> Result<(), String> {
+    let matches = Cli::command()
+        .mut_args(|a| a.env(None))
+        .try_get_matches_from([
+            "mandelbrot-animation",
+            "--max-iter=1234",
+            "--palette=#000000,#ffffff",
+            "--layer=palette=fire,opacity=0.3",
+            "--layer=palette=ocean,blend=screen",
+        ])
+        .map_err(|e| e.to_string())?;
+    let settings = settings::from_matches(&matches);
+    let parsed = config::parse(&config::to_toml(&settings))?;
+    let flattened: Vec<(String, String)> = parsed
+        .into_iter()
+        .flat_map(|(key, values)| values.into_iter().map(move |v| (key.clone(), v)))
+        .collect();
+    if flattened != settings {
+        return Err("settings change when written and read back".to_string());
+    }
+    settings::to_render_args(&settings).map(|_| ())
+}
+
+fn expressions() -> Result<(), String> {
+    let cases = [
+        ("2*sin(pi/2) + 3^2", 11.0),
+        ("-2^2", -4.0),
+        ("clamp(t*10, 0, 1)", 1.0),
+        ("fract(frame/4)", 0.25),
+    ];
+    for (text, expected) in cases {
+        let value = text.parse::<Expr>()?.eval(&[("t", 0.5), ("frame", 5.0)]);
+        if (value - expected).abs() > 1e-12 {
+            return Err(format!("{text} = {value}, expected {expected}"));
+        }
+    }
+    Ok(())
+}
+
+fn sha256_vector() -> Result<(), String> {
+    let digest = sha256::hex_digest(b"abc");
+    if digest != "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad" {
+        return Err(format!("sha256(\"abc\") = {digest}"));
+    }
+    Ok(())
+}
@@ -0,0 +1,50 @@
+use crate::RenderArgs;
+use clap::{ArgAction, ArgMatches, Args, FromArgMatches};
+
+/// Repeatable arguments without a value delimiter are recorded once per value.
+pub fn from_matches(matches: &ArgMatches) -> Vec<(String, String)> {
+    let cmd = RenderArgs::augment_args(clap::Command::new("settings"));
+    cmd.get_arguments()
+        .flat_map(|arg| {
+            let id = arg.get_id().as_str();
+            let values: Vec<_> = matches
+                .get_raw(id)
+                .into_iter()
+                .flatten()
+                .map(|v| v.to_string_lossy().into_owned())
+                .collect();
+            match arg.get_value_delimiter() {
+                _ if values.is_empty() => Vec::new(),
+                Some(delimiter) => vec![(id.to_string(), values.join(&delimiter.to_string()))],
+                None => values.into_iter().map(|v| (id.to_string(), v)).collect(),
+            }
+        })
+        .collect()
+}
+
+/// Rebuilds the arguments from recorded settings alone; `MANDELBROT_*`
+/// variables of the current environment are ignored.
+pub fn to_render_args(settings: &[(String, String)]) -> Result<RenderArgs, String> {
+    let cmd = RenderArgs::augment_args(clap::Command::new("settings")).mut_args(|a| a.env(None));
+    let mut argv = vec!["settings".to_string()];
+    for (key, value) in settings {
+        let Some(arg) = cmd.get_arguments().find(|a| a.get_id() == key.as_str()) else {
+            continue;
+        };
+        let Some(long) = arg.get_long() else {
+            continue;
+        };
+        match arg.get_action() {
+            ArgAction::SetTrue => {
+                if value == "true" {
+                    argv.push(format!("--{long}"));
+                }
+            }
+            _ => argv.push(format!("--{long}={value}")),
+        }
+    }
+    let matches = cmd
+        .try_get_matches_from(argv)
+        .map_err(|e| format!("settings: {e}"))?;
+    RenderArgs::from_arg_matches(&matches).map_err(|e| format!("settings: {e}"))
+}
@@ -0,0 +1,37 @@
+use std::sync::atomic::{AtomicI32, Ordering};
+
+static RECEIVED: AtomicI32 = AtomicI32::new(0);
+
+/// Turns the first SIGINT or SIGTERM into a flag the render loop checks
+/// between frames; a second one terminates the process as usual.
+#[cfg(unix)]
+pub fn install() {
+    extern "C" fn handle(sig: libc::c_int) {
+        RECEIVED.store(sig, Ordering::SeqCst);
+        // SAFETY: signal() is async-signal-safe; restoring the default makes a
+        // second signal fatal again.
+        unsafe {
+            libc::signal(sig, libc::SIG_DFL);
+        }
+    }
+    for sig in [libc::SIGINT, libc::SIGTERM] {
+        // SAFETY: `handle` only touches an atomic and calls signal().
+        unsafe {
+            libc::signal(
+                sig,
+                handle as extern "C" fn(libc::c_int) as libc::sighandler_t,
+            );
+        }
+    }
+}
+
+#[cfg(not(unix))]
+pub fn install() {}
+
+/// The signal that asked the run to stop, if any.
+pub fn received() -> Option<i32> {
+    match RECEIVED.load(Ordering::SeqCst) {
+        0 => None,
+        sig => Some(sig),
+    }
+}
@@ -0,0 +1,149 @@
+use crate::encode::FfmpegPipe;
+use crate::runlog;
+use image::{ImageBuffer, Rgb};
+use std::collections::VecDeque;
+use std::fs;
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+type Frame = ImageBuffer<Rgb<u8>, Vec<u8>>;
+
+/// Accepts `rtmp://` / `rtmps://` URLs and `.m3u8` HLS playlist paths.
+pub fn parse_target(s: &str) -> Result<String, String> {
+    let lower = s.to_ascii_lowercase();
+    if lower.starts_with("rtmp://") || lower.starts_with("rtmps://") || lower.ends_with(".m3u8") {
+        Ok(s.to_string())
+    } else {
+        Err(format!(
+            "unsupported stream target {s:?}, expected an rtmp:// URL or an .m3u8 playlist"
+        ))
+    }
+}
+
+fn output_args(target: &str, fps: u32) -> Vec<String> {
+    let mut args: Vec<String> = [
+        "-c:v",
+        "libx264",
+        "-preset",
+        "veryfast",
+        "-tune",
+        "zerolatency",
+        "-pix_fmt",
+        "yuv420p",
+    ]
+    .map(String::from)
+    .to_vec();
+    args.extend(["-g".to_string(), (fps * 2).max(1).to_string()]);
+    if target.to_ascii_lowercase().ends_with(".m3u8") {
+        args.extend(
+            [
+                "-f",
+                "hls",
+                "-hls_time",
+                "4",
+                "-hls_list_size",
+                "10",
+                "-hls_flags",
+                "delete_segments",
+            ]
+            .map(String::from),
+        );
+    } else {
+        args.extend(["-f", "flv"].map(String::from));
+    }
+    args.push(target.to_string());
+    args
+}
+
+/// Frames handed to ffmpeg on the broadcast clock by a background thread.
+/// The renderer may run up to `lead` frames ahead; the broadcast starts once
+/// that lead is built up, so short slow stretches never stall the stream.
+pub struct LiveStream {
+    sender: Option<SyncSender<Frame>>,
+    thread: Option<JoinHandle<Result<(), String>>>,
+}
+
+impl LiveStream {
+    pub fn start(
+        width: u32,
+        height: u32,
+        fps: u32,
+        target: &str,
+        lead_seconds: f64,
+    ) -> Result<LiveStream, String> {
+        if target.to_ascii_lowercase().ends_with(".m3u8")
+            && let Some(parent) = Path::new(target).parent()
+        {
+            fs::create_dir_all(parent).map_err(|e| format!("create {parent:?}: {e}"))?;
+        }
+        let fps = fps.max(1);
+        let args = output_args(target, fps);
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        let pipe = FfmpegPipe::spawn(width, height, fps, &args)?;
+        let lead = ((lead_seconds * fps as f64).round() as usize).max(1);
+        let (sender, receiver) = mpsc::sync_channel(lead);
+        let thread = thread::spawn(move || broadcast(pipe, receiver, fps, lead));
+        Ok(LiveStream {
+            sender: Some(sender),
+            thread: Some(thread),
+        })
+    }
+
+    /// Queues a frame, waiting while the renderer is a full lead ahead.
+    pub fn push(&mut self, img: &Frame) -> Result<(), String> {
+        let sent = match &self.sender {
+            Some(sender) => sender.send(img.clone()).is_ok(),
+            None => false,
+        };
+        if sent {
+            return Ok(());
+        }
+        // The broadcast thread only hangs up when it failed; report why.
+        self.sender = None;
+        match self.thread.take().map(JoinHandle::join) {
+            Some(Ok(Err(e))) => Err(e),
+            _ => Err("live stream stopped".to_string()),
+        }
+    }
+
+    /// Plays out the queued frames and waits for ffmpeg to exit.
+    pub fn finish(mut self) -> Result<(), String> {
+        drop(self.sender.take());
+        match self.thread.take().map(JoinHandle::join) {
+            Some(Ok(result)) => result,
+            Some(Err(_)) => Err("live stream thread panicked".to_string()),
+            None => Err("live stream stopped".to_string()),
+        }
+    }
+}
+
+fn broadcast(
+    mut pipe: FfmpegPipe,
+    receiver: Receiver<Frame>,
+    fps: u32,
+    lead: usize,
+) -> Result<(), String> {
+    let mut queue: VecDeque<Frame> = receiver.iter().take(lead).collect();
+    let interval = Duration::from_secs_f64(1.0 / fps as f64);
+    let mut clock = Instant::now();
+    let mut late = 0u64;
+    while let Some(img) = queue.pop_front().or_else(|| receiver.recv().ok()) {
+        let now = Instant::now();
+        if now < clock {
+            thread::sleep(clock - now);
+        } else if now - clock > interval {
+            // The renderer fell behind; restart the clock instead of bursting to catch up.
+            late += 1;
+            clock = now;
+        }
+        pipe.write_frame(&img)?;
+        clock += interval;
+    }
+    if late > 0 {
+        runlog::warn("stream_behind", &[("late_frames", late.into())]);
+        eprintln!("warning: rendering fell behind the live stream {late} time(s)");
+    }
+    pipe.finish()
+}
@@ -0,0 +1,54 @@
+use crate::config;
+use std::fmt::Display;
+use std::sync::OnceLock;
+
+/// Translations of console messages, HUD labels and overlay text, read from
+/// a strings file: one `key = "text"` per line, in the flat TOML of
+/// `--config`. `{name}` in a text stands for a value the message fills in.
+#[derive(Debug, Clone, Default)]
+pub struct Strings {
+    entries: Vec<(String, String)>,
+}
+
+impl Strings {
+    pub fn load(path: &str) -> Result<Strings, String> {
+        let entries = config::load(path)?
+            .into_iter()
+            .map(|(key, values)| match <[String; 1]>::try_from(values) {
+                Ok([text]) => Ok((key, text)),
+                Err(_) => Err(format!("{path}: {key} must be a single string")),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Strings { entries })
+    }
+
+    fn get(&self, key: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .rev()
+            .find(|(k, _)| k == key)
+            .map(|(_, text)| text.as_str())
+    }
+}
+
+static STRINGS: OnceLock<Strings> = OnceLock::new();
+
+/// Chooses the translations every later [`text`] uses. Only the first call
+/// takes effect; without one every message keeps its built-in English.
+pub fn set(strings: Strings) {
+    let _ = STRINGS.set(strings);
+}
+
+/// The translation of `key`, or `default` if the strings file has none, with
+/// every `{name}` of `vars` replaced by its value.
+pub fn text(key: &str, default: &str, vars: &[(&str, &dyn Display)]) -> String {
+    let template = STRINGS
+        .get()
+        .and_then(|strings| strings.get(key))
+        .unwrap_or(default);
+    let mut out = template.to_string();
+    for (name, value) in vars {
+        out = out.replace(&format!("{{{name}}}"), &value.to_string());
+    }
+    out
+}
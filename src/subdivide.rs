@@ -0,0 +1,210 @@
+use crate::health;
+use crate::render::{self, Complex, INTERIOR, IterationBuffer};
+use crate::{FrameInfo, RenderArgs};
+use rayon::prelude::*;
+
+/// Side of the squares the frame is first split into, one task each.
+const TILE: u32 = 64;
+/// Rectangles with at most this many pixels across inside their border are
+/// iterated pixel by pixel instead of split again.
+const MIN_INSIDE: u32 = 4;
+
+/// What the border of a rectangle says about its inside.
+enum Border {
+    /// Every border pixel is in the set, so every inside pixel is too: the
+    /// set has no holes for an escaping point to hide in.
+    Interior,
+    /// Every border pixel escaped within the same whole number of
+    /// iterations; the inside lies in the same band unless the rectangle
+    /// holds the whole set.
+    Band,
+    Mixed,
+}
+
+/// Iterations of a Mandelbrot frame by Mariani–Silver subdivision: only the
+/// border of a rectangle is iterated, and if it is uniform the inside is
+/// filled from it; otherwise the rectangle is split in four and each quarter
+/// is treated the same way. Interior fills are exact, band fills are
+/// interpolated from the border's smooth escape times.
+pub fn iterate(
+    args: &RenderArgs,
+    info: &FrameInfo,
+    width: u32,
+    height: u32,
+    max_iter: u32,
+) -> IterationBuffer {
+    let point = info.pixel_mapping(width, height);
+    let escape =
+        |x: u32, y: u32| render::mandelbrot_smooth(point(x, y), max_iter, args.derivative_bailout);
+    let (tiles_x, tiles_y) = (width.div_ceil(TILE), height.div_ceil(TILE));
+    let tiles: Vec<Tile> = (0..tiles_x * tiles_y)
+        .into_par_iter()
+        .map(|tile| {
+            let (x0, y0) = (tile % tiles_x * TILE, tile / tiles_x * TILE);
+            let (x1, y1) = ((x0 + TILE).min(width) - 1, (y0 + TILE).min(height) - 1);
+            let mut tile = Tile::new(x0, y0, x1, y1);
+            tile.subdivide(&escape, &point, x0, y0, x1, y1);
+            health::progressed();
+            tile
+        })
+        .collect();
+    let mut values = vec![0.0; width as usize * height as usize];
+    for tile in tiles {
+        for (row, line) in tile.values.chunks(tile.width as usize).enumerate() {
+            let start = (tile.y0 as usize + row) * width as usize + tile.x0 as usize;
+            values[start..start + line.len()].copy_from_slice(line);
+        }
+    }
+    IterationBuffer {
+        width,
+        height,
+        max_iter,
+        values,
+    }
+}
+
+struct Tile {
+    x0: u32,
+    y0: u32,
+    width: u32,
+    values: Vec<f64>,
+    known: Vec<bool>,
+}
+
+impl Tile {
+    fn new(x0: u32, y0: u32, x1: u32, y1: u32) -> Tile {
+        let len = ((x1 - x0 + 1) * (y1 - y0 + 1)) as usize;
+        Tile {
+            x0,
+            y0,
+            width: x1 - x0 + 1,
+            values: vec![0.0; len],
+            known: vec![false; len],
+        }
+    }
+
+    fn index(&self, x: u32, y: u32) -> usize {
+        ((y - self.y0) * self.width + (x - self.x0)) as usize
+    }
+
+    /// The value of frame pixel `(x, y)`, iterated the first time it is asked for.
+    fn at(&mut self, escape: &impl Fn(u32, u32) -> f64, x: u32, y: u32) -> f64 {
+        let idx = self.index(x, y);
+        if !self.known[idx] {
+            self.values[idx] = escape(x, y);
+            self.known[idx] = true;
+        }
+        self.values[idx]
+    }
+
+    fn set(&mut self, x: u32, y: u32, value: f64) {
+        let idx = self.index(x, y);
+        self.values[idx] = value;
+        self.known[idx] = true;
+    }
+
+    /// Fills the rectangle from `(x0, y0)` to `(x1, y1)`, both inclusive.
+    fn subdivide(
+        &mut self,
+        escape: &impl Fn(u32, u32) -> f64,
+        point: &impl Fn(u32, u32) -> Complex,
+        x0: u32,
+        y0: u32,
+        x1: u32,
+        y1: u32,
+    ) {
+        let mut border = Vec::with_capacity(2 * (x1 - x0 + y1 - y0 + 2) as usize);
+        for x in x0..=x1 {
+            border.push(self.at(escape, x, y0));
+            border.push(self.at(escape, x, y1));
+        }
+        for y in y0 + 1..y1 {
+            border.push(self.at(escape, x0, y));
+            border.push(self.at(escape, x1, y));
+        }
+        if x1 - x0 <= MIN_INSIDE + 1 || y1 - y0 <= MIN_INSIDE + 1 {
+            for y in y0 + 1..y1 {
+                for x in x0 + 1..x1 {
+                    self.at(escape, x, y);
+                }
+            }
+            return;
+        }
+        match classify(&border) {
+            Border::Interior => {
+                for y in y0 + 1..y1 {
+                    for x in x0 + 1..x1 {
+                        self.set(x, y, INTERIOR);
+                    }
+                }
+            }
+            // The set is connected and holds the origin, so a rectangle
+            // around all of it holds the origin too.
+            Border::Band if !contains_origin(point, x0, y0, x1, y1) => {
+                self.interpolate(x0, y0, x1, y1)
+            }
+            _ => {
+                let (mx, my) = ((x0 + x1) / 2, (y0 + y1) / 2);
+                self.subdivide(escape, point, x0, y0, mx, my);
+                self.subdivide(escape, point, mx, y0, x1, my);
+                self.subdivide(escape, point, x0, my, mx, y1);
+                self.subdivide(escape, point, mx, my, x1, y1);
+            }
+        }
+    }
+
+    /// Fills the inside of a rectangle with the Coons patch of its border:
+    /// the blend of both pairs of opposite edges, less the corners counted twice.
+    fn interpolate(&mut self, x0: u32, y0: u32, x1: u32, y1: u32) {
+        let value = |tile: &Tile, x, y| tile.values[tile.index(x, y)];
+        let corners = [
+            value(self, x0, y0),
+            value(self, x1, y0),
+            value(self, x0, y1),
+            value(self, x1, y1),
+        ];
+        for y in y0 + 1..y1 {
+            let v = (y - y0) as f64 / (y1 - y0) as f64;
+            let (left, right) = (value(self, x0, y), value(self, x1, y));
+            for x in x0 + 1..x1 {
+                let u = (x - x0) as f64 / (x1 - x0) as f64;
+                let (top, bottom) = (value(self, x, y0), value(self, x, y1));
+                let edges = (1.0 - v) * top + v * bottom + (1.0 - u) * left + u * right;
+                let twice = (1.0 - u) * (1.0 - v) * corners[0]
+                    + u * (1.0 - v) * corners[1]
+                    + (1.0 - u) * v * corners[2]
+                    + u * v * corners[3];
+                self.set(x, y, edges - twice);
+            }
+        }
+    }
+}
+
+fn classify(border: &[f64]) -> Border {
+    if border.iter().all(|&v| v == INTERIOR) {
+        return Border::Interior;
+    }
+    let band = border[0].floor();
+    if border.iter().all(|&v| v >= 0.0 && v.floor() == band) {
+        Border::Band
+    } else {
+        Border::Mixed
+    }
+}
+
+/// Whether the origin of the plane lies in the (possibly rotated) rectangle
+/// spanned by the pixel corners `(x0, y0)` and `(x1, y1)`.
+fn contains_origin(
+    point: &impl Fn(u32, u32) -> Complex,
+    x0: u32,
+    y0: u32,
+    x1: u32,
+    y1: u32,
+) -> bool {
+    let corners = [point(x0, y0), point(x1, y0), point(x1, y1), point(x0, y1)];
+    let side = |a: Complex, b: Complex| (b.re - a.re) * -a.im - (b.im - a.im) * -a.re;
+    let sides: Vec<f64> = (0..4)
+        .map(|i| side(corners[i], corners[(i + 1) % 4]))
+        .collect();
+    sides.iter().all(|&s| s >= 0.0) || sides.iter().all(|&s| s <= 0.0)
+}
@@ -0,0 +1,59 @@
+use crate::palette::Palette;
+use crate::render::render_frame;
+use crate::{Fractal, fixed_path};
+use image::{GenericImage, ImageBuffer, Rgb};
+use std::fs;
+use std::path::Path;
+
+#[derive(clap::Args, Debug)]
+pub struct PaletteArgs {
+    /// Built-in palette name or comma-separated `#rrggbb` gradient stops.
+    #[arg(long, default_value = "classic")]
+    palette: Palette,
+    #[arg(long, default_value = "out/palette.png")]
+    out: String,
+    #[arg(long, default_value_t = 640)]
+    width: u32,
+    #[arg(long, default_value_t = 48)]
+    strip_height: u32,
+    #[arg(long, default_value_t = 500)]
+    max_iter: u32,
+    /// Zoom of the sample fractal, centered on the animation's starting point.
+    #[arg(long, default_value_t = 0.01)]
+    zoom: f64,
+}
+
+pub fn run(args: PaletteArgs) -> Result<(), String> {
+    let width = args.width.max(1);
+    let sample_height = (width * 9 / 16).max(1);
+    let mut img: ImageBuffer<Rgb<u8>, Vec<u8>> =
+        ImageBuffer::new(width, args.strip_height + sample_height);
+
+    for x in 0..width {
+        let t = x as f64 / (width.max(2) - 1) as f64;
+        let color = Rgb(args.palette.color(t));
+        for y in 0..args.strip_height {
+            img.put_pixel(x, y, color);
+        }
+    }
+
+    let center = fixed_path(Fractal::Mandelbrot)[0];
+    let sample = render_frame(
+        width,
+        sample_height,
+        center,
+        args.zoom,
+        args.max_iter,
+        &args.palette,
+    );
+    img.copy_from(&sample, 0, args.strip_height)
+        .map_err(|e| format!("compose swatch: {e}"))?;
+
+    let out = Path::new(&args.out);
+    if let Some(parent) = out.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("create {parent:?}: {e}"))?;
+    }
+    img.save(out).map_err(|e| format!("save {out:?}: {e}"))?;
+    println!("palette swatch -> {}", out.display());
+    Ok(())
+}
@@ -0,0 +1,304 @@
+use crate::encode::{self, FfmpegPipe};
+use crate::{
+    RenderArgs, fixed_path, layout, output, render_animation_frame, settings, threads, timeline,
+};
+use clap::Args;
+use image::{ImageBuffer, Rgb, imageops};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+#[derive(clap::Args, Debug)]
+pub struct SweepArgs {
+    #[command(flatten)]
+    render: RenderArgs,
+    /// Setting varied across the columns, `key=v1,v2,...` or `key=start..end:count`.
+    #[arg(long)]
+    x: Axis,
+    /// Setting varied down the rows; a single row without it.
+    #[arg(long)]
+    y: Option<Axis>,
+    /// Frame of the animation every cell shows.
+    #[arg(long, default_value_t = 0, conflicts_with = "animate")]
+    frame: u32,
+    /// Encode the whole animation instead of one frame: a video per `cells`
+    /// or one `tiled` video of the mosaic.
+    #[arg(long)]
+    animate: Option<Layout>,
+    /// Mosaic image or video to write, `out/sweep.png` or `out/sweep.mp4` by
+    /// default; per-cell videos get `_<column>_<row>` added to the file name.
+    #[arg(long)]
+    output: Option<String>,
+}
+
+/// How `sweep --animate` lays out its videos.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    Cells,
+    Tiled,
+}
+
+impl FromStr for Layout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Layout, String> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "cells" => Ok(Layout::Cells),
+            "tiled" => Ok(Layout::Tiled),
+            other => Err(format!(
+                "unknown sweep layout {other:?}, expected cells or tiled"
+            )),
+        }
+    }
+}
+
+/// One direction of the grid: a render setting, or a `--track` property
+/// held at a constant value (`c_re`, `c_im`, `center_re`, `zoom`, ...).
+#[derive(Debug, Clone)]
+pub struct Axis {
+    key: String,
+    /// Set when `key` names a track property rather than a setting.
+    property: Option<timeline::Property>,
+    values: Vec<String>,
+}
+
+impl FromStr for Axis {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Axis, String> {
+        let (key, values) = s
+            .split_once('=')
+            .ok_or_else(|| format!("invalid sweep axis {s:?}, expected key=values"))?;
+        let key = key.trim().replace('-', "_");
+        let cmd = RenderArgs::augment_args(clap::Command::new("sweep"));
+        let property = if cmd.get_arguments().any(|a| a.get_id() == key.as_str()) {
+            None
+        } else {
+            Some(timeline::Property::from_name(&key).ok_or_else(|| {
+                format!("unknown setting or track property {key:?} in sweep axis {s:?}")
+            })?)
+        };
+        let values = match parse_range(values)? {
+            Some(values) => values,
+            None => values.split(',').map(|v| v.trim().to_string()).collect(),
+        };
+        if values.iter().any(|v| v.is_empty()) {
+            return Err(format!("empty value in sweep axis {s:?}"));
+        }
+        Ok(Axis {
+            key,
+            property,
+            values,
+        })
+    }
+}
+
+/// `start..end:count`, evenly spaced and including both ends.
+fn parse_range(s: &str) -> Result<Option<Vec<String>>, String> {
+    let Some((start, rest)) = s.split_once("..") else {
+        return Ok(None);
+    };
+    let (end, count) = rest
+        .split_once(':')
+        .ok_or_else(|| format!("expected start..end:count, got {s:?}"))?;
+    let number = |v: &str| {
+        v.trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|v| v.is_finite())
+            .ok_or_else(|| format!("invalid number {v:?} in range {s:?}"))
+    };
+    let (start, end) = (number(start)?, number(end)?);
+    let count: usize = count
+        .trim()
+        .parse()
+        .ok()
+        .filter(|c| *c >= 1)
+        .ok_or_else(|| format!("invalid count {count:?} in range {s:?}"))?;
+    Ok(Some(
+        (0..count)
+            .map(|i| match count {
+                1 => start,
+                _ => start + (end - start) * i as f64 / (count - 1) as f64,
+            })
+            // Rounded to 13 significant digits so steps like 0.1 print as such.
+            .map(|v| format!("{v:.12e}").parse::<f64>().unwrap_or(v).to_string())
+            .collect(),
+    ))
+}
+
+impl Axis {
+    fn label(&self, value: &str) -> String {
+        format!("{}={value}", self.key)
+    }
+}
+
+/// The run's settings with `value` put on `axis`: a setting replaces the
+/// recorded one, a track property replaces any track of that property.
+fn apply(settings: &mut Vec<(String, String)>, axis: &Axis, value: &str) {
+    match axis.property {
+        None => {
+            settings.retain(|(key, _)| *key != axis.key);
+            settings.push((axis.key.clone(), value.to_string()));
+        }
+        Some(property) => {
+            settings.retain(|(key, track)| {
+                key != "track"
+                    || !track
+                        .parse::<timeline::Track>()
+                        .is_ok_and(|t| t.property == property)
+            });
+            settings.push(("track".to_string(), format!("{}=0:{value}", axis.key)));
+        }
+    }
+}
+
+pub fn run(args: SweepArgs, settings: Vec<(String, String)>) -> Result<(), String> {
+    args.render.start_threads()?;
+    threads::install(move || sweep(args, settings))
+}
+
+fn sweep(args: SweepArgs, settings: Vec<(String, String)>) -> Result<(), String> {
+    let columns = &args.x.values;
+    let rows = args.y.as_ref().map_or(vec![None], |y| {
+        y.values.iter().map(Some).collect::<Vec<_>>()
+    });
+    if args.render.endless {
+        return Err("sweep renders stills, --endless has no fixed frames".to_string());
+    }
+
+    let mut cells = Vec::new();
+    for row in &rows {
+        for column in columns {
+            let mut cell = settings.clone();
+            apply(&mut cell, &args.x, column);
+            let mut labels = vec![args.x.label(column)];
+            if let (Some(y), Some(value)) = (&args.y, row) {
+                apply(&mut cell, y, value);
+                labels.push(y.label(value));
+            }
+            let label = labels.join(" ");
+            let cell_args = settings::to_render_args(&cell)
+                .and_then(|a| a.check().map(|()| a))
+                .map_err(|e| format!("sweep cell {label}: {e}"))?;
+            if args.frame >= cell_args.total_frames() {
+                return Err(format!(
+                    "sweep cell {label}: --frame {} is past the last frame {}",
+                    args.frame,
+                    cell_args.total_frames() - 1
+                ));
+            }
+            cells.push((cell_args, label));
+        }
+    }
+
+    let cols = columns.len() as u32;
+    let out = Path::new(args.output.as_deref().unwrap_or(match args.animate {
+        None => "out/sweep.png",
+        Some(_) => "out/sweep.mp4",
+    }));
+    if let Some(parent) = out.parent() {
+        fs::create_dir_all(parent).map_err(|e| output::describe_error("create", parent, &e))?;
+    }
+    match args.animate {
+        None => {
+            let mosaic = compose(&cells, cols, args.frame, true);
+            mosaic.save(out).map_err(|e| format!("save {out:?}: {e}"))?;
+            println!("sweep {cols}x{} -> {}", rows.len(), out.display());
+        }
+        Some(Layout::Tiled) => {
+            let (first, _) = &cells[0];
+            let total_frames = first.total_frames();
+            if let Some((_, label)) = cells.iter().find(|(a, _)| a.total_frames() != total_frames) {
+                return Err(format!(
+                    "sweep cell {label} has a different frame count, a tiled video needs them all equal"
+                ));
+            }
+            let output = out.to_string_lossy();
+            let mut pipe = None;
+            for frame in 0..total_frames {
+                let mosaic = even(compose(&cells, cols, frame, false));
+                let pipe = match &mut pipe {
+                    Some(pipe) => pipe,
+                    None => pipe.insert(FfmpegPipe::spawn(
+                        mosaic.width(),
+                        mosaic.height(),
+                        first.fps,
+                        &encode::h264_args(&output),
+                    )?),
+                };
+                pipe.write_frame(&mosaic)?;
+                if (frame + 1) % 10 == 0 || frame + 1 == total_frames {
+                    println!("sweep {}/{}", frame + 1, total_frames);
+                }
+            }
+            pipe.expect("an animation has frames").finish()?;
+            println!("sweep {cols}x{} video -> {output}", rows.len());
+        }
+        Some(Layout::Cells) => {
+            for (i, (cell_args, label)) in cells.iter().enumerate() {
+                let (column, row) = (i as u32 % cols, i as u32 / cols);
+                let video = cell_path(out, column, row);
+                let video = video.to_string_lossy();
+                let mut pipe = FfmpegPipe::spawn(
+                    cell_args.width & !1,
+                    cell_args.height & !1,
+                    cell_args.fps,
+                    &encode::h264_args(&video),
+                )?;
+                let path = fixed_path(cell_args.fractal);
+                let total_frames = cell_args.total_frames();
+                for frame in 0..total_frames {
+                    let img = render_animation_frame(cell_args, &path, frame, &cell_args.palette);
+                    pipe.write_frame(&even(img))?;
+                }
+                pipe.finish()?;
+                println!("cell {}/{}: {label} -> {video}", i + 1, cells.len());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Frame `frame` of every cell, laid out `cols` wide and captioned with
+/// the cell's values.
+fn compose(
+    cells: &[(RenderArgs, String)],
+    cols: u32,
+    frame: u32,
+    verbose: bool,
+) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+    let images: Vec<_> = cells
+        .iter()
+        .enumerate()
+        .map(|(i, (cell_args, label))| {
+            if verbose {
+                println!("cell {}/{}: {label}", i + 1, cells.len());
+            }
+            let path = fixed_path(cell_args.fractal);
+            let img = render_animation_frame(cell_args, &path, frame, &cell_args.palette);
+            (img, label.clone())
+        })
+        .collect();
+    layout::grid(&images, cols)
+}
+
+/// H.264 in yuv420p needs even dimensions; drops an odd last column or row.
+fn even(img: ImageBuffer<Rgb<u8>, Vec<u8>>) -> ImageBuffer<Rgb<u8>, Vec<u8>> {
+    if img.width().is_multiple_of(2) && img.height().is_multiple_of(2) {
+        return img;
+    }
+    imageops::crop_imm(&img, 0, 0, img.width() & !1, img.height() & !1).to_image()
+}
+
+/// `out/sweep.mp4` becomes `out/sweep_<column>_<row>.mp4`.
+fn cell_path(out: &Path, column: u32, row: u32) -> PathBuf {
+    let stem = out
+        .file_stem()
+        .map_or("sweep".into(), |s| s.to_string_lossy());
+    let name = match out.extension() {
+        Some(ext) => format!("{stem}_{column}_{row}.{}", ext.to_string_lossy()),
+        None => format!("{stem}_{column}_{row}"),
+    };
+    out.with_file_name(name)
+}
@@ -0,0 +1,232 @@
+use crate::flame::Rng;
+use crate::render::{Complex, IterationBuffer};
+use image::{ImageBuffer, Rgb};
+use rayon::prelude::*;
+use std::f64::consts::TAU;
+use std::str::FromStr;
+
+/// Amplitude of each noise octave relative to the next coarser one.
+const GAIN: f64 = 0.7;
+
+/// What `--texture` modulates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    /// Brightness of the finished colors.
+    Value,
+    /// Where along the palette each pixel's escape time lands.
+    Position,
+}
+
+impl FromStr for Target {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Target, String> {
+        match s {
+            "value" => Ok(Target::Value),
+            "position" => Ok(Target::Position),
+            other => Err(format!(
+                "unknown texture target {other:?}, expected value or position"
+            )),
+        }
+    }
+}
+
+/// `--texture`: fractal noise laid over the complex plane. It is anchored to
+/// plane coordinates, so it travels with the set as the camera zooms, and
+/// only the octaves between a few pixels and the view's size are summed, so
+/// it looks equally fine-grained at every depth.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Texture {
+    pub target: Target,
+    /// Largest relative change of brightness or palette position.
+    pub amount: f64,
+    /// Width of the finest noise features, in pixels.
+    pub size: f64,
+    pub seed: u64,
+}
+
+impl FromStr for Texture {
+    type Err = String;
+
+    /// Parses `key=value` pairs such as `target=position,amount=0.1,size=6,seed=3`.
+    fn from_str(s: &str) -> Result<Texture, String> {
+        let mut texture = Texture {
+            target: Target::Value,
+            amount: 0.25,
+            size: 8.0,
+            seed: 0,
+        };
+        for pair in s.split(',').filter(|p| !p.trim().is_empty()) {
+            let (key, value) = pair
+                .split_once('=')
+                .ok_or_else(|| format!("texture: expected key=value, got {pair:?}"))?;
+            let value = value.trim();
+            match key.trim() {
+                "target" => texture.target = value.parse()?,
+                "amount" => {
+                    texture.amount = value
+                        .parse::<f64>()
+                        .map_err(|_| format!("texture: invalid amount {value:?}"))?
+                        .clamp(0.0, 1.0)
+                }
+                "size" => {
+                    texture.size = value
+                        .parse::<f64>()
+                        .ok()
+                        .filter(|size| *size >= 1.0)
+                        .ok_or_else(|| {
+                            format!("texture: invalid size {value:?}, expected 1 or more pixels")
+                        })?
+                }
+                "seed" => {
+                    texture.seed = value
+                        .parse()
+                        .map_err(|_| format!("texture: invalid seed {value:?}"))?
+                }
+                other => return Err(format!("texture: unknown key {other:?}")),
+            }
+        }
+        Ok(texture)
+    }
+}
+
+impl Texture {
+    /// The noise, between about -1 and 1, at every pixel of a frame whose
+    /// pixels sit `pixel` plane units apart and lie at `point(x, y)`.
+    fn field(
+        &self,
+        width: u32,
+        height: u32,
+        pixel: f64,
+        point: impl Fn(u32, u32) -> Complex + Sync,
+    ) -> Vec<f64> {
+        let octaves = self.octaves(pixel * self.size, pixel * width.min(height) as f64);
+        // Independent octaves add up like random steps.
+        let total = octaves.iter().map(|o| o.1 * o.1).sum::<f64>().sqrt();
+        (0..width as usize * height as usize)
+            .into_par_iter()
+            .map(|idx| {
+                let c = point(idx as u32 % width, idx as u32 / width);
+                let sum: f64 = octaves
+                    .iter()
+                    .map(|&(octave, weight)| {
+                        let wavelength = 2.0_f64.powi(octave);
+                        let seed =
+                            self.seed ^ (octave as i64 as u64).wrapping_mul(0xA24B_AED4_963E_E407);
+                        weight * simplex(c.re / wavelength, c.im / wavelength, seed)
+                    })
+                    .sum();
+                if total > 0.0 {
+                    (sum / total).clamp(-1.0, 1.0)
+                } else {
+                    0.0
+                }
+            })
+            .collect()
+    }
+
+    /// Power-of-two wavelengths (as exponents) from `finest` to `coarsest`
+    /// plane units with their weights; octaves fade in and out over one
+    /// doubling at either end, so nothing pops as the zoom moves past them.
+    fn octaves(&self, finest: f64, coarsest: f64) -> Vec<(i32, f64)> {
+        let (lo, hi) = (finest.log2(), coarsest.log2());
+        let fade = |x: f64| {
+            let x = x.clamp(0.0, 1.0);
+            x * x * (3.0 - 2.0 * x)
+        };
+        (lo.floor() as i32..=hi.ceil() as i32)
+            .map(|octave| {
+                let at = octave as f64;
+                let weight = fade(at - lo) * fade(hi - at) * GAIN.powf(hi - at);
+                (octave, weight)
+            })
+            .filter(|o| o.1 > 0.0)
+            .collect()
+    }
+
+    /// Spreads the escape times of the exterior pixels up and down by up to `amount` of themselves.
+    pub fn shift(
+        &self,
+        iterations: &IterationBuffer,
+        pixel: f64,
+        point: impl Fn(u32, u32) -> Complex + Sync,
+    ) -> IterationBuffer {
+        let noise = self.field(iterations.width, iterations.height, pixel, point);
+        let values = iterations
+            .values
+            .par_iter()
+            .zip(noise)
+            .map(|(&v, n)| {
+                if v >= 0.0 {
+                    v * (1.0 + self.amount * n)
+                } else {
+                    v
+                }
+            })
+            .collect();
+        IterationBuffer {
+            width: iterations.width,
+            height: iterations.height,
+            max_iter: iterations.max_iter,
+            values,
+        }
+    }
+
+    /// Brightens and darkens the colors by up to `amount`.
+    pub fn shade(
+        &self,
+        img: &mut ImageBuffer<Rgb<u8>, Vec<u8>>,
+        pixel: f64,
+        point: impl Fn(u32, u32) -> Complex + Sync,
+    ) {
+        let noise = self.field(img.width(), img.height(), pixel, point);
+        img.as_mut()
+            .par_chunks_mut(3)
+            .zip(noise)
+            .for_each(|(rgb, n)| {
+                let factor = 1.0 + self.amount * n;
+                for c in rgb {
+                    *c = (*c as f64 * factor).round().clamp(0.0, 255.0) as u8;
+                }
+            });
+    }
+}
+
+/// 2D simplex noise with a random gradient per lattice point.
+fn simplex(x: f64, y: f64, seed: u64) -> f64 {
+    // Skew to the triangle lattice and back: (√3 - 1) / 2 and (3 - √3) / 6.
+    const F2: f64 = 0.366_025_403_784_438_6;
+    const G2: f64 = 0.211_324_865_405_187_1;
+    let s = (x + y) * F2;
+    let (i, j) = ((x + s).floor(), (y + s).floor());
+    let t = (i + j) * G2;
+    let (x0, y0) = (x - (i - t), y - (j - t));
+    let (i1, j1) = if x0 > y0 { (1.0, 0.0) } else { (0.0, 1.0) };
+    let corners = [
+        (0.0, 0.0, x0, y0),
+        (i1, j1, x0 - i1 + G2, y0 - j1 + G2),
+        (1.0, 1.0, x0 - 1.0 + 2.0 * G2, y0 - 1.0 + 2.0 * G2),
+    ];
+    let sum: f64 = corners
+        .into_iter()
+        .map(|(di, dj, dx, dy)| {
+            let falloff = 0.5 - dx * dx - dy * dy;
+            if falloff <= 0.0 {
+                return 0.0;
+            }
+            let (gx, gy) = lattice_gradient(i + di, j + dj, seed);
+            falloff.powi(4) * (gx * dx + gy * dy)
+        })
+        .sum();
+    // Scales the sum to about -1..1.
+    70.0 * sum
+}
+
+fn lattice_gradient(i: f64, j: f64, seed: u64) -> (f64, f64) {
+    let hash = (i as i64 as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        ^ (j as i64 as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F)
+        ^ seed;
+    let angle = Rng::new(hash).next_f64() * TAU;
+    let (sin, cos) = angle.sin_cos();
+    (cos, sin)
+}
@@ -0,0 +1,62 @@
+use std::sync::OnceLock;
+
+/// Nice value `--nice` lowers the process to, on the usual -20 to 19 scale.
+#[cfg(unix)]
+const NICE: libc::c_int = 10;
+
+static POOL: OnceLock<rayon::ThreadPool> = OnceLock::new();
+
+/// Builds the pool every later [`install`] runs in: `threads` workers, or
+/// one per core without a count. With `nice` the process first lowers its
+/// scheduling priority so the workers, started after, inherit it. Only the
+/// first call takes effect.
+pub fn configure(threads: Option<usize>, nice: bool) -> Result<(), String> {
+    if POOL.get().is_some() {
+        return Ok(());
+    }
+    if nice {
+        lower_priority()?;
+    }
+    let mut builder = rayon::ThreadPoolBuilder::new().thread_name(|i| format!("render-{i}"));
+    if let Some(threads) = threads {
+        builder = builder.num_threads(threads);
+    }
+    let pool = builder
+        .build()
+        .map_err(|e| format!("start the worker threads: {e}"))?;
+    let _ = POOL.set(pool);
+    Ok(())
+}
+
+/// Runs `f` inside the pool of [`configure`], so every parallel iterator in
+/// it shares that pool's workers; without one, in rayon's global pool.
+/// Threads of our own that iterate in parallel go through here too.
+pub fn install<R: Send>(f: impl FnOnce() -> R + Send) -> R {
+    match POOL.get() {
+        Some(pool) => pool.install(f),
+        None => f(),
+    }
+}
+
+/// Raises the nice value to [`NICE`], leaving a higher one alone. On Linux
+/// this only covers the calling thread and the threads it starts later.
+#[cfg(unix)]
+fn lower_priority() -> Result<(), String> {
+    // SAFETY: getpriority and setpriority only read and change the
+    // scheduling priority of this process.
+    unsafe {
+        let current = libc::getpriority(libc::PRIO_PROCESS, 0);
+        if current >= NICE || libc::setpriority(libc::PRIO_PROCESS, 0, NICE) == 0 {
+            return Ok(());
+        }
+    }
+    Err(format!(
+        "--nice: lower priority: {}",
+        std::io::Error::last_os_error()
+    ))
+}
+
+#[cfg(not(unix))]
+fn lower_priority() -> Result<(), String> {
+    Err("--nice is only supported on Unix".to_string())
+}
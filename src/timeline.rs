@@ -0,0 +1,209 @@
+use std::str::FromStr;
+
+/// A frame property a `--track` can animate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Property {
+    CenterRe,
+    CenterIm,
+    Zoom,
+    /// Degrees, counter-clockwise around the frame center.
+    Rotation,
+    PalettePhase,
+    MaxIter,
+    /// Real and imaginary part of the Julia seed, or of `c` in the rational map and Newton polynomial.
+    CRe,
+    CIm,
+    /// Entries of `--transform`, row by row.
+    TransformA,
+    TransformB,
+    TransformC,
+    TransformD,
+}
+
+/// The `--transform` entry each transform property animates.
+pub const TRANSFORM: [(usize, Property); 4] = [
+    (0, Property::TransformA),
+    (1, Property::TransformB),
+    (2, Property::TransformC),
+    (3, Property::TransformD),
+];
+
+const PROPERTIES: [(&str, Property); 12] = [
+    ("center_re", Property::CenterRe),
+    ("center_im", Property::CenterIm),
+    ("zoom", Property::Zoom),
+    ("rotation", Property::Rotation),
+    ("palette_phase", Property::PalettePhase),
+    ("max_iter", Property::MaxIter),
+    ("c_re", Property::CRe),
+    ("c_im", Property::CIm),
+    ("transform_a", Property::TransformA),
+    ("transform_b", Property::TransformB),
+    ("transform_c", Property::TransformC),
+    ("transform_d", Property::TransformD),
+];
+
+impl Property {
+    pub fn name(self) -> &'static str {
+        PROPERTIES.iter().find(|(_, p)| *p == self).unwrap().0
+    }
+
+    /// The property called `name`, spelled with `_` or `-`.
+    pub fn from_name(name: &str) -> Option<Property> {
+        let name = name.trim().replace('-', "_");
+        PROPERTIES.iter().find(|(n, _)| *n == name).map(|(_, p)| *p)
+    }
+}
+
+/// How a track moves from one keyframe to the next.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Easing {
+    Linear,
+    In,
+    Out,
+    InOut,
+    /// Keep the keyframe's value until the next keyframe.
+    Hold,
+}
+
+impl FromStr for Easing {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Easing, String> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "linear" => Ok(Easing::Linear),
+            "in" => Ok(Easing::In),
+            "out" => Ok(Easing::Out),
+            "in-out" | "in_out" => Ok(Easing::InOut),
+            "hold" => Ok(Easing::Hold),
+            other => Err(format!(
+                "unknown easing {other:?}, expected linear, in, out, in-out or hold"
+            )),
+        }
+    }
+}
+
+impl Easing {
+    fn apply(self, u: f64) -> f64 {
+        match self {
+            Easing::Linear => u,
+            Easing::In => u * u * u,
+            Easing::Out => 1.0 - (1.0 - u).powi(3),
+            Easing::InOut if u < 0.5 => 4.0 * u * u * u,
+            Easing::InOut => 1.0 - (2.0 - 2.0 * u).powi(3) / 2.0,
+            Easing::Hold => 0.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Key {
+    t: f64,
+    value: f64,
+    /// Easing of the segment from this key to the next one.
+    easing: Easing,
+}
+
+/// Keyframes of one property over the animation progress `t` (0 at the
+/// first frame, 1 at the last), written
+/// `property=t:value[:easing],t:value[:easing],...`,
+/// e.g. `zoom=0:3,0.5:1e-3:in-out,1:1e-6`. Before the first and after the
+/// last key the track holds that key's value.
+#[derive(Debug, Clone)]
+pub struct Track {
+    pub property: Property,
+    keys: Vec<Key>,
+}
+
+impl FromStr for Track {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Track, String> {
+        let (name, keys) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected property=t:value,..., got {s:?}"))?;
+        let property = Property::from_name(name).ok_or_else(|| {
+            let names: Vec<&str> = PROPERTIES.iter().map(|(n, _)| *n).collect();
+            format!(
+                "unknown track property {name:?}, expected one of {}",
+                names.join(", ")
+            )
+        })?;
+        let mut parsed = Vec::new();
+        for key in keys.split(',') {
+            let mut parts = key.split(':');
+            let number = |part: Option<&str>, what: &str| -> Result<f64, String> {
+                let part = part.ok_or_else(|| format!("keyframe {key:?} has no {what}"))?;
+                part.trim()
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|v| v.is_finite())
+                    .ok_or_else(|| format!("invalid {what} {part:?} in keyframe {key:?}"))
+            };
+            let t = number(parts.next(), "time")?;
+            let value = number(parts.next(), "value")?;
+            let easing = match parts.next() {
+                Some(easing) => easing.parse()?,
+                None => Easing::Linear,
+            };
+            if parts.next().is_some() {
+                return Err(format!("expected t:value[:easing], got {key:?}"));
+            }
+            if let Some(last) = parsed.last().map(|k: &Key| k.t)
+                && t <= last
+            {
+                return Err(format!(
+                    "keyframe times must increase, got {t} after {last}"
+                ));
+            }
+            parsed.push(Key { t, value, easing });
+        }
+        if property == Property::Zoom && parsed.iter().any(|k| k.value <= 0.0) {
+            return Err("zoom keyframes must be positive".to_string());
+        }
+        if property == Property::MaxIter && parsed.iter().any(|k| k.value < 1.0) {
+            return Err("max_iter keyframes must be at least 1".to_string());
+        }
+        Ok(Track {
+            property,
+            keys: parsed,
+        })
+    }
+}
+
+impl Track {
+    /// The track's value at progress `t`. Zoom moves geometrically between
+    /// keys, like the default zoom, everything else linearly.
+    pub fn value(&self, t: f64) -> f64 {
+        let Some(i) = self.keys.iter().rposition(|k| k.t <= t) else {
+            return self.keys[0].value;
+        };
+        let (a, Some(b)) = (self.keys[i], self.keys.get(i + 1)) else {
+            return self.keys[i].value;
+        };
+        let u = a.easing.apply((t - a.t) / (b.t - a.t));
+        if self.property == Property::Zoom {
+            a.value * (b.value / a.value).powf(u)
+        } else {
+            a.value + (b.value - a.value) * u
+        }
+    }
+}
+
+/// The track animating `property`, if any.
+pub fn find(tracks: &[Track], property: Property) -> Option<&Track> {
+    tracks.iter().find(|track| track.property == property)
+}
+
+/// At most one track per property.
+pub fn check(tracks: &[Track]) -> Result<(), String> {
+    for (i, track) in tracks.iter().enumerate() {
+        if tracks[..i].iter().any(|t| t.property == track.property) {
+            return Err(format!(
+                "--track {} is given more than once",
+                track.property.name()
+            ));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,179 @@
+use crate::manifest::Manifest;
+use crate::{antialias, compare, errormap, layout, timeline};
+use crate::{
+    encode_main_frame, fixed_path, frame_info, iterate, output, render_animation_frame,
+    render_main_frame, sha256,
+};
+use std::fs;
+use std::path::Path;
+
+#[derive(clap::Args, Debug)]
+pub struct VerifyArgs {
+    /// Directory holding the frames and their run manifest.
+    #[arg(long, default_value = "out/frames")]
+    run_dir: String,
+    /// Re-render this many frames spread over the run and compare pixels.
+    #[arg(long, default_value_t = 0)]
+    rerender: u32,
+}
+
+pub fn run(args: VerifyArgs) -> Result<(), String> {
+    let dir = output::resolve_dir(Path::new(&args.run_dir))?;
+    let manifest = Manifest::read(&dir)?;
+    let mut failures = 0;
+
+    for entry in &manifest.frames {
+        let filepath = dir.join(&entry.file);
+        match fs::read(&filepath) {
+            Ok(bytes) => {
+                if sha256::hex_digest(&bytes) != entry.sha256 {
+                    println!("hash mismatch: {}", filepath.display());
+                    failures += 1;
+                }
+            }
+            Err(e) => {
+                println!("unreadable: {}: {e}", filepath.display());
+                failures += 1;
+            }
+        }
+    }
+
+    let samples = (args.rerender as usize).min(manifest.frames.len());
+    let mut rerendered = 0;
+    if samples > 0 {
+        let render_args = manifest.render_args()?;
+        let path = fixed_path(render_args.fractal);
+        for k in 0..samples {
+            let entry = &manifest.frames[k * manifest.frames.len() / samples];
+            let filepath = dir.join(&entry.file);
+            let palette = match &entry.variant {
+                Some(label)
+                    if [
+                        compare::LABEL,
+                        layout::LABEL,
+                        errormap::LABEL,
+                        antialias::LABEL,
+                    ]
+                    .contains(&label.as_str()) =>
+                {
+                    None
+                }
+                None => Some(&render_args.palette),
+                Some(label) => match render_args.palettes.iter().find(|p| &p.label == label) {
+                    Some(variant) => Some(&variant.palette),
+                    None => {
+                        println!("unknown palette {label:?} for {}", filepath.display());
+                        failures += 1;
+                        continue;
+                    }
+                },
+            };
+            if manifest.is_degraded(entry.index) {
+                println!(
+                    "re-render skipped: {} ran over --frame-budget",
+                    filepath.display()
+                );
+                continue;
+            }
+            let mut frame_args = render_args.clone();
+            if let Some(max_iter) = manifest.frame_max_iter(entry.index) {
+                frame_args.max_iter = max_iter;
+                frame_args
+                    .track
+                    .retain(|track| track.property != timeline::Property::MaxIter);
+            }
+            if entry.variant.is_none() && !frame_args.png_frames() {
+                let info = frame_info(&frame_args, &path, entry.index);
+                let iterations = iterate(
+                    &frame_args,
+                    &info,
+                    frame_args.width,
+                    frame_args.height,
+                    info.max_iter,
+                );
+                let img = render_main_frame(&frame_args, &path, entry.index);
+                let expected = encode_main_frame(&frame_args, &iterations, &info, &img)?;
+                rerendered += 1;
+                if fs::read(&filepath).is_ok_and(|bytes| bytes == expected) {
+                    println!("re-render ok: {}", filepath.display());
+                } else {
+                    println!("re-render mismatch: {}", filepath.display());
+                    failures += 1;
+                }
+                continue;
+            }
+            let expected = match palette {
+                Some(_) if entry.variant.is_none() => {
+                    render_main_frame(&frame_args, &path, entry.index)
+                }
+                Some(palette) => render_animation_frame(&frame_args, &path, entry.index, palette),
+                None if entry.variant.as_deref() == Some(errormap::LABEL) => {
+                    let info = frame_info(&frame_args, &path, entry.index);
+                    let iterations = iterate(
+                        &frame_args,
+                        &info,
+                        frame_args.width,
+                        frame_args.height,
+                        info.max_iter,
+                    );
+                    errormap::measure(&frame_args, &info, &iterations).0
+                }
+                None if entry.variant.as_deref() == Some(antialias::LABEL) => {
+                    let info = frame_info(&frame_args, &path, entry.index);
+                    let iterations = iterate(
+                        &frame_args,
+                        &info,
+                        frame_args.width,
+                        frame_args.height,
+                        info.max_iter,
+                    );
+                    antialias::sample_map(&iterations, &frame_args, &info).0
+                }
+                None if entry.variant.as_deref() == Some(layout::LABEL) => {
+                    let own = render_main_frame(&frame_args, &path, entry.index);
+                    let mut others = Vec::new();
+                    for view in &render_args.view {
+                        let view_args = view.args(&manifest.settings)?;
+                        let img = render_animation_frame(
+                            &view_args,
+                            &path,
+                            entry.index,
+                            &view_args.palette,
+                        );
+                        others.push((view, img));
+                    }
+                    layout::views_frame(own, others, render_args.view_columns)
+                }
+                None => {
+                    let other = compare::other_args(&manifest.settings, &render_args.compare)?;
+                    compare::side_by_side(
+                        &render_main_frame(&frame_args, &path, entry.index),
+                        &render_animation_frame(&other, &path, entry.index, &other.palette),
+                        &render_args.compare,
+                    )
+                }
+            };
+            rerendered += 1;
+            let matches = image::open(&filepath)
+                .map(|img| img.to_rgb8() == expected)
+                .unwrap_or(false);
+            if matches {
+                println!("re-render ok: {}", filepath.display());
+            } else {
+                println!("re-render mismatch: {}", filepath.display());
+                failures += 1;
+            }
+        }
+    }
+
+    println!(
+        "verified {} frames ({} re-rendered), {} failures",
+        manifest.frames.len(),
+        rerendered,
+        failures
+    );
+    if failures > 0 {
+        return Err(format!("{failures} frame(s) failed verification"));
+    }
+    Ok(())
+}
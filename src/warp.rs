@@ -0,0 +1,76 @@
+use crate::rational;
+use crate::render::Complex;
+use std::f64::consts::PI;
+use std::str::FromStr;
+
+/// A conformal map the view goes through before --transform and rotation.
+/// It acts on a pixel's offset from the frame center measured in units of
+/// the zoom, where the middle of the shorter side is 1 away.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Warp {
+    None,
+    /// The exponential map: across the frame the angle around the center
+    /// goes once round, and down the frame the distance from it grows
+    /// exponentially, so one frame shows many zoom levels as a tunnel whose
+    /// far end is the center.
+    Tunnel,
+    /// The Möbius transform `(a w + b) / (c w + d)`.
+    Mobius([Complex; 4]),
+}
+
+impl FromStr for Warp {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Warp, String> {
+        let s = s.trim();
+        match s.to_ascii_lowercase().as_str() {
+            "none" => return Ok(Warp::None),
+            "tunnel" | "exp" => return Ok(Warp::Tunnel),
+            _ => {}
+        }
+        let Some(coefficients) = s
+            .split_once(':')
+            .filter(|(name, _)| name.trim().eq_ignore_ascii_case("mobius"))
+            .map(|(_, coefficients)| coefficients)
+        else {
+            return Err(format!(
+                "unknown warp {s:?}, expected none, tunnel or mobius:a,b,c,d"
+            ));
+        };
+        let coefficients = coefficients
+            .split(',')
+            .map(rational::parse_complex)
+            .collect::<Result<Vec<_>, _>>()?;
+        let Ok([a, b, c, d]) = <[Complex; 4]>::try_from(coefficients) else {
+            return Err(format!(
+                "{s:?}: a Möbius warp takes four complex coefficients a,b,c,d"
+            ));
+        };
+        if a.mul(d).sub(b.mul(c)).norm_sqr() == 0.0 {
+            return Err(format!(
+                "{s:?}: a d - b c is 0, which maps the whole view to one point"
+            ));
+        }
+        Ok(Warp::Mobius([a, b, c, d]))
+    }
+}
+
+impl Warp {
+    /// Where the warp takes the offset `w`; `half_width` is half the frame
+    /// width in the same units, the distance the tunnel takes to go round.
+    pub fn apply(self, w: Complex, half_width: f64) -> Complex {
+        match self {
+            Warp::None => w,
+            Warp::Tunnel => {
+                let k = PI / half_width;
+                let radius = (k * w.im).exp();
+                let (sin, cos) = (k * w.re).sin_cos();
+                Complex {
+                    re: radius * cos,
+                    im: radius * sin,
+                }
+            }
+            Warp::Mobius([a, b, c, d]) => a.mul(w).add(b).div(c.mul(w).add(d)),
+        }
+    }
+}